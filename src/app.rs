@@ -1,14 +1,23 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use anyhow::Result;
-use log::{info, warn, error};
+use log::{info, warn, error, debug};
 
 use crate::audio::AudioProcessor;
-use crate::realtime_audio::RealTimeAudioProcessor;
-use crate::network::{NetworkManager, ConnectionConfig};
+use crate::realtime_audio::{RealTimeAudioProcessor, CapturePipeline, PlaybackPipeline, AudioFrame};
+use crate::jitter_buffer::JitterBufferConfig;
+use crate::capabilities::NegotiatedAudio;
+use crate::network::{NetworkManager, ConnectionConfig, ReceivedAudioFrame, ReceivedControl};
+use crate::congestion::{CongestionController, EncoderControl, FeedbackReport, ReceiveStatistics, RedundancySwitch};
+use crate::transport::Transport;
 use crate::ui::UserInterface;
+use crate::terminal_ui::TerminalApp;
 use crate::security::SecurityConfig;
+use crate::keystore::{self, IdentityKeystore};
+use crate::known_peers::KnownPeers;
 use crate::config::{ConfigManager, AppConfig};
 use crate::monitoring::{HealthMonitor, DefaultHealthChecks, MetricsCollector, HealthReport, PerformanceMetrics};
 use crate::error_recovery::{ErrorRecoveryManager, ErrorEvent, create_audio_error, create_network_error, ErrorSeverity};
@@ -20,13 +29,109 @@ pub struct VocalCommunicationApp {
     realtime_audio: Option<RealTimeAudioProcessor>,
     network_manager: Arc<Mutex<NetworkManager>>,
     user_interface: Arc<Mutex<UserInterface>>,
+    // Call state for the terminal UI: the safety code, and the user's confirmation of it
+    terminal: Arc<Mutex<TerminalApp>>,
     config_manager: ConfigManager,
+    identity: SecurityConfig,
     health_monitor: Arc<HealthMonitor>,
     metrics_collector: MetricsCollector,
     error_recovery: Arc<ErrorRecoveryManager>,
+    // Shared with the capture pipeline so congestion control can retune the encoder
+    encoder_control: EncoderControl,
+    // Codec and format the pipelines run: the configured preference until a call negotiates its own
+    call_audio: Arc<Mutex<NegotiatedAudio>>,
+    // Cleared by `stop` to end the network and legacy audio threads; each `start` hands out a fresh one
+    threads_running: Arc<std::sync::atomic::AtomicBool>,
     is_running: bool,
 }
 
+/// Receive statistics for senders outlive a pause in their audio by this long
+const RECEIVE_STATS_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Feedback in both directions: reports on what we receive go out to each sender, and
+/// senders' reports on our stream drive the congestion controller
+struct RateAdaptation {
+    receive_stats: HashMap<SocketAddr, ReceiveStatistics>,
+    // `None` when adaptive bitrate is off; we still report to peers
+    controller: Option<CongestionController>,
+    // Automatic redundancy when there is no controller to decide it
+    redundancy_switch: Option<RedundancySwitch>,
+    encoder_control: EncoderControl,
+    health_monitor: Arc<HealthMonitor>,
+}
+
+impl RateAdaptation {
+    fn new(config: &AppConfig, encoder_control: EncoderControl, health_monitor: Arc<HealthMonitor>) -> Self {
+        let controller = config.to_congestion_config()
+            .and_then(|congestion_config| match CongestionController::new(congestion_config) {
+                Ok(controller) => Some(controller),
+                Err(e) => {
+                    warn!("Adaptive bitrate disabled: {}", e);
+                    None
+                }
+            });
+
+        Self {
+            receive_stats: HashMap::new(),
+            controller,
+            redundancy_switch: config.to_redundancy_switch(),
+            encoder_control,
+            health_monitor,
+        }
+    }
+
+    fn on_audio(&mut self, received: &ReceivedAudioFrame) {
+        let now = Instant::now();
+        self.receive_stats.entry(received.peer)
+            .or_insert_with(|| ReceiveStatistics::new(now))
+            .record(received.sequence_number, received.timestamp, received.payload.len(), now);
+    }
+
+    /// Send any reports that are due and act on the ones peers sent us
+    fn exchange(&mut self, network: &mut NetworkManager, runtime: &tokio::runtime::Handle) {
+        let now = Instant::now();
+        self.receive_stats.retain(|_, stats| {
+            stats.last_arrival().is_some_and(|arrival| now.duration_since(arrival) < RECEIVE_STATS_IDLE_TIMEOUT)
+        });
+
+        for (&peer, stats) in self.receive_stats.iter_mut() {
+            if let Some(report) = stats.report(now)
+                && let Err(e) = runtime.block_on(network.send_control(peer, &report.encode())) {
+                // Plaintext peers have no control channel
+                debug!("Feedback to {} not sent: {}", peer, e);
+            }
+        }
+
+        while let Ok(Some(control)) = network.receive_control() {
+            self.on_control(control, now);
+        }
+    }
+
+    fn on_control(&mut self, control: ReceivedControl, now: Instant) {
+        let report = match FeedbackReport::decode(&control.payload) {
+            Ok(report) => report,
+            Err(e) => {
+                warn!("Ignoring control message from {}: {}", control.peer, e);
+                return;
+            }
+        };
+
+        if let Some(controller) = self.controller.as_mut()
+            && let Some(decision) = controller.on_report(control.peer, report, now) {
+            info!("Congestion control: {}", decision);
+            self.encoder_control.request(decision.target);
+            self.health_monitor.record_rate_decision(decision);
+        }
+
+        if let Some(switch) = self.redundancy_switch.as_mut()
+            && let Some(target) = switch.on_report(control.peer, &report, now) {
+            info!("Redundancy {} after {:.1}% loss reported by {}",
+                  if target.redundancy { "on" } else { "off" }, report.loss_fraction * 100.0, control.peer);
+            self.encoder_control.request(target);
+        }
+    }
+}
+
 impl VocalCommunicationApp {
     pub fn new() -> Self {
         // Initialize logging
@@ -55,7 +160,7 @@ impl VocalCommunicationApp {
         let audio_processor = Arc::new(Mutex::new(audio_processor));
 
         // Initialize real-time audio processor
-        let realtime_audio = match RealTimeAudioProcessor::new() {
+        let realtime_audio = match RealTimeAudioProcessor::with_config(config.to_audio_configuration()) {
             Ok(processor) => {
                 info!("Real-time audio processor created successfully");
                 Some(processor)
@@ -66,17 +171,54 @@ impl VocalCommunicationApp {
             }
         };
 
-        // Create security configuration for encrypted communications
-        let security_config = SecurityConfig::new().expect("Failed to create security config");
+        // Load the persistent identity used for encrypted communications
+        let mut security_config = Self::load_identity();
+        config.apply_to_security_config(&mut security_config);
 
-        let network_manager = Arc::new(Mutex::new(NetworkManager::new(
-            ConnectionConfig {
-                remote_host: config.network.remote_host.clone(),
-                port: config.network.port,
-                use_encryption: config.security.encryption_enabled,
-                security_config: Some(security_config),
-            }
-        )));
+        let network_manager = NetworkManager::new(Self::connection_config(config, &security_config));
+        Self::assemble(config_manager, audio_processor, realtime_audio, network_manager, security_config)
+    }
+
+    /// Headless app over an existing transport: no audio devices, keystore or config file.
+    /// Lets two complete apps call each other in-process, e.g. over a loopback pair.
+    pub fn with_transport(config: AppConfig, identity: SecurityConfig, transport: Arc<dyn Transport>) -> Self {
+        let mut security_config = identity;
+        config.apply_to_security_config(&mut security_config);
+
+        let mut audio_processor = AudioProcessor::new();
+        config.apply_to_audio_processor(&mut audio_processor);
+
+        let network_manager = NetworkManager::with_transport(Self::connection_config(&config, &security_config), transport);
+        Self::assemble(
+            ConfigManager::with_config(config),
+            Arc::new(Mutex::new(audio_processor)),
+            None,
+            network_manager,
+            security_config,
+        )
+    }
+
+    fn connection_config(config: &AppConfig, security_config: &SecurityConfig) -> ConnectionConfig {
+        ConnectionConfig {
+            remote_host: config.network.remote_host.clone(),
+            port: config.network.port,
+            local_port: None,
+            use_encryption: config.security.encryption_enabled,
+            security_config: Some(security_config.clone()),
+            max_peers: config.network.max_peers,
+            turn_server: config.turn_server_addr(),
+            transport: config.network.transport,
+        }
+    }
+
+    fn assemble(
+        config_manager: ConfigManager,
+        audio_processor: Arc<Mutex<AudioProcessor>>,
+        realtime_audio: Option<RealTimeAudioProcessor>,
+        network_manager: NetworkManager,
+        security_config: SecurityConfig,
+    ) -> Self {
+        let network_manager = Arc::new(Mutex::new(network_manager));
 
         // Initialize health monitoring
         let health_monitor = Arc::new(HealthMonitor::new());
@@ -95,19 +237,53 @@ impl VocalCommunicationApp {
         // Register default health checks
         Self::setup_default_health_checks(&health_monitor);
 
+        let call_audio = Arc::new(Mutex::new(config_manager.get_config().preferred_audio()));
+
         Self {
             audio_processor,
             realtime_audio,
             network_manager,
             user_interface,
+            terminal: Arc::new(Mutex::new(TerminalApp::new())),
             config_manager,
+            identity: security_config,
             health_monitor,
             metrics_collector,
             error_recovery,
+            encoder_control: EncoderControl::default(),
+            call_audio,
+            threads_running: Arc::new(std::sync::atomic::AtomicBool::new(false)),
             is_running: false,
         }
     }
 
+    /// Load the identity keystore and known peers, falling back to a temporary identity on failure
+    fn load_identity() -> SecurityConfig {
+        let passphrase = keystore::passphrase_from_env();
+        let identity = IdentityKeystore::open_default()
+            .and_then(|keystore| keystore.load_or_create(passphrase.as_deref()));
+
+        let identity = match identity {
+            Ok(identity) => {
+                info!("Loaded identity {}", identity.fingerprint());
+                identity
+            }
+            Err(e) => {
+                error!("Failed to load identity keystore: {}", e);
+                error!("Falling back to a temporary identity for this session");
+                SecurityConfig::new().expect("Failed to create security config")
+            }
+        };
+
+        // Pinned peers persist across sessions; without the file we only pin in memory
+        match KnownPeers::load_default() {
+            Ok(known_peers) => *identity.known_peers.lock().unwrap() = known_peers,
+            Err(e) => error!("Failed to load known peers, pinning in memory only: {}", e),
+        }
+
+        identity
+    }
+
     fn setup_default_health_checks(health_monitor: &Arc<HealthMonitor>) {
         health_monitor.register_check("audio_system".to_string(), DefaultHealthChecks::audio_system());
         health_monitor.register_check("network_connectivity".to_string(), DefaultHealthChecks::network_connectivity());
@@ -120,19 +296,42 @@ impl VocalCommunicationApp {
     pub async fn start(&mut self) -> Result<()> {
         info!("Starting Humr voice communication application");
         self.is_running = true;
+        self.threads_running = Arc::new(std::sync::atomic::AtomicBool::new(true));
 
         // Start health monitoring
         self.health_monitor.start_monitoring()
             .map_err(|e| anyhow::anyhow!("Failed to start health monitoring: {}", e))?;
         info!("Health monitoring started");
 
+        // Received packets for the playback pipeline (none in legacy mode)
+        let mut received_producer = None;
+
         // Initialize and start real-time audio processor
+        let audio = self.call_audio();
         if let Some(ref mut realtime_audio) = self.realtime_audio {
             info!("Initializing real-time audio system");
             match realtime_audio.initialize() {
                 Ok(_) => {
+                    // Capture pipeline feeds encoded packets to the network send thread
+                    let encoded_consumer = Self::build_capture_pipeline(self.config_manager.get_config(), &audio)
+                        .map(|pipeline| pipeline.with_encoder_control(self.encoder_control.clone()))
+                        .and_then(|pipeline| realtime_audio.enable_capture_pipeline(pipeline));
+
+                    // Playback pipeline is fed by the network processing thread
+                    match Self::build_playback_pipeline(self.config_manager.get_config(), &audio)
+                        .and_then(|pipeline| realtime_audio.enable_playback_pipeline(pipeline)) {
+                        Ok(producer) => received_producer = Some(producer),
+                        Err(e) => error!("Failed to enable playback pipeline: {}", e),
+                    }
+
                     match realtime_audio.start() {
-                        Ok(_) => info!("Real-time audio system started successfully"),
+                        Ok(_) => {
+                            info!("Real-time audio system started successfully");
+                            match encoded_consumer {
+                                Ok(consumer) => self.start_network_send_thread(consumer),
+                                Err(e) => error!("Failed to enable capture pipeline: {}", e),
+                            }
+                        }
                         Err(e) => {
                             let error = create_audio_error(
                                 format!("Failed to start real-time audio: {}", e),
@@ -162,13 +361,19 @@ impl VocalCommunicationApp {
             self.start_legacy_audio_threads();
         }
 
-        // Start network processing thread (still using legacy approach for now)
+        // Start network processing thread
         let network_clone = self.network_manager.clone();
-        let running_flag = Arc::new(std::sync::atomic::AtomicBool::new(true));
-        let running_clone = running_flag.clone();
+        let terminal_clone = self.terminal.clone();
+        let rate_adaptation = RateAdaptation::new(
+            self.config_manager.get_config(),
+            self.encoder_control.clone(),
+            self.health_monitor.clone(),
+        );
+        let runtime = tokio::runtime::Handle::current();
+        let running_clone = self.threads_running.clone();
 
         thread::spawn(move || {
-            Self::network_processing_loop(network_clone, running_clone);
+            Self::network_processing_loop(network_clone, terminal_clone, received_producer, rate_adaptation, runtime, running_clone);
         });
 
         // Start UI
@@ -179,43 +384,160 @@ impl VocalCommunicationApp {
         Ok(())
     }
 
+    /// Build the capture-side processing chain for `audio` from the application configuration
+    pub(crate) fn build_capture_pipeline(config: &AppConfig, audio: &NegotiatedAudio) -> Result<CapturePipeline> {
+        let noise_suppression = config.processing.noise_suppression.enabled
+            .then(|| config.to_noise_suppression_config());
+        let echo_cancellation = config.processing.echo_cancellation.enabled
+            .then(|| config.to_echo_cancellation_config());
+
+        CapturePipeline::new(noise_suppression, echo_cancellation, config.to_call_opus_config(audio))?
+            .with_codec(audio.codec)?
+            .with_redundancy(config.to_redundancy_config())
+    }
+
+    /// Build the receive-side processing chain for `audio` from the application configuration
+    pub(crate) fn build_playback_pipeline(config: &AppConfig, audio: &NegotiatedAudio) -> Result<PlaybackPipeline> {
+        PlaybackPipeline::new(JitterBufferConfig::for_transport(config.network.transport), config.to_call_opus_config(audio))?
+            .with_codec(audio.codec)
+    }
+
+    /// Codec and format the audio pipelines run
+    pub fn call_audio(&self) -> NegotiatedAudio {
+        self.call_audio.lock().map(|audio| *audio)
+            .unwrap_or_else(|_| self.config_manager.get_config().preferred_audio())
+    }
+
+    /// Rebuild the audio pipelines, and reopen the devices, for the audio a call settled on
+    fn apply_call_audio(&mut self, audio: NegotiatedAudio) -> Result<()> {
+        info!("Call audio: {}", audio);
+        if let Ok(mut call_audio) = self.call_audio.lock() {
+            *call_audio = audio;
+        }
+
+        // The legacy capture loop picks the change up itself
+        if let Some(ref mut realtime_audio) = self.realtime_audio {
+            let config = self.config_manager.get_config();
+            let capture = Self::build_capture_pipeline(config, &audio)?
+                .with_encoder_control(self.encoder_control.clone());
+            let playback = Self::build_playback_pipeline(config, &audio)?;
+            realtime_audio.replace_pipelines(capture, playback)?;
+        }
+        Ok(())
+    }
+
+    /// Start thread draining encoded packets from the real-time processor to the network
+    fn start_network_send_thread(&self, encoded_consumer: ringbuf::HeapCons<Vec<u8>>) {
+        let network_clone = self.network_manager.clone();
+        let runtime = tokio::runtime::Handle::current();
+        let running_clone = self.threads_running.clone();
+
+        thread::spawn(move || {
+            Self::network_send_loop(network_clone, encoded_consumer, runtime, running_clone);
+        });
+    }
+
+    fn network_send_loop(
+        network_manager: Arc<Mutex<NetworkManager>>,
+        mut encoded_consumer: ringbuf::HeapCons<Vec<u8>>,
+        runtime: tokio::runtime::Handle,
+        running: Arc<std::sync::atomic::AtomicBool>
+    ) {
+        use ringbuf::traits::Consumer;
+
+        info!("Network send loop started");
+
+        while running.load(std::sync::atomic::Ordering::Relaxed) {
+            if let Some(packet) = encoded_consumer.try_pop() {
+                if let Ok(network) = network_manager.lock()
+                    && network.is_connected()
+                    && let Err(e) = runtime.block_on(network.send_audio_frame(&packet))
+                {
+                    warn!("Failed to send audio frame: {}", e);
+                }
+            } else {
+                thread::sleep(Duration::from_millis(2));
+            }
+        }
+        info!("Network send loop stopped");
+    }
+
     /// Legacy audio threading for fallback compatibility
     fn start_legacy_audio_threads(&self) {
         warn!("Starting legacy audio threads (fallback mode)");
 
+        let config = self.config_manager.get_config().clone();
+        let mut audio = self.call_audio();
+        let pipeline = match Self::build_capture_pipeline(&config, &audio) {
+            Ok(pipeline) => pipeline.with_encoder_control(self.encoder_control.clone()),
+            Err(e) => {
+                error!("Failed to create capture pipeline for legacy audio: {}", e);
+                return;
+            }
+        };
+
+        // Rebuilt whenever a call settles on other audio
+        let call_audio = self.call_audio.clone();
+        let encoder_control = self.encoder_control.clone();
+        let rebuild = move || -> Option<CapturePipeline> {
+            let current = call_audio.lock().map(|current| *current).ok()?;
+            if current == audio {
+                return None;
+            }
+            audio = current;
+            match Self::build_capture_pipeline(&config, &audio) {
+                Ok(pipeline) => Some(pipeline.with_encoder_control(encoder_control.clone())),
+                Err(e) => {
+                    error!("Failed to rebuild legacy capture pipeline for {}: {}", audio, e);
+                    None
+                }
+            }
+        };
+
         let audio_clone = self.audio_processor.clone();
         let network_clone = self.network_manager.clone();
-        let running_flag = Arc::new(std::sync::atomic::AtomicBool::new(true));
-        let running_clone = running_flag.clone();
+        let runtime = tokio::runtime::Handle::current();
+        let running_clone = self.threads_running.clone();
 
         thread::spawn(move || {
-            Self::legacy_audio_capture_loop(audio_clone, network_clone, running_clone);
+            Self::legacy_audio_capture_loop(audio_clone, network_clone, pipeline, rebuild, runtime, running_clone);
         });
     }
 
     fn legacy_audio_capture_loop(
         audio_processor: Arc<Mutex<AudioProcessor>>,
         network_manager: Arc<Mutex<NetworkManager>>,
+        mut pipeline: CapturePipeline,
+        mut rebuild: impl FnMut() -> Option<CapturePipeline>,
+        runtime: tokio::runtime::Handle,
         running: Arc<std::sync::atomic::AtomicBool>
     ) {
         warn!("Running legacy audio capture loop (fallback mode)");
 
         let mut frame_counter = 0;
         while running.load(std::sync::atomic::Ordering::Relaxed) {
-            thread::sleep(Duration::from_millis(20)); // ~50fps audio frames
+            if let Some(rebuilt) = rebuild() {
+                pipeline = rebuilt;
+            }
+            let format = pipeline.format();
+            thread::sleep(Duration::from_millis(format.frame_duration_ms as u64)); // One frame period
 
-            // LEGACY: Simulating audio capture for proof of concept
-            let dummy_audio_frame = vec![0u8; 1024]; // 1KB frame
+            // LEGACY: No capture device available, keep the stream alive with encoded silence
+            let mut frame = AudioFrame::silence_in(format);
+            frame.sequence = frame_counter;
 
             if let (Ok(_processor), Ok(network)) =
                 (audio_processor.lock(), network_manager.lock()) {
 
-                // LEGACY STUB - Process audio frame
-                // processor.process_audio_frame(&input_samples, &mut output_samples);
-
-                // LEGACY STUB - Send to network
                 if network.is_connected() {
-                    let _ = network.send_audio_frame(&dummy_audio_frame);
+                    match pipeline.process(&mut frame) {
+                        Ok(packet) => {
+                            if let Err(e) = runtime.block_on(network.send_audio_frame(&packet)) {
+                                warn!("Failed to send audio frame: {}", e);
+                            }
+                        }
+                        Err(e) => error!("Legacy capture pipeline failed: {}", e),
+                    }
                 }
             }
 
@@ -229,32 +551,54 @@ impl VocalCommunicationApp {
 
     fn network_processing_loop(
         network_manager: Arc<Mutex<NetworkManager>>,
+        terminal: Arc<Mutex<TerminalApp>>,
+        mut received_producer: Option<ringbuf::HeapProd<ReceivedAudioFrame>>,
+        mut rate_adaptation: RateAdaptation,
+        runtime: tokio::runtime::Handle,
         running: Arc<std::sync::atomic::AtomicBool>
     ) {
+        use ringbuf::traits::Producer;
+
         info!("Network processing loop started");
 
-        while running.load(std::sync::atomic::Ordering::Relaxed) {
+        'receive: while running.load(std::sync::atomic::Ordering::Relaxed) {
             thread::sleep(Duration::from_millis(10));
 
             if let Ok(mut network) = network_manager.lock() {
-                if network.is_connected() {
-                    // THIS IS A STUB - Receive and process network audio
+                if !network.is_connected() {
+                    continue;
+                }
+
+                // Drain everything received since the last pass
+                loop {
                     match network.receive_audio_frame() {
-                        Ok(audio_data) => {
-                            if !audio_data.is_empty() {
-                                // THIS IS A STUB - Would queue for audio playback
-                                // In Phase 2, this will integrate with the real-time audio processor
-                                if log::log_enabled!(log::Level::Debug) {
-                                    info!("Received audio frame: {} bytes", audio_data.len());
+                        Ok(Some(received)) => {
+                            rate_adaptation.on_audio(&received);
+                            if let Some(producer) = received_producer.as_mut() {
+                                // Queue for the playback pipeline (jitter buffer + decoder)
+                                if producer.try_push(received).is_err() {
+                                    warn!("Playback queue full, dropping received audio frame");
                                 }
+                            } else {
+                                debug!("Received audio frame {} ({} bytes) with no playback pipeline",
+                                      received.sequence_number, received.payload.len());
                             }
                         }
+                        Ok(None) => break,
                         Err(e) => {
                             error!("Network receive error: {}", e);
-                            break;
+                            break 'receive;
                         }
                     }
                 }
+
+                rate_adaptation.exchange(&mut network, &runtime);
+
+                match runtime.block_on(Self::persist_verification_request(&network, &terminal)) {
+                    Ok(true) => info!("Peer verified; its identity key is now pinned as verified"),
+                    Ok(false) => {}
+                    Err(e) => warn!("Failed to record peer verification: {}", e),
+                }
             }
         }
         info!("Network processing loop stopped");
@@ -263,6 +607,7 @@ impl VocalCommunicationApp {
     pub fn stop(&mut self) {
         info!("Stopping voice communication app...");
         self.is_running = false;
+        self.threads_running.store(false, std::sync::atomic::Ordering::Relaxed);
 
         // Stop real-time audio processor
         if let Some(ref mut realtime_audio) = self.realtime_audio {
@@ -281,33 +626,118 @@ impl VocalCommunicationApp {
         self.realtime_audio.as_ref().map(|processor| processor.get_stats())
     }
 
-    pub async fn connect_to_peer(&self, host: &str, port: u16) -> Result<()> {
-        // Create new security config for this connection
-        let security_config = SecurityConfig::new()?;
+    /// Per-participant gain, mute and speaking levels for multi-party calls
+    pub fn mixer(&self) -> Option<crate::mixer::MixerHandle> {
+        self.realtime_audio.as_ref().map(|processor| processor.mixer())
+    }
+
+    pub async fn connect_to_peer(&mut self, host: &str, port: u16) -> Result<()> {
+        // Reuse our persistent identity for this connection
+        let mut security_config = self.identity.clone();
+        self.config_manager.get_config().apply_to_security_config(&mut security_config);
 
         let config = ConnectionConfig {
             remote_host: host.to_string(),
             port,
+            local_port: None,
             use_encryption: true, // ASSUMPTION: Always use encryption for security
             security_config: Some(security_config),
+            max_peers: self.config_manager.get_config().network.max_peers,
+            turn_server: self.config_manager.get_config().turn_server_addr(),
+            transport: self.config_manager.get_config().network.transport,
         };
 
+        let mut negotiated = None;
         if let Ok(mut network) = self.network_manager.lock() {
-            network.update_config(config);
+            network.update_config(config).await;
             network.establish_connection().await?;
+            negotiated = network.negotiated_audio().await;
+            self.show_safety_code(&network).await;
 
             if let Ok(mut ui) = self.user_interface.lock() {
                 ui.show_connection_status(true);
             }
         }
 
+        if let Some(audio) = negotiated {
+            self.apply_call_audio(audio)?;
+        }
         Ok(())
     }
 
+    /// Wait for a peer to call us and complete the handshake, returning its address
+    // The worker threads share the manager through a std Mutex, as in `connect_to_peer`
+    #[allow(clippy::await_holding_lock)]
+    pub async fn accept_peer(&mut self) -> Result<SocketAddr> {
+        let (peer_addr, negotiated) = {
+            let mut network = self.network_manager.lock()
+                .map_err(|_| anyhow::anyhow!("Network manager lock poisoned"))?;
+            let peer_addr = network.accept_connection().await?;
+            self.show_safety_code(&network).await;
+            (peer_addr, network.negotiated_audio().await)
+        };
+
+        if let Ok(mut ui) = self.user_interface.lock() {
+            ui.show_connection_status(true);
+        }
+        if let Some(audio) = negotiated {
+            self.apply_call_audio(audio)?;
+        }
+        Ok(peer_addr)
+    }
+
+    /// Shared terminal UI state, for `run_shared_terminal_ui`
+    pub fn terminal_app(&self) -> Arc<Mutex<TerminalApp>> {
+        self.terminal.clone()
+    }
+
+    /// Put the active peer's safety code in the terminal UI, with whether it was verified before
+    async fn show_safety_code(&self, network: &NetworkManager) {
+        let code = network.short_auth_string().await;
+        let verified = network.is_peer_verified().await;
+        if let Ok(mut terminal) = self.terminal.lock() {
+            terminal.enter_call(code, verified);
+        }
+    }
+
+    /// Persist the safety code confirmation the user gave in the terminal UI, if any.
+    /// Returns whether a confirmation was recorded.
+    // The worker threads share the manager through a std Mutex, as in `connect_to_peer`
+    #[allow(clippy::await_holding_lock)]
+    pub async fn persist_verification(&self) -> Result<bool> {
+        let network = self.network_manager.lock()
+            .map_err(|_| anyhow::anyhow!("Network manager lock poisoned"))?;
+        Self::persist_verification_request(&network, &self.terminal).await
+    }
+
+    async fn persist_verification_request(network: &NetworkManager, terminal: &Mutex<TerminalApp>) -> Result<bool> {
+        let requested = terminal.lock().is_ok_and(|mut terminal| terminal.take_verification_request());
+        if !requested {
+            return Ok(false);
+        }
+
+        if let Err(e) = network.confirm_peer_verified().await {
+            // Show the peer as unverified again so the user can retry
+            if let Ok(mut terminal) = terminal.lock() {
+                terminal.peer_verified = false;
+            }
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    /// Shared handle to the network manager, for sending and receiving outside `start`
+    pub fn network_manager(&self) -> Arc<Mutex<NetworkManager>> {
+        self.network_manager.clone()
+    }
+
     pub fn disconnect_from_peer(&self) {
         if let Ok(mut network) = self.network_manager.lock() {
             network.disconnect();
 
+            if let Ok(mut terminal) = self.terminal.lock() {
+                terminal.set_safety_code(None, false);
+            }
             if let Ok(mut ui) = self.user_interface.lock() {
                 ui.show_connection_status(false);
             }
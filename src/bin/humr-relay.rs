@@ -0,0 +1,65 @@
+use anyhow::{Result, anyhow};
+use clap::{Arg, Command};
+use humr::known_peers::parse_trusted_keys;
+use humr::relay::{RelayConfig, RelayServer};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Initialize logging
+    env_logger::init();
+
+    let matches = Command::new("humr-relay")
+        .version("0.1.0")
+        .author("Humr Development Team")
+        .about("Selective forwarding relay for Humr group calls")
+        .arg(
+            Arg::new("bind")
+                .long("bind")
+                .value_name("ADDR")
+                .help("Address to listen on")
+                .default_value("0.0.0.0")
+        )
+        .arg(
+            Arg::new("port")
+                .long("port")
+                .value_name("PORT")
+                .help("UDP port to listen on")
+                .value_parser(clap::value_parser!(u16))
+                .default_value("8090")
+        )
+        .arg(
+            Arg::new("max-participants")
+                .long("max-participants")
+                .value_name("COUNT")
+                .help("Capacity of rooms created on first join")
+                .value_parser(clap::value_parser!(u8).range(2..))
+                .default_value("10")
+        )
+        .arg(
+            Arg::new("allow")
+                .long("allow")
+                .value_name("IDENTITY_KEY")
+                .help("Base64 identity key allowed to join (repeatable; default admits any)")
+                .action(clap::ArgAction::Append)
+        )
+        .get_matches();
+
+    let bind = matches.get_one::<String>("bind").unwrap();
+    let port = *matches.get_one::<u16>("port").unwrap();
+
+    let allowed: Vec<String> = matches.get_many::<String>("allow").unwrap_or_default().cloned().collect();
+    let allowed_identities = parse_trusted_keys(&allowed);
+    if allowed_identities.len() != allowed.len() {
+        return Err(anyhow!("Invalid identity key in --allow"));
+    }
+
+    let config = RelayConfig {
+        default_max_participants: *matches.get_one::<u8>("max-participants").unwrap(),
+        allowed_identities,
+        ..RelayConfig::default()
+    };
+
+    let server = RelayServer::bind(&format!("{}:{}", bind, port), config).await?;
+    println!("📡 humr-relay listening on {}", server.local_addr()?);
+    server.run().await
+}
@@ -0,0 +1,74 @@
+use anyhow::{Result, anyhow};
+use clap::{Arg, Command};
+use humr::known_peers::parse_trusted_keys;
+use humr::turn::{TurnConfig, TurnServer};
+use std::net::IpAddr;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Initialize logging
+    env_logger::init();
+
+    let matches = Command::new("humr-turn")
+        .version("0.1.0")
+        .author("Humr Development Team")
+        .about("TURN-style relay for Humr peers that cannot reach each other directly")
+        .arg(
+            Arg::new("bind")
+                .long("bind")
+                .value_name("ADDR")
+                .help("Address to listen on")
+                .default_value("0.0.0.0")
+        )
+        .arg(
+            Arg::new("port")
+                .long("port")
+                .value_name("PORT")
+                .help("UDP port to listen on (also answers STUN)")
+                .value_parser(clap::value_parser!(u16))
+                .default_value("3478")
+        )
+        .arg(
+            Arg::new("max-allocations")
+                .long("max-allocations")
+                .value_name("COUNT")
+                .help("Maximum number of relayed addresses handed out at once")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("64")
+        )
+        .arg(
+            Arg::new("external-ip")
+                .long("external-ip")
+                .value_name("IP")
+                .help("Public IP to advertise in relayed addresses when behind a 1:1 NAT")
+                .value_parser(clap::value_parser!(IpAddr))
+        )
+        .arg(
+            Arg::new("allow")
+                .long("allow")
+                .value_name("IDENTITY_KEY")
+                .help("Base64 identity key allowed to allocate (repeatable; default admits any)")
+                .action(clap::ArgAction::Append)
+        )
+        .get_matches();
+
+    let bind = matches.get_one::<String>("bind").unwrap();
+    let port = *matches.get_one::<u16>("port").unwrap();
+
+    let allowed: Vec<String> = matches.get_many::<String>("allow").unwrap_or_default().cloned().collect();
+    let allowed_identities = parse_trusted_keys(&allowed);
+    if allowed_identities.len() != allowed.len() {
+        return Err(anyhow!("Invalid identity key in --allow"));
+    }
+
+    let config = TurnConfig {
+        max_allocations: *matches.get_one::<usize>("max-allocations").unwrap(),
+        allowed_identities,
+        external_ip: matches.get_one::<IpAddr>("external-ip").copied(),
+        ..TurnConfig::default()
+    };
+
+    let server = TurnServer::bind(&format!("{}:{}", bind, port), config).await?;
+    println!("📡 humr-turn listening on {}", server.local_addr()?);
+    server.run().await
+}
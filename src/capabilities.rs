@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+
+use crate::codec::CodecKind;
+use crate::opus_codec::OpusConfig;
+use crate::realtime_audio::AudioFormat;
+use crate::wire::{Reader, WireError};
+
+/// Version of the audio payloads (redundant blocks and feedback reports) a peer sends
+pub const AUDIO_PROTOCOL_VERSION: u8 = 1;
+
+/// FEC and DTX flag bits in an encoded offer
+const FLAG_FEC: u8 = 0x01;
+const FLAG_DTX: u8 = 0x02;
+
+/// Audio a peer can send and receive, carried in the signed handshake.
+/// Lists are in order of preference; the initiator's order wins.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityOffer {
+    pub protocol_version: u8,
+    pub codecs: Vec<CodecKind>,
+    pub sample_rates: Vec<u32>,
+    pub channels: Vec<u16>,
+    pub frame_durations_ms: Vec<u32>,
+    /// Opus in-band FEC
+    pub fec: bool,
+    /// Opus discontinuous transmission
+    pub dtx: bool,
+}
+
+impl Default for CapabilityOffer {
+    fn default() -> Self {
+        Self::new(vec![CodecKind::Opus], AudioFormat::STANDARD, &OpusConfig::default())
+    }
+}
+
+impl CapabilityOffer {
+    /// Offer `codecs` in every format the pipelines run, `preferred` first, with FEC and
+    /// DTX as `opus` sets them when Opus is among them
+    pub fn new(codecs: Vec<CodecKind>, preferred: AudioFormat, opus: &OpusConfig) -> Self {
+        fn preferred_first<T: Copy + PartialEq>(preferred: T, all: &[T]) -> Vec<T> {
+            std::iter::once(preferred).chain(all.iter().copied().filter(|&value| value != preferred)).collect()
+        }
+
+        let offers_opus = codecs.contains(&CodecKind::Opus);
+        Self {
+            protocol_version: AUDIO_PROTOCOL_VERSION,
+            codecs,
+            sample_rates: preferred_first(preferred.sample_rate, &AudioFormat::SAMPLE_RATES),
+            channels: preferred_first(preferred.channels, &AudioFormat::CHANNEL_COUNTS),
+            frame_durations_ms: preferred_first(preferred.frame_duration_ms, &AudioFormat::FRAME_DURATIONS_MS),
+            fec: offers_opus && opus.fec_enabled,
+            dtx: offers_opus && opus.dtx_enabled,
+        }
+    }
+
+    /// Offer a single codec in a single format, with FEC and DTX as `opus` sets them
+    pub fn for_format(codec: CodecKind, format: AudioFormat, opus: &OpusConfig) -> Self {
+        let is_opus = codec == CodecKind::Opus;
+        Self {
+            protocol_version: AUDIO_PROTOCOL_VERSION,
+            codecs: vec![codec],
+            sample_rates: vec![format.sample_rate],
+            channels: vec![format.channels],
+            frame_durations_ms: vec![format.frame_duration_ms],
+            fec: is_opus && opus.fec_enabled,
+            dtx: is_opus && opus.dtx_enabled,
+        }
+    }
+
+    /// Settle on the best format both offers allow. The initiator's preferences order the
+    /// choice, so both sides reach the same answer; errors are worded from our side.
+    pub fn negotiate(&self, peer: &CapabilityOffer, we_initiated: bool) -> Result<NegotiatedAudio, NegotiationError> {
+        if self.protocol_version != peer.protocol_version {
+            return Err(NegotiationError::ProtocolVersion { ours: self.protocol_version, theirs: peer.protocol_version });
+        }
+
+        let (initiator, responder) = if we_initiated { (self, peer) } else { (peer, self) };
+
+        let frame_duration_ms = first_common(&initiator.frame_durations_ms, &responder.frame_durations_ms)
+            .ok_or_else(|| NegotiationError::NoCommonFrameDuration {
+                ours: self.frame_durations_ms.clone(),
+                theirs: peer.frame_durations_ms.clone(),
+            })?;
+
+        let common_codecs: Vec<CodecKind> = initiator.codecs.iter().copied()
+            .filter(|codec| responder.codecs.contains(codec))
+            .collect();
+        if common_codecs.is_empty() {
+            return Err(NegotiationError::NoCommonCodec { ours: self.codecs.clone(), theirs: peer.codecs.clone() });
+        }
+
+        // Opus takes the best rate and channel count both sides run; the fixed-rate
+        // codecs need their own rate and channel count on both sides
+        let opus_format = first_common(&initiator.sample_rates, &responder.sample_rates)
+            .zip(first_common(&initiator.channels, &responder.channels))
+            .map(|(sample_rate, channels)| AudioFormat { sample_rate, channels, frame_duration_ms });
+        let runs = |offer: &CapabilityOffer, format: AudioFormat| {
+            offer.sample_rates.contains(&format.sample_rate) && offer.channels.contains(&format.channels)
+        };
+
+        let (codec, format) = common_codecs.iter().copied()
+            .find_map(|codec| match codec {
+                CodecKind::Opus => opus_format.map(|format| (codec, format)),
+                _ => Some(codec.format(AudioFormat { frame_duration_ms, ..AudioFormat::STANDARD }))
+                    .filter(|&format| runs(initiator, format) && runs(responder, format))
+                    .map(|format| (codec, format)),
+            })
+            .ok_or_else(|| NegotiationError::NoCommonFormat {
+                codecs: common_codecs.clone(),
+                ours: (self.sample_rates.clone(), self.channels.clone()),
+                theirs: (peer.sample_rates.clone(), peer.channels.clone()),
+            })?;
+
+        let is_opus = codec == CodecKind::Opus;
+        Ok(NegotiatedAudio {
+            codec,
+            format,
+            fec: is_opus && self.fec && peer.fec,
+            dtx: is_opus && self.dtx && peer.dtx,
+        })
+    }
+
+    /// Append the offer to a handshake body.
+    ///
+    /// Layout: protocol version (1), then codec payload types, sample rates (4 each),
+    /// channel counts (1 each) and frame durations in ms (1 each), each list led by
+    /// its length (1), then FEC/DTX flags (1)
+    pub fn encode(&self, body: &mut Vec<u8>) -> Result<(), WireError> {
+        fn list_len(len: usize) -> Result<u8, WireError> {
+            u8::try_from(len).map_err(|_| WireError::InvalidBody("capability list too long"))
+        }
+
+        body.push(self.protocol_version);
+        body.push(list_len(self.codecs.len())?);
+        body.extend(self.codecs.iter().map(|codec| codec.payload_type()));
+        body.push(list_len(self.sample_rates.len())?);
+        for sample_rate in &self.sample_rates {
+            body.extend_from_slice(&sample_rate.to_be_bytes());
+        }
+        body.push(list_len(self.channels.len())?);
+        for &channels in &self.channels {
+            body.push(u8::try_from(channels).map_err(|_| WireError::InvalidBody("channel count out of range"))?);
+        }
+        body.push(list_len(self.frame_durations_ms.len())?);
+        for &frame_duration_ms in &self.frame_durations_ms {
+            body.push(u8::try_from(frame_duration_ms).map_err(|_| WireError::InvalidBody("frame duration out of range"))?);
+        }
+
+        let mut flags = 0;
+        if self.fec {
+            flags |= FLAG_FEC;
+        }
+        if self.dtx {
+            flags |= FLAG_DTX;
+        }
+        body.push(flags);
+        Ok(())
+    }
+
+    /// Read an offer written by [`CapabilityOffer::encode`]
+    pub(crate) fn decode(reader: &mut Reader) -> Result<Self, WireError> {
+        if reader.remaining() == 0 {
+            return Err(WireError::InvalidBody("handshake carries no capability offer"));
+        }
+        let protocol_version = reader.u8()?;
+
+        // Codecs we do not know are passed over, so negotiation can say what is missing
+        let count = reader.u8()? as usize;
+        let codecs = reader.take(count)?.iter()
+            .filter_map(|&payload_type| CodecKind::from_payload_type(payload_type))
+            .collect();
+        let count = reader.u8()? as usize;
+        let sample_rates = (0..count).map(|_| reader.u32()).collect::<Result<_, _>>()?;
+        let count = reader.u8()? as usize;
+        let channels = reader.take(count)?.iter().map(|&channels| channels as u16).collect();
+        let count = reader.u8()? as usize;
+        let frame_durations_ms = reader.take(count)?.iter().map(|&duration| duration as u32).collect();
+        let flags = reader.u8()?;
+
+        Ok(Self {
+            protocol_version,
+            codecs,
+            sample_rates,
+            channels,
+            frame_durations_ms,
+            fec: flags & FLAG_FEC != 0,
+            dtx: flags & FLAG_DTX != 0,
+        })
+    }
+}
+
+/// First of `preferred` that `other` also lists
+fn first_common<T: Copy + PartialEq>(preferred: &[T], other: &[T]) -> Option<T> {
+    preferred.iter().copied().find(|value| other.contains(value))
+}
+
+/// Audio both sides of a call settled on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NegotiatedAudio {
+    pub codec: CodecKind,
+    pub format: AudioFormat,
+    pub fec: bool,
+    pub dtx: bool,
+}
+
+impl std::fmt::Display for NegotiatedAudio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+        write!(f, "{}, {}, FEC {}, DTX {}", self.codec, self.format, on_off(self.fec), on_off(self.dtx))
+    }
+}
+
+/// Why two peers' offers have nothing in common
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NegotiationError {
+    ProtocolVersion { ours: u8, theirs: u8 },
+    NoCommonCodec { ours: Vec<CodecKind>, theirs: Vec<CodecKind> },
+    NoCommonFrameDuration { ours: Vec<u32>, theirs: Vec<u32> },
+    /// The shared codecs, and each side's sample rates and channel counts
+    NoCommonFormat { codecs: Vec<CodecKind>, ours: (Vec<u32>, Vec<u16>), theirs: (Vec<u32>, Vec<u16>) },
+}
+
+impl std::fmt::Display for NegotiationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn join<T: std::fmt::Display>(items: &[T], unit: &str) -> String {
+            items.iter().map(|item| format!("{}{}", item, unit)).collect::<Vec<_>>().join(", ")
+        }
+
+        match self {
+            NegotiationError::ProtocolVersion { ours, theirs } =>
+                write!(f, "Peer speaks audio protocol version {}, we speak version {}", theirs, ours),
+            NegotiationError::NoCommonCodec { ours, theirs } =>
+                write!(f, "No codec in common: we offer {}; peer offers {}", join(ours, ""), join(theirs, "")),
+            NegotiationError::NoCommonFrameDuration { ours, theirs } =>
+                write!(f, "No frame size in common: we use {}; peer uses {}", join(ours, " ms"), join(theirs, " ms")),
+            NegotiationError::NoCommonFormat { codecs, ours, theirs } => write!(
+                f,
+                "No audio format in common for {}: we run {} with {} channels; peer runs {} with {} channels",
+                join(codecs, ""),
+                join(&ours.0, " Hz"),
+                join(&ours.1, ""),
+                join(&theirs.0, " Hz"),
+                join(&theirs.1, ""),
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NegotiationError {}
@@ -0,0 +1,216 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::congestion::EncoderTarget;
+use crate::g711::{G711Codec, G711Law};
+use crate::g722::G722Codec;
+use crate::opus_codec::{OpusCodec, OpusConfig};
+use crate::realtime_audio::{AudioFormat, AudioFrame};
+use crate::redundancy::PAYLOAD_TYPE_OPUS;
+
+/// Voice codecs a call can use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodecKind {
+    /// Opus at the configured format and an adaptive bitrate
+    #[default]
+    Opus,
+    /// G.722 sub-band ADPCM: 16 kHz mono at 64 kbps
+    G722,
+    /// G.711 μ-law: 8 kHz mono at 64 kbps
+    Pcmu,
+    /// G.711 A-law: 8 kHz mono at 64 kbps
+    Pcma,
+}
+
+impl CodecKind {
+    /// Every codec, best quality per bit first
+    pub const ALL: [CodecKind; 4] = [CodecKind::Opus, CodecKind::G722, CodecKind::Pcmu, CodecKind::Pcma];
+
+    /// Payload type marking this codec's blocks (the static RTP types for G.711 and G.722)
+    pub fn payload_type(self) -> u8 {
+        match self {
+            CodecKind::Opus => PAYLOAD_TYPE_OPUS,
+            CodecKind::Pcmu => 0,
+            CodecKind::Pcma => 8,
+            CodecKind::G722 => 9,
+        }
+    }
+
+    pub fn from_payload_type(payload_type: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.payload_type() == payload_type)
+    }
+
+    /// Format of this codec's frames. Opus takes `preferred` as is; the fixed-rate
+    /// codecs keep only its frame duration.
+    pub fn format(self, preferred: AudioFormat) -> AudioFormat {
+        let (sample_rate, channels) = match self {
+            CodecKind::Opus => return preferred,
+            CodecKind::G722 => (16000, 1),
+            CodecKind::Pcmu | CodecKind::Pcma => (8000, 1),
+        };
+        AudioFormat { sample_rate, channels, ..preferred }
+    }
+
+    /// Whether `VoiceCodec::set_bitrate` changes the encoding
+    pub fn has_adjustable_bitrate(self) -> bool {
+        self == CodecKind::Opus
+    }
+
+    /// Build a codec; Opus uses all of `opus`, the others only its frame duration
+    pub fn create(self, opus: &OpusConfig) -> Result<Box<dyn VoiceCodec>> {
+        let frame_duration_ms = opus.format().frame_duration_ms;
+        Ok(match self {
+            CodecKind::Opus => Box::new(OpusCodec::new(opus.clone())?),
+            CodecKind::G722 => Box::new(G722Codec::new(frame_duration_ms)?),
+            CodecKind::Pcmu => Box::new(G711Codec::new(G711Law::MuLaw, frame_duration_ms)?),
+            CodecKind::Pcma => Box::new(G711Codec::new(G711Law::ALaw, frame_duration_ms)?),
+        })
+    }
+}
+
+impl std::fmt::Display for CodecKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CodecKind::Opus => "Opus",
+            CodecKind::G722 => "G.722",
+            CodecKind::Pcmu => "G.711 μ-law",
+            CodecKind::Pcma => "G.711 A-law",
+        })
+    }
+}
+
+/// Voice codec statistics
+#[derive(Debug, Clone)]
+pub struct CodecStats {
+    pub codec: CodecKind,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bitrate: u32,
+    /// Encoder complexity; 0 for codecs without the setting
+    pub complexity: u32,
+    /// Loss the encoder is tuned for; 0 for codecs without the setting
+    pub packet_loss_percent: u8,
+    pub frame_duration_ms: u32,
+    pub frames_encoded: u64,
+    pub frames_decoded: u64,
+    pub encoding_errors: u64,
+    pub decoding_errors: u64,
+    pub total_bytes_encoded: u64,
+    pub average_compression_ratio: f64,
+}
+
+/// Encoder and decoder for one codec, one frame at a time
+pub trait VoiceCodec: Send {
+    fn kind(&self) -> CodecKind;
+
+    /// Format of the frames encoded and decoded
+    fn format(&self) -> AudioFormat;
+
+    fn encode(&mut self, frame: &AudioFrame) -> Result<Vec<u8>>;
+
+    fn decode(&mut self, data: &[u8]) -> Result<AudioFrame>;
+
+    /// Packet loss concealment: a stand-in for one lost frame
+    fn conceal(&mut self) -> Result<AudioFrame>;
+
+    /// Rebuild a lost frame from in-band FEC in the packet after it
+    fn decode_fec(&mut self, _next_packet: &[u8]) -> Result<AudioFrame> {
+        Err(anyhow!("{} carries no in-band FEC", self.kind()))
+    }
+
+    fn bitrate(&self) -> u32;
+
+    /// Change the target bitrate; fixed-rate codecs ignore it
+    fn set_bitrate(&mut self, bitrate: u32) -> Result<()>;
+
+    /// Follow the congestion controller; codecs without other settings only take the bitrate
+    fn apply_target(&mut self, target: &EncoderTarget) -> Result<()> {
+        if target.bitrate != self.bitrate() {
+            self.set_bitrate(target.bitrate)?;
+        }
+        Ok(())
+    }
+
+    fn stats(&self) -> CodecStats;
+
+    /// Drop encoder and decoder history, e.g. after a connection drop
+    fn reset(&mut self) -> Result<()>;
+}
+
+/// Frame counters shared by the waveform codecs
+#[derive(Debug, Default)]
+pub(crate) struct CodecCounters {
+    pub frames_encoded: u64,
+    pub frames_decoded: u64,
+    pub encoding_errors: u64,
+    pub decoding_errors: u64,
+    pub total_bytes_encoded: u64,
+}
+
+impl CodecCounters {
+    pub fn stats(&self, codec: CodecKind, format: AudioFormat, bitrate: u32) -> CodecStats {
+        let average_compression_ratio = if self.total_bytes_encoded > 0 {
+            let uncompressed_bytes = self.frames_encoded * format.frame_samples() as u64 * 4; // 4 bytes per f32
+            uncompressed_bytes as f64 / self.total_bytes_encoded as f64
+        } else {
+            0.0
+        };
+
+        CodecStats {
+            codec,
+            sample_rate: format.sample_rate,
+            channels: format.channels,
+            bitrate,
+            complexity: 0,
+            packet_loss_percent: 0,
+            frame_duration_ms: format.frame_duration_ms,
+            frames_encoded: self.frames_encoded,
+            frames_decoded: self.frames_decoded,
+            encoding_errors: self.encoding_errors,
+            decoding_errors: self.decoding_errors,
+            total_bytes_encoded: self.total_bytes_encoded,
+            average_compression_ratio,
+        }
+    }
+}
+
+/// Concealment for codecs without their own: repeat the last decoded frame, halving its level each time
+#[derive(Debug, Default)]
+pub(crate) struct FadeConcealer {
+    last: Vec<f32>,
+    gain: f32,
+}
+
+impl FadeConcealer {
+    /// Remember a frame that decoded normally
+    pub fn update(&mut self, samples: &[f32]) {
+        self.last.clear();
+        self.last.extend_from_slice(samples);
+        self.gain = 1.0;
+    }
+
+    pub fn conceal(&mut self, format: AudioFormat) -> AudioFrame {
+        self.gain *= 0.5;
+        if self.last.len() != format.frame_samples() {
+            return AudioFrame::silence_in(format);
+        }
+        AudioFrame::with_format(self.last.iter().map(|sample| sample * self.gain).collect(), format)
+    }
+
+    pub fn reset(&mut self) {
+        self.last.clear();
+    }
+}
+
+/// Frame samples as 16-bit PCM, checking the frame is in `format`
+pub(crate) fn frame_to_pcm(frame: &AudioFrame, format: AudioFormat) -> Result<Vec<i16>> {
+    if frame.samples.len() != format.frame_samples() {
+        return Err(anyhow!("Expected {} samples of {}, got {}", format.frame_samples(), format, frame.samples.len()));
+    }
+    Ok(frame.samples.iter().map(|sample| (sample.clamp(-1.0, 1.0) * 32767.0) as i16).collect())
+}
+
+pub(crate) fn pcm_to_frame(pcm: impl Iterator<Item = i16>, format: AudioFormat) -> AudioFrame {
+    AudioFrame::with_format(pcm.map(|sample| sample as f32 / 32767.0).collect(), format)
+}
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
+use std::net::{SocketAddr, ToSocketAddrs};
 use anyhow::{Result, Context};
 use log::{info, warn, error};
 
@@ -8,7 +9,15 @@ use crate::audio::AudioProcessor;
 use crate::opus_codec::OpusConfig;
 use crate::noise_suppression::NoiseSuppressionConfig;
 use crate::echo_cancellation::EchoCancellationConfig;
-use crate::network::ConnectionConfig;
+use crate::network::{ConnectionConfig, DEFAULT_MAX_PEERS};
+use crate::known_peers::{self, TrustPolicy};
+use crate::security::SecurityConfig;
+use crate::transport::TransportKind;
+use crate::congestion::{CongestionConfig, EncoderTarget, RedundancySwitch};
+use crate::redundancy::{RedundancyConfig, RedundancyMode};
+use crate::realtime_audio::{AudioConfiguration, AudioFormat, AudioProfile};
+use crate::codec::CodecKind;
+use crate::capabilities::{CapabilityOffer, NegotiatedAudio};
 
 /// Persistent application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,13 +47,30 @@ pub struct NetworkSettings {
     pub auto_connect: bool,
     pub connection_timeout_ms: u32,
     pub keepalive_interval_ms: u32,
+    /// Maximum number of simultaneous peers in a call
+    #[serde(default = "default_max_peers")]
+    pub max_peers: usize,
+    /// TURN-style relay (host:port) to fall back to when direct UDP fails
+    #[serde(default)]
+    pub turn_server: Option<String>,
+    /// Packet transport: udp, or tcp/websocket where outbound UDP is blocked
+    #[serde(default)]
+    pub transport: TransportKind,
+}
+
+fn default_max_peers() -> usize {
+    DEFAULT_MAX_PEERS
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecuritySettings {
     pub encryption_enabled: bool,
     pub key_rotation_interval_ms: u32,
+    /// Base64 Ed25519 identity keys that are always trusted
     pub trusted_peers: Vec<String>,
+    /// How unknown peers are treated: strict, tofu or permissive
+    #[serde(default)]
+    pub trust_policy: TrustPolicy,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,10 +98,61 @@ pub struct EchoCancellationSettings {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CodecSettings {
+    /// Sample rate, channels and frame duration: standard (48 kHz stereo, 20 ms)
+    /// or low_bandwidth (16 kHz mono, 60 ms)
+    #[serde(default)]
+    pub profile: AudioProfile,
+    /// Codecs in order of preference: opus, g722, pcmu, pcma. Audio runs the first.
+    #[serde(default = "default_codecs")]
+    pub codecs: Vec<CodecKind>,
     pub bitrate: u32,
     pub complexity: u32,
     pub fec_enabled: bool,
     pub dtx_enabled: bool,
+    /// Packet loss (%) in-band FEC protects against until receiver feedback updates it
+    #[serde(default = "default_packet_loss_percent")]
+    pub packet_loss_percent: u8,
+    /// Let receiver feedback steer the bitrate between `min_bitrate` and `max_bitrate`
+    #[serde(default = "default_adaptive_bitrate")]
+    pub adaptive_bitrate: bool,
+    #[serde(default = "default_min_bitrate")]
+    pub min_bitrate: u32,
+    #[serde(default = "default_max_bitrate")]
+    pub max_bitrate: u32,
+    /// Repeat earlier frames in each packet: off, auto (while feedback reports loss) or always
+    #[serde(default = "default_redundancy")]
+    pub redundancy: RedundancyMode,
+    /// How many earlier frames each packet repeats
+    #[serde(default = "default_redundancy_depth")]
+    pub redundancy_depth: usize,
+}
+
+fn default_codecs() -> Vec<CodecKind> {
+    CodecKind::ALL.to_vec()
+}
+
+fn default_packet_loss_percent() -> u8 {
+    OpusConfig::default().packet_loss_percent
+}
+
+fn default_adaptive_bitrate() -> bool {
+    true
+}
+
+fn default_min_bitrate() -> u32 {
+    CongestionConfig::default().min_bitrate
+}
+
+fn default_max_bitrate() -> u32 {
+    CongestionConfig::default().max_bitrate
+}
+
+fn default_redundancy() -> RedundancyMode {
+    RedundancyMode::Auto
+}
+
+fn default_redundancy_depth() -> usize {
+    RedundancyConfig::default().depth
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -120,6 +197,9 @@ impl Default for NetworkSettings {
             auto_connect: false,
             connection_timeout_ms: 5000,
             keepalive_interval_ms: 30000,
+            max_peers: DEFAULT_MAX_PEERS,
+            turn_server: None,
+            transport: TransportKind::Udp,
         }
     }
 }
@@ -130,6 +210,7 @@ impl Default for SecuritySettings {
             encryption_enabled: true,
             key_rotation_interval_ms: 300000, // 5 minutes
             trusted_peers: Vec::new(),
+            trust_policy: TrustPolicy::default(),
         }
     }
 }
@@ -169,10 +250,18 @@ impl Default for EchoCancellationSettings {
 impl Default for CodecSettings {
     fn default() -> Self {
         Self {
+            profile: AudioProfile::default(),
+            codecs: default_codecs(),
             bitrate: 64000,
             complexity: 5,
             fec_enabled: true,
             dtx_enabled: true,
+            packet_loss_percent: default_packet_loss_percent(),
+            adaptive_bitrate: default_adaptive_bitrate(),
+            min_bitrate: default_min_bitrate(),
+            max_bitrate: default_max_bitrate(),
+            redundancy: default_redundancy(),
+            redundancy_depth: default_redundancy_depth(),
         }
     }
 }
@@ -285,29 +374,123 @@ impl ConfigManager {
         Ok(())
     }
 
-    fn get_config_path() -> Result<PathBuf> {
-        let config_dir = if let Some(config_dir) = dirs::config_dir() {
-            config_dir.join("humr")
+    /// Directory holding the configuration file and identity keystore
+    pub fn config_dir() -> Result<PathBuf> {
+        if let Some(config_dir) = dirs::config_dir() {
+            Ok(config_dir.join("humr"))
         } else {
             // Fallback to home directory
             let home_dir = dirs::home_dir()
                 .context("Could not determine home directory")?;
-            home_dir.join(".humr")
-        };
+            Ok(home_dir.join(".humr"))
+        }
+    }
 
-        Ok(config_dir.join("config.toml"))
+    fn get_config_path() -> Result<PathBuf> {
+        Ok(Self::config_dir()?.join("config.toml"))
     }
 }
 
 // Conversion methods to integrate with existing systems
 impl AppConfig {
+    /// Codec audio runs with, and the one calls offer
+    pub fn preferred_codec(&self) -> CodecKind {
+        self.processing.codec.codecs.first().copied().unwrap_or_default()
+    }
+
+    /// Format every audio stage runs at: the profile's, as far as the preferred codec allows
+    pub fn to_audio_format(&self) -> AudioFormat {
+        self.preferred_codec().format(self.processing.codec.profile.format())
+    }
+
+    pub fn to_audio_configuration(&self) -> AudioConfiguration {
+        AudioConfiguration::default().with_format(self.to_audio_format())
+    }
+
     pub fn to_opus_config(&self) -> OpusConfig {
         OpusConfig {
             bitrate: self.processing.codec.bitrate,
             complexity: self.processing.codec.complexity,
             fec_enabled: self.processing.codec.fec_enabled,
             dtx_enabled: self.processing.codec.dtx_enabled,
-            ..OpusConfig::default()
+            packet_loss_percent: self.processing.codec.packet_loss_percent,
+            ..OpusConfig::default().with_format(self.processing.codec.profile.format())
+        }
+    }
+
+    /// Opus settings for audio a call settled on: its format, FEC and DTX, with the
+    /// configured bitrate and complexity. Other codecs only use the frame duration.
+    pub fn to_call_opus_config(&self, audio: &NegotiatedAudio) -> OpusConfig {
+        OpusConfig {
+            fec_enabled: audio.fec,
+            dtx_enabled: audio.dtx,
+            ..self.to_opus_config().with_format(audio.format)
+        }
+    }
+
+    /// Audio to run outside a call, and the first choice offered to peers
+    pub fn preferred_audio(&self) -> NegotiatedAudio {
+        let codec = self.preferred_codec();
+        let is_opus = codec == CodecKind::Opus;
+        NegotiatedAudio {
+            codec,
+            format: self.to_audio_format(),
+            fec: is_opus && self.processing.codec.fec_enabled,
+            dtx: is_opus && self.processing.codec.dtx_enabled,
+        }
+    }
+
+    /// Audio offered in handshakes: every configured codec in every format the pipelines
+    /// run, the profile's first. Calls rebuild the pipelines for whatever is settled on.
+    pub fn to_capability_offer(&self) -> CapabilityOffer {
+        let mut codecs = self.processing.codec.codecs.clone();
+        if codecs.is_empty() {
+            codecs.push(self.preferred_codec());
+        }
+        CapabilityOffer::new(codecs, self.processing.codec.profile.format(), &self.to_opus_config())
+    }
+
+    /// Congestion controller bounds, or `None` when adaptive bitrate is off
+    pub fn to_congestion_config(&self) -> Option<CongestionConfig> {
+        let codec = &self.processing.codec;
+        if !codec.adaptive_bitrate {
+            return None;
+        }
+        let defaults = CongestionConfig::default();
+        Some(CongestionConfig {
+            min_bitrate: codec.min_bitrate,
+            max_bitrate: codec.max_bitrate,
+            initial_bitrate: codec.bitrate.clamp(codec.min_bitrate, codec.max_bitrate.max(codec.min_bitrate)),
+            complexity: codec.complexity,
+            degraded_complexity: defaults.degraded_complexity.min(codec.complexity),
+            packet_loss_percent: codec.packet_loss_percent,
+            redundancy_above_loss: defaults.redundancy_above_loss.filter(|_| codec.redundancy == RedundancyMode::Auto),
+            ..defaults
+        })
+    }
+
+    /// Loss-driven redundancy at a fixed bitrate: `None` unless redundancy is automatic and
+    /// adaptive bitrate, whose controller otherwise decides it, is off
+    pub fn to_redundancy_switch(&self) -> Option<RedundancySwitch> {
+        let codec = &self.processing.codec;
+        if codec.adaptive_bitrate || codec.redundancy != RedundancyMode::Auto {
+            return None;
+        }
+        let threshold = CongestionConfig::default().redundancy_above_loss?;
+        Some(RedundancySwitch::new(threshold, EncoderTarget {
+            bitrate: codec.bitrate,
+            complexity: codec.complexity,
+            mono: false,
+            redundancy: false,
+            packet_loss_percent: codec.packet_loss_percent,
+        }))
+    }
+
+    pub fn to_redundancy_config(&self) -> RedundancyConfig {
+        RedundancyConfig {
+            mode: self.processing.codec.redundancy,
+            depth: self.processing.codec.redundancy_depth,
+            ..RedundancyConfig::default()
         }
     }
 
@@ -328,12 +511,46 @@ impl AppConfig {
         }
     }
 
+    /// In-band rekey interval; zero disables rekeying
+    pub fn key_rotation_interval(&self) -> Option<std::time::Duration> {
+        match self.security.key_rotation_interval_ms {
+            0 => None,
+            ms => Some(std::time::Duration::from_millis(ms as u64)),
+        }
+    }
+
+    /// Apply rekeying and trust settings to a security configuration
+    pub fn apply_to_security_config(&self, security_config: &mut SecurityConfig) {
+        security_config.rekey_interval = self.key_rotation_interval();
+        security_config.trust_policy = self.security.trust_policy;
+        security_config.capabilities = self.to_capability_offer();
+        for key in known_peers::parse_trusted_keys(&self.security.trusted_peers) {
+            security_config.add_trusted_peer(key);
+        }
+    }
+
     pub fn to_connection_config(&self) -> ConnectionConfig {
         ConnectionConfig {
             remote_host: self.network.remote_host.clone(),
             port: self.network.port,
+            local_port: None,
             use_encryption: self.security.encryption_enabled,
             security_config: None, // Will be set separately
+            max_peers: self.network.max_peers,
+            turn_server: self.turn_server_addr(),
+            transport: self.network.transport,
+        }
+    }
+
+    /// Resolve the configured relay, ignoring it when the name does not resolve
+    pub fn turn_server_addr(&self) -> Option<SocketAddr> {
+        let server = self.network.turn_server.as_deref()?;
+        match server.to_socket_addrs() {
+            Ok(mut addrs) => addrs.next(),
+            Err(e) => {
+                warn!("Cannot resolve relay {}: {}", server, e);
+                None
+            }
         }
     }
 
@@ -401,4 +618,72 @@ mod tests {
         let connection_config = config.to_connection_config();
         assert_eq!(connection_config.remote_host, config.network.remote_host);
     }
+
+    #[test]
+    fn test_low_bandwidth_profile() {
+        let mut config: AppConfig = toml::from_str(&toml::to_string(&AppConfig::default()).unwrap()
+            .replace("profile = \"standard\"", "profile = \"low_bandwidth\"")).unwrap();
+        assert_eq!(config.processing.codec.profile, AudioProfile::LowBandwidth);
+
+        let format = config.to_audio_format();
+        assert_eq!((format.sample_rate, format.channels, format.frame_duration_ms), (16000, 1, 60));
+        assert_eq!(config.to_opus_config().format(), format);
+        assert_eq!(config.to_audio_configuration().format(), format);
+
+        config.processing.codec.profile = AudioProfile::Standard;
+        assert_eq!(config.to_opus_config().format(), AudioFormat::default());
+    }
+
+    #[test]
+    fn test_codec_preference() {
+        let mut config = AppConfig::default();
+        assert_eq!(config.processing.codec.codecs, CodecKind::ALL.to_vec());
+        assert_eq!(config.preferred_codec(), CodecKind::Opus);
+
+        let toml = toml::to_string(&config).unwrap();
+        assert!(toml.contains(r#"codecs = ["opus", "g722", "pcmu", "pcma"]"#));
+
+        config.processing.codec.codecs = vec![CodecKind::G722, CodecKind::Pcmu];
+        assert_eq!(config.preferred_codec(), CodecKind::G722);
+        let format = config.to_audio_format();
+        assert_eq!((format.sample_rate, format.channels, format.frame_duration_ms), (16000, 1, 20));
+
+        // Nothing listed falls back to Opus
+        config.processing.codec.codecs.clear();
+        assert_eq!(config.preferred_codec(), CodecKind::Opus);
+    }
+
+    #[test]
+    fn test_auto_redundancy_without_adaptive_bitrate() {
+        let mut config = AppConfig::default();
+        assert_eq!(config.processing.codec.redundancy, RedundancyMode::Auto);
+        assert!(config.to_congestion_config().is_some_and(|congestion| congestion.redundancy_above_loss.is_some()));
+        assert!(config.to_redundancy_switch().is_none());
+
+        // With the controller off, loss reports still switch redundancy at the fixed bitrate
+        config.processing.codec.adaptive_bitrate = false;
+        assert!(config.to_congestion_config().is_none());
+        let target = config.to_redundancy_switch().expect("no redundancy switch").target();
+        assert_eq!(target.bitrate, config.processing.codec.bitrate);
+        assert!(!target.redundancy);
+
+        config.processing.codec.redundancy = RedundancyMode::Off;
+        assert!(config.to_redundancy_switch().is_none());
+    }
+
+    #[test]
+    fn test_call_audio_sets_codec_format() {
+        let config = AppConfig::default();
+        let preferred = config.preferred_audio();
+        assert_eq!((preferred.codec, preferred.format), (CodecKind::Opus, config.to_audio_format()));
+        let opus = config.to_call_opus_config(&preferred);
+        assert_eq!((opus.format(), opus.fec_enabled), (config.to_opus_config().format(), config.processing.codec.fec_enabled));
+
+        // A call's format and FEC/DTX replace the profile's; bitrate stays configured
+        let audio = NegotiatedAudio { format: AudioFormat::LOW_BANDWIDTH, fec: false, dtx: true, ..preferred };
+        let opus = config.to_call_opus_config(&audio);
+        assert_eq!(opus.format(), AudioFormat::LOW_BANDWIDTH);
+        assert!(!opus.fec_enabled && opus.dtx_enabled);
+        assert_eq!(opus.bitrate, config.processing.codec.bitrate);
+    }
 }
\ No newline at end of file
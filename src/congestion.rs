@@ -0,0 +1,468 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::wire::{Reader, WireError};
+
+/// How often a receiver reports on each sender's stream
+pub const FEEDBACK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Leading byte of a feedback report on the control channel
+pub const FEEDBACK_REPORT_TAG: u8 = 0x01;
+
+/// Reports older than this no longer count, e.g. once a peer has left
+const REPORT_TTL: Duration = Duration::from_secs(3);
+
+/// Loss above this backs the bitrate off in proportion to it
+const HIGH_LOSS: f32 = 0.10;
+/// Loss below this leaves room to probe upwards
+const LOW_LOSS: f32 = 0.02;
+/// Loss from here on also drops encoder complexity
+const DEGRADED_LOSS: f32 = 0.05;
+/// Delay growth (ms per second) that means a queue is building on the path
+const OVERUSE_TREND: f32 = 10.0;
+/// Multiplicative decrease when queues build
+const DELAY_BACKOFF: f64 = 0.85;
+/// Additive increase per feedback interval on a clean link
+const ADDITIVE_INCREASE: u32 = 4000;
+/// Granularity of the loss percentage handed to the encoder's in-band FEC
+const LOSS_PERCENT_STEP: u8 = 5;
+
+/// Receiver's view of one sender's stream over the last feedback interval
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeedbackReport {
+    /// Highest frame number received so far
+    pub highest_sequence: u64,
+    /// Fraction of the interval's frames that never arrived
+    pub loss_fraction: f32,
+    /// Interarrival jitter (RFC 3550) in milliseconds
+    pub jitter_ms: f32,
+    /// Growth of one-way delay in ms per second; positive while queues fill
+    pub delay_trend: f32,
+    /// Payload bitrate that actually arrived, in bits per second
+    pub received_bitrate: u32,
+}
+
+impl FeedbackReport {
+    /// Layout: tag (1), highest sequence (8), loss as a 16-bit fraction (2),
+    /// jitter (f32, 4), delay trend (f32, 4), received bitrate (4), all big-endian
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(23);
+        payload.push(FEEDBACK_REPORT_TAG);
+        payload.extend_from_slice(&self.highest_sequence.to_be_bytes());
+        payload.extend_from_slice(&((self.loss_fraction.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16).to_be_bytes());
+        payload.extend_from_slice(&self.jitter_ms.to_bits().to_be_bytes());
+        payload.extend_from_slice(&self.delay_trend.to_bits().to_be_bytes());
+        payload.extend_from_slice(&self.received_bitrate.to_be_bytes());
+        payload
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<Self, WireError> {
+        let mut reader = Reader::new(payload);
+        if reader.array::<1>()?[0] != FEEDBACK_REPORT_TAG {
+            return Err(WireError::InvalidBody("not a feedback report"));
+        }
+        let report = Self {
+            highest_sequence: reader.u64()?,
+            loss_fraction: reader.u16()? as f32 / u16::MAX as f32,
+            jitter_ms: f32::from_bits(reader.u32()?),
+            delay_trend: f32::from_bits(reader.u32()?),
+            received_bitrate: reader.u32()?,
+        };
+        if reader.remaining() > 0 {
+            return Err(WireError::TrailingBytes(reader.remaining()));
+        }
+        if !report.jitter_ms.is_finite() || !report.delay_trend.is_finite() {
+            return Err(WireError::InvalidBody("non-finite feedback value"));
+        }
+        Ok(report)
+    }
+}
+
+/// Receive-side statistics for one sender, turned into a report every interval
+pub struct ReceiveStatistics {
+    // Arrival times are measured from here
+    origin: Instant,
+    interval_start: Instant,
+    highest_sequence: Option<u64>,
+    // Highest sequence when the interval began
+    interval_base: Option<u64>,
+    received: u64,
+    bytes: u64,
+    jitter_ms: f64,
+    last_transit: Option<f64>,
+    last_arrival: Option<Instant>,
+    // (arrival, transit) in milliseconds, for the delay trend
+    delay_samples: Vec<(f64, f64)>,
+}
+
+impl ReceiveStatistics {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            origin: now,
+            interval_start: now,
+            highest_sequence: None,
+            interval_base: None,
+            received: 0,
+            bytes: 0,
+            jitter_ms: 0.0,
+            last_transit: None,
+            last_arrival: None,
+            delay_samples: Vec::new(),
+        }
+    }
+
+    /// Record an arrival; `sent_ms` is the sender's clock, or 0 when it sent none
+    pub fn record(&mut self, sequence: u64, sent_ms: u64, payload_len: usize, arrival: Instant) {
+        self.received += 1;
+        self.bytes += payload_len as u64;
+        self.last_arrival = Some(arrival);
+        self.interval_base.get_or_insert(sequence.saturating_sub(1));
+        self.highest_sequence = self.highest_sequence.max(Some(sequence));
+
+        if sent_ms == 0 {
+            return;
+        }
+
+        // Clocks are not synchronised, so transit carries a constant offset that
+        // cancels out of both jitter and trend
+        let arrival_ms = arrival.saturating_duration_since(self.origin).as_secs_f64() * 1000.0;
+        let transit = arrival_ms - sent_ms as f64;
+        if let Some(last_transit) = self.last_transit {
+            self.jitter_ms += ((transit - last_transit).abs() - self.jitter_ms) / 16.0;
+        }
+        self.last_transit = Some(transit);
+        self.delay_samples.push((arrival_ms, transit));
+    }
+
+    pub fn last_arrival(&self) -> Option<Instant> {
+        self.last_arrival
+    }
+
+    /// Report on the interval once it has run its length, then start the next one
+    pub fn report(&mut self, now: Instant) -> Option<FeedbackReport> {
+        let elapsed = now.saturating_duration_since(self.interval_start);
+        if elapsed < FEEDBACK_INTERVAL {
+            return None;
+        }
+        let highest_sequence = self.highest_sequence?;
+
+        let expected = highest_sequence - self.interval_base.unwrap_or(highest_sequence);
+        let loss_fraction = if expected > 0 {
+            expected.saturating_sub(self.received) as f32 / expected as f32
+        } else {
+            0.0
+        };
+
+        let report = FeedbackReport {
+            highest_sequence,
+            loss_fraction,
+            jitter_ms: self.jitter_ms as f32,
+            delay_trend: delay_slope(&self.delay_samples) as f32 * 1000.0,
+            received_bitrate: (self.bytes as f64 * 8.0 / elapsed.as_secs_f64()) as u32,
+        };
+
+        self.interval_start = now;
+        self.interval_base = Some(highest_sequence);
+        self.received = 0;
+        self.bytes = 0;
+        self.delay_samples.clear();
+        Some(report)
+    }
+}
+
+/// Least-squares slope of transit time against arrival time
+fn delay_slope(samples: &[(f64, f64)]) -> f64 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+    let n = samples.len() as f64;
+    let mean_x = samples.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = samples.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let (covariance, variance) = samples.iter().fold((0.0, 0.0), |(covariance, variance), (x, y)| {
+        (covariance + (x - mean_x) * (y - mean_y), variance + (x - mean_x).powi(2))
+    });
+    if variance > 0.0 { covariance / variance } else { 0.0 }
+}
+
+/// Encoder settings chosen by the controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderTarget {
+    pub bitrate: u32,
+    pub complexity: u32,
+    /// Code a single channel to save bits on a poor link
+    pub mono: bool,
+    /// Repeat earlier frames in each packet to ride out bursts of loss
+    pub redundancy: bool,
+    /// Loss the encoder's in-band FEC protects against, in percent
+    pub packet_loss_percent: u8,
+}
+
+/// Shared slot through which the controller retunes the capture encoder.
+/// The audio thread only ever `try_lock`s it, so it never waits on the network side.
+#[derive(Clone, Default)]
+pub struct EncoderControl {
+    pending: Arc<Mutex<Option<EncoderTarget>>>,
+}
+
+impl EncoderControl {
+    /// Ask the encoder to switch to `target` before its next frame
+    pub fn request(&self, target: EncoderTarget) {
+        if let Ok(mut pending) = self.pending.lock() {
+            *pending = Some(target);
+        }
+    }
+
+    /// Latest unapplied request, if any
+    pub fn take(&self) -> Option<EncoderTarget> {
+        self.pending.try_lock().ok()?.take()
+    }
+}
+
+/// Bounds and settings for the sender-side controller
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CongestionConfig {
+    pub min_bitrate: u32,
+    pub max_bitrate: u32,
+    pub initial_bitrate: u32,
+    /// Encoder complexity while the link is healthy
+    pub complexity: u32,
+    /// Encoder complexity once loss sets in or the bitrate runs low
+    pub degraded_complexity: u32,
+    /// Below this bitrate complexity drops to `degraded_complexity`
+    pub reduced_complexity_below: u32,
+    /// Below this bitrate the encoder codes mono
+    pub mono_below: u32,
+    /// Loss at which packets start repeating earlier frames; `None` never asks for it
+    pub redundancy_above_loss: Option<f32>,
+    /// Loss percentage in-band FEC assumes until feedback arrives
+    pub packet_loss_percent: u8,
+}
+
+impl Default for CongestionConfig {
+    fn default() -> Self {
+        Self {
+            min_bitrate: 12000,
+            max_bitrate: 96000,
+            initial_bitrate: 64000,
+            complexity: 5,
+            degraded_complexity: 2,
+            reduced_complexity_below: 32000,
+            mono_below: 24000,
+            redundancy_above_loss: Some(0.03),
+            packet_loss_percent: 5,
+        }
+    }
+}
+
+impl CongestionConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.min_bitrate < 6000 || self.max_bitrate > 512000 || self.min_bitrate > self.max_bitrate {
+            return Err(anyhow!("Bitrate bounds must satisfy 6000 <= min <= max <= 512000"));
+        }
+        if !(self.min_bitrate..=self.max_bitrate).contains(&self.initial_bitrate) {
+            return Err(anyhow!("Initial bitrate must lie between the bounds"));
+        }
+        if self.complexity > 10 || self.degraded_complexity > self.complexity {
+            return Err(anyhow!("Complexity must be <= 10, degraded complexity no higher"));
+        }
+        if let Some(threshold) = self.redundancy_above_loss
+            && !(LOW_LOSS..=1.0).contains(&threshold) {
+            return Err(anyhow!("Redundancy loss threshold must lie between {} and 1", LOW_LOSS));
+        }
+        if self.packet_loss_percent > 100 {
+            return Err(anyhow!("Packet loss percentage must be <= 100"));
+        }
+        Ok(())
+    }
+}
+
+/// Why the controller changed the encoder target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateChangeReason {
+    PacketLoss,
+    /// One-way delay is growing: a queue on the path is filling
+    QueueGrowth,
+    /// The link is clean, so probe for more
+    Headroom,
+}
+
+impl fmt::Display for RateChangeReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateChangeReason::PacketLoss => write!(f, "packet loss"),
+            RateChangeReason::QueueGrowth => write!(f, "queue growth"),
+            RateChangeReason::Headroom => write!(f, "headroom"),
+        }
+    }
+}
+
+/// A change of encoder target, with the conditions that caused it
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateDecision {
+    pub previous: EncoderTarget,
+    pub target: EncoderTarget,
+    pub reason: RateChangeReason,
+    /// Worst loss and delay trend across peers' reports
+    pub loss_fraction: f32,
+    pub delay_trend: f32,
+    pub timestamp: u64, // Unix timestamp
+}
+
+impl fmt::Display for RateDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {} kbps, complexity {}, {}{}, FEC for {}% loss ({}: loss {:.1}%, delay trend {:+.1} ms/s)",
+               self.previous.bitrate / 1000, self.target.bitrate / 1000, self.target.complexity,
+               if self.target.mono { "mono" } else { "stereo" },
+               if self.target.redundancy { ", redundant" } else { "" },
+               self.target.packet_loss_percent,
+               self.reason, self.loss_fraction * 100.0, self.delay_trend)
+    }
+}
+
+/// Sender-side AIMD controller in the spirit of GCC: loss above 10% or a growing
+/// one-way delay backs the bitrate off multiplicatively, a clean link adds to it.
+/// Every peer hears the same encoding, so the worst recent report decides.
+pub struct CongestionController {
+    config: CongestionConfig,
+    target: EncoderTarget,
+    reports: HashMap<SocketAddr, (FeedbackReport, Instant)>,
+    last_increase: Option<Instant>,
+}
+
+impl CongestionController {
+    pub fn new(config: CongestionConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            target: EncoderTarget {
+                bitrate: config.initial_bitrate,
+                complexity: config.complexity,
+                mono: config.initial_bitrate < config.mono_below,
+                redundancy: false,
+                packet_loss_percent: config.packet_loss_percent,
+            },
+            config,
+            reports: HashMap::new(),
+            last_increase: None,
+        })
+    }
+
+    pub fn target(&self) -> EncoderTarget {
+        self.target
+    }
+
+    pub fn config(&self) -> &CongestionConfig {
+        &self.config
+    }
+
+    /// Fold in a peer's report; returns the decision when the encoder target changes
+    pub fn on_report(&mut self, peer: SocketAddr, report: FeedbackReport, now: Instant) -> Option<RateDecision> {
+        self.reports.insert(peer, (report, now));
+        self.reports.retain(|_, (_, received)| now.saturating_duration_since(*received) < REPORT_TTL);
+
+        let loss = self.reports.values().map(|(report, _)| report.loss_fraction).fold(0.0, f32::max);
+        let trend = self.reports.values().map(|(report, _)| report.delay_trend).fold(f32::MIN, f32::max);
+
+        let current = self.target.bitrate as f64;
+        let (bitrate, rate_reason) = if loss > HIGH_LOSS {
+            (current * (1.0 - 0.5 * loss as f64), Some(RateChangeReason::PacketLoss))
+        } else if trend > OVERUSE_TREND {
+            (current * DELAY_BACKOFF, Some(RateChangeReason::QueueGrowth))
+        } else if loss < LOW_LOSS
+            && self.last_increase.is_none_or(|last| now.saturating_duration_since(last) >= FEEDBACK_INTERVAL) {
+            self.last_increase = Some(now);
+            (current + ADDITIVE_INCREASE as f64, Some(RateChangeReason::Headroom))
+        } else {
+            (current, None)
+        };
+        let bitrate = (bitrate.round() as u32).clamp(self.config.min_bitrate, self.config.max_bitrate);
+
+        // Once degraded, complexity stays down until loss is low again
+        let degraded = loss >= DEGRADED_LOSS
+            || (self.target.complexity < self.config.complexity && loss >= LOW_LOSS)
+            || bitrate < self.config.reduced_complexity_below;
+        // Redundancy likewise stays on until loss is low again
+        let redundancy = self.config.redundancy_above_loss
+            .is_some_and(|threshold| loss >= threshold || (self.target.redundancy && loss >= LOW_LOSS));
+
+        // FEC's expected loss rises straight to the reported loss, and only eases off,
+        // a step per report, once the link is clean
+        let reported_percent = ((loss * 100.0 / LOSS_PERCENT_STEP as f32).ceil() as u8)
+            .saturating_mul(LOSS_PERCENT_STEP)
+            .min(100);
+        let held_percent = if loss < LOW_LOSS {
+            self.target.packet_loss_percent.saturating_sub(LOSS_PERCENT_STEP)
+        } else {
+            self.target.packet_loss_percent
+        };
+
+        let target = EncoderTarget {
+            bitrate,
+            complexity: if degraded { self.config.degraded_complexity } else { self.config.complexity },
+            mono: bitrate < self.config.mono_below,
+            redundancy,
+            packet_loss_percent: reported_percent.max(held_percent),
+        };
+
+        if target == self.target {
+            return None;
+        }
+
+        let worsened = degraded
+            || (redundancy && !self.target.redundancy)
+            || target.packet_loss_percent > self.target.packet_loss_percent;
+        let reason = rate_reason
+            .filter(|_| target.bitrate != self.target.bitrate)
+            .unwrap_or(if worsened { RateChangeReason::PacketLoss } else { RateChangeReason::Headroom });
+        let previous = std::mem::replace(&mut self.target, target);
+        Some(RateDecision {
+            previous,
+            target,
+            reason,
+            loss_fraction: loss,
+            delay_trend: trend,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        })
+    }
+}
+
+/// Redundancy driven by peers' loss reports alone, for when the bitrate is fixed.
+/// Switches with the controller's hysteresis: on at the threshold, off once loss is low.
+pub struct RedundancySwitch {
+    threshold: f32,
+    // Fixed encoder settings the switch toggles redundancy in
+    target: EncoderTarget,
+    reports: HashMap<SocketAddr, (f32, Instant)>,
+}
+
+impl RedundancySwitch {
+    /// Switch at `threshold` loss, kept above the level that turns redundancy off
+    pub fn new(threshold: f32, target: EncoderTarget) -> Self {
+        Self {
+            threshold: threshold.max(LOW_LOSS),
+            target: EncoderTarget { redundancy: false, ..target },
+            reports: HashMap::new(),
+        }
+    }
+
+    pub fn target(&self) -> EncoderTarget {
+        self.target
+    }
+
+    /// Fold in a peer's report; returns the new target when redundancy turns on or off
+    pub fn on_report(&mut self, peer: SocketAddr, report: &FeedbackReport, now: Instant) -> Option<EncoderTarget> {
+        self.reports.insert(peer, (report.loss_fraction, now));
+        self.reports.retain(|_, (_, received)| now.saturating_duration_since(*received) < REPORT_TTL);
+
+        let loss = self.reports.values().map(|(loss, _)| *loss).fold(0.0, f32::max);
+        let redundancy = loss >= self.threshold || (self.target.redundancy && loss >= LOW_LOSS);
+        if redundancy == self.target.redundancy {
+            return None;
+        }
+        self.target.redundancy = redundancy;
+        Some(self.target)
+    }
+}
@@ -6,6 +6,8 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, RwLock};
 
+use crate::stun::{Candidate, CandidateKind};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoomInfo {
     pub room_id: String,
@@ -27,6 +29,32 @@ pub enum ConnectionMethod {
     Bluetooth { device_id: String },
     QRCode { data: String },
     MagicLink { url: String },
+    /// Address to run connectivity checks against when punching through NAT
+    IceCandidate { candidate: Candidate },
+}
+
+impl RoomInfo {
+    /// Candidates the host advertised for hole punching
+    pub fn candidates(&self) -> Vec<Candidate> {
+        self.connection_methods.iter()
+            .filter_map(|method| match method {
+                ConnectionMethod::IceCandidate { candidate } => Some(*candidate),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Connection methods for gathered candidates: one per candidate, plus an
+/// `Internet` entry for the first server-reflexive address
+pub fn candidate_methods(candidates: &[Candidate]) -> Vec<ConnectionMethod> {
+    let mut methods = Vec::new();
+
+    if let Some(reflexive) = candidates.iter().find(|candidate| candidate.kind == CandidateKind::ServerReflexive) {
+        methods.push(ConnectionMethod::Internet { public_ip: reflexive.addr.ip(), port: reflexive.addr.port() });
+    }
+    methods.extend(candidates.iter().map(|&candidate| ConnectionMethod::IceCandidate { candidate }));
+    methods
 }
 
 #[derive(Debug, Clone)]
@@ -97,6 +125,12 @@ impl DiscoveryManager {
     }
 
     pub async fn create_room(&mut self, display_name: String, port: u16) -> Result<RoomInfo> {
+        self.create_room_with_candidates(display_name, port, &[]).await
+    }
+
+    /// Create a room advertising candidates from `NetworkManager::gather_candidates`,
+    /// so joiners behind NAT can punch through to the host
+    pub async fn create_room_with_candidates(&mut self, display_name: String, port: u16, candidates: &[Candidate]) -> Result<RoomInfo> {
         let room_id = generate_room_code();
         let host_name = get_hostname().unwrap_or_else(|| "Unknown".to_string());
 
@@ -107,14 +141,14 @@ impl DiscoveryManager {
             connection_methods.push(ConnectionMethod::LocalNetwork { ip: local_ip });
         }
 
-        // Try UPnP for internet access
-        if let Some(upnp) = &self.upnp_service {
-            if let Ok(public_ip) = upnp.get_external_ip().await {
-                if upnp.forward_port(port).await.is_ok() {
-                    connection_methods.push(ConnectionMethod::Internet { public_ip, port });
-                }
-            }
+        // Internet reachability comes from STUN; UPnP only helps keep the port open
+        let has_reflexive = candidates.iter().any(|candidate| candidate.kind == CandidateKind::ServerReflexive);
+        if has_reflexive
+            && let Some(upnp) = &self.upnp_service
+            && let Err(e) = upnp.forward_port(port).await {
+            log::warn!("UPnP port forwarding failed: {}", e);
         }
+        connection_methods.extend(candidate_methods(candidates));
 
         // Generate QR code data
         let qr_data = format!("humr://{}/{}/{}", room_id, host_name, port);
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use log::info;
 use std::collections::VecDeque;
-use crate::realtime_audio::{AudioFrame, SAMPLE_RATE, CHANNELS, FRAME_SIZE_SAMPLES};
+use crate::realtime_audio::{AudioFrame, SAMPLE_RATE, FRAME_SIZE_SAMPLES};
 
 /// Echo cancellation configuration
 #[derive(Debug, Clone)]
@@ -108,15 +108,16 @@ impl EchoCancellationProcessor {
         self.frames_processed += 1;
 
         // Process each channel separately
-        let samples_per_channel = FRAME_SIZE_SAMPLES;
+        let channels = microphone_frame.channels() as usize;
+        let samples_per_channel = microphone_frame.samples.len() / channels;
 
-        for channel in 0..CHANNELS as usize {
+        for channel in 0..channels {
             // Extract channel samples
             let mut ref_samples = Vec::with_capacity(samples_per_channel);
             let mut mic_samples = Vec::with_capacity(samples_per_channel);
 
             for i in 0..samples_per_channel {
-                let sample_idx = i * CHANNELS as usize + channel;
+                let sample_idx = i * channels + channel;
 
                 if sample_idx < reference_frame.samples.len() {
                     ref_samples.push(reference_frame.samples[sample_idx]);
@@ -132,7 +133,7 @@ impl EchoCancellationProcessor {
 
             // Write back processed microphone samples
             for i in 0..samples_per_channel {
-                let sample_idx = i * CHANNELS as usize + channel;
+                let sample_idx = i * channels + channel;
                 if sample_idx < microphone_frame.samples.len() && i < mic_samples.len() {
                     microphone_frame.samples[sample_idx] = mic_samples[i];
                 }
@@ -0,0 +1,174 @@
+use anyhow::{Result, anyhow};
+use log::info;
+
+use crate::codec::{CodecCounters, CodecKind, CodecStats, FadeConcealer, VoiceCodec, frame_to_pcm, pcm_to_frame};
+use crate::realtime_audio::{AudioFormat, AudioFrame};
+
+/// G.711 runs at 8 kHz with one byte per sample
+const SAMPLE_RATE: u32 = 8000;
+const BITRATE: u32 = 64000;
+
+/// μ-law bias added before finding the segment
+const ULAW_BIAS: i32 = 0x84;
+/// Largest magnitude μ-law codes before the bias overflows 15 bits
+const ULAW_CLIP: i32 = 32635;
+/// Upper end of each A-law segment, in 13-bit magnitudes
+const ALAW_SEGMENT_END: [i32; 8] = [0x1F, 0x3F, 0x7F, 0xFF, 0x1FF, 0x3FF, 0x7FF, 0xFFF];
+
+/// Companding law
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum G711Law {
+    /// μ-law (PCMU), used in North America and Japan
+    MuLaw,
+    /// A-law (PCMA), used elsewhere
+    ALaw,
+}
+
+/// Compress a 16-bit sample to a μ-law code
+pub fn linear_to_ulaw(sample: i16) -> u8 {
+    let mut magnitude = sample as i32;
+    let sign = if magnitude < 0 {
+        magnitude = -magnitude;
+        0x80
+    } else {
+        0x00
+    };
+    magnitude = magnitude.min(ULAW_CLIP) + ULAW_BIAS;
+
+    // The bias puts the top bit between 7 and 14
+    let exponent = 31 - magnitude.leading_zeros() as i32 - 7;
+    let mantissa = (magnitude >> (exponent + 3)) & 0x0F;
+    !(sign | (exponent << 4) | mantissa) as u8
+}
+
+pub fn ulaw_to_linear(code: u8) -> i16 {
+    let code = !code;
+    let exponent = (code >> 4) & 0x07;
+    let mantissa = (code & 0x0F) as i32;
+    let magnitude = (((mantissa << 3) + ULAW_BIAS) << exponent) - ULAW_BIAS;
+    if code & 0x80 != 0 { -magnitude as i16 } else { magnitude as i16 }
+}
+
+/// Compress a 16-bit sample to an A-law code
+pub fn linear_to_alaw(sample: i16) -> u8 {
+    let mut magnitude = sample as i32 >> 3;
+    let mask = if magnitude >= 0 {
+        0xD5
+    } else {
+        magnitude = -magnitude - 1;
+        0x55
+    };
+
+    let code = match ALAW_SEGMENT_END.iter().position(|&end| magnitude <= end) {
+        None => 0x7F,
+        Some(segment) => {
+            let shift = if segment < 2 { 1 } else { segment };
+            ((segment as i32) << 4) | ((magnitude >> shift) & 0x0F)
+        }
+    };
+    (code ^ mask) as u8
+}
+
+pub fn alaw_to_linear(code: u8) -> i16 {
+    let code = code ^ 0x55;
+    let segment = (code & 0x70) >> 4;
+    let mut magnitude = ((code & 0x0F) as i32) << 4;
+    magnitude += match segment {
+        0 => 8,
+        _ => 0x108,
+    };
+    if segment > 1 {
+        magnitude <<= segment - 1;
+    }
+    if code & 0x80 != 0 { magnitude as i16 } else { -magnitude as i16 }
+}
+
+/// G.711 codec: stateless 8 kHz companding, one byte per sample
+pub struct G711Codec {
+    law: G711Law,
+    format: AudioFormat,
+    counters: CodecCounters,
+    concealer: FadeConcealer,
+}
+
+impl G711Codec {
+    pub fn new(law: G711Law, frame_duration_ms: u32) -> Result<Self> {
+        let format = AudioFormat { sample_rate: SAMPLE_RATE, channels: 1, frame_duration_ms };
+        format.validate()?;
+        info!("Creating G.711 {:?} codec: {}", law, format);
+
+        Ok(Self {
+            law,
+            format,
+            counters: CodecCounters::default(),
+            concealer: FadeConcealer::default(),
+        })
+    }
+
+    pub fn law(&self) -> G711Law {
+        self.law
+    }
+}
+
+impl VoiceCodec for G711Codec {
+    fn kind(&self) -> CodecKind {
+        match self.law {
+            G711Law::MuLaw => CodecKind::Pcmu,
+            G711Law::ALaw => CodecKind::Pcma,
+        }
+    }
+
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn encode(&mut self, frame: &AudioFrame) -> Result<Vec<u8>> {
+        let pcm = frame_to_pcm(frame, self.format).inspect_err(|_| self.counters.encoding_errors += 1)?;
+        let compress = match self.law {
+            G711Law::MuLaw => linear_to_ulaw,
+            G711Law::ALaw => linear_to_alaw,
+        };
+        let encoded: Vec<u8> = pcm.into_iter().map(compress).collect();
+
+        self.counters.frames_encoded += 1;
+        self.counters.total_bytes_encoded += encoded.len() as u64;
+        Ok(encoded)
+    }
+
+    fn decode(&mut self, data: &[u8]) -> Result<AudioFrame> {
+        if data.len() != self.format.frame_samples() {
+            self.counters.decoding_errors += 1;
+            return Err(anyhow!("G.711 frame must be {} bytes, got {}", self.format.frame_samples(), data.len()));
+        }
+        let expand = match self.law {
+            G711Law::MuLaw => ulaw_to_linear,
+            G711Law::ALaw => alaw_to_linear,
+        };
+        let frame = pcm_to_frame(data.iter().map(|&code| expand(code)), self.format);
+
+        self.counters.frames_decoded += 1;
+        self.concealer.update(&frame.samples);
+        Ok(frame)
+    }
+
+    fn conceal(&mut self) -> Result<AudioFrame> {
+        Ok(self.concealer.conceal(self.format))
+    }
+
+    fn bitrate(&self) -> u32 {
+        BITRATE
+    }
+
+    fn set_bitrate(&mut self, _bitrate: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn stats(&self) -> CodecStats {
+        self.counters.stats(self.kind(), self.format, BITRATE)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.concealer.reset();
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+use anyhow::{Result, anyhow};
+use log::info;
+
+use crate::codec::{CodecCounters, CodecKind, CodecStats, FadeConcealer, VoiceCodec, frame_to_pcm, pcm_to_frame};
+use crate::realtime_audio::{AudioFormat, AudioFrame};
+
+/// G.722 runs at 16 kHz, coding each pair of samples into one byte (mode 1, 64 kbps)
+const SAMPLE_RATE: u32 = 16000;
+const BITRATE: u32 = 64000;
+
+// Tables from ITU-T G.722, in the integer form of the reference implementation
+
+/// Lower sub-band quantiser decision levels
+const Q6: [i32; 32] = [
+    0, 35, 72, 110, 150, 190, 233, 276, 323, 370, 422, 473, 530, 587, 650, 714,
+    786, 858, 940, 1023, 1121, 1219, 1339, 1458, 1612, 1765, 1980, 2195, 2557, 2919, 0, 0,
+];
+/// Lower sub-band codes for negative and positive differences, by decision interval
+const ILN: [i32; 32] = [
+    0, 63, 62, 31, 30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20, 19,
+    18, 17, 16, 15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 0,
+];
+const ILP: [i32; 32] = [
+    0, 61, 60, 59, 58, 57, 56, 55, 54, 53, 52, 51, 50, 49, 48, 47,
+    46, 45, 44, 43, 42, 41, 40, 39, 38, 37, 36, 35, 34, 33, 32, 0,
+];
+/// Inverse quantiser outputs: 6-bit lower band (decoding), 4-bit lower band (prediction), 2-bit higher band
+const QM6: [i32; 64] = [
+    -136, -136, -136, -136, -24808, -21904, -19008, -16704,
+    -14984, -13512, -12280, -11192, -10232, -9360, -8576, -7856,
+    -7192, -6576, -6000, -5456, -4944, -4464, -4008, -3576,
+    -3168, -2776, -2400, -2032, -1688, -1360, -1040, -728,
+    24808, 21904, 19008, 16704, 14984, 13512, 12280, 11192,
+    10232, 9360, 8576, 7856, 7192, 6576, 6000, 5456,
+    4944, 4464, 4008, 3576, 3168, 2776, 2400, 2032,
+    1688, 1360, 1040, 728, 432, 136, -432, -136,
+];
+const QM4: [i32; 16] = [
+    0, -20456, -12896, -8968, -6288, -4240, -2584, -1200,
+    20456, 12896, 8968, 6288, 4240, 2584, 1200, 0,
+];
+const QM2: [i32; 4] = [-7408, -1616, 7408, 1616];
+/// Log scale factor adaptation
+const WL: [i32; 8] = [-60, -30, 58, 172, 334, 538, 1198, 3042];
+const RL42: [i32; 16] = [0, 7, 6, 5, 4, 3, 2, 1, 7, 6, 5, 4, 3, 2, 1, 0];
+const WH: [i32; 3] = [0, -214, 798];
+const RH2: [i32; 4] = [2, 1, 2, 1];
+/// Higher sub-band codes, by sign and magnitude interval
+const IHN: [i32; 3] = [0, 1, 0];
+const IHP: [i32; 3] = [0, 3, 2];
+/// Log to linear scale factor conversion
+const ILB: [i32; 32] = [
+    2048, 2093, 2139, 2186, 2233, 2282, 2332, 2383, 2435, 2489, 2543, 2599, 2656, 2714, 2774, 2834,
+    2896, 2960, 3025, 3091, 3158, 3228, 3298, 3371, 3444, 3520, 3597, 3676, 3756, 3838, 3922, 4008,
+];
+/// Quadrature mirror filter coefficients
+const QMF: [i32; 12] = [3, -11, 12, 32, -210, 951, 3876, -805, 362, -156, 53, -11];
+
+fn saturate(value: i32) -> i32 {
+    value.clamp(i16::MIN as i32, i16::MAX as i32)
+}
+
+/// Adaptive predictor and scale factor of one sub-band
+#[derive(Debug, Clone, Default)]
+struct Band {
+    // Predicted signal, and its pole and zero sections
+    s: i32,
+    sp: i32,
+    sz: i32,
+    // Reconstructed signal, partial reconstruction and quantised difference histories
+    r: [i32; 3],
+    p: [i32; 3],
+    d: [i32; 7],
+    // Pole and zero predictor coefficients, current and updated
+    a: [i32; 3],
+    ap: [i32; 3],
+    b: [i32; 7],
+    bp: [i32; 7],
+    // Log and linear scale factors
+    nb: i32,
+    det: i32,
+}
+
+impl Band {
+    fn new(det: i32) -> Self {
+        Self { det, ..Self::default() }
+    }
+
+    /// Scale factor adaptation shared by both bands (blocks 3L/3H, LOGSCL and SCALEL)
+    fn adapt_scale(&mut self, log_step: i32, max_nb: i32, shift: i32) {
+        self.nb = (((self.nb * 127) >> 7) + log_step).clamp(0, max_nb);
+        let index = ((self.nb >> 6) & 31) as usize;
+        let shift = shift - (self.nb >> 11);
+        let linear = if shift < 0 { ILB[index] << -shift } else { ILB[index] >> shift };
+        self.det = linear << 2;
+    }
+
+    /// Update the predictor with quantised difference `d` (block 4)
+    fn update(&mut self, d: i32) {
+        // RECONS, PARREC
+        self.d[0] = d;
+        self.r[0] = saturate(self.s + d);
+        self.p[0] = saturate(self.sz + d);
+
+        // UPPOL2
+        let sg: [i32; 3] = std::array::from_fn(|i| self.p[i] >> 15);
+        let wd1 = saturate(self.a[1] << 2);
+        let wd2 = (if sg[0] == sg[1] { -wd1 } else { wd1 }).min(32767);
+        let mut wd3 = (wd2 >> 7) + if sg[0] == sg[2] { 128 } else { -128 };
+        wd3 += (self.a[2] * 32512) >> 15;
+        self.ap[2] = wd3.clamp(-12288, 12288);
+
+        // UPPOL1
+        let wd1 = if sg[0] == sg[1] { 192 } else { -192 };
+        let wd2 = (self.a[1] * 32640) >> 15;
+        let limit = saturate(15360 - self.ap[2]);
+        self.ap[1] = saturate(wd1 + wd2).clamp(-limit, limit);
+
+        // UPZERO
+        let step = if d == 0 { 0 } else { 128 };
+        let sign = d >> 15;
+        for i in 1..7 {
+            let wd2 = if self.d[i] >> 15 == sign { step } else { -step };
+            let wd3 = (self.b[i] * 32640) >> 15;
+            self.bp[i] = saturate(wd2 + wd3);
+        }
+
+        // DELAYA
+        for i in (1..7).rev() {
+            self.d[i] = self.d[i - 1];
+            self.b[i] = self.bp[i];
+        }
+        for i in (1..3).rev() {
+            self.r[i] = self.r[i - 1];
+            self.p[i] = self.p[i - 1];
+            self.a[i] = self.ap[i];
+        }
+
+        // FILTEP, FILTEZ, PREDIC
+        let wd1 = (self.a[1] * saturate(self.r[1] + self.r[1])) >> 15;
+        let wd2 = (self.a[2] * saturate(self.r[2] + self.r[2])) >> 15;
+        self.sp = saturate(wd1 + wd2);
+        self.sz = saturate((1..7).map(|i| (self.b[i] * saturate(self.d[i] + self.d[i])) >> 15).sum());
+        self.s = saturate(self.sp + self.sz);
+    }
+}
+
+/// Lower and higher band state plus the QMF delay line, one per direction
+#[derive(Debug, Clone)]
+struct State {
+    bands: [Band; 2],
+    qmf: [i32; 24],
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self { bands: [Band::new(32), Band::new(8)], qmf: [0; 24] }
+    }
+}
+
+impl State {
+    /// Shift two samples into the QMF delay line
+    fn push_qmf(&mut self, first: i32, second: i32) {
+        self.qmf.copy_within(2.., 0);
+        self.qmf[22] = first;
+        self.qmf[23] = second;
+    }
+
+    /// Sums of the delay line's even and odd taps against the mirrored filter
+    fn qmf_sums(&self) -> (i32, i32) {
+        (0..12).fold((0, 0), |(even, odd), i| {
+            (even + self.qmf[2 * i] * QMF[i], odd + self.qmf[2 * i + 1] * QMF[11 - i])
+        })
+    }
+
+    fn encode_pair(&mut self, first: i16, second: i16) -> u8 {
+        self.push_qmf(first as i32, second as i32);
+        let (even, odd) = self.qmf_sums();
+        let xlow = (odd + even) >> 14;
+        let xhigh = (odd - even) >> 14;
+
+        // Lower band: 6-bit quantiser, predictor driven by the 4 most significant bits
+        let low = &mut self.bands[0];
+        let el = saturate(xlow - low.s);
+        let magnitude = if el >= 0 { el } else { -(el + 1) };
+        let interval = (1..30).find(|&i| magnitude < (Q6[i] * low.det) >> 12).unwrap_or(30);
+        let ilow = if el < 0 { ILN[interval] } else { ILP[interval] };
+        let ril = (ilow >> 2) as usize;
+        let dlow = (low.det * QM4[ril]) >> 15;
+        low.adapt_scale(WL[RL42[ril] as usize], 18432, 8);
+        low.update(dlow);
+
+        // Higher band: 2-bit quantiser
+        let high = &mut self.bands[1];
+        let eh = saturate(xhigh - high.s);
+        let magnitude = if eh >= 0 { eh } else { -(eh + 1) };
+        let interval = if magnitude >= (564 * high.det) >> 12 { 2 } else { 1 };
+        let ihigh = (if eh < 0 { IHN[interval] } else { IHP[interval] }) as usize;
+        let dhigh = (high.det * QM2[ihigh]) >> 15;
+        high.adapt_scale(WH[RH2[ihigh] as usize], 22528, 10);
+        high.update(dhigh);
+
+        ((ihigh as i32) << 6 | ilow) as u8
+    }
+
+    fn decode_pair(&mut self, code: u8) -> (i16, i16) {
+        let ilow = (code & 0x3F) as usize;
+        let ihigh = (code >> 6) as usize;
+
+        let low = &mut self.bands[0];
+        let rlow = (low.s + ((low.det * QM6[ilow]) >> 15)).clamp(-16384, 16383);
+        let ril = ilow >> 2;
+        let dlow = (low.det * QM4[ril]) >> 15;
+        low.adapt_scale(WL[RL42[ril] as usize], 18432, 8);
+        low.update(dlow);
+
+        let high = &mut self.bands[1];
+        let dhigh = (high.det * QM2[ihigh]) >> 15;
+        let rhigh = (dhigh + high.s).clamp(-16384, 16383);
+        high.adapt_scale(WH[RH2[ihigh] as usize], 22528, 10);
+        high.update(dhigh);
+
+        self.push_qmf(rlow + rhigh, rlow - rhigh);
+        let (even, odd) = self.qmf_sums();
+        (saturate(odd >> 11) as i16, saturate(even >> 11) as i16)
+    }
+}
+
+/// G.722 codec: 16 kHz wideband split into two sub-bands, each coded with ADPCM
+pub struct G722Codec {
+    format: AudioFormat,
+    encoder: State,
+    decoder: State,
+    counters: CodecCounters,
+    concealer: FadeConcealer,
+}
+
+impl G722Codec {
+    pub fn new(frame_duration_ms: u32) -> Result<Self> {
+        let format = AudioFormat { sample_rate: SAMPLE_RATE, channels: 1, frame_duration_ms };
+        format.validate()?;
+        info!("Creating G.722 codec: {}", format);
+
+        Ok(Self {
+            format,
+            encoder: State::default(),
+            decoder: State::default(),
+            counters: CodecCounters::default(),
+            concealer: FadeConcealer::default(),
+        })
+    }
+}
+
+impl VoiceCodec for G722Codec {
+    fn kind(&self) -> CodecKind {
+        CodecKind::G722
+    }
+
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn encode(&mut self, frame: &AudioFrame) -> Result<Vec<u8>> {
+        let pcm = frame_to_pcm(frame, self.format).inspect_err(|_| self.counters.encoding_errors += 1)?;
+        let encoded: Vec<u8> = pcm.chunks_exact(2)
+            .map(|pair| self.encoder.encode_pair(pair[0], pair[1]))
+            .collect();
+
+        self.counters.frames_encoded += 1;
+        self.counters.total_bytes_encoded += encoded.len() as u64;
+        Ok(encoded)
+    }
+
+    fn decode(&mut self, data: &[u8]) -> Result<AudioFrame> {
+        if data.len() * 2 != self.format.frame_samples() {
+            self.counters.decoding_errors += 1;
+            return Err(anyhow!("G.722 frame must be {} bytes, got {}", self.format.frame_samples() / 2, data.len()));
+        }
+        let decoder = &mut self.decoder;
+        let pcm = data.iter().flat_map(|&code| {
+            let (first, second) = decoder.decode_pair(code);
+            [first, second]
+        });
+        let frame = pcm_to_frame(pcm, self.format);
+
+        self.counters.frames_decoded += 1;
+        self.concealer.update(&frame.samples);
+        Ok(frame)
+    }
+
+    fn conceal(&mut self) -> Result<AudioFrame> {
+        Ok(self.concealer.conceal(self.format))
+    }
+
+    fn bitrate(&self) -> u32 {
+        BITRATE
+    }
+
+    fn set_bitrate(&mut self, _bitrate: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn stats(&self) -> CodecStats {
+        self.counters.stats(CodecKind::G722, self.format, BITRATE)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.encoder = State::default();
+        self.decoder = State::default();
+        self.concealer.reset();
+        Ok(())
+    }
+}
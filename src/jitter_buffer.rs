@@ -2,7 +2,8 @@ use anyhow::Result;
 use log::{info, warn, debug};
 use std::collections::VecDeque;
 use std::time::{Duration, Instant};
-use crate::realtime_audio::AudioFrame;
+use crate::realtime_audio::{AudioFrame, FRAME_SIZE_MS};
+use crate::transport::TransportKind;
 
 /// Adaptive jitter buffer configuration
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -21,6 +22,10 @@ pub struct JitterBufferConfig {
     pub underrun_threshold: u32,
     /// Overrun threshold count
     pub overrun_threshold: u32,
+    /// Skip to the newest frames when a stall leaves more than twice the target buffered
+    pub discard_stall_backlog: bool,
+    /// Audio carried by each frame, in milliseconds
+    pub frame_duration_ms: u32,
 }
 
 impl Default for JitterBufferConfig {
@@ -33,6 +38,43 @@ impl Default for JitterBufferConfig {
             late_packet_threshold_ms: 150,
             underrun_threshold: 5,
             overrun_threshold: 15,
+            discard_stall_backlog: false,
+            frame_duration_ms: FRAME_SIZE_MS,
+        }
+    }
+}
+
+impl JitterBufferConfig {
+    /// Settings suited to the transport carrying the audio. Stream transports never lose
+    /// packets but hold everything behind a retransmission, then deliver it in one burst,
+    /// so they start with more headroom and drop a stall's backlog instead of playing it late.
+    pub fn for_transport(kind: TransportKind) -> Self {
+        if !kind.has_head_of_line_blocking() {
+            return Self::default();
+        }
+        Self {
+            initial_target_size: 5,
+            max_size: 50,
+            late_packet_threshold_ms: 400,
+            discard_stall_backlog: true,
+            ..Self::default()
+        }
+    }
+
+    /// Same buffering depth in time for frames of `frame_duration_ms`, rounding frame counts up
+    pub fn with_frame_duration(self, frame_duration_ms: u32) -> Self {
+        if frame_duration_ms == 0 || frame_duration_ms == self.frame_duration_ms {
+            return self;
+        }
+        let rescale = |frames: usize| {
+            (frames * self.frame_duration_ms as usize).div_ceil(frame_duration_ms as usize).max(1)
+        };
+        Self {
+            initial_target_size: rescale(self.initial_target_size),
+            max_size: rescale(self.max_size),
+            min_size: rescale(self.min_size),
+            frame_duration_ms,
+            ..self
         }
     }
 }
@@ -41,6 +83,8 @@ impl Default for JitterBufferConfig {
 #[derive(Debug, Clone)]
 pub struct AudioPacket {
     pub frame: AudioFrame,
+    /// Encoded payload, decoded at playout time (empty for pre-decoded frames)
+    pub payload: Vec<u8>,
     pub sequence_number: u32,
     pub timestamp: u64,
     pub arrival_time: Instant,
@@ -50,6 +94,18 @@ impl AudioPacket {
     pub fn new(frame: AudioFrame, timestamp: u64, sequence_number: u32) -> Self {
         Self {
             frame,
+            payload: Vec::new(),
+            sequence_number,
+            timestamp,
+            arrival_time: Instant::now(),
+        }
+    }
+
+    /// Create packet carrying encoded audio that is decoded at playout time
+    pub fn encoded(payload: Vec<u8>, timestamp: u64, sequence_number: u32) -> Self {
+        Self {
+            frame: AudioFrame::new(Vec::new()),
+            payload,
             sequence_number,
             timestamp,
             arrival_time: Instant::now(),
@@ -57,6 +113,17 @@ impl AudioPacket {
     }
 }
 
+/// Next playout slot pulled from the jitter buffer
+#[derive(Debug)]
+pub enum PlayoutSlot {
+    /// The expected packet is available
+    Packet(AudioPacket),
+    /// The expected packet is missing while later packets are buffered; conceal it
+    Lost { sequence_number: u32 },
+    /// Nothing to play yet
+    Empty,
+}
+
 /// Adaptive jitter buffer for handling network timing variations
 pub struct AdaptiveJitterBuffer {
     config: JitterBufferConfig,
@@ -67,6 +134,7 @@ pub struct AdaptiveJitterBuffer {
     pub(crate) buffer_overruns: u64,
     pub(crate) late_packets: u64,
     pub(crate) duplicate_packets: u64,
+    pub(crate) recovered_packets: u64,
 
     // Adaptive parameters
     pub(crate) current_target_size: usize,
@@ -93,6 +161,7 @@ impl AdaptiveJitterBuffer {
             buffer_overruns: 0,
             late_packets: 0,
             duplicate_packets: 0,
+            recovered_packets: 0,
             network_delay_samples: VecDeque::new(),
             last_adaptation_time: Instant::now(),
             average_delay: 0.0,
@@ -123,6 +192,30 @@ impl AdaptiveJitterBuffer {
             return Ok(());
         }
 
+        self.insert_packet(packet);
+
+        // Adapt buffer size based on network conditions
+        self.adapt_buffer_size();
+
+        Ok(())
+    }
+
+    /// Fill a gap with a frame rebuilt from a later packet's redundant data. Returns
+    /// whether it was used; slots already buffered or played out are left alone, and
+    /// the copy does not count towards delay or duplicate statistics.
+    pub fn put_recovered(&mut self, packet: AudioPacket) -> bool {
+        if packet.sequence_number < self.expected_sequence
+            || self.buffer.iter().any(|existing| existing.sequence_number == packet.sequence_number) {
+            return false;
+        }
+
+        debug!("Recovered packet from redundancy: seq={}", packet.sequence_number);
+        self.recovered_packets += 1;
+        self.insert_packet(packet);
+        true
+    }
+
+    fn insert_packet(&mut self, packet: AudioPacket) {
         // Insert packet in correct position (sorted by sequence number)
         let insert_pos = self.find_insert_position(packet.sequence_number);
         self.buffer.insert(insert_pos, packet);
@@ -135,11 +228,6 @@ impl AdaptiveJitterBuffer {
                 warn!("Buffer overflow: dropped packet seq={}", dropped.sequence_number);
             }
         }
-
-        // Adapt buffer size based on network conditions
-        self.adapt_buffer_size();
-
-        Ok(())
     }
 
     /// Get next audio frame for playback
@@ -171,6 +259,46 @@ impl AdaptiveJitterBuffer {
         None
     }
 
+    /// Get next playout slot, reporting missing packets so the caller can conceal them.
+    /// Unlike `get_frame`, gaps are walked one sequence number at a time.
+    pub fn next_playout(&mut self) -> PlayoutSlot {
+        if self.config.discard_stall_backlog && self.buffer.len() > self.current_target_size * 2 {
+            let excess = self.buffer.len() - self.current_target_size;
+            self.buffer.drain(..excess);
+            self.late_packets += excess as u64;
+            if let Some(front) = self.buffer.front() {
+                debug!("Discarded {} frames of stall backlog, resuming at seq={}", excess, front.sequence_number);
+                self.expected_sequence = front.sequence_number;
+            }
+        }
+
+        if let Some(position) = self.buffer.iter().position(|p| p.sequence_number == self.expected_sequence)
+            && let Some(packet) = self.buffer.remove(position)
+        {
+            self.last_played_timestamp = packet.timestamp;
+            self.expected_sequence = packet.sequence_number.wrapping_add(1);
+            return PlayoutSlot::Packet(packet);
+        }
+
+        // Declare the expected packet lost once enough later packets are waiting behind it
+        let later_packets = self.buffer.iter()
+            .filter(|p| p.sequence_number > self.expected_sequence)
+            .count();
+        if later_packets > 0 && later_packets >= self.current_target_size {
+            let sequence_number = self.expected_sequence;
+            debug!("Packet lost: seq={}", sequence_number);
+            self.expected_sequence = self.expected_sequence.wrapping_add(1);
+            return PlayoutSlot::Lost { sequence_number };
+        }
+
+        PlayoutSlot::Empty
+    }
+
+    /// Buffered packet with the given sequence number, left in place
+    pub fn peek(&self, sequence_number: u32) -> Option<&AudioPacket> {
+        self.buffer.iter().find(|packet| packet.sequence_number == sequence_number)
+    }
+
     /// Find the correct position to insert a packet (maintaining sequence order)
     fn find_insert_position(&self, sequence: u32) -> usize {
         for (i, packet) in self.buffer.iter().enumerate() {
@@ -217,7 +345,7 @@ impl AdaptiveJitterBuffer {
     fn update_delay_statistics(&mut self, packet: &AudioPacket) {
         // Calculate packet delay (arrival time vs expected time)
         let _expected_arrival = Duration::from_millis(
-            packet.sequence_number as u64 * 20
+            packet.sequence_number as u64 * self.config.frame_duration_ms as u64
         );
         let actual_delay = packet.arrival_time.elapsed().as_millis() as f64;
 
@@ -284,6 +412,7 @@ impl AdaptiveJitterBuffer {
             overruns: self.buffer_overruns,
             late_packets: self.late_packets,
             duplicate_packets: self.duplicate_packets,
+            recovered_packets: self.recovered_packets,
             average_delay_ms: self.average_delay,
             delay_jitter_ms: self.delay_variance.sqrt(),
             packets_received: self.buffer_underruns + self.buffer.len() as u64,
@@ -304,6 +433,7 @@ impl AdaptiveJitterBuffer {
         self.buffer_overruns = 0;
         self.late_packets = 0;
         self.duplicate_packets = 0;
+        self.recovered_packets = 0;
         self.network_delay_samples.clear();
         self.current_target_size = self.config.initial_target_size;
         self.average_delay = 0.0;
@@ -321,6 +451,8 @@ pub struct JitterBufferStats {
     pub overruns: u64,
     pub late_packets: u64,
     pub duplicate_packets: u64,
+    /// Gaps filled from redundant copies in later packets
+    pub recovered_packets: u64,
     pub average_delay_ms: f64,
     pub delay_jitter_ms: f64,
     pub packets_received: u64,
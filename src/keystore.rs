@@ -0,0 +1,262 @@
+use anyhow::{Result, Context, anyhow};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, aead::{Aead, KeyInit, Payload}};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use log::info;
+
+use crate::config::ConfigManager;
+use crate::security::SecurityConfig;
+
+/// Environment variable holding the passphrase for an encrypted identity
+pub const IDENTITY_PASSPHRASE_ENV: &str = "HUMR_IDENTITY_PASSPHRASE";
+
+/// Keystore file name inside the config directory
+pub const IDENTITY_FILE_NAME: &str = "identity.toml";
+
+/// Current keystore file format version
+const KEYSTORE_VERSION: u32 = 1;
+
+/// PBKDF2-HMAC-SHA256 iterations for newly written keystores
+pub const DEFAULT_KDF_ITERATIONS: u32 = 600_000;
+
+/// Associated data label binding the encrypted key to its format
+const KEYSTORE_AAD_LABEL: &[u8] = b"HUMR_IDENTITY_V1";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// On-disk identity file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IdentityFile {
+    version: u32,
+    /// Ed25519 public key (base64), readable without the passphrase
+    public_key: String,
+    /// Secret key (base64), plaintext or ciphertext depending on `encryption`
+    secret_key: String,
+    encryption: Option<KeyEncryption>,
+}
+
+/// Passphrase encryption parameters for the secret key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyEncryption {
+    kdf: String,
+    iterations: u32,
+    salt: String,
+    nonce: String,
+}
+
+/// Persistent Ed25519 identity stored under the config directory
+pub struct IdentityKeystore {
+    path: PathBuf,
+    kdf_iterations: u32,
+}
+
+impl IdentityKeystore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            kdf_iterations: DEFAULT_KDF_ITERATIONS,
+        }
+    }
+
+    /// Keystore at the default location (`<config dir>/identity.toml`)
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(ConfigManager::config_dir()?.join(IDENTITY_FILE_NAME)))
+    }
+
+    /// Override the key derivation cost used when saving
+    pub fn with_kdf_iterations(mut self, iterations: u32) -> Self {
+        self.kdf_iterations = iterations;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Whether the stored secret key requires a passphrase
+    pub fn is_encrypted(&self) -> Result<bool> {
+        Ok(self.read_file()?.encryption.is_some())
+    }
+
+    /// Load the stored identity, creating and saving a new one on first use
+    pub fn load_or_create(&self, passphrase: Option<&str>) -> Result<SecurityConfig> {
+        if self.exists() {
+            return self.load(passphrase);
+        }
+
+        let config = SecurityConfig::new()?;
+        self.save(&config, passphrase)?;
+        info!("Created new identity keystore at {:?}", self.path);
+        Ok(config)
+    }
+
+    /// Load the stored identity
+    pub fn load(&self, passphrase: Option<&str>) -> Result<SecurityConfig> {
+        let file = self.read_file()?;
+        let public_key = decode_fixed::<32>(&file.public_key, "public key")?;
+
+        let secret_key = match &file.encryption {
+            None => decode_fixed::<32>(&file.secret_key, "secret key")?,
+            Some(encryption) => {
+                let passphrase = passphrase
+                    .ok_or_else(|| anyhow!("Identity keystore is encrypted; set {}", IDENTITY_PASSPHRASE_ENV))?;
+                decrypt_secret_key(encryption, &file.secret_key, &public_key, passphrase)?
+            }
+        };
+
+        let config = SecurityConfig::from_identity_key(&secret_key)?;
+        if config.get_public_identity().to_bytes() != public_key {
+            return Err(anyhow!("Identity keystore public key does not match secret key"));
+        }
+
+        Ok(config)
+    }
+
+    /// Write the identity, encrypting the secret key when a passphrase is given
+    pub fn save(&self, config: &SecurityConfig, passphrase: Option<&str>) -> Result<()> {
+        let public_key = config.get_public_identity().to_bytes();
+        let secret_key = config.identity_signing_key.to_bytes();
+
+        let (secret_key, encryption) = match passphrase {
+            None => (BASE64.encode(secret_key), None),
+            Some(passphrase) => {
+                let (ciphertext, encryption) =
+                    encrypt_secret_key(&secret_key, &public_key, passphrase, self.kdf_iterations)?;
+                (BASE64.encode(ciphertext), Some(encryption))
+            }
+        };
+
+        let file = IdentityFile {
+            version: KEYSTORE_VERSION,
+            public_key: BASE64.encode(public_key),
+            secret_key,
+            encryption,
+        };
+        let contents = toml::to_string_pretty(&file)
+            .context("Failed to serialize identity keystore")?;
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create keystore directory")?;
+        }
+        write_private_file(&self.path, contents.as_bytes())
+            .context("Failed to write identity keystore")?;
+
+        info!("Identity keystore saved to: {:?}", self.path);
+        Ok(())
+    }
+
+    fn read_file(&self) -> Result<IdentityFile> {
+        let contents = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read identity keystore {:?}", self.path))?;
+        let file: IdentityFile = toml::from_str(&contents)
+            .context("Failed to parse identity keystore")?;
+
+        if file.version != KEYSTORE_VERSION {
+            return Err(anyhow!("Unsupported identity keystore version: {}", file.version));
+        }
+        Ok(file)
+    }
+}
+
+/// Read the keystore passphrase from the environment, if set
+pub fn passphrase_from_env() -> Option<String> {
+    std::env::var(IDENTITY_PASSPHRASE_ENV).ok().filter(|passphrase| !passphrase.is_empty())
+}
+
+fn encrypt_secret_key(
+    secret_key: &[u8; 32],
+    public_key: &[u8; 32],
+    passphrase: &str,
+    iterations: u32,
+) -> Result<(Vec<u8>, KeyEncryption)> {
+    let salt: [u8; SALT_LEN] = rand::random();
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let cipher = passphrase_cipher(passphrase, &salt, iterations)?;
+
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: secret_key, aad: &keystore_aad(public_key) })
+        .map_err(|_| anyhow!("Failed to encrypt identity key"))?;
+
+    Ok((ciphertext, KeyEncryption {
+        kdf: "pbkdf2-sha256".to_string(),
+        iterations,
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce),
+    }))
+}
+
+fn decrypt_secret_key(
+    encryption: &KeyEncryption,
+    ciphertext: &str,
+    public_key: &[u8; 32],
+    passphrase: &str,
+) -> Result<[u8; 32]> {
+    if encryption.kdf != "pbkdf2-sha256" {
+        return Err(anyhow!("Unsupported keystore KDF: {}", encryption.kdf));
+    }
+
+    let salt = decode_fixed::<SALT_LEN>(&encryption.salt, "salt")?;
+    let nonce = decode_fixed::<NONCE_LEN>(&encryption.nonce, "nonce")?;
+    let ciphertext = BASE64.decode(ciphertext).context("Invalid secret key encoding")?;
+    let cipher = passphrase_cipher(passphrase, &salt, encryption.iterations)?;
+
+    let secret_key = cipher
+        .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &keystore_aad(public_key) })
+        .map_err(|_| anyhow!("Wrong passphrase or corrupted identity keystore"))?;
+
+    secret_key.try_into().map_err(|_| anyhow!("Invalid secret key length"))
+}
+
+/// Derive the key-wrapping cipher from a passphrase with PBKDF2-HMAC-SHA256
+fn passphrase_cipher(passphrase: &str, salt: &[u8], iterations: u32) -> Result<ChaCha20Poly1305> {
+    let iterations = NonZeroU32::new(iterations)
+        .ok_or_else(|| anyhow!("KDF iterations must be non-zero"))?;
+
+    let mut key = [0u8; 32];
+    ring::pbkdf2::derive(ring::pbkdf2::PBKDF2_HMAC_SHA256, iterations, salt, passphrase.as_bytes(), &mut key);
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+fn keystore_aad(public_key: &[u8; 32]) -> Vec<u8> {
+    [KEYSTORE_AAD_LABEL, public_key.as_slice()].concat()
+}
+
+fn decode_fixed<const N: usize>(value: &str, field: &str) -> Result<[u8; N]> {
+    let bytes = BASE64.decode(value)
+        .with_context(|| format!("Invalid {} encoding", field))?;
+    bytes.try_into().map_err(|_| anyhow!("Invalid {} length", field))
+}
+
+/// Write a file readable only by the owner, replacing any existing file
+fn write_private_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let temp_path = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(&temp_path)?;
+    #[cfg(unix)]
+    {
+        // The mode only applies on creation; tighten a leftover temp file too
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)
+}
@@ -0,0 +1,365 @@
+use anyhow::{Result, Context, anyhow};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::{info, warn};
+
+use crate::config::ConfigManager;
+use crate::security::identity_fingerprint;
+
+/// Known-peers file name inside the config directory
+pub const KNOWN_PEERS_FILE_NAME: &str = "known_peers";
+
+/// Key type tag written on every entry, as in SSH's known_hosts
+const KEY_TYPE: &str = "ed25519";
+
+/// Trailing marker on entries whose key was confirmed by comparing codes
+const VERIFIED_MARKER: &str = "verified";
+
+/// How to treat peers whose identity key is not yet known
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrustPolicy {
+    /// Only connect to peers already in known_peers or the trusted list
+    Strict,
+    /// Pin unknown peers on first use, reject them if their key later changes
+    #[default]
+    Tofu,
+    /// Accept unknown peers without recording them
+    Permissive,
+}
+
+/// Trust failure reported by the handshake; downcast from `anyhow::Error` to surface it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrustError {
+    /// Strict policy and the peer's key is not known
+    UnknownPeer { name: Option<String>, fingerprint: String },
+    /// The peer presented a different key from the one pinned for its name
+    KeyChanged {
+        name: String,
+        known_fingerprint: String,
+        presented_fingerprint: String,
+        first_seen: String,
+        /// The old key had been confirmed out of band, so the change is especially suspect
+        was_verified: bool,
+    },
+}
+
+impl std::fmt::Display for TrustError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrustError::UnknownPeer { name: Some(name), fingerprint } =>
+                write!(f, "Unknown peer {} ({}) rejected by strict trust policy", name, fingerprint),
+            TrustError::UnknownPeer { name: None, fingerprint } =>
+                write!(f, "Unknown peer {} rejected by strict trust policy", fingerprint),
+            TrustError::KeyChanged { name, known_fingerprint, presented_fingerprint, first_seen, was_verified } => write!(
+                f,
+                "IDENTITY KEY CHANGED for {}: {} since {} as {}, now presenting {}. \
+                 Someone may be impersonating this peer; remove the entry from known_peers only if the change is expected",
+                name,
+                if *was_verified { "verified" } else { "known" },
+                first_seen,
+                known_fingerprint,
+                presented_fingerprint
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TrustError {}
+
+/// Outcome of a successful trust check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustDecision {
+    /// Key matches a known_peers entry or the trusted list
+    Known,
+    /// Key was unknown and has now been pinned
+    Pinned,
+    /// Key was unknown and accepted without pinning
+    Accepted,
+}
+
+/// A pinned peer identity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownPeer {
+    pub name: String,
+    pub public_key: VerifyingKey,
+    /// UTC timestamp the key was first seen, `YYYY-MM-DDTHH:MM:SSZ`
+    pub first_seen: String,
+    /// Confirmed by comparing short authentication strings
+    pub verified: bool,
+}
+
+/// SSH-style known_peers store: one `<name> ed25519 <base64 key> <first seen> [verified]` entry per line
+#[derive(Debug, Clone, Default)]
+pub struct KnownPeers {
+    path: Option<PathBuf>,
+    peers: Vec<KnownPeer>,
+}
+
+impl KnownPeers {
+    /// Store that is never written to disk
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Load a known_peers file, starting empty if it does not exist
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let peers = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read known peers {:?}", path))?;
+            parse_known_peers(&contents)?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self { path: Some(path), peers })
+    }
+
+    /// Load the known_peers file from the config directory
+    pub fn load_default() -> Result<Self> {
+        Self::load(ConfigManager::config_dir()?.join(KNOWN_PEERS_FILE_NAME))
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn peers(&self) -> &[KnownPeer] {
+        &self.peers
+    }
+
+    pub fn get(&self, name: &str) -> Option<&KnownPeer> {
+        self.peers.iter().find(|peer| peer.name == name)
+    }
+
+    /// First entry pinned to this key, under any name
+    pub fn find_by_key(&self, public_key: &VerifyingKey) -> Option<&KnownPeer> {
+        self.peers.iter().find(|peer| &peer.public_key == public_key)
+    }
+
+    /// Pin a key under a name; an existing entry with a different key is a `KeyChanged` error
+    pub fn insert(&mut self, name: &str, public_key: VerifyingKey) -> Result<(), TrustError> {
+        if let Some(existing) = self.get(name) {
+            if existing.public_key != public_key {
+                return Err(key_changed(existing, &public_key));
+            }
+            return Ok(());
+        }
+
+        self.peers.push(KnownPeer {
+            name: entry_name(name),
+            public_key,
+            first_seen: utc_timestamp(std::time::SystemTime::now()),
+            verified: false,
+        });
+        Ok(())
+    }
+
+    /// Mark a key as verified, pinning it first if needed, and save the store
+    pub fn mark_verified(&mut self, name: Option<&str>, public_key: &VerifyingKey) -> Result<()> {
+        match name {
+            Some(name) => self.insert(name, *public_key)?,
+            None if self.find_by_key(public_key).is_none() => {
+                self.insert(&anonymous_peer_name(public_key), *public_key)?
+            }
+            None => {}
+        }
+
+        for peer in self.peers.iter_mut().filter(|peer| &peer.public_key == public_key) {
+            peer.verified = true;
+        }
+        self.save()
+    }
+
+    /// Whether any entry for this key has been verified
+    pub fn is_verified(&self, public_key: &VerifyingKey) -> bool {
+        self.peers.iter().any(|peer| &peer.public_key == public_key && peer.verified)
+    }
+
+    /// Forget a peer, e.g. after an expected key change
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.peers.len();
+        self.peers.retain(|peer| peer.name != name);
+        self.peers.len() != before
+    }
+
+    /// Check a handshake peer against the store and policy, pinning it under TOFU.
+    /// `name` is how we addressed the peer (e.g. `host:port`); listeners pass `None`.
+    /// `trusted` holds keys pre-approved through configuration.
+    pub fn verify(
+        &mut self,
+        name: Option<&str>,
+        public_key: &VerifyingKey,
+        trusted: &[VerifyingKey],
+        policy: TrustPolicy,
+    ) -> Result<TrustDecision, TrustError> {
+        // A pinned name must always present the same key
+        if let Some(name) = name
+            && let Some(existing) = self.get(name) {
+            if &existing.public_key != public_key {
+                return Err(key_changed(existing, public_key));
+            }
+            return Ok(TrustDecision::Known);
+        }
+
+        if trusted.contains(public_key) || self.find_by_key(public_key).is_some() {
+            return Ok(TrustDecision::Known);
+        }
+
+        match policy {
+            TrustPolicy::Strict => Err(TrustError::UnknownPeer {
+                name: name.map(str::to_string),
+                fingerprint: identity_fingerprint(public_key),
+            }),
+            TrustPolicy::Permissive => Ok(TrustDecision::Accepted),
+            TrustPolicy::Tofu => {
+                let pin_name = match name {
+                    Some(name) => name.to_string(),
+                    None => anonymous_peer_name(public_key),
+                };
+                self.insert(&pin_name, *public_key)?;
+                if let Err(e) = self.save() {
+                    warn!("Failed to persist known peer {}: {}", pin_name, e);
+                }
+                info!("Pinned new peer {} ({})", pin_name, identity_fingerprint(public_key));
+                Ok(TrustDecision::Pinned)
+            }
+        }
+    }
+
+    /// Write the store back to its file; in-memory stores are left alone
+    pub fn save(&self) -> Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let mut contents = String::from("# Humr known peers: <name> ed25519 <public key> <first seen> [verified]\n");
+        for peer in &self.peers {
+            contents.push_str(&format!(
+                "{} {} {} {}{}\n",
+                peer.name,
+                KEY_TYPE,
+                BASE64.encode(peer.public_key.as_bytes()),
+                peer.first_seen,
+                if peer.verified { format!(" {}", VERIFIED_MARKER) } else { String::new() }
+            ));
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("Failed to create known peers directory")?;
+        }
+        fs::write(path, contents)
+            .context("Failed to write known peers file")?;
+        Ok(())
+    }
+}
+
+/// Parse base64 identity keys such as `SecuritySettings::trusted_peers`, skipping invalid ones
+pub fn parse_trusted_keys(encoded: &[String]) -> Vec<VerifyingKey> {
+    encoded
+        .iter()
+        .filter_map(|key| match decode_public_key(key) {
+            Ok(key) => Some(key),
+            Err(e) => {
+                warn!("Ignoring invalid trusted peer key {}: {}", key, e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn parse_known_peers(contents: &str) -> Result<Vec<KnownPeer>> {
+    let mut peers = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (name, key_type, key, first_seen, verified) = match fields.as_slice() {
+            [name, key_type, key, first_seen, marker] if *marker == VERIFIED_MARKER => {
+                (*name, *key_type, *key, *first_seen, true)
+            }
+            [name, key_type, key, first_seen] => (*name, *key_type, *key, *first_seen, false),
+            [name, key_type, key] => (*name, *key_type, *key, "unknown", false),
+            _ => return Err(anyhow!(
+                "known_peers line {}: expected <name> {} <key> <first seen> [{}]",
+                index + 1, KEY_TYPE, VERIFIED_MARKER
+            )),
+        };
+
+        if key_type != KEY_TYPE {
+            return Err(anyhow!("known_peers line {}: unsupported key type {}", index + 1, key_type));
+        }
+
+        let public_key = decode_public_key(key)
+            .with_context(|| format!("known_peers line {}", index + 1))?;
+        peers.push(KnownPeer {
+            name: name.to_string(),
+            public_key,
+            first_seen: first_seen.to_string(),
+            verified,
+        });
+    }
+
+    Ok(peers)
+}
+
+fn decode_public_key(encoded: &str) -> Result<VerifyingKey> {
+    let bytes: [u8; 32] = BASE64.decode(encoded.trim())
+        .context("Invalid public key encoding")?
+        .try_into()
+        .map_err(|_| anyhow!("Invalid public key length"))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| anyhow!("Invalid public key: {}", e))
+}
+
+fn key_changed(existing: &KnownPeer, presented: &VerifyingKey) -> TrustError {
+    TrustError::KeyChanged {
+        name: existing.name.clone(),
+        known_fingerprint: identity_fingerprint(&existing.public_key),
+        presented_fingerprint: identity_fingerprint(presented),
+        first_seen: existing.first_seen.clone(),
+        was_verified: existing.verified,
+    }
+}
+
+/// Name for a peer that reached us without an address we dialled
+fn anonymous_peer_name(public_key: &VerifyingKey) -> String {
+    let fingerprint = identity_fingerprint(public_key);
+    format!("peer-{}", fingerprint.replace(' ', "").get(..8).unwrap_or_default())
+}
+
+/// Entry names are whitespace-delimited on disk
+fn entry_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// Format a time as `YYYY-MM-DDTHH:MM:SSZ` (UTC)
+fn utc_timestamp(time: std::time::SystemTime) -> String {
+    let secs = time.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    // Civil-from-days conversion (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, rem / 3_600, (rem % 3_600) / 60, rem % 60
+    )
+}
@@ -50,8 +50,21 @@
 //! - [`app`]: Main application orchestration and high-level API
 //! - [`audio`]: Basic audio processing and device management
 //! - [`realtime_audio`]: Lock-free real-time audio processing pipeline
+//! - [`mixer`]: Multi-party mixing with per-participant gain, mute and level metering
+//! - [`codec`]: Voice codec trait implemented by Opus, G.711 ([`g711`]) and G.722 ([`g722`])
+//! - [`capabilities`]: Signed audio capability offers and the format a call settles on
 //! - [`security`]: Cryptographic protocols and secure session management
-//! - [`network`]: UDP networking with encryption and handshake protocols
+//! - [`keystore`]: Persistent identity key storage with optional passphrase encryption
+//! - [`known_peers`]: SSH-style pinned peer identities and trust policies
+//! - [`wire`]: Binary packet framing for handshake and audio messages
+//! - [`network`]: UDP networking with a per-peer table of encrypted sessions and handshake protocols
+//! - [`relay`]: Selective forwarding relay for group calls, with end-to-end sender keys
+//! - [`stun`]: STUN binding requests and candidates for NAT traversal
+//! - [`turn`]: TURN-style relay allocations used when direct UDP fails
+//! - [`transport`]: UDP transport, TCP and WebSocket fallbacks, and an in-process loopback for tests
+//! - [`netsim`]: Seeded loss, delay, jitter, reordering and bandwidth impairment for reproducible tests
+//! - [`congestion`]: Receiver feedback reports and sender-side bitrate adaptation
+//! - [`redundancy`]: RFC 2198-style audio payloads repeating earlier frames against bursty loss
 //! - [`config`]: Configuration management with persistence and validation
 //! - [`monitoring`]: Health monitoring and performance metrics collection
 //! - [`error_recovery`]: Error handling with circuit breakers and automatic recovery
@@ -63,11 +76,11 @@
 //! 1. **Input Capture**: Platform-specific audio device capture
 //! 2. **Noise Suppression**: Advanced time-domain noise reduction
 //! 3. **Echo Cancellation**: Adaptive echo cancellation
-//! 4. **Encoding**: Opus audio compression
+//! 4. **Encoding**: Opus, G.722 or G.711 compression
 //! 5. **Encryption**: ChaCha20-Poly1305 authenticated encryption
 //! 6. **Network**: UDP transmission with jitter buffering
 //! 7. **Decryption**: Message authentication and decryption
-//! 8. **Decoding**: Opus audio decompression
+//! 8. **Decoding**: Decompression with the same codec
 //! 9. **Output Playback**: Platform-specific audio device playback
 //!
 //! ## Security Model
@@ -84,21 +97,60 @@ pub mod audio;
 /// Lock-free real-time audio processing with configurable parameters
 pub mod realtime_audio;
 
+/// Multi-party audio mixer with per-participant gain and soft clipping
+pub mod mixer;
+
 /// Adaptive jitter buffer for network packet reordering
 pub mod jitter_buffer;
 
 /// Opus audio codec integration for high-quality compression
 pub mod opus_codec;
 
+/// Voice codec trait, codec selection and negotiation
+pub mod codec;
+
+/// G.711 μ-law and A-law codecs
+pub mod g711;
+
+/// G.722 wideband ADPCM codec
+pub mod g722;
+
+/// Codec and audio format negotiation in the handshake
+pub mod capabilities;
+
 /// Advanced noise suppression with speech preservation
 pub mod noise_suppression;
 
 /// Adaptive echo cancellation for full-duplex communication
 pub mod echo_cancellation;
 
+/// Versioned binary wire format for protocol messages
+pub mod wire;
+
 /// UDP networking with encryption and secure handshake protocols
 pub mod network;
 
+/// Selective forwarding relay server and client for group calls
+pub mod relay;
+
+/// STUN client and responder with host and server-reflexive candidates
+pub mod stun;
+
+/// TURN-style relay server that shuttles ciphertext for clients behind strict NATs
+pub mod turn;
+
+/// Packet transports: UDP, length-prefixed TCP or WebSocket fallbacks, and in-process loopback
+pub mod transport;
+
+/// Network impairment simulator: transport wrapper and `humr netsim` UDP proxy
+pub mod netsim;
+
+/// Receiver feedback and congestion control driving the Opus encoder
+pub mod congestion;
+
+/// Redundant audio payloads and the sender-side redundancy encoder
+pub mod redundancy;
+
 /// Interactive command-line user interface
 pub mod ui;
 
@@ -111,6 +163,12 @@ pub mod platform;
 /// Cryptographic protocols and secure session management
 pub mod security;
 
+/// Persistent identity key storage with optional passphrase encryption
+pub mod keystore;
+
+/// SSH-style known peers store with strict, TOFU and permissive policies
+pub mod known_peers;
+
 /// Configuration management with persistence and validation
 pub mod config;
 
@@ -137,6 +195,7 @@ pub use app::VocalCommunicationApp;
 pub use config::AppConfig;
 pub use realtime_audio::{AudioConfiguration, RealTimeAudioProcessor};
 pub use security::{SecurityConfig, SecureSession};
+pub use keystore::IdentityKeystore;
 pub use monitoring::{HealthMonitor, MetricsCollector, HealthReport};
 pub use discovery::{DiscoveryManager, RoomInfo, ConnectionMethod, MagicLinkService, QRCodeGenerator};
-pub use terminal_ui::{TerminalApp, run_terminal_ui};
\ No newline at end of file
+pub use terminal_ui::{TerminalApp, run_terminal_ui, run_shared_terminal_ui};
\ No newline at end of file
@@ -16,20 +16,24 @@ pub struct RoomName {
 }
 
 impl RoomName {
-    pub fn generate() -> Self {
-        let adjectives = [
-            "sunset", "ocean", "forest", "mountain", "river", "cloud", "star", "moon",
-            "dawn", "twilight", "aurora", "crystal", "silver", "golden", "emerald", "azure",
-            "crimson", "amber", "jade", "pearl", "coral", "midnight", "thunder", "lightning",
-            "whisper", "echo", "shadow", "bright", "calm", "swift", "gentle", "fierce",
-        ];
+    /// Word lists shared with the session's short authentication string
+    pub const ADJECTIVES: [&'static str; 32] = [
+        "sunset", "ocean", "forest", "mountain", "river", "cloud", "star", "moon",
+        "dawn", "twilight", "aurora", "crystal", "silver", "golden", "emerald", "azure",
+        "crimson", "amber", "jade", "pearl", "coral", "midnight", "thunder", "lightning",
+        "whisper", "echo", "shadow", "bright", "calm", "swift", "gentle", "fierce",
+    ];
+
+    pub const NOUNS: [&'static str; 32] = [
+        "dragon", "phoenix", "tiger", "eagle", "wolf", "dolphin", "hawk", "falcon",
+        "bear", "lion", "panther", "raven", "swan", "deer", "fox", "owl",
+        "shark", "whale", "leopard", "cheetah", "lynx", "jaguar", "cobra", "viper",
+        "heron", "condor", "osprey", "kestrel", "sparrow", "robin", "cardinal", "wren",
+    ];
 
-        let nouns = [
-            "dragon", "phoenix", "tiger", "eagle", "wolf", "dolphin", "hawk", "falcon",
-            "bear", "lion", "panther", "raven", "swan", "deer", "fox", "owl",
-            "shark", "whale", "leopard", "cheetah", "lynx", "jaguar", "cobra", "viper",
-            "falcon", "condor", "osprey", "kestrel", "sparrow", "robin", "cardinal", "wren",
-        ];
+    pub fn generate() -> Self {
+        let adjectives = Self::ADJECTIVES;
+        let nouns = Self::NOUNS;
 
         let adj_idx = fastrand::usize(..adjectives.len());
         let noun_idx = fastrand::usize(..nouns.len());
@@ -149,6 +153,7 @@ pub struct LighthouseService {
     pub qr_generator: super::discovery::QRCodeGenerator,
     pub magic_link_service: super::discovery::MagicLinkService,
     discovery_engine: DiscoveryEngine,
+    candidates: Vec<Candidate>,
     event_sender: mpsc::UnboundedSender<LighthouseEvent>,
     #[allow(dead_code)]
     event_receiver: mpsc::UnboundedReceiver<LighthouseEvent>,
@@ -186,6 +191,7 @@ impl LighthouseService {
             qr_generator: super::discovery::QRCodeGenerator,
             magic_link_service: super::discovery::MagicLinkService,
             discovery_engine: DiscoveryEngine::new(tx.clone()),
+            candidates: Vec::new(),
             event_sender: tx,
             event_receiver: rx,
         }
@@ -249,6 +255,11 @@ impl LighthouseService {
         format!("https://humr.chat/{}", self.room_name.to_string())
     }
 
+    /// Advertise candidates gathered with `NetworkManager::gather_candidates`
+    pub fn set_candidates(&mut self, candidates: Vec<Candidate>) {
+        self.candidates = candidates;
+    }
+
     pub fn get_connection_methods(&self) -> Vec<ConnectionMethod> {
         let mut methods = Vec::new();
 
@@ -259,12 +270,6 @@ impl LighthouseService {
                         ip: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 100)), // TODO: Get real local IP
                     });
                 }
-                DiscoveryMethod::UPnP { external_port, .. } => {
-                    methods.push(ConnectionMethod::Internet {
-                        public_ip: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 42)), // TODO: Get real external IP
-                        port: *external_port,
-                    });
-                }
                 DiscoveryMethod::BluetoothLE { .. } => {
                     methods.push(ConnectionMethod::Bluetooth {
                         device_id: "humr-device".to_string(), // TODO: Get real device ID
@@ -274,6 +279,9 @@ impl LighthouseService {
             }
         }
 
+        // The public address is whatever STUN reported, not a UPnP guess
+        methods.extend(candidate_methods(&self.candidates));
+
         methods.push(ConnectionMethod::QRCode {
             data: self.room_name.to_qr_data(),
         });
@@ -293,7 +301,8 @@ impl LighthouseService {
 }
 
 // Re-use ConnectionMethod from discovery module
-use super::discovery::ConnectionMethod;
+use super::discovery::{ConnectionMethod, candidate_methods};
+use super::stun::Candidate;
 
 #[derive(Debug, Clone)]
 pub struct DiscoveredPeer {
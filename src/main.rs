@@ -2,6 +2,9 @@ use anyhow::Result;
 use clap::{Arg, Command};
 use env_logger;
 use humr::run_terminal_ui;
+use humr::keystore::{IdentityKeystore, IDENTITY_PASSPHRASE_ENV, passphrase_from_env};
+use humr::netsim::{ImpairmentConfig, LossModel, NetsimProxy};
+use std::time::Duration;
 use tokio;
 
 #[tokio::main]
@@ -43,8 +46,124 @@ async fn main() -> Result<()> {
                 .value_parser(clap::value_parser!(u16))
                 .default_value("8080")
         )
+        .subcommand(
+            Command::new("identity")
+                .about(format!(
+                    "Manage the persistent identity key (passphrase read from {})",
+                    IDENTITY_PASSPHRASE_ENV
+                ))
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("fingerprint")
+                        .about("Print the fingerprint of the local identity")
+                )
+                .subcommand(
+                    Command::new("export")
+                        .about("Write the local identity to a keystore file")
+                        .arg(Arg::new("file").value_name("FILE").required(true))
+                )
+                .subcommand(
+                    Command::new("import")
+                        .about("Replace the local identity with one from a keystore file")
+                        .arg(Arg::new("file").value_name("FILE").required(true))
+                        .arg(
+                            Arg::new("force")
+                                .long("force")
+                                .help("Overwrite an existing identity")
+                                .action(clap::ArgAction::SetTrue)
+                        )
+                )
+        )
+        .subcommand(
+            Command::new("netsim")
+                .about("Run a UDP proxy that adds loss, delay, jitter, reordering and duplication")
+                .arg(
+                    Arg::new("listen")
+                        .long("listen")
+                        .value_name("ADDR")
+                        .help("Address clients send to")
+                        .default_value("127.0.0.1:9080")
+                )
+                .arg(
+                    Arg::new("target")
+                        .long("target")
+                        .value_name("ADDR")
+                        .help("Address traffic is forwarded to")
+                        .value_parser(clap::value_parser!(std::net::SocketAddr))
+                        .required(true)
+                )
+                .arg(
+                    Arg::new("loss")
+                        .long("loss")
+                        .value_name("PROBABILITY")
+                        .help("Packet loss probability (average rate when --burst is set)")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.0")
+                )
+                .arg(
+                    Arg::new("burst")
+                        .long("burst")
+                        .value_name("PACKETS")
+                        .help("Mean loss burst length; switches to Gilbert-Elliott bursty loss")
+                        .value_parser(clap::value_parser!(f64))
+                )
+                .arg(
+                    Arg::new("delay-ms")
+                        .long("delay-ms")
+                        .value_name("MS")
+                        .help("Fixed one-way delay")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("0")
+                )
+                .arg(
+                    Arg::new("jitter-ms")
+                        .long("jitter-ms")
+                        .value_name("MS")
+                        .help("Maximum random extra delay per packet")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("0")
+                )
+                .arg(
+                    Arg::new("reorder")
+                        .long("reorder")
+                        .value_name("PROBABILITY")
+                        .help("Chance a packet is held back so later packets overtake it")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.0")
+                )
+                .arg(
+                    Arg::new("duplicate")
+                        .long("duplicate")
+                        .value_name("PROBABILITY")
+                        .help("Chance a packet is delivered twice")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.0")
+                )
+                .arg(
+                    Arg::new("bandwidth-kbps")
+                        .long("bandwidth-kbps")
+                        .value_name("KBPS")
+                        .help("Bandwidth cap in each direction")
+                        .value_parser(clap::value_parser!(u64))
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .value_name("SEED")
+                        .help("Random seed, so a run can be reproduced")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("1")
+                )
+        )
         .get_matches();
 
+    if let Some(("identity", identity_matches)) = matches.subcommand() {
+        return run_identity_command(identity_matches);
+    }
+    if let Some(("netsim", netsim_matches)) = matches.subcommand() {
+        return run_netsim_command(netsim_matches).await;
+    }
+
     let ui_type = matches.get_one::<String>("ui").unwrap();
     let mode = matches.get_one::<String>("mode").unwrap();
     let port = *matches.get_one::<u16>("port").unwrap();
@@ -86,6 +205,76 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+fn run_identity_command(matches: &clap::ArgMatches) -> Result<()> {
+    let keystore = IdentityKeystore::open_default()?;
+    let passphrase = passphrase_from_env();
+
+    match matches.subcommand() {
+        Some(("fingerprint", _)) => {
+            let identity = keystore.load_or_create(passphrase.as_deref())?;
+            println!("🔑 Identity fingerprint: {}", identity.fingerprint());
+            println!("📁 Keystore: {}", keystore.path().display());
+        }
+        Some(("export", export_matches)) => {
+            let file = export_matches.get_one::<String>("file").unwrap();
+            let identity = keystore.load_or_create(passphrase.as_deref())?;
+            IdentityKeystore::new(file).save(&identity, passphrase.as_deref())?;
+            println!("📤 Exported identity {} to {}", identity.fingerprint(), file);
+            if passphrase.is_none() {
+                println!("⚠️  Export is not passphrase protected; set {} to encrypt it", IDENTITY_PASSPHRASE_ENV);
+            }
+        }
+        Some(("import", import_matches)) => {
+            let file = import_matches.get_one::<String>("file").unwrap();
+            if keystore.exists() && !import_matches.get_flag("force") {
+                eprintln!("❌ An identity already exists at {}. Use --force to replace it.", keystore.path().display());
+                std::process::exit(1);
+            }
+            let identity = IdentityKeystore::new(file).load(passphrase.as_deref())?;
+            keystore.save(&identity, passphrase.as_deref())?;
+            println!("📥 Imported identity {}", identity.fingerprint());
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+async fn run_netsim_command(matches: &clap::ArgMatches) -> Result<()> {
+    let listen = matches.get_one::<String>("listen").unwrap();
+    let target = *matches.get_one::<std::net::SocketAddr>("target").unwrap();
+    let loss = *matches.get_one::<f64>("loss").unwrap();
+    let seed = *matches.get_one::<u64>("seed").unwrap();
+
+    let loss_model = match matches.get_one::<f64>("burst") {
+        _ if loss <= 0.0 => LossModel::None,
+        // Every packet in the bad state is lost, so the average rate sets how often bursts start
+        Some(&burst) if burst > 1.0 => {
+            let bad_to_good = 1.0 / burst;
+            let good_to_bad = (loss * bad_to_good / (1.0 - loss).max(f64::EPSILON)).min(1.0);
+            LossModel::GilbertElliott { good_to_bad, bad_to_good, loss_in_good: 0.0, loss_in_bad: 1.0 }
+        }
+        _ => LossModel::Random { probability: loss },
+    };
+    let upstream = ImpairmentConfig {
+        loss: loss_model,
+        delay: Duration::from_millis(*matches.get_one::<u64>("delay-ms").unwrap()),
+        jitter: Duration::from_millis(*matches.get_one::<u64>("jitter-ms").unwrap()),
+        reorder_probability: *matches.get_one::<f64>("reorder").unwrap(),
+        duplicate_probability: *matches.get_one::<f64>("duplicate").unwrap(),
+        bandwidth_bps: matches.get_one::<u64>("bandwidth-kbps").map(|kbps| kbps * 1000),
+        seed,
+        ..ImpairmentConfig::default()
+    };
+    // Same impairments back, but an independent random sequence
+    let downstream = ImpairmentConfig { seed: seed.wrapping_add(1), ..upstream.clone() };
+
+    let proxy = NetsimProxy::bind(listen, target, upstream, downstream).await?;
+    println!("🌩️  Netsim proxy on {} -> {}", proxy.local_addr()?, target);
+    println!("🎲 Seed {} (reuse it to replay the same impairments)", seed);
+    proxy.run().await
+}
+
 async fn start_host_mode(port: u16) -> Result<()> {
     use humr::{DiscoveryManager, QRCodeGenerator, MagicLinkService};
 
@@ -123,6 +312,9 @@ async fn start_host_mode(port: u16) -> Result<()> {
             humr::ConnectionMethod::MagicLink { url } => {
                 println!("  • Magic Link: {}", url);
             }
+            humr::ConnectionMethod::IceCandidate { candidate } => {
+                println!("  • Candidate ({:?}): {}", candidate.kind, candidate.addr);
+            }
             _ => {}
         }
     }
@@ -0,0 +1,275 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use crate::realtime_audio::AudioFrame;
+
+/// Highest per-participant gain accepted from the UI (+12 dB)
+pub const MAX_PARTICIPANT_GAIN: f32 = 4.0;
+
+/// Mixer configuration
+#[derive(Debug, Clone, Copy)]
+pub struct MixerConfig {
+    /// Level above which the soft clipper starts compressing (0.0-1.0)
+    pub limiter_threshold: f32,
+    /// Smoothed RMS level at which a participant counts as speaking
+    pub speaking_threshold: f32,
+    /// Per-frame decay of the level meter once a participant goes quiet (0.0-1.0)
+    pub level_release: f32,
+}
+
+impl Default for MixerConfig {
+    fn default() -> Self {
+        Self {
+            limiter_threshold: 0.8,
+            speaking_threshold: 0.02,
+            level_release: 0.85,
+        }
+    }
+}
+
+impl MixerConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.limiter_threshold <= 0.0 || self.limiter_threshold >= 1.0 {
+            return Err(anyhow!("Limiter threshold must be between 0.0 and 1.0 (exclusive)"));
+        }
+        if !(0.0..=1.0).contains(&self.speaking_threshold) {
+            return Err(anyhow!("Speaking threshold must be between 0.0 and 1.0"));
+        }
+        if !(0.0..1.0).contains(&self.level_release) {
+            return Err(anyhow!("Level release must be between 0.0 and 1.0 (exclusive)"));
+        }
+        Ok(())
+    }
+}
+
+/// Gain and mute for one participant
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParticipantControl {
+    pub gain: f32,
+    pub muted: bool,
+}
+
+impl Default for ParticipantControl {
+    fn default() -> Self {
+        Self { gain: 1.0, muted: false }
+    }
+}
+
+/// Level meter reading for one participant
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParticipantLevel {
+    pub peer: SocketAddr,
+    /// Smoothed RMS of the decoded stream before gain and mute
+    pub level: f32,
+    pub is_speaking: bool,
+    pub gain: f32,
+    pub muted: bool,
+}
+
+/// Mixer statistics
+#[derive(Debug, Clone, Default)]
+pub struct MixerStats {
+    pub frames_mixed: u64,
+    /// Samples the soft clipper had to compress
+    pub samples_limited: u64,
+}
+
+/// State shared between the processing thread and the UI
+#[derive(Default)]
+struct MixerShared {
+    controls: HashMap<SocketAddr, ParticipantControl>,
+    // Bumped on every control change so the mixer only copies when needed
+    controls_version: u64,
+    levels: Vec<ParticipantLevel>,
+    stats: MixerStats,
+}
+
+/// UI-side handle for adjusting participants and reading their levels
+#[derive(Clone, Default)]
+pub struct MixerHandle {
+    shared: Arc<Mutex<MixerShared>>,
+}
+
+impl MixerHandle {
+    /// Set a participant's gain, clamped to `0.0..=MAX_PARTICIPANT_GAIN`
+    pub fn set_gain(&self, peer: SocketAddr, gain: f32) {
+        self.update(peer, |control| control.gain = gain.clamp(0.0, MAX_PARTICIPANT_GAIN));
+    }
+
+    pub fn set_muted(&self, peer: SocketAddr, muted: bool) {
+        self.update(peer, |control| control.muted = muted);
+    }
+
+    pub fn control(&self, peer: SocketAddr) -> ParticipantControl {
+        self.shared.lock()
+            .map(|shared| shared.controls.get(&peer).copied().unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    /// Latest per-participant levels, loudest first
+    pub fn levels(&self) -> Vec<ParticipantLevel> {
+        self.shared.lock().map(|shared| shared.levels.clone()).unwrap_or_default()
+    }
+
+    /// Participants currently above the speaking threshold
+    pub fn active_speakers(&self) -> Vec<SocketAddr> {
+        self.levels().into_iter()
+            .filter(|level| level.is_speaking)
+            .map(|level| level.peer)
+            .collect()
+    }
+
+    pub fn stats(&self) -> MixerStats {
+        self.shared.lock().map(|shared| shared.stats.clone()).unwrap_or_default()
+    }
+
+    fn update(&self, peer: SocketAddr, apply: impl FnOnce(&mut ParticipantControl)) {
+        if let Ok(mut shared) = self.shared.lock() {
+            apply(shared.controls.entry(peer).or_default());
+            shared.controls_version += 1;
+        }
+    }
+}
+
+/// Sums decoded participant frames with per-participant gain and mute, then soft-clips the result
+pub struct AudioMixer {
+    config: MixerConfig,
+    handle: MixerHandle,
+    // Local copy of the controls, refreshed when the UI changes them
+    controls: HashMap<SocketAddr, ParticipantControl>,
+    controls_version: u64,
+    levels: HashMap<SocketAddr, f32>,
+    stats: MixerStats,
+}
+
+impl AudioMixer {
+    pub fn new(config: MixerConfig) -> Result<Self> {
+        Self::with_handle(config, MixerHandle::default())
+    }
+
+    /// Create a mixer driven by an existing handle, e.g. one the UI already holds
+    pub fn with_handle(config: MixerConfig, handle: MixerHandle) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            handle,
+            controls: HashMap::new(),
+            controls_version: 0,
+            levels: HashMap::new(),
+            stats: MixerStats::default(),
+        })
+    }
+
+    pub fn handle(&self) -> MixerHandle {
+        self.handle.clone()
+    }
+
+    /// Mix one frame from each participant that has audio this period.
+    /// Returns `None` when no participant contributed a frame.
+    pub fn mix(&mut self, inputs: &[(SocketAddr, AudioFrame)]) -> Option<AudioFrame> {
+        let first = &inputs.first()?.1;
+        self.sync_controls();
+
+        // Meters keep decaying for participants without a frame this period
+        for level in self.levels.values_mut() {
+            *level *= self.config.level_release;
+        }
+
+        let len = inputs.iter().map(|(_, frame)| frame.samples.len()).max().unwrap_or(0);
+        let mut mixed = AudioFrame {
+            samples: vec![0.0; len],
+            timestamp: first.timestamp,
+            sequence: first.sequence,
+            format: first.format,
+        };
+
+        for (peer, frame) in inputs {
+            let rms = frame_rms(&frame.samples);
+            let level = self.levels.entry(*peer).or_insert(0.0);
+            // Fast attack, slow release
+            *level = level.max(rms);
+
+            let control = self.controls.get(peer).copied().unwrap_or_default();
+            if control.muted || control.gain == 0.0 {
+                continue;
+            }
+            for (out, sample) in mixed.samples.iter_mut().zip(&frame.samples) {
+                *out += sample * control.gain;
+            }
+        }
+
+        let threshold = self.config.limiter_threshold;
+        for sample in mixed.samples.iter_mut() {
+            if sample.abs() > threshold {
+                self.stats.samples_limited += 1;
+            }
+            *sample = soft_clip(*sample, threshold);
+        }
+
+        self.stats.frames_mixed += 1;
+        self.publish_levels();
+        Some(mixed)
+    }
+
+    /// Forget a participant's meter, e.g. after it left the call. Its gain and mute are kept.
+    pub fn remove_participant(&mut self, peer: SocketAddr) {
+        self.levels.remove(&peer);
+        self.publish_levels();
+    }
+
+    pub fn get_config(&self) -> MixerConfig {
+        self.config
+    }
+
+    pub fn get_stats(&self) -> MixerStats {
+        self.stats.clone()
+    }
+
+    fn sync_controls(&mut self) {
+        // Never block the processing thread on the UI; pick changes up next frame instead
+        if let Ok(shared) = self.handle.shared.try_lock()
+            && shared.controls_version != self.controls_version {
+            self.controls.clone_from(&shared.controls);
+            self.controls_version = shared.controls_version;
+        }
+    }
+
+    fn publish_levels(&self) {
+        if let Ok(mut shared) = self.handle.shared.try_lock() {
+            shared.levels.clear();
+            shared.levels.extend(self.levels.iter().map(|(&peer, &level)| {
+                let control = self.controls.get(&peer).copied().unwrap_or_default();
+                ParticipantLevel {
+                    peer,
+                    level,
+                    is_speaking: level >= self.config.speaking_threshold,
+                    gain: control.gain,
+                    muted: control.muted,
+                }
+            }));
+            shared.levels.sort_by(|a, b| b.level.total_cmp(&a.level));
+            shared.stats.clone_from(&self.stats);
+        }
+    }
+}
+
+/// Pass samples below `threshold` unchanged and compress the rest smoothly towards ±1.0
+pub fn soft_clip(sample: f32, threshold: f32) -> f32 {
+    let magnitude = sample.abs();
+    if magnitude <= threshold {
+        return sample;
+    }
+
+    let headroom = 1.0 - threshold;
+    let compressed = threshold + headroom * ((magnitude - threshold) / headroom).tanh();
+    compressed.copysign(sample)
+}
+
+fn frame_rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt()
+}
@@ -1,10 +1,15 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use anyhow::Result;
 use log::{info, warn, error, debug};
 
+use crate::congestion::RateDecision;
+
+/// Number of congestion control decisions kept for inspection
+const MAX_RATE_DECISIONS: usize = 50;
+
 /// System health status levels
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum HealthStatus {
@@ -67,6 +72,7 @@ pub struct HealthMonitor {
     checks: Arc<Mutex<HashMap<String, HealthCheckFn>>>,
     last_report: Arc<Mutex<Option<HealthReport>>>,
     metrics: Arc<Mutex<PerformanceMetrics>>,
+    rate_decisions: Arc<Mutex<VecDeque<RateDecision>>>,
     start_time: Instant,
     check_interval: Duration,
 }
@@ -77,6 +83,7 @@ impl HealthMonitor {
             checks: Arc::new(Mutex::new(HashMap::new())),
             last_report: Arc::new(Mutex::new(None)),
             metrics: Arc::new(Mutex::new(PerformanceMetrics::new())),
+            rate_decisions: Arc::new(Mutex::new(VecDeque::new())),
             start_time: Instant::now(),
             check_interval: Duration::from_secs(30), // Default 30 second intervals
         }
@@ -180,6 +187,23 @@ impl HealthMonitor {
         }
     }
 
+    /// Record an encoder bitrate, complexity or channel change made by congestion control
+    pub fn record_rate_decision(&self, decision: RateDecision) {
+        if let Ok(mut decisions) = self.rate_decisions.lock() {
+            if decisions.len() == MAX_RATE_DECISIONS {
+                decisions.pop_front();
+            }
+            decisions.push_back(decision);
+        }
+    }
+
+    /// Recent congestion control decisions, oldest first
+    pub fn rate_decisions(&self) -> Vec<RateDecision> {
+        self.rate_decisions.lock()
+            .map(|decisions| decisions.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
     /// Start automatic health checking in background
     pub fn start_monitoring(&self) -> Result<()> {
         let checks = self.checks.clone();
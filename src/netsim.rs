@@ -0,0 +1,376 @@
+use anyhow::{Result, anyhow};
+use log::{debug, info, warn};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::Instant;
+
+use crate::transport::{Transport, TransportFuture, TransportKind};
+
+/// How packets are dropped
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LossModel {
+    #[default]
+    None,
+    /// Every packet is dropped independently with the same probability
+    Random { probability: f64 },
+    /// Gilbert-Elliott two-state chain: rare transitions into a bad state that drops
+    /// most packets give the bursty loss seen on congested or wireless links
+    GilbertElliott {
+        /// Per-packet chance of moving from the good state to the bad one
+        good_to_bad: f64,
+        /// Per-packet chance of recovering; mean burst length is its inverse
+        bad_to_good: f64,
+        loss_in_good: f64,
+        loss_in_bad: f64,
+    },
+}
+
+/// Impairments applied to one direction of traffic
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImpairmentConfig {
+    pub loss: LossModel,
+    /// Fixed one-way delay
+    pub delay: Duration,
+    /// Extra delay drawn uniformly from `0..=jitter` per packet
+    pub jitter: Duration,
+    /// Chance a packet is held back by `reorder_delay` so later packets overtake it
+    pub reorder_probability: f64,
+    pub reorder_delay: Duration,
+    /// Chance a packet is delivered twice
+    pub duplicate_probability: f64,
+    /// Link rate in bits per second; packets queue behind each other. `None` is unlimited.
+    pub bandwidth_bps: Option<u64>,
+    /// Longest a packet waits for the capped link before it is dropped instead
+    pub max_queue_delay: Duration,
+    /// Seed for every random decision, so a run can be replayed exactly
+    pub seed: u64,
+}
+
+impl Default for ImpairmentConfig {
+    fn default() -> Self {
+        Self {
+            loss: LossModel::None,
+            delay: Duration::ZERO,
+            jitter: Duration::ZERO,
+            reorder_probability: 0.0,
+            reorder_delay: Duration::from_millis(40),
+            duplicate_probability: 0.0,
+            bandwidth_bps: None,
+            max_queue_delay: Duration::from_millis(500),
+            seed: 0,
+        }
+    }
+}
+
+impl ImpairmentConfig {
+    pub fn validate(&self) -> Result<()> {
+        let probabilities = match self.loss {
+            LossModel::None => vec![],
+            LossModel::Random { probability } => vec![probability],
+            LossModel::GilbertElliott { good_to_bad, bad_to_good, loss_in_good, loss_in_bad } => {
+                vec![good_to_bad, bad_to_good, loss_in_good, loss_in_bad]
+            }
+        };
+        if probabilities.into_iter()
+            .chain([self.reorder_probability, self.duplicate_probability])
+            .any(|probability| !(0.0..=1.0).contains(&probability)) {
+            return Err(anyhow!("Impairment probabilities must be between 0.0 and 1.0"));
+        }
+        if self.bandwidth_bps == Some(0) {
+            return Err(anyhow!("Bandwidth cap must be positive"));
+        }
+        Ok(())
+    }
+}
+
+/// What the simulator did to the packets it was given
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImpairmentStats {
+    pub packets_in: u64,
+    pub packets_out: u64,
+    pub dropped_loss: u64,
+    /// Dropped because the capped link's queue was already too long
+    pub dropped_queue: u64,
+    pub reordered: u64,
+    pub duplicated: u64,
+}
+
+/// Seeded packet fate generator. Times are offsets from the start of the simulation, so
+/// the same config, seed and send times always give the same drops and arrival times.
+pub struct Impairment {
+    config: ImpairmentConfig,
+    rng: StdRng,
+    in_bad_state: bool,
+    // When the capped link finishes sending what is already queued
+    link_free_at: Duration,
+    stats: ImpairmentStats,
+}
+
+impl Impairment {
+    pub fn new(config: ImpairmentConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            rng: StdRng::seed_from_u64(config.seed),
+            config,
+            in_bad_state: false,
+            link_free_at: Duration::ZERO,
+            stats: ImpairmentStats::default(),
+        })
+    }
+
+    /// Decide the fate of a `len`-byte packet sent at `sent_at`. Returns how long after
+    /// `sent_at` each copy arrives: empty when dropped, two entries when duplicated.
+    pub fn schedule(&mut self, sent_at: Duration, len: usize) -> Vec<Duration> {
+        self.stats.packets_in += 1;
+        if self.lose() {
+            self.stats.dropped_loss += 1;
+            return Vec::new();
+        }
+
+        // Serialise onto the capped link behind whatever is queued
+        let mut departure = sent_at;
+        if let Some(bandwidth_bps) = self.config.bandwidth_bps {
+            departure = departure.max(self.link_free_at);
+            if departure - sent_at > self.config.max_queue_delay {
+                self.stats.dropped_queue += 1;
+                return Vec::new();
+            }
+            departure += Duration::from_secs_f64(len as f64 * 8.0 / bandwidth_bps as f64);
+            self.link_free_at = departure;
+        }
+
+        let mut base = departure - sent_at + self.config.delay;
+        if self.chance(self.config.reorder_probability) {
+            self.stats.reordered += 1;
+            base += self.config.reorder_delay;
+        }
+
+        let mut arrivals = vec![base + self.jitter()];
+        if self.chance(self.config.duplicate_probability) {
+            self.stats.duplicated += 1;
+            arrivals.push(base + self.jitter());
+        }
+        self.stats.packets_out += arrivals.len() as u64;
+        arrivals
+    }
+
+    /// Run a whole send schedule through offline, returning what arrives and when,
+    /// ordered by arrival. Ties keep send order.
+    pub fn simulate<T: AsRef<[u8]> + Clone>(&mut self, sends: impl IntoIterator<Item = (Duration, T)>) -> Vec<(Duration, T)> {
+        let mut arrivals = Vec::new();
+        for (sent_at, packet) in sends {
+            for delay in self.schedule(sent_at, packet.as_ref().len()) {
+                arrivals.push((sent_at + delay, packet.clone()));
+            }
+        }
+        arrivals.sort_by_key(|(arrival, _)| *arrival);
+        arrivals
+    }
+
+    pub fn stats(&self) -> ImpairmentStats {
+        self.stats.clone()
+    }
+
+    pub fn config(&self) -> &ImpairmentConfig {
+        &self.config
+    }
+
+    fn lose(&mut self) -> bool {
+        match self.config.loss {
+            LossModel::None => false,
+            LossModel::Random { probability } => self.chance(probability),
+            LossModel::GilbertElliott { good_to_bad, bad_to_good, loss_in_good, loss_in_bad } => {
+                let flip = if self.in_bad_state { bad_to_good } else { good_to_bad };
+                if self.chance(flip) {
+                    self.in_bad_state = !self.in_bad_state;
+                }
+                self.chance(if self.in_bad_state { loss_in_bad } else { loss_in_good })
+            }
+        }
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.rng.gen_bool(probability.min(1.0))
+    }
+
+    fn jitter(&mut self) -> Duration {
+        match self.config.jitter.as_micros() as u64 {
+            0 => Duration::ZERO,
+            jitter_us => Duration::from_micros(self.rng.gen_range(0..=jitter_us)),
+        }
+    }
+}
+
+/// Send `packet` after each delay, without holding up the caller
+fn deliver_later(transport: Arc<dyn Transport>, packet: Vec<u8>, target: SocketAddr, delays: Vec<Duration>, sent_at: Instant) {
+    for delay in delays {
+        let transport = Arc::clone(&transport);
+        let packet = packet.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep_until(sent_at + delay).await;
+            if let Err(e) = transport.send_to(&packet, target).await {
+                debug!("Simulated delivery to {} failed: {}", target, e);
+            }
+        });
+    }
+}
+
+/// Transport wrapper that impairs everything sent through it. Wrap both ends, each with
+/// its own config, to impair both directions.
+pub struct SimulatedTransport {
+    inner: Arc<dyn Transport>,
+    impairment: Mutex<Impairment>,
+    started: Instant,
+}
+
+impl SimulatedTransport {
+    pub fn new(inner: Arc<dyn Transport>, config: ImpairmentConfig) -> Result<Self> {
+        Ok(Self {
+            inner,
+            impairment: Mutex::new(Impairment::new(config)?),
+            started: Instant::now(),
+        })
+    }
+
+    pub fn stats(&self) -> ImpairmentStats {
+        self.impairment.lock().map(|impairment| impairment.stats()).unwrap_or_default()
+    }
+}
+
+impl Transport for SimulatedTransport {
+    fn send_to<'a>(&'a self, packet: &'a [u8], target: SocketAddr) -> TransportFuture<'a, usize> {
+        let now = Instant::now();
+        let delays = match self.impairment.lock() {
+            Ok(mut impairment) => impairment.schedule(now - self.started, packet.len()),
+            Err(_) => vec![Duration::ZERO],
+        };
+        deliver_later(Arc::clone(&self.inner), packet.to_vec(), target, delays, now);
+        // Like UDP, a dropped packet still counts as sent
+        Box::pin(async move { Ok(packet.len()) })
+    }
+
+    fn recv_from<'a>(&'a self, buffer: &'a mut [u8]) -> TransportFuture<'a, (usize, SocketAddr)> {
+        self.inner.recv_from(buffer)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn kind(&self) -> TransportKind {
+        self.inner.kind()
+    }
+}
+
+/// UDP proxy that impairs traffic between clients and one target. Each client gets its
+/// own upstream socket, so the target sees one address per client as it would behind a NAT.
+pub struct NetsimProxy {
+    socket: Arc<UdpSocket>,
+    target: SocketAddr,
+    // Client to target, and target to client
+    upstream: Arc<Mutex<Impairment>>,
+    downstream: Arc<Mutex<Impairment>>,
+    started: Instant,
+}
+
+impl NetsimProxy {
+    pub async fn bind(listen: &str, target: SocketAddr, upstream: ImpairmentConfig, downstream: ImpairmentConfig) -> Result<Self> {
+        let socket = UdpSocket::bind(listen).await
+            .map_err(|e| anyhow!("Failed to bind netsim socket to {}: {}", listen, e))?;
+        Ok(Self {
+            socket: Arc::new(socket),
+            target,
+            upstream: Arc::new(Mutex::new(Impairment::new(upstream)?)),
+            downstream: Arc::new(Mutex::new(Impairment::new(downstream)?)),
+            started: Instant::now(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Traffic from clients towards the target
+    pub fn upstream_stats(&self) -> ImpairmentStats {
+        self.upstream.lock().map(|impairment| impairment.stats()).unwrap_or_default()
+    }
+
+    /// Traffic from the target back to clients
+    pub fn downstream_stats(&self) -> ImpairmentStats {
+        self.downstream.lock().map(|impairment| impairment.stats()).unwrap_or_default()
+    }
+
+    /// Forward until the listening socket fails
+    pub async fn run(&self) -> Result<()> {
+        info!("Netsim forwarding {} -> {}", self.local_addr()?, self.target);
+        let mut clients: HashMap<SocketAddr, Arc<UdpSocket>> = HashMap::new();
+        let mut buffer = vec![0u8; 65536];
+
+        loop {
+            let (len, client) = match self.socket.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(anyhow!("Netsim receive error: {}", e)),
+            };
+
+            let upstream_socket = match clients.get(&client) {
+                Some(socket) => Arc::clone(socket),
+                None => {
+                    let bind_addr = if self.target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+                    let socket = Arc::new(UdpSocket::bind(bind_addr).await?);
+                    tokio::spawn(relay_downstream(
+                        Arc::clone(&socket),
+                        Arc::clone(&self.socket),
+                        client,
+                        Arc::clone(&self.downstream),
+                        self.started,
+                    ));
+                    debug!("Netsim client {} via {}", client, socket.local_addr()?);
+                    clients.insert(client, Arc::clone(&socket));
+                    socket
+                }
+            };
+
+            let now = Instant::now();
+            let delays = match self.upstream.lock() {
+                Ok(mut impairment) => impairment.schedule(now - self.started, len),
+                Err(_) => continue,
+            };
+            deliver_later(upstream_socket, buffer[..len].to_vec(), self.target, delays, now);
+        }
+    }
+}
+
+/// Pass the target's replies on to one client through the impairment
+async fn relay_downstream(
+    upstream_socket: Arc<UdpSocket>,
+    listen_socket: Arc<UdpSocket>,
+    client: SocketAddr,
+    downstream: Arc<Mutex<Impairment>>,
+    started: Instant,
+) {
+    let mut buffer = vec![0u8; 65536];
+    loop {
+        let len = match upstream_socket.recv_from(&mut buffer).await {
+            Ok((len, _)) => len,
+            Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+            Err(e) => {
+                warn!("Netsim upstream socket for {} failed: {}", client, e);
+                break;
+            }
+        };
+
+        let now = Instant::now();
+        let delays = match downstream.lock() {
+            Ok(mut impairment) => impairment.schedule(now - started, len),
+            Err(_) => break,
+        };
+        deliver_later(Arc::clone(&listen_socket) as Arc<dyn Transport>, buffer[..len].to_vec(), client, delays, now);
+    }
+}
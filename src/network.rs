@@ -1,259 +1,921 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::net::SocketAddr;
-use tokio::net::UdpSocket;
-use tokio::sync::{mpsc, Mutex};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio::sync::{mpsc, Mutex, Notify};
+use tokio::task::JoinHandle;
 use anyhow::{Result, anyhow};
-use serde_json;
+use log::{debug, error, info, warn};
+use ed25519_dalek::Signer;
 
-use crate::security::{SecureSession, SecureMessage, SecurityConfig};
+use crate::capabilities::{CapabilityOffer, NegotiatedAudio, NegotiationError};
+use crate::opus_codec::OpusConfig;
+use crate::security::{SecureSession, SecureMessage, SecurityConfig, ShortAuthString, HANDSHAKE_MAX_AGE};
+use crate::wire::{self, MessageType};
+use crate::known_peers::TrustError;
+use crate::stun::{self, Candidate, StunMessage, TransactionId, STUN_INITIAL_RTO, STUN_MAX_ATTEMPTS};
+use crate::turn::{self, TurnMessage};
+use crate::transport::{self, Transport, TransportKind};
+
+/// Audio payload received from a peer, tagged with the sender's frame number
+#[derive(Debug, Clone)]
+pub struct ReceivedAudioFrame {
+    pub peer: SocketAddr,
+    pub sequence_number: u64,
+    /// Sender clock in milliseconds when the frame was sent; 0 for plaintext peers
+    pub timestamp: u64,
+    pub payload: Vec<u8>,
+}
+
+/// Decrypted control payload from a secure peer, e.g. receiver feedback
+#[derive(Debug, Clone)]
+pub struct ReceivedControl {
+    pub peer: SocketAddr,
+    pub payload: Vec<u8>,
+}
+
+/// What a secure packet delivered to the application
+enum Delivery {
+    Audio(ReceivedAudioFrame),
+    Control(ReceivedControl),
+}
+
+/// Interval between Handshake retransmissions while waiting for an answer
+const HANDSHAKE_RETRANSMIT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long an initiator waits for a HandshakeResponse
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval between rounds of connectivity checks to a peer's candidates
+const CONNECTIVITY_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long to punch towards a peer's candidates before giving up
+const CONNECTIVITY_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Direct checks get this head start before checks also go out through our relay
+const RELAY_FALLBACK_DELAY: Duration = Duration::from_secs(1);
+
+/// How long after a check succeeds to wait for the peer's own checks to settle who dials
+const ROLE_RESOLUTION_GRACE: Duration = Duration::from_millis(200);
+
+/// Interval between relay allocation retransmissions
+const TURN_RETRANSMIT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long to wait for a relay to grant an allocation
+const TURN_ALLOCATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Peers we hear nothing from for this long are dropped from the table
+pub const PEER_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default peer table size, matching `RoomInfo::max_participants`
+pub const DEFAULT_MAX_PEERS: usize = 10;
+
+/// Handshake we answered and the response we sent, replayed if the initiator retransmits
+#[derive(Clone)]
+struct CachedHandshakeReply {
+    request: Vec<u8>,
+    response: Vec<u8>,
+}
+
+/// Session answering a restart Handshake from an established peer. The live session
+/// stays in place until this one authenticates a packet from the peer.
+struct PendingRestart {
+    session: SecureSession,
+    reply: CachedHandshakeReply,
+}
+
+/// Per-peer traffic counters
+#[derive(Debug, Clone, Default)]
+pub struct PeerStats {
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Packets that failed decoding, authentication or replay checks
+    pub packets_rejected: u64,
+    /// Frames we failed to encrypt, route or send to this peer
+    pub send_errors: u64,
+    pub last_received: Option<Instant>,
+}
+
+/// Snapshot of one entry in the peer table
+#[derive(Debug, Clone)]
+pub struct PeerInfo {
+    pub addr: SocketAddr,
+    /// Base64 identity key, once the handshake has completed
+    pub identity: Option<String>,
+    pub is_secure: bool,
+    pub is_established: bool,
+    /// Reached through our TURN-style relay allocation rather than directly
+    pub via_relay: bool,
+    /// Codec and format settled in the handshake; `None` for plaintext peers
+    pub audio: Option<NegotiatedAudio>,
+    pub stats: PeerStats,
+}
+
+/// Totals across the peer table, and the transport carrying them
+#[derive(Debug, Clone, Default)]
+pub struct NetworkStats {
+    /// `None` until the transport is bound
+    pub transport: Option<TransportKind>,
+    pub established_peers: usize,
+    /// Counters summed over every peer; `last_received` is the most recent of them
+    pub totals: PeerStats,
+}
+
+/// One remote participant
+struct Peer {
+    // `None` for plaintext peers
+    session: Option<SecureSession>,
+    stats: PeerStats,
+    // Handshake we sent while dialling, cleared once the peer answers
+    dial_request: Option<Vec<u8>>,
+    handshake_reply: Option<CachedHandshakeReply>,
+    // Trust failure recorded by the receive task for the dialling side to report
+    failure: Option<anyhow::Error>,
+    // Whether a connect or accept call has returned this peer yet
+    announced: bool,
+    // Packets to this peer are wrapped and sent through our relay allocation
+    via_relay: bool,
+    // Plaintext packets carry no frame number, so number them on arrival
+    plaintext_sequence: u64,
+    // Idle time counts from here until the peer sends something
+    added_at: Instant,
+    // Sent an authenticated Disconnect; the receive task drops it
+    departed: bool,
+    // New session from a restart Handshake, waiting to prove itself
+    restart: Option<PendingRestart>,
+}
+
+impl Peer {
+    fn new(session: Option<SecureSession>) -> Self {
+        Self {
+            session,
+            stats: PeerStats::default(),
+            dial_request: None,
+            handshake_reply: None,
+            failure: None,
+            announced: false,
+            via_relay: false,
+            plaintext_sequence: 0,
+            added_at: Instant::now(),
+            departed: false,
+            restart: None,
+        }
+    }
+
+    /// Ready for audio: plaintext peers always, secure peers once the handshake completes
+    fn is_established(&self) -> bool {
+        self.session.as_ref().is_none_or(|session| session.is_session_active())
+    }
+
+    /// A Handshake on an established session, from the same identity key, that is not a
+    /// retransmission of one we answered: the peer restarted and is calling again
+    fn is_restart(&self, packet_data: &[u8]) -> bool {
+        let peer_identity = match self.session.as_ref() {
+            Some(session) if session.is_session_active() => session.get_peer_identity(),
+            _ => None,
+        };
+        let same_identity = match (peer_identity, wire::decode_message(packet_data)) {
+            (Some(peer_identity), Ok(SecureMessage::Handshake { identity_public_key, .. })) => {
+                peer_identity.as_bytes() == &identity_public_key
+            }
+            _ => false,
+        };
+        let answered = |reply: &CachedHandshakeReply| reply.request == packet_data;
+
+        same_identity
+            && !self.handshake_reply.as_ref().is_some_and(answered)
+            && !self.restart.as_ref().is_some_and(|restart| answered(&restart.reply))
+    }
+
+    fn identity(&self) -> Option<String> {
+        self.session.as_ref()
+            .and_then(|session| session.get_peer_identity())
+            .map(|pk| base64::Engine::encode(&base64::engine::general_purpose::STANDARD, pk.as_bytes()))
+    }
+
+    fn info(&self, addr: SocketAddr) -> PeerInfo {
+        PeerInfo {
+            addr,
+            identity: self.identity(),
+            is_secure: self.session.is_some(),
+            is_established: self.is_established(),
+            via_relay: self.via_relay,
+            audio: self.session.as_ref().and_then(|session| session.negotiated_audio()),
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+/// Binding response matched to one of our outstanding STUN transactions
+struct StunReply {
+    from: SocketAddr,
+    mapped_address: SocketAddr,
+    // The response came through our relay allocation
+    via_relay: bool,
+}
+
+/// Peer table shared between the manager and its receive task
+struct PeerTable {
+    peers: HashMap<SocketAddr, Peer>,
+    // STUN requests we sent from the shared socket, answered through the receive task
+    stun_transactions: HashMap<TransactionId, mpsc::UnboundedSender<StunReply>>,
+    // Tie-breaker from the latest connectivity check a peer sent us
+    remote_tie_breaker: Option<u64>,
+    // TURN-style relay we allocate on; packets from it carry relayed traffic or control replies
+    turn_server: Option<SocketAddr>,
+    relayed_address: Option<SocketAddr>,
+    // Waiting allocation, if any, for control replies from the relay
+    turn_replies: Option<mpsc::UnboundedSender<TurnMessage>>,
+    max_peers: usize,
+    use_encryption: bool,
+    security_config: Option<SecurityConfig>,
+    // Initiator ephemeral keys of Handshakes we started sessions for, so a captured
+    // Handshake cannot be answered again from any address while its timestamp is fresh
+    answered_handshakes: HashMap<[u8; 32], Instant>,
+    // Woken whenever a peer joins, completes its handshake or fails the trust check
+    changed: Arc<Notify>,
+}
+
+impl PeerTable {
+    fn has_room_for(&self, addr: &SocketAddr) -> bool {
+        self.peers.contains_key(addr) || self.peers.len() < self.max_peers
+    }
+
+    /// Session for a new handshake. Once the call's audio is settled with a peer, only
+    /// that codec and format are offered, since the pipelines run one format for everyone.
+    fn new_session(&self) -> Result<SecureSession> {
+        let mut config = self.security_config.clone()
+            .ok_or_else(|| anyhow!("Encryption enabled but no security configuration provided"))?;
+        if let Some(audio) = self.call_audio() {
+            let opus = OpusConfig { fec_enabled: audio.fec, dtx_enabled: audio.dtx, ..OpusConfig::default() };
+            config.capabilities = CapabilityOffer::for_format(audio.codec, audio.format, &opus);
+        }
+        Ok(SecureSession::new(config))
+    }
+
+    /// Audio settled with the established peers, if any
+    fn call_audio(&self) -> Option<NegotiatedAudio> {
+        self.peers.values()
+            .filter(|peer| peer.is_established())
+            .find_map(|peer| peer.session.as_ref()?.negotiated_audio())
+    }
+
+    /// Drop peers we have heard nothing from for `PEER_IDLE_TIMEOUT`
+    fn expire_idle_peers(&mut self) {
+        let before = self.peers.len();
+        self.peers.retain(|addr, peer| {
+            let idle = peer.stats.last_received.unwrap_or(peer.added_at).elapsed() >= PEER_IDLE_TIMEOUT;
+            if idle {
+                info!("Peer {} timed out", addr);
+            }
+            !idle
+        });
+        if self.peers.len() != before {
+            self.changed.notify_waiters();
+        }
+    }
+}
 
 pub struct NetworkManager {
     connection_config: ConnectionConfig,
     is_connected: bool,
-    udp_socket: Option<Arc<UdpSocket>>,
+    transport: Option<Arc<dyn Transport>>,
+    // First peer connected; the single-peer accessors below refer to it
     peer_addr: Option<SocketAddr>,
-    // Async channels for UDP audio frames
-    audio_tx: Option<mpsc::UnboundedSender<Vec<u8>>>,
-    audio_rx: Option<mpsc::UnboundedReceiver<Vec<u8>>>,
-    // Security components
-    secure_session: Arc<Mutex<Option<SecureSession>>>,
-    pending_handshake: bool,
+    peers: Arc<Mutex<PeerTable>>,
+    audio_rx: Option<mpsc::UnboundedReceiver<ReceivedAudioFrame>>,
+    control_rx: Option<mpsc::UnboundedReceiver<ReceivedControl>>,
+    receiver_task: Option<JoinHandle<()>>,
+    turn_refresh_task: Option<JoinHandle<()>>,
 }
 
 #[derive(Clone)]
 pub struct ConnectionConfig {
     pub remote_host: String,
     pub port: u16,
+    /// Local port to bind; defaults to `port` (0 picks an ephemeral port)
+    pub local_port: Option<u16>,
     pub use_encryption: bool,
     // Removed legacy encryption_key field - now handled by SecurityConfig
     pub security_config: Option<SecurityConfig>,
+    /// Maximum number of peers, including ones still mid-handshake
+    pub max_peers: usize,
+    /// TURN-style relay to allocate on when gathering candidates, tried as the last resort
+    pub turn_server: Option<SocketAddr>,
+    /// Carrier for protocol packets; stream transports are a fallback for UDP-blocked networks
+    pub transport: TransportKind,
 }
 
 impl NetworkManager {
     pub fn new(config: ConnectionConfig) -> Self {
-        // Initialize secure session if security config provided
-        let secure_session = if let Some(security_config) = config.security_config.clone() {
-            Arc::new(Mutex::new(Some(SecureSession::new(security_config))))
-        } else {
-            Arc::new(Mutex::new(None))
+        let peers = PeerTable {
+            peers: HashMap::new(),
+            stun_transactions: HashMap::new(),
+            remote_tie_breaker: None,
+            turn_server: None,
+            relayed_address: None,
+            turn_replies: None,
+            max_peers: config.max_peers,
+            use_encryption: config.use_encryption,
+            security_config: config.security_config.clone(),
+            answered_handshakes: HashMap::new(),
+            changed: Arc::new(Notify::new()),
         };
 
         Self {
             connection_config: config,
             is_connected: false,
-            udp_socket: None,
+            transport: None,
             peer_addr: None,
-            audio_tx: None,
+            peers: Arc::new(Mutex::new(peers)),
             audio_rx: None,
-            secure_session,
-            pending_handshake: false,
+            control_rx: None,
+            receiver_task: None,
+            turn_refresh_task: None,
         }
     }
 
-    /// Establish UDP connection with peer
-    pub async fn establish_connection(&mut self) -> Result<()> {
-        let local_addr = format!("0.0.0.0:{}", self.connection_config.port);
-        let remote_addr = format!("{}:{}", self.connection_config.remote_host, self.connection_config.port);
+    /// Manager over an already-bound transport, e.g. a loopback endpoint in tests.
+    /// `config.transport` and the local port are ignored.
+    pub fn with_transport(config: ConnectionConfig, transport: Arc<dyn Transport>) -> Self {
+        let mut manager = Self::new(config);
+        manager.transport = Some(transport);
+        manager
+    }
 
-        // Ensure we have security config if encryption is enabled
-        {
-            let session_guard = self.secure_session.lock().await;
-            if self.connection_config.use_encryption && session_guard.is_none() {
-                return Err(anyhow!("Encryption enabled but no security configuration provided"));
-            }
+    /// Bind the configured transport if it is not bound yet, returning its address.
+    /// Incoming handshakes are answered from this point on.
+    pub async fn bind(&mut self) -> Result<SocketAddr> {
+        if let Some(ref transport) = self.transport {
+            let local_addr = transport.local_addr()?;
+            self.start_receiver()?;
+            return Ok(local_addr);
         }
 
-        // Bind UDP socket
-        let socket = UdpSocket::bind(&local_addr).await
-            .map_err(|e| anyhow!("Failed to bind UDP socket to {}: {}", local_addr, e))?;
+        let local_port = self.connection_config.local_port.unwrap_or(self.connection_config.port);
+        let local_addr = format!("0.0.0.0:{}", local_port);
 
-        println!("UDP socket bound to {}", local_addr);
+        let transport = transport::bind_transport(&local_addr, self.connection_config.transport).await?;
+        let bound_addr = transport.local_addr()?;
+
+        info!("{} transport bound to {}", transport.kind(), bound_addr);
+
+        self.transport = Some(transport);
+        self.start_receiver()?;
+        Ok(bound_addr)
+    }
+
+    /// Local address of the bound transport
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.transport.as_ref().and_then(|transport| transport.local_addr().ok())
+    }
+
+    /// Transport carrying our packets, once bound
+    pub fn transport_kind(&self) -> Option<TransportKind> {
+        self.transport.as_ref().map(|transport| transport.kind())
+    }
+
+    fn socket(&self) -> Result<Arc<dyn Transport>> {
+        self.transport.clone().ok_or_else(|| anyhow!("No transport bound"))
+    }
+
+    /// Establish UDP connection with the configured peer
+    pub async fn establish_connection(&mut self) -> Result<()> {
+        let remote_addr = format!("{}:{}", self.connection_config.remote_host, self.connection_config.port);
 
         // Parse remote address
         let peer_addr: SocketAddr = remote_addr.parse()
             .map_err(|e| anyhow!("Invalid remote address {}: {}", remote_addr, e))?;
 
-        // Store socket and peer address
-        let socket_arc = Arc::new(socket);
-        self.udp_socket = Some(Arc::clone(&socket_arc));
-        self.peer_addr = Some(peer_addr);
+        self.dial_peer(peer_addr).await
+    }
 
-        // Set up audio channels
-        let (audio_tx, audio_rx) = mpsc::unbounded_channel();
-        self.audio_tx = Some(audio_tx);
-        self.audio_rx = Some(audio_rx);
+    /// Add a peer by address, running the handshake as initiator when encryption is enabled
+    pub async fn dial_peer(&mut self, peer_addr: SocketAddr) -> Result<()> {
+        self.dial(peer_addr, false).await
+    }
+
+    async fn dial(&mut self, peer_addr: SocketAddr, via_relay: bool) -> Result<()> {
+        self.check_security_config()?;
+        self.bind().await?;
 
-        // Start UDP receive loop
-        self.start_udp_receiver(socket_arc, peer_addr).await?;
+        let socket = self.socket()?;
 
-        // Perform handshake if encryption is enabled
-        if self.connection_config.use_encryption {
-            self.perform_udp_handshake(peer_addr).await?;
+        let handshake_packet = {
+            let mut table = self.peers.lock().await;
+            if !table.has_room_for(&peer_addr) {
+                return Err(anyhow!("Peer table full ({} peers)", table.max_peers));
+            }
+
+            let use_encryption = table.use_encryption;
+            let relay = table.turn_server.filter(|_| via_relay);
+            match table.peers.get_mut(&peer_addr) {
+                // Already connected, e.g. the peer dialled us first
+                Some(peer) if peer.is_established() => {
+                    peer.announced = true;
+                    None
+                }
+                _ if use_encryption => {
+                    let mut session = table.new_session()?;
+                    // Pin the peer's identity under the address we dialled
+                    session.set_peer_name(Some(peer_addr.to_string()));
+                    let packet = wire::encode_message(&session.initiate_handshake()?)?;
+
+                    let mut peer = Peer::new(Some(session));
+                    peer.dial_request = Some(packet.clone());
+                    peer.via_relay = via_relay;
+                    table.peers.insert(peer_addr, peer);
+                    Some(route(relay, peer_addr, packet)?)
+                }
+                _ => {
+                    let mut peer = Peer::new(None);
+                    peer.announced = true;
+                    peer.via_relay = via_relay;
+                    table.peers.insert(peer_addr, peer);
+                    None
+                }
+            }
+        };
+
+        if let Some((target, packet)) = handshake_packet {
+            info!("Initiating secure UDP handshake with {}", peer_addr);
+            socket.send_to(&packet, target).await?;
+            self.await_dialled_peer(socket.as_ref(), peer_addr, target, &packet).await?;
         }
 
-        self.is_connected = true;
-        println!("UDP connection established with {}", peer_addr);
+        self.mark_connected(peer_addr);
         Ok(())
     }
 
-    /// Start UDP receiver loop
-    async fn start_udp_receiver(&mut self, socket: Arc<UdpSocket>, _peer_addr: SocketAddr) -> Result<()> {
-        let audio_tx = self.audio_tx.as_ref()
-            .ok_or_else(|| anyhow!("Audio transmitter not initialized"))?
-            .clone();
-        let secure_session = Arc::clone(&self.secure_session);
-
-        tokio::spawn(async move {
-            let mut buffer = vec![0u8; 2048]; // Smaller buffer for UDP packets
+    /// Retransmit our Handshake until the receive task reports the peer established
+    async fn await_dialled_peer(&self, socket: &dyn Transport, peer_addr: SocketAddr, target: SocketAddr, packet: &[u8]) -> Result<()> {
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
 
-            loop {
-                match socket.recv_from(&mut buffer).await {
-                    Ok((len, _addr)) => {
-                        let packet_data = buffer[..len].to_vec();
-
-                        // Try to decrypt if we have a secure session
-                        let audio_data = {
-                            let session_guard = secure_session.lock().await;
-                            if let Some(ref session) = *session_guard {
-                                // Try to parse as SecureMessage
-                                match serde_json::from_slice::<SecureMessage>(&packet_data) {
-                                    Ok(secure_msg) => {
-                                        match session.decrypt_audio_frame(secure_msg) {
-                                            Ok(decrypted) => decrypted,
-                                            Err(e) => {
-                                                eprintln!("Decryption failed: {}", e);
-                                                continue;
-                                            }
-                                        }
-                                    }
-                                    Err(_) => {
-                                        // Assume plaintext for development/fallback
-                                        packet_data
-                                    }
-                                }
-                            } else {
-                                // No encryption, use raw data
-                                packet_data
-                            }
-                        };
+        loop {
+            let changed = Arc::clone(&self.peers.lock().await.changed);
+            // Register for wakeups before checking so a notification can't slip in between
+            let notified = changed.notified();
 
-                        if audio_tx.send(audio_data).is_err() {
-                            eprintln!("Audio channel closed, stopping UDP receiver");
-                            break;
-                        }
+            {
+                let mut table = self.peers.lock().await;
+                match table.peers.get_mut(&peer_addr) {
+                    Some(peer) if peer.is_established() => {
+                        peer.announced = true;
+                        info!("Secure UDP handshake completed with {}", peer_addr);
+                        return Ok(());
                     }
-                    Err(e) => {
-                        eprintln!("UDP receive error: {}", e);
-                        break;
+                    Some(peer) if peer.failure.is_some() => {
+                        let failure = peer.failure.take();
+                        table.peers.remove(&peer_addr);
+                        return Err(failure.unwrap_or_else(|| anyhow!("Handshake failed")));
                     }
+                    Some(_) => {}
+                    None => return Err(anyhow!("Peer {} removed during handshake", peer_addr)),
                 }
             }
-        });
 
-        Ok(())
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                self.peers.lock().await.peers.remove(&peer_addr);
+                return Err(anyhow!("Handshake timeout - peer may not be ready"));
+            }
+
+            if tokio::time::timeout(HANDSHAKE_RETRANSMIT_INTERVAL.min(remaining), notified).await.is_err() {
+                socket.send_to(packet, target).await?;
+            }
+        }
     }
 
-    /// Perform secure handshake over UDP
-    async fn perform_udp_handshake(&mut self, peer_addr: SocketAddr) -> Result<()> {
-        let socket = self.udp_socket.as_ref()
-            .ok_or_else(|| anyhow!("No UDP socket available"))?;
+    /// Public address this socket is seen from, as reported by a STUN server
+    pub async fn discover_reflexive_address(&mut self, stun_server: SocketAddr) -> Result<SocketAddr> {
+        self.bind().await?;
+        let socket = self.socket()?;
+
+        let transaction_id = stun::new_transaction_id();
+        let (reply_tx, mut reply_rx) = mpsc::unbounded_channel();
+        self.peers.lock().await.stun_transactions.insert(transaction_id, reply_tx);
+
+        let request = stun::encode_stun_message(&StunMessage::BindingRequest { transaction_id, tie_breaker: None });
+        let mut rto = STUN_INITIAL_RTO;
+        let mut result = Err(anyhow!("STUN server {} did not answer", stun_server));
+
+        for _ in 0..STUN_MAX_ATTEMPTS {
+            if let Err(e) = socket.send_to(&request, stun_server).await {
+                result = Err(anyhow!("Failed to send STUN request to {}: {}", stun_server, e));
+                break;
+            }
+            if let Ok(Some(reply)) = tokio::time::timeout(rto, reply_rx.recv()).await {
+                result = Ok(reply.mapped_address);
+                break;
+            }
+            rto *= 2;
+        }
+
+        self.peers.lock().await.stun_transactions.remove(&transaction_id);
+        result
+    }
+
+    /// Addresses peers can try to reach this socket at: the local interface, the reflexive
+    /// address reported by each STUN server that answers, and a relayed address when a
+    /// TURN-style server is configured
+    pub async fn gather_candidates(&mut self, stun_servers: &[SocketAddr]) -> Result<Vec<Candidate>> {
+        let local_addr = self.bind().await?;
+        let mut candidates = Vec::new();
+
+        let host_ip = match local_addr.ip() {
+            ip if ip.is_unspecified() => stun::local_interface_ip(),
+            ip => Some(ip),
+        };
+        if let Some(ip) = host_ip {
+            candidates.push(Candidate::host(SocketAddr::new(ip, local_addr.port())));
+        }
+
+        for &server in stun_servers {
+            match self.discover_reflexive_address(server).await {
+                // Not behind a NAT from this server's point of view
+                Ok(addr) if candidates.iter().any(|candidate| candidate.addr == addr) => {}
+                Ok(addr) => candidates.push(Candidate::server_reflexive(addr)),
+                Err(e) => warn!("STUN discovery via {} failed: {}", server, e),
+            }
+        }
+
+        // Relayed last: it works through any NAT but costs a hop through the relay
+        if let Some(turn_server) = self.connection_config.turn_server {
+            match self.allocate_relay(turn_server).await {
+                Ok(candidate) => candidates.push(candidate),
+                Err(e) => warn!("Relay allocation on {} failed: {}", turn_server, e),
+            }
+        }
+
+        Ok(candidates)
+    }
+
+    /// Punch through to a peer that exchanged candidates with us, then run the handshake.
+    /// Both sides call this at the same time: every round sends a STUN binding request to
+    /// each remote candidate, which opens our NAT binding towards it. Checks carry a random
+    /// tie-breaker and, as in ICE, only the side with the higher one dials the first
+    /// candidate that answered; the other waits for that handshake. When a TURN-style relay
+    /// is allocated, checks also go out through it once direct checks have had a head start.
+    /// Returns the address the peer was reached at.
+    pub async fn connect_via_candidates(&mut self, remote: &[Candidate]) -> Result<SocketAddr> {
+        self.check_security_config()?;
+        self.bind().await?;
+        let socket = self.socket()?;
+
+        let mut ordered = remote.to_vec();
+        ordered.sort_by_key(|candidate| std::cmp::Reverse(candidate.priority));
+        ordered.dedup_by_key(|candidate| candidate.addr);
+        if ordered.is_empty() {
+            return Err(anyhow!("Peer offered no candidates"));
+        }
+
+        let (known_before, turn_server) = {
+            let mut table = self.peers.lock().await;
+            table.remote_tie_breaker = None;
+            let known: HashSet<SocketAddr> = table.peers.keys().copied().collect();
+            (known, table.turn_server.filter(|_| table.relayed_address.is_some()))
+        };
+
+        // Let the peer's checks through our relayed address from the start
+        let permission = match turn_server {
+            Some(_) => {
+                let mut peers: Vec<IpAddr> = ordered.iter().map(|candidate| candidate.addr.ip()).collect();
+                peers.sort();
+                peers.dedup();
+                peers.truncate(u8::MAX as usize);
+                Some(turn::encode_turn_message(&TurnMessage::CreatePermission { peers })?)
+            }
+            None => None,
+        };
+
+        let tie_breaker: u64 = rand::random();
+        let (reply_tx, mut reply_rx) = mpsc::unbounded_channel();
+        let mut outstanding = Vec::new();
+        let started = Instant::now();
+        let deadline = started + CONNECTIVITY_CHECK_TIMEOUT;
+        // First pair that answered: remote address, whether it was through the relay, and when
+        let mut selected: Option<(SocketAddr, bool, Instant)> = None;
+
+        let outcome = loop {
+            let (remote_tie_breaker, joined) = {
+                let mut table = self.peers.lock().await;
+                let joined = table.peers.iter_mut()
+                    .find(|(addr, peer)| !known_before.contains(addr) && !peer.announced && peer.is_established())
+                    .map(|(&addr, peer)| {
+                        peer.announced = true;
+                        addr
+                    });
+                (table.remote_tie_breaker, joined)
+            };
+
+            // The controlling side got there first
+            if let Some(peer_addr) = joined {
+                break Ok((peer_addr, None));
+            }
+
+            if let Some((addr, via_relay, answered_at)) = selected {
+                let controlling = match remote_tie_breaker {
+                    Some(remote) => tie_breaker > remote,
+                    // The peer isn't probing us, so nobody else is going to dial
+                    None => answered_at.elapsed() >= ROLE_RESOLUTION_GRACE,
+                };
+                if controlling {
+                    break Ok((addr, Some(via_relay)));
+                }
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break Err(anyhow!("No candidate answered connectivity checks"));
+            }
 
-        // We'll act as initiator for simplicity
-        println!("Initiating secure UDP handshake with {}", peer_addr);
+            let relay_checks = turn_server.filter(|_| started.elapsed() >= RELAY_FALLBACK_DELAY);
+            let mut checks = Vec::with_capacity(ordered.len() * 2 + 1);
+            if let (Some(server), Some(permission)) = (turn_server, &permission) {
+                checks.push((server, permission.clone()));
+            }
+            {
+                let mut table = self.peers.lock().await;
+                let direct = ordered.iter().map(|candidate| (candidate.addr, None));
+                let relayed = ordered.iter().filter(|_| relay_checks.is_some()).map(|candidate| (candidate.addr, relay_checks));
+                for (addr, relay) in direct.chain(relayed) {
+                    let transaction_id = stun::new_transaction_id();
+                    table.stun_transactions.insert(transaction_id, reply_tx.clone());
+                    outstanding.push(transaction_id);
+                    let check = stun::encode_stun_message(&StunMessage::BindingRequest { transaction_id, tie_breaker: Some(tie_breaker) });
+                    checks.push(route(relay, addr, check)?);
+                }
+            }
+
+            for (addr, check) in checks {
+                // An unroutable candidate, e.g. the wrong address family, shouldn't stop the others
+                if let Err(e) = socket.send_to(&check, addr).await {
+                    warn!("Connectivity check to {} failed: {}", addr, e);
+                }
+            }
 
-        let handshake_msg = {
-            let mut session_guard = self.secure_session.lock().await;
-            let session = session_guard.as_mut()
-                .ok_or_else(|| anyhow!("No secure session available"))?;
-            session.initiate_handshake()?
+            if let Ok(Some(reply)) = tokio::time::timeout(CONNECTIVITY_CHECK_INTERVAL.min(remaining), reply_rx.recv()).await {
+                selected.get_or_insert((reply.from, reply.via_relay, Instant::now()));
+            }
         };
 
-        let handshake_json = serde_json::to_vec(&handshake_msg)?;
+        {
+            let mut table = self.peers.lock().await;
+            for transaction_id in &outstanding {
+                table.stun_transactions.remove(transaction_id);
+            }
+        }
+
+        let (peer_addr, dial) = outcome?;
+        info!("Connectivity check to {} succeeded", peer_addr);
+        match dial {
+            Some(via_relay) => self.dial(peer_addr, via_relay).await?,
+            None => self.mark_connected(peer_addr),
+        }
+        Ok(peer_addr)
+    }
 
-        // Send handshake packet
-        socket.send_to(&handshake_json, peer_addr).await?;
+    /// Reserve a relayed address on a TURN-style server, authenticating with our identity key.
+    /// Peers that cannot reach us directly can reach us there; the server only sees ciphertext.
+    pub async fn allocate_relay(&mut self, server: SocketAddr) -> Result<Candidate> {
+        let identity = self.connection_config.security_config.clone()
+            .ok_or_else(|| anyhow!("Relay allocation needs a security configuration"))?;
+        self.bind().await?;
+        let socket = self.socket()?;
 
-        // Wait for response with timeout
-        let mut buffer = vec![0u8; 4096];
-        let response_result = tokio::time::timeout(
-            std::time::Duration::from_secs(5),
-            socket.recv_from(&mut buffer)
-        ).await;
+        {
+            let table = self.peers.lock().await;
+            if table.turn_server == Some(server)
+                && let Some(relayed_address) = table.relayed_address {
+                return Ok(Candidate::relayed(relayed_address));
+            }
+        }
 
-        match response_result {
-            Ok(Ok((len, _))) => {
-                let response_msg: SecureMessage = serde_json::from_slice(&buffer[..len])?;
+        let (reply_tx, mut reply_rx) = mpsc::unbounded_channel();
+        {
+            let mut table = self.peers.lock().await;
+            table.turn_server = Some(server);
+            table.relayed_address = None;
+            table.turn_replies = Some(reply_tx);
+        }
 
-                let mut session_guard = self.secure_session.lock().await;
-                let session = session_guard.as_mut()
-                    .ok_or_else(|| anyhow!("No secure session available"))?;
-                session.process_handshake_response(response_msg)?;
+        let mut outstanding = turn::encode_turn_message(&TurnMessage::AllocateRequest)?;
+        let deadline = Instant::now() + TURN_ALLOCATE_TIMEOUT;
+        socket.send_to(&outstanding, server).await?;
 
-                println!("Secure UDP handshake completed");
+        let allocated = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break Err(anyhow!("Relay allocation timeout - {} may not be reachable", server));
             }
-            Ok(Err(e)) => return Err(anyhow!("UDP receive error during handshake: {}", e)),
-            Err(_) => return Err(anyhow!("Handshake timeout - peer may not be ready")),
+
+            let message = match tokio::time::timeout(TURN_RETRANSMIT_INTERVAL.min(remaining), reply_rx.recv()).await {
+                Ok(Some(message)) => message,
+                Ok(None) => break Err(anyhow!("Receive task stopped during relay allocation")),
+                Err(_) => {
+                    socket.send_to(&outstanding, server).await?;
+                    continue;
+                }
+            };
+
+            match message {
+                TurnMessage::AllocateChallenge { nonce } => {
+                    let identity_public_key = identity.get_public_identity().to_bytes();
+                    let signature = identity.identity_signing_key.sign(&turn::allocate_digest(&identity_public_key, &nonce)).to_bytes();
+                    outstanding = turn::encode_turn_message(&TurnMessage::Allocate { identity_public_key, nonce, signature })?;
+                    socket.send_to(&outstanding, server).await?;
+                }
+                TurnMessage::AllocateSuccess { relayed_address, lifetime_secs } => break Ok((relayed_address, lifetime_secs)),
+                TurnMessage::AllocateError { reason } => break Err(anyhow!("Relay refused allocation: {}", reason)),
+                _ => {}
+            }
+        };
+
+        let mut table = self.peers.lock().await;
+        table.turn_replies = None;
+        let (relayed_address, lifetime_secs) = match allocated {
+            Ok(allocated) => allocated,
+            Err(e) => {
+                table.turn_server = None;
+                return Err(e);
+            }
+        };
+        table.relayed_address = Some(relayed_address);
+        drop(table);
+
+        // Refresh at half the lifetime so one lost refresh doesn't cost the allocation
+        let refresh = turn::encode_turn_message(&TurnMessage::Refresh)?;
+        let refresh_interval = Duration::from_secs(lifetime_secs.max(1) as u64) / 2;
+        if let Some(task) = self.turn_refresh_task.take() {
+            task.abort();
         }
+        self.turn_refresh_task = Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval_at(Instant::now() + refresh_interval, refresh_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = socket.send_to(&refresh, server).await {
+                    warn!("Failed to refresh relay allocation: {}", e);
+                }
+            }
+        }));
+
+        info!("Relay {} allocated {} for us", server, relayed_address);
+        Ok(Candidate::relayed(relayed_address))
+    }
+
+    /// Listen for a peer: wait until a new peer completes its handshake and return its address.
+    /// Call repeatedly to admit further peers up to `max_peers`.
+    pub async fn accept_connection(&mut self) -> Result<SocketAddr> {
+        self.check_security_config()?;
+        let local_addr = self.bind().await?;
+
+        info!("Waiting for peers on {}", local_addr);
+
+        loop {
+            let changed = Arc::clone(&self.peers.lock().await.changed);
+            let notified = changed.notified();
 
-        self.pending_handshake = false;
+            let joined = {
+                let mut table = self.peers.lock().await;
+                table.peers.iter_mut()
+                    .find(|(_, peer)| !peer.announced && peer.is_established())
+                    .map(|(&addr, peer)| {
+                        peer.announced = true;
+                        addr
+                    })
+            };
+
+            if let Some(peer_addr) = joined {
+                self.mark_connected(peer_addr);
+                return Ok(peer_addr);
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Ensure we have security config if encryption is enabled
+    fn check_security_config(&self) -> Result<()> {
+        if self.connection_config.use_encryption && self.connection_config.security_config.is_none() {
+            return Err(anyhow!("Encryption enabled but no security configuration provided"));
+        }
+        Ok(())
+    }
+
+    fn mark_connected(&mut self, peer_addr: SocketAddr) {
+        self.peer_addr.get_or_insert(peer_addr);
+        self.is_connected = true;
+        info!("UDP connection established with {}", peer_addr);
+    }
+
+    /// Start the receive task for the bound socket
+    fn start_receiver(&mut self) -> Result<()> {
+        if self.receiver_task.is_some() {
+            return Ok(());
+        }
+
+        let socket = self.socket()?;
+        let (audio_tx, audio_rx) = mpsc::unbounded_channel();
+        self.audio_rx = Some(audio_rx);
+        let (control_tx, control_rx) = mpsc::unbounded_channel();
+        self.control_rx = Some(control_rx);
+
+        let peers = Arc::clone(&self.peers);
+        self.receiver_task = Some(tokio::spawn(receive_loop(socket, peers, audio_tx, control_tx)));
         Ok(())
     }
 
     pub fn disconnect(&mut self) {
+        if let Some(task) = self.receiver_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.turn_refresh_task.take() {
+            task.abort();
+        }
+        // The receive task held the only other reference, so the lock is free
+        if let Ok(mut table) = self.peers.try_lock() {
+            table.peers.clear();
+            table.stun_transactions.clear();
+            table.turn_server = None;
+            table.relayed_address = None;
+        }
         self.is_connected = false;
-        self.udp_socket = None;
+        self.transport = None;
         self.peer_addr = None;
-        self.audio_tx = None;
         self.audio_rx = None;
+        self.control_rx = None;
     }
 
     pub fn is_connected(&self) -> bool {
         self.is_connected
     }
 
-    /// Send audio frame over UDP
+    /// Send an audio frame to every established peer, each under its own session keys.
+    /// A failure for one peer is logged and counted in its stats; the others still get the frame.
     pub async fn send_audio_frame(&self, frame_data: &[u8]) -> Result<()> {
         if !self.is_connected {
             return Err(anyhow!("Not connected"));
         }
 
-        let socket = self.udp_socket.as_ref()
-            .ok_or_else(|| anyhow!("No UDP socket available"))?;
-        let peer_addr = self.peer_addr
-            .ok_or_else(|| anyhow!("No peer address set"))?;
-
-        // If encryption is enabled, encrypt the frame
-        let data_to_send = if self.connection_config.use_encryption {
-            let mut session_guard = self.secure_session.lock().await;
-            if let Some(ref mut session) = *session_guard {
-                if session.is_session_active() {
-                    // Encrypt the audio frame
-                    let encrypted_msg = session.encrypt_audio_frame(frame_data)?;
-                    serde_json::to_vec(&encrypted_msg)?
-                } else {
-                    return Err(anyhow!("Secure session not established"));
+        let socket = self.socket()?;
+
+        // (peer, destination, packet)
+        let mut outgoing = Vec::new();
+        let mut peers_tried = 0;
+        {
+            let mut guard = self.peers.lock().await;
+            let table = &mut *guard;
+            table.expire_idle_peers();
+            for (&addr, peer) in table.peers.iter_mut().filter(|(_, peer)| peer.is_established()) {
+                peers_tried += 1;
+                let relay = table.turn_server.filter(|_| peer.via_relay);
+                match audio_packets(peer, relay, addr, frame_data) {
+                    Ok(packets) => {
+                        outgoing.extend(packets.into_iter().map(|(target, packet)| (addr, target, packet)));
+                        peer.stats.packets_sent += 1;
+                        peer.stats.bytes_sent += frame_data.len() as u64;
+                    }
+                    Err(e) => {
+                        peer.stats.send_errors += 1;
+                        warn!("Failed to prepare audio for {}: {}", addr, e);
+                    }
                 }
-            } else {
-                return Err(anyhow!("Encryption enabled but no secure session"));
             }
-        } else {
-            // Send plaintext
-            frame_data.to_vec()
-        };
+        }
 
-        // Send UDP packet directly
-        socket.send_to(&data_to_send, peer_addr).await
-            .map_err(|e| anyhow!("Failed to send UDP packet: {}", e))?;
+        if peers_tried == 0 {
+            return Err(anyhow!("No established peers"));
+        }
+
+        let mut failed = Vec::new();
+        for (addr, target, packet) in outgoing {
+            if let Err(e) = socket.send_to(&packet, target).await {
+                warn!("Failed to send packet to {}: {}", addr, e);
+                failed.push(addr);
+            }
+        }
+
+        if !failed.is_empty() {
+            let mut table = self.peers.lock().await;
+            for addr in &failed {
+                if let Some(peer) = table.peers.get_mut(addr) {
+                    peer.stats.send_errors += 1;
+                }
+            }
+        }
 
         Ok(())
     }
 
-    pub fn receive_audio_frame(&mut self) -> Result<Vec<u8>> {
+    /// Receive next decrypted audio frame from any peer, if one is queued
+    pub fn receive_audio_frame(&mut self) -> Result<Option<ReceivedAudioFrame>> {
         if let Some(ref mut audio_rx) = self.audio_rx {
             match audio_rx.try_recv() {
-                Ok(data) => Ok(data),
-                Err(mpsc::error::TryRecvError::Empty) => Ok(vec![]),
+                Ok(frame) => Ok(Some(frame)),
+                Err(mpsc::error::TryRecvError::Empty) => Ok(None),
                 Err(mpsc::error::TryRecvError::Disconnected) => Err(anyhow!("Audio channel closed")),
             }
         } else {
@@ -261,34 +923,629 @@ impl NetworkManager {
         }
     }
 
+    /// Send a control payload to one secure peer, encrypted outside the audio sequence
+    pub async fn send_control(&self, peer_addr: SocketAddr, payload: &[u8]) -> Result<()> {
+        let socket = self.socket()?;
+
+        let (target, packet) = {
+            let mut guard = self.peers.lock().await;
+            let table = &mut *guard;
+            let peer = table.peers.get_mut(&peer_addr)
+                .filter(|peer| peer.is_established())
+                .ok_or_else(|| anyhow!("No established peer at {}", peer_addr))?;
+            let session = peer.session.as_mut()
+                .ok_or_else(|| anyhow!("Control messages need a secure session with {}", peer_addr))?;
+            let message = session.encrypt_control(payload)?;
+            peer.stats.packets_sent += 1;
+            peer.stats.bytes_sent += payload.len() as u64;
+            route(table.turn_server.filter(|_| peer.via_relay), peer_addr, wire::encode_message(&message)?)?
+        };
+
+        socket.send_to(&packet, target).await
+            .map_err(|e| anyhow!("Failed to send control message to {}: {}", peer_addr, e))?;
+        Ok(())
+    }
+
+    /// Receive next decrypted control payload from any peer, if one is queued
+    pub fn receive_control(&mut self) -> Result<Option<ReceivedControl>> {
+        match self.control_rx.as_mut() {
+            Some(control_rx) => match control_rx.try_recv() {
+                Ok(control) => Ok(Some(control)),
+                Err(mpsc::error::TryRecvError::Empty) => Ok(None),
+                Err(mpsc::error::TryRecvError::Disconnected) => Err(anyhow!("Control channel closed")),
+            },
+            None => Err(anyhow!("No control receiver available")),
+        }
+    }
+
     pub fn send_control_signal(&self, signal_type: &str, params: &HashMap<String, String>) -> Result<()> {
         // ASSUMPTION: Control signals would be JSON-encoded and sent with special prefix
-        println!("Sending control signal: {} with params: {:?}", signal_type, params);
+        debug!("Sending control signal: {} with params: {:?}", signal_type, params);
         Ok(())
     }
 
     pub async fn update_config(&mut self, config: ConnectionConfig) {
-        // Update security session if config changed
-        if let Some(security_config) = config.security_config.clone() {
-            let mut session_guard = self.secure_session.lock().await;
-            *session_guard = Some(SecureSession::new(security_config));
+        // New peers pick up the settings; established sessions keep their keys
+        {
+            let mut table = self.peers.lock().await;
+            table.max_peers = config.max_peers;
+            table.use_encryption = config.use_encryption;
+            table.security_config = config.security_config.clone();
         }
         self.connection_config = config;
     }
 
-    /// Get peer's verified identity (if secure session is active)
+    /// Snapshot of every peer in the table, including ones mid-handshake
+    pub async fn peers(&self) -> Vec<PeerInfo> {
+        let table = self.peers.lock().await;
+        table.peers.iter().map(|(&addr, peer)| peer.info(addr)).collect()
+    }
+
+    /// Traffic totals and the transport in use
+    pub async fn stats(&self) -> NetworkStats {
+        let table = self.peers.lock().await;
+        let mut stats = NetworkStats { transport: self.transport_kind(), ..NetworkStats::default() };
+        for peer in table.peers.values() {
+            if peer.is_established() {
+                stats.established_peers += 1;
+            }
+            let totals = &mut stats.totals;
+            totals.packets_sent += peer.stats.packets_sent;
+            totals.packets_received += peer.stats.packets_received;
+            totals.bytes_sent += peer.stats.bytes_sent;
+            totals.bytes_received += peer.stats.bytes_received;
+            totals.packets_rejected += peer.stats.packets_rejected;
+            totals.send_errors += peer.stats.send_errors;
+            totals.last_received = totals.last_received.max(peer.stats.last_received);
+        }
+        stats
+    }
+
+    /// Number of peers ready for audio
+    pub async fn peer_count(&self) -> usize {
+        let table = self.peers.lock().await;
+        table.peers.values().filter(|peer| peer.is_established()).count()
+    }
+
+    /// Drop a peer, sending it a signed Disconnect if its session is up; later packets
+    /// from its address are treated as unknown
+    pub async fn remove_peer(&mut self, peer_addr: SocketAddr) -> bool {
+        let (removed, farewell) = {
+            let mut table = self.peers.lock().await;
+            let turn_server = table.turn_server;
+            match table.peers.remove(&peer_addr) {
+                Some(peer) => {
+                    let farewell = peer.session.as_ref()
+                        .filter(|session| session.is_session_active())
+                        .map(|session| {
+                            let packet = wire::encode_message(&session.create_disconnect("Left the call")?)?;
+                            route(turn_server.filter(|_| peer.via_relay), peer_addr, packet)
+                        });
+                    (true, farewell)
+                }
+                None => (false, None),
+            }
+        };
+
+        if let Some(farewell) = farewell {
+            let sent = match (farewell, self.socket()) {
+                (Ok((target, packet)), Ok(socket)) => socket.send_to(&packet, target).await.map_err(anyhow::Error::from),
+                (Err(e), _) | (_, Err(e)) => Err(e),
+            };
+            if let Err(e) = sent {
+                warn!("Failed to send disconnect to {}: {}", peer_addr, e);
+            }
+        }
+
+        if self.peer_addr == Some(peer_addr) {
+            self.peer_addr = None;
+        }
+        removed
+    }
+
+    /// Get the first peer's verified identity (if secure session is active)
     pub async fn get_peer_identity(&self) -> Option<String> {
-        let session_guard = self.secure_session.lock().await;
-        session_guard.as_ref()
-            .and_then(|session| session.get_peer_identity())
-            .map(|pk| base64::Engine::encode(&base64::engine::general_purpose::STANDARD, pk.as_bytes()))
+        let table = self.peers.lock().await;
+        table.peers.get(&self.peer_addr?)?.identity()
+    }
+
+    /// Codec and format settled with the first peer
+    pub async fn negotiated_audio(&self) -> Option<NegotiatedAudio> {
+        let table = self.peers.lock().await;
+        table.peers.get(&self.peer_addr?)?.session.as_ref()?.negotiated_audio()
+    }
+
+    /// Short authentication string for comparing with the first peer out of band
+    pub async fn short_auth_string(&self) -> Option<ShortAuthString> {
+        let table = self.peers.lock().await;
+        table.peers.get(&self.peer_addr?)?.session.as_ref()?.short_auth_string()
+    }
+
+    /// Persist the first peer as verified after both users confirmed matching codes
+    pub async fn confirm_peer_verified(&self) -> Result<()> {
+        let table = self.peers.lock().await;
+        self.peer_addr
+            .and_then(|addr| table.peers.get(&addr))
+            .and_then(|peer| peer.session.as_ref())
+            .ok_or_else(|| anyhow!("No secure session available"))?
+            .mark_peer_verified()
     }
 
-    /// Check if secure session is active
+    /// Whether the first peer was verified by comparing codes, now or in an earlier call
+    pub async fn is_peer_verified(&self) -> bool {
+        let table = self.peers.lock().await;
+        self.peer_addr
+            .and_then(|addr| table.peers.get(&addr))
+            .and_then(|peer| peer.session.as_ref())
+            .is_some_and(|session| session.is_peer_verified())
+    }
+
+    /// Check if the first peer's secure session is active
     pub async fn is_secure_session_active(&self) -> bool {
-        let session_guard = self.secure_session.lock().await;
-        session_guard.as_ref()
-            .map(|session| session.is_session_active())
-            .unwrap_or(false)
+        let table = self.peers.lock().await;
+        self.peer_addr
+            .and_then(|addr| table.peers.get(&addr))
+            .and_then(|peer| peer.session.as_ref())
+            .is_some_and(|session| session.is_session_active())
+    }
+}
+
+impl Drop for NetworkManager {
+    fn drop(&mut self) {
+        if let Some(task) = self.receiver_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.turn_refresh_task.take() {
+            task.abort();
+        }
+    }
+}
+
+/// Route every datagram through the peer table.
+/// Unknown addresses may only open a handshake; audio is accepted from established peers alone.
+async fn receive_loop(
+    socket: Arc<dyn Transport>,
+    peers: Arc<Mutex<PeerTable>>,
+    audio_tx: mpsc::UnboundedSender<ReceivedAudioFrame>,
+    control_tx: mpsc::UnboundedSender<ReceivedControl>,
+) {
+    let mut buffer = vec![0u8; 2048]; // Smaller buffer for UDP packets
+
+    loop {
+        let (len, source) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(e) => {
+                error!("Receive error: {}", e);
+                break;
+            }
+        };
+
+        // Traffic through our relay allocation arrives wrapped; replies go back the same way
+        let inbound = unwrap_relayed(&peers, source, &buffer[..len]).await;
+        let (addr, packet_data, relay) = match &inbound {
+            Inbound::Direct => (source, &buffer[..len], None),
+            Inbound::Relayed { peer, payload } => (*peer, &payload[..], Some(source)),
+            Inbound::Consumed => continue,
+        };
+        let len = packet_data.len();
+
+        // STUN shares the socket: answer connectivity checks and route our own responses
+        if stun::is_stun_message(packet_data) {
+            handle_stun_packet(socket.as_ref(), &peers, addr, packet_data, relay).await;
+            continue;
+        }
+
+        let mut replies = Vec::new();
+        let received = {
+            let mut guard = peers.lock().await;
+            let table = &mut *guard;
+            table.expire_idle_peers();
+
+            let is_new = !table.peers.contains_key(&addr);
+            let restarted = !is_new && table.peers[&addr].is_restart(packet_data);
+            if is_new {
+                admit_unknown_peer(table, addr, packet_data, relay.is_some(), &mut replies);
+            } else if restarted
+                && let Some((session, reply)) = answer_handshake(table, addr, packet_data, &mut replies)
+                && let Some(peer) = table.peers.get_mut(&addr)
+            {
+                // The live session carries on until the new one authenticates a packet
+                peer.restart = Some(PendingRestart { session, reply });
+            }
+
+            match table.peers.get_mut(&addr) {
+                // Unknown source that did not complete a handshake
+                None => None,
+                Some(peer) => {
+                    peer.stats.packets_received += 1;
+                    peer.stats.bytes_received += len as u64;
+                    peer.stats.last_received = Some(Instant::now());
+
+                    if peer.session.is_none() {
+                        peer.plaintext_sequence += 1;
+                        Some(Delivery::Audio(ReceivedAudioFrame {
+                            peer: addr,
+                            sequence_number: peer.plaintext_sequence,
+                            timestamp: 0,
+                            payload: packet_data.to_vec(),
+                        }))
+                    } else if is_new || restarted {
+                        // The handshake we just answered
+                        None
+                    } else {
+                        let was_established = peer.is_established();
+                        let received = match complete_restart(peer, addr, packet_data) {
+                            Some(received) => Some(received),
+                            None => handle_secure_packet(peer, addr, packet_data, &mut replies),
+                        };
+                        if peer.departed {
+                            table.peers.remove(&addr);
+                            table.changed.notify_waiters();
+                        } else if peer.is_established() != was_established || peer.failure.is_some() {
+                            table.changed.notify_waiters();
+                        }
+                        received
+                    }
+                }
+            }
+        };
+
+        for reply in replies {
+            let sent = match route(relay, addr, reply) {
+                Ok((target, reply)) => socket.send_to(&reply, target).await.map_err(anyhow::Error::from),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = sent {
+                warn!("Failed to send reply to {}: {}", addr, e);
+            }
+        }
+
+        let closed = match received {
+            Some(Delivery::Audio(frame)) => audio_tx.send(frame).is_err(),
+            // Nobody listening for control traffic is not fatal
+            Some(Delivery::Control(control)) => {
+                let _ = control_tx.send(control);
+                false
+            }
+            None => false,
+        };
+        if closed {
+            warn!("Audio channel closed, stopping UDP receiver");
+            break;
+        }
+    }
+}
+
+/// What a datagram turned out to be once relay framing is taken into account
+enum Inbound {
+    /// Sent straight to our socket
+    Direct,
+    /// Passed on by our relay allocation from `peer`
+    Relayed { peer: SocketAddr, payload: Vec<u8> },
+    /// Relay control traffic, already handled
+    Consumed,
+}
+
+/// Unwrap `Data` from our relay and hand its control replies to a waiting allocation
+async fn unwrap_relayed(peers: &Mutex<PeerTable>, source: SocketAddr, packet_data: &[u8]) -> Inbound {
+    let table = peers.lock().await;
+    if table.turn_server != Some(source) || stun::is_stun_message(packet_data) {
+        return Inbound::Direct;
+    }
+
+    match turn::decode_turn_message(packet_data) {
+        Ok(TurnMessage::Data { peer, payload }) => Inbound::Relayed { peer, payload },
+        Ok(message) => {
+            if let Some(ref replies) = table.turn_replies {
+                let _ = replies.send(message);
+            }
+            Inbound::Consumed
+        }
+        Err(e) => {
+            warn!("Malformed relay packet from {}: {}", source, e);
+            Inbound::Consumed
+        }
+    }
+}
+
+/// Packets carrying one audio frame to `peer`, plus a rekey request when one is due
+fn audio_packets(peer: &mut Peer, relay: Option<SocketAddr>, addr: SocketAddr, frame_data: &[u8]) -> Result<Vec<(SocketAddr, Vec<u8>)>> {
+    let session = match peer.session.as_mut() {
+        Some(session) => session,
+        // Send plaintext
+        None => return Ok(vec![route(relay, addr, frame_data.to_vec())?]),
+    };
+
+    let encrypted_msg = session.encrypt_audio_frame(frame_data)?;
+    let mut packets = vec![route(relay, addr, wire::encode_message(&encrypted_msg)?)?];
+    // Piggyback rekeying on the send path so it follows the call's lifetime
+    if let Some(rekey_msg) = session.poll_rekey()? {
+        packets.push(route(relay, addr, wire::encode_message(&rekey_msg)?)?);
+    }
+    Ok(packets)
+}
+
+/// Where to send a packet for `addr`: straight to it, or wrapped in `Send` to our relay
+fn route(relay: Option<SocketAddr>, addr: SocketAddr, packet: Vec<u8>) -> Result<(SocketAddr, Vec<u8>)> {
+    match relay {
+        Some(server) => Ok((server, turn::encode_turn_message(&TurnMessage::Send { peer: addr, payload: packet })?)),
+        None => Ok((addr, packet)),
+    }
+}
+
+/// Answer binding requests from anyone, since a peer punching towards us may not be in
+/// the table yet, and hand binding responses to whoever sent the matching request
+async fn handle_stun_packet(socket: &dyn Transport, peers: &Mutex<PeerTable>, addr: SocketAddr, packet_data: &[u8], relay: Option<SocketAddr>) {
+    match stun::decode_stun_message(packet_data) {
+        Ok(StunMessage::BindingRequest { tie_breaker, .. }) => {
+            if tie_breaker.is_some() {
+                peers.lock().await.remote_tie_breaker = tie_breaker;
+            }
+            let response = stun::binding_response(packet_data, addr)
+                .map(|response| route(relay, addr, response));
+            if let Some(Ok((target, response))) = response
+                && let Err(e) = socket.send_to(&response, target).await {
+                warn!("Failed to answer connectivity check from {}: {}", addr, e);
+            }
+        }
+        Ok(StunMessage::BindingSuccess { transaction_id, mapped_address }) => {
+            if let Some(reply_tx) = peers.lock().await.stun_transactions.remove(&transaction_id) {
+                let _ = reply_tx.send(StunReply { from: addr, mapped_address, via_relay: relay.is_some() });
+            }
+        }
+        Err(e) => warn!("Malformed STUN message from {}: {}", addr, e),
+    }
+}
+
+/// Packet from an address not in the table: answer a valid Handshake and drop anything else
+fn admit_unknown_peer(table: &mut PeerTable, addr: SocketAddr, packet_data: &[u8], via_relay: bool, replies: &mut Vec<Vec<u8>>) {
+    if !table.has_room_for(&addr) {
+        warn!("Peer table full, ignoring packet from {}", addr);
+        return;
+    }
+
+    if !table.use_encryption {
+        // Without a handshake the first datagram identifies the peer
+        let mut peer = Peer::new(None);
+        peer.via_relay = via_relay;
+        table.peers.insert(addr, peer);
+        table.changed.notify_waiters();
+        return;
+    }
+
+    match wire::peek_message_type(packet_data) {
+        Ok(MessageType::Handshake) => {}
+        // A caller on another protocol version hears which one we speak
+        Err(wire::WireError::UnsupportedVersion(version)) if packet_data.get(1) == Some(&(MessageType::Handshake as u8)) => {
+            warn!("Rejected handshake from {}: it speaks wire version {}, we speak {}", addr, version, wire::WIRE_VERSION);
+            replies.push(wire::version_notice());
+            return;
+        }
+        _ => return,
+    }
+
+    if let Some((session, reply)) = answer_handshake(table, addr, packet_data, replies) {
+        let mut peer = Peer::new(Some(session));
+        peer.handshake_reply = Some(reply);
+        peer.via_relay = via_relay;
+        table.peers.insert(addr, peer);
+        table.changed.notify_waiters();
+        info!("Answered secure UDP handshake from {}", addr);
+    }
+}
+
+/// Answer a Handshake with a fresh session, returning it with our reply if the call can
+/// go ahead. A caller with no audio in common is answered, so it learns why, but gets
+/// no session; nothing already in the table is touched either way.
+fn answer_handshake(table: &mut PeerTable, addr: SocketAddr, packet_data: &[u8], replies: &mut Vec<Vec<u8>>) -> Option<(SecureSession, CachedHandshakeReply)> {
+    table.answered_handshakes.retain(|_, answered_at| answered_at.elapsed() < HANDSHAKE_MAX_AGE);
+
+    // Callers are identified by key alone
+    let answered = wire::decode_message(packet_data)
+        .map_err(anyhow::Error::from)
+        .and_then(|message| {
+            let ephemeral_public_key = match message {
+                SecureMessage::Handshake { ephemeral_public_key, .. } => ephemeral_public_key,
+                _ => return Err(anyhow!("Expected handshake message")),
+            };
+            if table.answered_handshakes.contains_key(&ephemeral_public_key) {
+                return Err(anyhow!("Replayed handshake"));
+            }
+
+            let mut session = table.new_session()?;
+            let response = session.process_handshake(message)?
+                .ok_or_else(|| anyhow!("Handshake produced no response"))?;
+            Ok((session, ephemeral_public_key, wire::encode_message(&response)?))
+        });
+
+    match answered {
+        Ok((session, ephemeral_public_key, response)) => {
+            replies.push(response.clone());
+            if let Some(mismatch) = session.audio_mismatch() {
+                warn!("Rejected call from {}: {}", addr, mismatch);
+                return None;
+            }
+            table.answered_handshakes.insert(ephemeral_public_key, Instant::now());
+            Some((session, CachedHandshakeReply { request: packet_data.to_vec(), response }))
+        }
+        Err(e) => {
+            warn!("Rejected handshake from {}: {}", addr, e);
+            None
+        }
     }
-}
\ No newline at end of file
+}
+
+/// Packet from a peer with a restart pending: once the new session decrypts one, it
+/// replaces the live session. Anything else is left to the live session.
+fn complete_restart(peer: &mut Peer, addr: SocketAddr, packet_data: &[u8]) -> Option<Delivery> {
+    let session = &mut peer.restart.as_mut()?.session;
+    let delivery = match wire::decode_message(packet_data).ok()? {
+        message @ SecureMessage::EncryptedAudio { frame_number, timestamp, .. } => session.decrypt_audio_frame(message).ok()
+            .map(|payload| Delivery::Audio(ReceivedAudioFrame { peer: addr, sequence_number: frame_number, timestamp, payload }))?,
+        message @ SecureMessage::EncryptedControl { .. } => session.decrypt_control(message).ok()
+            .map(|payload| Delivery::Control(ReceivedControl { peer: addr, payload }))?,
+        _ => return None,
+    };
+
+    let restart = peer.restart.take()?;
+    peer.session = Some(restart.session);
+    peer.handshake_reply = Some(restart.reply);
+    peer.dial_request = None;
+    info!("Peer {} restarted its session", addr);
+    Some(delivery)
+}
+
+/// Dispatch a packet from a known secure peer on the wire header's type byte
+fn handle_secure_packet(
+    peer: &mut Peer,
+    addr: SocketAddr,
+    packet_data: &[u8],
+    replies: &mut Vec<Vec<u8>>,
+) -> Option<Delivery> {
+    let session = peer.session.as_mut()?;
+
+    let message_type = match wire::peek_message_type(packet_data) {
+        Ok(message_type) => message_type,
+        // The peer we are dialling speaks another protocol version
+        Err(wire::WireError::UnsupportedVersion(version)) if !session.is_session_active() => {
+            peer.failure = Some(anyhow!("Peer speaks wire protocol version {}, we speak version {}", version, wire::WIRE_VERSION));
+            return None;
+        }
+        Err(e) => {
+            peer.stats.packets_rejected += 1;
+            warn!("Malformed packet from {}: {}", addr, e);
+            return None;
+        }
+    };
+
+    // Still dialling: only handshake traffic is meaningful
+    if !session.is_session_active() {
+        let message = match wire::decode_message(packet_data) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Ignoring malformed packet from {} during handshake: {}", addr, e);
+                return None;
+            }
+        };
+
+        match message {
+            SecureMessage::HandshakeResponse { .. } => match session.process_handshake_response(message) {
+                Ok(()) => peer.dial_request = None,
+                Err(e) if e.is::<TrustError>() || e.is::<NegotiationError>() => peer.failure = Some(e),
+                Err(e) => warn!("Rejected handshake response from {}: {}", addr, e),
+            },
+            SecureMessage::Handshake { identity_public_key, .. } => {
+                // Simultaneous open: the higher identity key keeps the initiator role
+                if !session.yields_to_peer(&identity_public_key) {
+                    return None;
+                }
+
+                match session.process_handshake(message) {
+                    Ok(Some(response)) => match wire::encode_message(&response) {
+                        Ok(response) => {
+                            peer.handshake_reply = Some(CachedHandshakeReply { request: packet_data.to_vec(), response: response.clone() });
+                            peer.dial_request = None;
+                            replies.push(response);
+                            // The peer we dialled has no audio format in common with us
+                            match session.audio_mismatch() {
+                                Some(mismatch) => peer.failure = Some(mismatch.clone().into()),
+                                None => info!("Answered secure UDP handshake from {}", addr),
+                            }
+                        }
+                        Err(e) => error!("Failed to encode handshake response: {}", e),
+                    },
+                    Ok(None) => {}
+                    // The peer we dialled failed the trust check
+                    Err(e) if e.is::<TrustError>() => peer.failure = Some(e),
+                    Err(e) => warn!("Rejected handshake from {}: {}", addr, e),
+                }
+            }
+            _ => peer.stats.packets_rejected += 1,
+        }
+        return None;
+    }
+
+    match message_type {
+        MessageType::EncryptedAudio => {
+            let secure_msg = match wire::decode_message(packet_data) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    peer.stats.packets_rejected += 1;
+                    warn!("Malformed audio packet from {}: {}", addr, e);
+                    return None;
+                }
+            };
+            let (sequence_number, timestamp) = match secure_msg {
+                SecureMessage::EncryptedAudio { frame_number, timestamp, .. } => (frame_number, timestamp),
+                _ => return None,
+            };
+            match session.decrypt_audio_frame(secure_msg) {
+                Ok(payload) => Some(Delivery::Audio(ReceivedAudioFrame { peer: addr, sequence_number, timestamp, payload })),
+                Err(e) => {
+                    peer.stats.packets_rejected += 1;
+                    warn!("Decryption failed for {}: {}", addr, e);
+                    None
+                }
+            }
+        }
+        MessageType::EncryptedControl => {
+            let decrypted = wire::decode_message(packet_data)
+                .map_err(anyhow::Error::from)
+                .and_then(|message| session.decrypt_control(message));
+            match decrypted {
+                Ok(payload) => Some(Delivery::Control(ReceivedControl { peer: addr, payload })),
+                Err(e) => {
+                    peer.stats.packets_rejected += 1;
+                    warn!("Rejected control message from {}: {}", addr, e);
+                    None
+                }
+            }
+        }
+        MessageType::Handshake => {
+            // The initiator retransmits until it sees our response, so repeat it; a
+            // restart from the peer's key was answered before we got here, and other
+            // keys cannot take over the address
+            let restart_reply = peer.restart.as_ref().map(|restart| &restart.reply);
+            if let Some(reply) = restart_reply.into_iter().chain(peer.handshake_reply.as_ref())
+                .find(|reply| reply.request == packet_data) {
+                replies.push(reply.response.clone());
+            }
+            None
+        }
+        MessageType::HandshakeResponse => {
+            // Late duplicate of a response we already processed
+            None
+        }
+        MessageType::Rekey | MessageType::RekeyResponse => {
+            let reply = wire::decode_message(packet_data)
+                .map_err(anyhow::Error::from)
+                .and_then(|message| session.process_rekey(message));
+            match reply {
+                Ok(Some(reply)) => match wire::encode_message(&reply) {
+                    Ok(reply) => replies.push(reply),
+                    Err(e) => error!("Failed to encode rekey response: {}", e),
+                },
+                Ok(None) => info!("Session keys with {} rotated (generation {})", addr, session.key_generation()),
+                Err(e) => warn!("Rekey from {} rejected: {}", addr, e),
+            }
+            None
+        }
+        MessageType::Disconnect => {
+            // Only the peer's identity key can end its session; anything else is a forgery
+            let verified = wire::decode_message(packet_data)
+                .map_err(anyhow::Error::from)
+                .and_then(|message| session.verify_disconnect(&message));
+            match verified {
+                Ok(reason) => {
+                    info!("Peer {} disconnected: {}", addr, reason);
+                    peer.departed = true;
+                }
+                Err(e) => {
+                    peer.stats.packets_rejected += 1;
+                    warn!("Rejected disconnect from {}: {}", addr, e);
+                }
+            }
+            None
+        }
+    }
+}
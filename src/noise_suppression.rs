@@ -1,7 +1,7 @@
 use anyhow::Result;
 use log::info;
 use std::collections::VecDeque;
-use crate::realtime_audio::{AudioFrame, SAMPLE_RATE, CHANNELS, FRAME_SIZE_SAMPLES};
+use crate::realtime_audio::{AudioFrame, SAMPLE_RATE};
 
 /// Noise suppression configuration
 #[derive(Debug, Clone)]
@@ -297,13 +297,14 @@ impl NoiseSuppressionProcessor {
         self.frames_processed += 1;
 
         // Process each channel separately
-        let samples_per_channel = FRAME_SIZE_SAMPLES;
+        let channels = frame.channels() as usize;
+        let samples_per_channel = frame.samples.len() / channels;
 
-        for channel in 0..CHANNELS as usize {
+        for channel in 0..channels {
             // Extract channel samples
             let mut channel_samples = Vec::with_capacity(samples_per_channel);
             for i in 0..samples_per_channel {
-                let sample_idx = i * CHANNELS as usize + channel;
+                let sample_idx = i * channels + channel;
                 if sample_idx < frame.samples.len() {
                     channel_samples.push(frame.samples[sample_idx]);
                 }
@@ -314,7 +315,7 @@ impl NoiseSuppressionProcessor {
 
             // Write back processed samples
             for i in 0..samples_per_channel {
-                let sample_idx = i * CHANNELS as usize + channel;
+                let sample_idx = i * channels + channel;
                 if sample_idx < frame.samples.len() && i < channel_samples.len() {
                     frame.samples[sample_idx] = channel_samples[i];
                 }
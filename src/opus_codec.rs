@@ -1,7 +1,9 @@
 use anyhow::{Result, anyhow};
 use log::{info, warn, error};
 use audiopus::{coder::Encoder, coder::Decoder, Channels, Application, SampleRate, Bitrate};
-use crate::realtime_audio::{AudioFrame, SAMPLE_RATE, CHANNELS, FRAME_SIZE_SAMPLES, FRAME_SIZE_SAMPLES_PER_CHANNEL};
+use crate::realtime_audio::{AudioFrame, AudioFormat, SAMPLE_RATE, CHANNELS};
+use crate::codec::{CodecKind, CodecStats, VoiceCodec};
+use crate::congestion::EncoderTarget;
 
 /// Opus codec configuration for voice communication
 #[derive(Debug, Clone)]
@@ -24,6 +26,8 @@ pub struct OpusConfig {
     pub fec_enabled: bool,
     /// Discontinuous Transmission enabled
     pub dtx_enabled: bool,
+    /// Packet loss (0-100%) the encoder spends in-band FEC bits protecting against
+    pub packet_loss_percent: u8,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -45,6 +49,7 @@ impl Default for OpusConfig {
             frame_size_ms: 20,
             fec_enabled: true,  // Enable FEC for better error resilience
             dtx_enabled: true, // Enable DTX for efficient bandwidth usage
+            packet_loss_percent: 5, // Modest FEC until receiver feedback says otherwise
         }
     }
 }
@@ -77,8 +82,32 @@ impl OpusConfig {
             return Err(anyhow!("Frame duration must be 10, 20, 40, or 60 ms"));
         }
 
+        if self.packet_loss_percent > 100 {
+            return Err(anyhow!("Packet loss percentage must be <= 100"));
+        }
+
         Ok(())
     }
+
+    /// Format of the frames this codec encodes and decodes
+    pub fn format(&self) -> AudioFormat {
+        AudioFormat {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            frame_duration_ms: self.frame_size_ms,
+        }
+    }
+
+    /// Code frames in `format`
+    pub fn with_format(self, format: AudioFormat) -> Self {
+        Self {
+            sample_rate: format.sample_rate,
+            channels: format.channels,
+            frame_duration_ms: format.frame_duration_ms,
+            frame_size_ms: format.frame_duration_ms,
+            ..self
+        }
+    }
 }
 
 /// High-quality Opus audio codec for voice communication
@@ -93,23 +122,16 @@ pub struct OpusCodec {
     encoding_errors: u64,
     decoding_errors: u64,
     total_bytes_encoded: u64,
+    // Encoder forced to code one channel
+    force_mono: bool,
 }
 
 impl OpusCodec {
     /// Create new Opus codec with specified configuration
     pub fn new(config: OpusConfig) -> Result<Self> {
-        info!("Creating Opus codec: {}Hz, {} channels, {} kbps",
-              config.sample_rate, config.channels, config.bitrate / 1000);
+        info!("Creating Opus codec: {}, {} kbps", config.format(), config.bitrate / 1000);
 
-        // Validate configuration matches AudioFrame constants
-        if config.sample_rate != SAMPLE_RATE {
-            return Err(anyhow!("Opus sample rate ({}) must match AudioFrame sample rate ({})",
-                              config.sample_rate, SAMPLE_RATE));
-        }
-        if config.channels != CHANNELS {
-            return Err(anyhow!("Opus channels ({}) must match AudioFrame channels ({})",
-                              config.channels, CHANNELS));
-        }
+        config.validate()?;
 
         // Convert to audiopus types
         let opus_sample_rate = match config.sample_rate {
@@ -136,17 +158,7 @@ impl OpusCodec {
         // Create encoder
         let mut encoder = Encoder::new(opus_sample_rate, opus_channels, opus_application)
             .map_err(|e| anyhow!("Failed to create Opus encoder: {}", e))?;
-
-        // Configure encoder settings
-        encoder.set_bitrate(Bitrate::BitsPerSecond(config.bitrate as i32))
-            .map_err(|e| anyhow!("Failed to set Opus bitrate: {}", e))?;
-
-        encoder.set_complexity(config.complexity as u8)
-            .map_err(|e| anyhow!("Failed to set Opus complexity: {}", e))?;
-
-        // Enable VBR for better quality
-        encoder.set_vbr(true)
-            .map_err(|e| anyhow!("Failed to enable Opus VBR: {}", e))?;
+        Self::configure_encoder(&mut encoder, &config)?;
 
         // Create decoder
         let decoder = Decoder::new(opus_sample_rate, opus_channels)
@@ -154,7 +166,8 @@ impl OpusCodec {
 
         // Pre-allocate buffers
         let max_encoded_size = 4000; // Opus max packet size
-        let decoded_buffer_size = FRAME_SIZE_SAMPLES; // Already includes both channels
+        // One frame, all channels; its length sets how much audio PLC and FEC produce
+        let decoded_buffer_size = config.format().frame_samples();
 
         info!("Opus codec created successfully");
 
@@ -169,9 +182,34 @@ impl OpusCodec {
             encoding_errors: 0,
             decoding_errors: 0,
             total_bytes_encoded: 0,
+            force_mono: false,
         })
     }
 
+    /// Apply bitrate, complexity, VBR, in-band FEC, DTX and expected loss from `config`
+    fn configure_encoder(encoder: &mut Encoder, config: &OpusConfig) -> Result<()> {
+        encoder.set_bitrate(Bitrate::BitsPerSecond(config.bitrate as i32))
+            .map_err(|e| anyhow!("Failed to set Opus bitrate: {}", e))?;
+
+        encoder.set_complexity(config.complexity as u8)
+            .map_err(|e| anyhow!("Failed to set Opus complexity: {}", e))?;
+
+        // Enable VBR for better quality
+        encoder.set_vbr(true)
+            .map_err(|e| anyhow!("Failed to enable Opus VBR: {}", e))?;
+
+        // In-band FEC only spends bits when the expected loss is above zero
+        encoder.set_inband_fec(config.fec_enabled)
+            .map_err(|e| anyhow!("Failed to set Opus in-band FEC: {}", e))?;
+        encoder.set_packet_loss_perc(config.packet_loss_percent)
+            .map_err(|e| anyhow!("Failed to set Opus packet loss percentage: {}", e))?;
+
+        encoder.set_dtx(config.dtx_enabled)
+            .map_err(|e| anyhow!("Failed to set Opus DTX: {}", e))?;
+
+        Ok(())
+    }
+
     /// Encode audio frame to compressed data
     pub fn encode(&mut self, frame: &AudioFrame) -> Result<Vec<u8>> {
         // Convert f32 samples to i16 for Opus (Opus expects 16-bit samples)
@@ -225,29 +263,12 @@ impl OpusCodec {
         };
 
         // Verify we got the expected number of samples (Opus returns samples per channel)
-        let expected_samples = FRAME_SIZE_SAMPLES_PER_CHANNEL;
+        let expected_samples = self.config.format().frame_samples_per_channel();
         if decoded_len != expected_samples {
             warn!("Opus decoded {} samples, expected {}", decoded_len, expected_samples);
         }
 
-        // Convert i16 samples back to f32 and create AudioFrame
-        // decoded_len is samples per channel, but the buffer contains interleaved samples
-        // So for stereo, we need decoded_len * channels total samples
-        let total_samples = decoded_len * self.config.channels as usize;
-        let mut f32_samples = Vec::with_capacity(total_samples);
-        for &i16_sample in self.decoded_buffer_i16[..total_samples].iter() {
-            // Convert from i16 range back to f32 range
-            f32_samples.push(i16_sample as f32 / 32767.0);
-        }
-        let mut frame = AudioFrame::new(f32_samples);
-
-        // Set frame metadata
-        frame.timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
-
-        Ok(frame)
+        Ok(self.decoded_frame(decoded_len))
     }
 
     /// Handle packet loss by generating concealment frame
@@ -265,24 +286,58 @@ impl OpusCodec {
             Ok(len) => len,
             Err(e) => {
                 error!("Opus packet loss concealment failed: {}", e);
-                return Ok(AudioFrame::silence()); // Fallback to silence
+                return Ok(AudioFrame::silence_in(self.config.format())); // Fallback to silence
+            }
+        };
+
+        Ok(self.decoded_frame(decoded_len))
+    }
+
+    /// Rebuild a lost frame from the in-band FEC data carried by the packet that follows it.
+    /// Without FEC data in `next_packet` this falls back to packet loss concealment;
+    /// `next_packet` itself still has to be decoded normally afterwards.
+    pub fn decode_fec(&mut self, next_packet: &[u8]) -> Result<AudioFrame> {
+        use audiopus::{packet::Packet, MutSignals};
+
+        let packet = Packet::try_from(next_packet)
+            .map_err(|e| anyhow!("Failed to create Opus packet: {}", e))?;
+
+        // The output length tells Opus how much audio to recover: exactly one frame
+        let signals = MutSignals::try_from(&mut self.decoded_buffer_i16[..])
+            .map_err(|e| anyhow!("Failed to create signals wrapper: {}", e))?;
+
+        let decoded_len = match self.decoder.decode(Some(packet), signals, true) {
+            Ok(len) => {
+                self.frames_decoded += 1;
+                len
+            }
+            Err(e) => {
+                self.decoding_errors += 1;
+                error!("Opus FEC decoding failed: {}", e);
+                return Err(anyhow!("Opus FEC decoding failed: {}", e));
             }
         };
 
-        // Convert i16 samples back to f32 and create AudioFrame
-        let mut f32_samples = Vec::with_capacity(decoded_len);
-        for &i16_sample in self.decoded_buffer_i16[..decoded_len].iter() {
+        Ok(self.decoded_frame(decoded_len))
+    }
+
+    /// Convert `decoded_len` samples per channel of the interleaved i16 buffer into a frame
+    fn decoded_frame(&self, decoded_len: usize) -> AudioFrame {
+        let total_samples = decoded_len * self.config.channels as usize;
+        let mut f32_samples = Vec::with_capacity(total_samples);
+        for &i16_sample in self.decoded_buffer_i16[..total_samples].iter() {
             // Convert from i16 range back to f32 range
             f32_samples.push(i16_sample as f32 / 32767.0);
         }
-        let mut frame = AudioFrame::new(f32_samples);
+        let mut frame = AudioFrame::with_format(f32_samples, self.config.format());
 
+        // Set frame metadata
         frame.timestamp = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_millis() as u64;
 
-        Ok(frame)
+        frame
     }
 
     /// Update codec bitrate dynamically
@@ -312,6 +367,37 @@ impl OpusCodec {
         Ok(())
     }
 
+    /// Update the packet loss percentage in-band FEC protects against
+    pub fn set_packet_loss_percent(&mut self, percent: u8) -> Result<()> {
+        if percent > 100 {
+            return Err(anyhow!("Packet loss percentage must be 0-100, got {}", percent));
+        }
+
+        info!("Updating Opus expected packet loss: {}% -> {}%", self.config.packet_loss_percent, percent);
+
+        self.encoder.set_packet_loss_perc(percent)
+            .map_err(|e| anyhow!("Failed to update Opus packet loss percentage: {}", e))?;
+
+        self.config.packet_loss_percent = percent;
+        Ok(())
+    }
+
+    /// Code a single channel regardless of the input layout, or return to the configured layout
+    pub fn set_mono(&mut self, mono: bool) -> Result<()> {
+        let channels = if mono { Channels::Mono } else { Channels::Auto };
+        self.encoder.set_force_channels(channels)
+            .map_err(|e| anyhow!("Failed to update Opus channel mode: {}", e))?;
+
+        info!("Opus encoder now coding {}", if mono { "mono" } else { "the input layout" });
+        self.force_mono = mono;
+        Ok(())
+    }
+
+    /// Whether the encoder is forced to mono
+    pub fn is_mono(&self) -> bool {
+        self.force_mono
+    }
+
     /// Get current codec configuration
     pub fn get_config(&self) -> &OpusConfig {
         &self.config
@@ -320,7 +406,7 @@ impl OpusCodec {
     /// Get codec statistics
     pub fn get_stats(&self) -> OpusStats {
         let average_compression_ratio = if self.frames_encoded > 0 {
-            let uncompressed_bytes = self.frames_encoded * FRAME_SIZE_SAMPLES as u64 * 4; // 4 bytes per f32
+            let uncompressed_bytes = self.frames_encoded * self.config.format().frame_samples() as u64 * 4; // 4 bytes per f32
             uncompressed_bytes as f64 / self.total_bytes_encoded as f64
         } else {
             0.0
@@ -331,6 +417,7 @@ impl OpusCodec {
             channels: self.config.channels,
             bitrate: self.config.bitrate,
             complexity: self.config.complexity,
+            packet_loss_percent: self.config.packet_loss_percent,
             frame_duration_ms: self.config.frame_duration_ms,
             frames_encoded: self.frames_encoded,
             frames_decoded: self.frames_decoded,
@@ -367,17 +454,14 @@ impl OpusCodec {
             OpusApplication::LowDelay => Application::LowDelay,
         };
 
-        // Recreate encoder
+        // Recreate encoder and restore settings
         let mut encoder = Encoder::new(opus_sample_rate, opus_channels, opus_application)
             .map_err(|e| anyhow!("Failed to recreate Opus encoder: {}", e))?;
-
-        // Restore settings
-        encoder.set_bitrate(Bitrate::BitsPerSecond(self.config.bitrate as i32))
-            .map_err(|e| anyhow!("Failed to restore Opus bitrate: {}", e))?;
-        encoder.set_complexity(self.config.complexity as u8)
-            .map_err(|e| anyhow!("Failed to restore Opus complexity: {}", e))?;
-        encoder.set_vbr(true)
-            .map_err(|e| anyhow!("Failed to restore Opus VBR: {}", e))?;
+        Self::configure_encoder(&mut encoder, &self.config)?;
+        if self.force_mono {
+            encoder.set_force_channels(Channels::Mono)
+                .map_err(|e| anyhow!("Failed to restore Opus channel mode: {}", e))?;
+        }
 
         // Recreate decoder
         let decoder = Decoder::new(opus_sample_rate, opus_channels)
@@ -390,6 +474,80 @@ impl OpusCodec {
     }
 }
 
+impl VoiceCodec for OpusCodec {
+    fn kind(&self) -> CodecKind {
+        CodecKind::Opus
+    }
+
+    fn format(&self) -> AudioFormat {
+        self.config.format()
+    }
+
+    fn encode(&mut self, frame: &AudioFrame) -> Result<Vec<u8>> {
+        OpusCodec::encode(self, frame)
+    }
+
+    fn decode(&mut self, data: &[u8]) -> Result<AudioFrame> {
+        OpusCodec::decode(self, data)
+    }
+
+    fn conceal(&mut self) -> Result<AudioFrame> {
+        self.decode_lost_packet()
+    }
+
+    fn decode_fec(&mut self, next_packet: &[u8]) -> Result<AudioFrame> {
+        OpusCodec::decode_fec(self, next_packet)
+    }
+
+    fn bitrate(&self) -> u32 {
+        self.config.bitrate
+    }
+
+    fn set_bitrate(&mut self, bitrate: u32) -> Result<()> {
+        OpusCodec::set_bitrate(self, bitrate)
+    }
+
+    /// Bitrate plus complexity, forced mono and expected loss
+    fn apply_target(&mut self, target: &EncoderTarget) -> Result<()> {
+        if target.bitrate != self.config.bitrate {
+            self.set_bitrate(target.bitrate)?;
+        }
+        if target.complexity != self.config.complexity {
+            self.set_complexity(target.complexity)?;
+        }
+        if target.mono != self.is_mono() {
+            self.set_mono(target.mono)?;
+        }
+        if target.packet_loss_percent != self.config.packet_loss_percent {
+            self.set_packet_loss_percent(target.packet_loss_percent)?;
+        }
+        Ok(())
+    }
+
+    fn stats(&self) -> CodecStats {
+        let stats = self.get_stats();
+        CodecStats {
+            codec: CodecKind::Opus,
+            sample_rate: stats.sample_rate,
+            channels: stats.channels,
+            bitrate: stats.bitrate,
+            complexity: stats.complexity,
+            packet_loss_percent: stats.packet_loss_percent,
+            frame_duration_ms: self.config.format().frame_duration_ms,
+            frames_encoded: stats.frames_encoded,
+            frames_decoded: stats.frames_decoded,
+            encoding_errors: stats.encoding_errors,
+            decoding_errors: stats.decoding_errors,
+            total_bytes_encoded: stats.total_bytes_encoded,
+            average_compression_ratio: stats.average_compression_ratio,
+        }
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        OpusCodec::reset(self)
+    }
+}
+
 /// Opus codec statistics
 #[derive(Debug, Clone)]
 pub struct OpusStats {
@@ -397,6 +555,7 @@ pub struct OpusStats {
     pub channels: u16,
     pub bitrate: u32,
     pub complexity: u32,
+    pub packet_loss_percent: u8,
     pub frame_duration_ms: u32,
     pub frames_encoded: u64,
     pub frames_decoded: u64,
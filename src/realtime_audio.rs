@@ -1,11 +1,102 @@
 use anyhow::{Result, anyhow};
 use log::{info, error, warn};
 use ringbuf::{HeapRb, traits::*};
+use std::collections::{HashMap, hash_map::Entry};
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU64, Ordering}};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use cpal::{Device, Stream, StreamConfig, SampleRate, BufferSize};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use crate::noise_suppression::{NoiseSuppressionProcessor, NoiseSuppressionConfig};
+use crate::echo_cancellation::{EchoCancellationProcessor, EchoCancellationConfig};
+use crate::opus_codec::OpusConfig;
+use crate::codec::{CodecKind, CodecStats, VoiceCodec};
+use crate::jitter_buffer::{AdaptiveJitterBuffer, JitterBufferConfig, JitterBufferStats, AudioPacket, PlayoutSlot};
+use crate::network::ReceivedAudioFrame;
+use crate::mixer::{AudioMixer, MixerConfig, MixerHandle};
+use crate::congestion::{EncoderControl, EncoderTarget};
+use crate::redundancy::{RedundancyConfig, RedundancyEncoder, RedundantPayload};
+use serde::{Deserialize, Serialize};
+
+/// Sample rate, channel layout and frame duration shared by capture, codec and playback
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AudioFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub frame_duration_ms: u32,
+}
+
+impl Default for AudioFormat {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+impl AudioFormat {
+    /// 48 kHz stereo in 20 ms frames
+    pub const STANDARD: Self = Self { sample_rate: SAMPLE_RATE, channels: CHANNELS, frame_duration_ms: FRAME_SIZE_MS };
+    /// 16 kHz mono in 60 ms frames, for constrained links
+    pub const LOW_BANDWIDTH: Self = Self { sample_rate: 16000, channels: 1, frame_duration_ms: 60 };
+
+    /// Sample rates the pipelines run, best first
+    pub const SAMPLE_RATES: [u32; 5] = [48000, 24000, 16000, 12000, 8000];
+    /// Channel counts the pipelines run, best first
+    pub const CHANNEL_COUNTS: [u16; 2] = [2, 1];
+    /// Frame durations the pipelines run, in ms
+    pub const FRAME_DURATIONS_MS: [u32; 4] = [10, 20, 40, 60];
+
+    /// Samples per channel in one frame
+    pub fn frame_samples_per_channel(&self) -> usize {
+        (self.sample_rate as u64 * self.frame_duration_ms as u64 / 1000) as usize
+    }
+
+    /// Interleaved samples in one frame
+    pub fn frame_samples(&self) -> usize {
+        self.frame_samples_per_channel() * self.channels as usize
+    }
+
+    /// Check the format is one Opus can code
+    pub fn validate(&self) -> Result<()> {
+        if !Self::SAMPLE_RATES.contains(&self.sample_rate) {
+            return Err(anyhow!("Sample rate must be 8000, 12000, 16000, 24000, or 48000 Hz"));
+        }
+        if !Self::CHANNEL_COUNTS.contains(&self.channels) {
+            return Err(anyhow!("Channels must be 1 (mono) or 2 (stereo)"));
+        }
+        if !Self::FRAME_DURATIONS_MS.contains(&self.frame_duration_ms) {
+            return Err(anyhow!("Frame duration must be 10, 20, 40, or 60 ms"));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for AudioFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let layout = if self.channels == 1 { "mono" } else { "stereo" };
+        write!(f, "{} kHz {} in {} ms frames", self.sample_rate as f32 / 1000.0, layout, self.frame_duration_ms)
+    }
+}
+
+/// Named audio formats selectable from the configuration file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioProfile {
+    /// 48 kHz stereo, 20 ms frames
+    #[default]
+    Standard,
+    /// 16 kHz mono, 60 ms frames: fewer, smaller packets at the cost of latency and bandwidth
+    LowBandwidth,
+}
+
+impl AudioProfile {
+    pub fn format(self) -> AudioFormat {
+        match self {
+            AudioProfile::Standard => AudioFormat::STANDARD,
+            AudioProfile::LowBandwidth => AudioFormat::LOW_BANDWIDTH,
+        }
+    }
+}
 
 /// Runtime configurable audio parameters
 #[derive(Debug, Clone)]
@@ -47,6 +138,25 @@ impl AudioConfiguration {
         self.frame_size_samples() * self.buffer_capacity_multiplier
     }
 
+    /// Format of the frames captured and played back
+    pub fn format(&self) -> AudioFormat {
+        AudioFormat {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            frame_duration_ms: self.frame_duration_ms,
+        }
+    }
+
+    /// Capture and play back frames in `format`
+    pub fn with_format(self, format: AudioFormat) -> Self {
+        Self {
+            sample_rate: format.sample_rate,
+            channels: format.channels,
+            frame_duration_ms: format.frame_duration_ms,
+            ..self
+        }
+    }
+
     /// Validate the configuration parameters
     pub fn validate(&self) -> Result<()> {
         if self.sample_rate < 8000 || self.sample_rate > 192000 {
@@ -78,6 +188,11 @@ pub const SAMPLE_RATE: u32 = 48000;
 pub const CHANNELS: u16 = 2;
 /// Ring buffer capacity (store ~500ms of audio)
 pub const RING_BUFFER_CAPACITY: usize = FRAME_SIZE_SAMPLES * 25;
+/// Decoded frames kept queued ahead of the output callback
+const PLAYOUT_QUEUE_FRAMES: usize = 2;
+
+/// Drop a peer's playback pipeline after this long without packets
+const PEER_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Real-time safe audio frame container
 #[derive(Debug, Clone)]
@@ -85,6 +200,8 @@ pub struct AudioFrame {
     pub samples: Vec<f32>,
     pub timestamp: u64,
     pub sequence: u32,
+    /// Layout of `samples`
+    pub format: AudioFormat,
 }
 
 /// Zero-copy audio buffer for efficient memory management
@@ -109,6 +226,7 @@ impl ZeroCopyAudioBuffer {
                 samples: vec![0.0; FRAME_SIZE_SAMPLES * CHANNELS as usize],
                 timestamp: 0,
                 sequence: i as u32,
+                format: AudioFormat::default(),
             });
         }
 
@@ -191,38 +309,500 @@ impl ZeroCopyBufferStats {
 
 impl AudioFrame {
     pub fn new(samples: Vec<f32>) -> Self {
+        Self::with_format(samples, AudioFormat::default())
+    }
+
+    /// Frame of interleaved `samples` in `format`
+    pub fn with_format(samples: Vec<f32>, format: AudioFormat) -> Self {
         Self {
             samples,
             timestamp: 0,
             sequence: 0,
+            format,
         }
     }
 
     pub fn empty() -> Self {
-        Self {
-            samples: vec![0.0; FRAME_SIZE_SAMPLES * CHANNELS as usize],
-            timestamp: 0,
-            sequence: 0,
-        }
+        Self::silence_in(AudioFormat::default())
     }
 
     pub fn silence() -> Self {
         Self::empty()
     }
 
+    /// One frame of silence in `format`
+    pub fn silence_in(format: AudioFormat) -> Self {
+        Self::with_format(vec![0.0; format.frame_samples()], format)
+    }
+
     pub fn len(&self) -> usize {
         self.samples.len()
     }
 
     pub fn channels(&self) -> u16 {
-        CHANNELS
+        self.format.channels
     }
 
     pub fn sample_rate(&self) -> u32 {
-        SAMPLE_RATE
+        self.format.sample_rate
+    }
+}
+
+/// Capture-side signal chain: noise suppression -> echo cancellation -> encoding
+pub struct CapturePipeline {
+    noise_suppressor: Option<NoiseSuppressionProcessor>,
+    echo_canceller: Option<EchoCancellationProcessor>,
+    codec: Box<dyn VoiceCodec>,
+    // Settings `with_codec` builds codecs from
+    opus: OpusConfig,
+    // Most recent far-end (played back) frame, used as the AEC reference
+    far_end_reference: AudioFrame,
+    // Bitrate, complexity and channel changes from the congestion controller
+    encoder_control: EncoderControl,
+    // Frames the encoded packet and repeats earlier frames while active
+    redundancy: RedundancyEncoder,
+}
+
+impl CapturePipeline {
+    /// Create capture pipeline encoding with Opus; `None` disables the corresponding stage
+    pub fn new(
+        noise_suppression: Option<NoiseSuppressionConfig>,
+        echo_cancellation: Option<EchoCancellationConfig>,
+        opus: OpusConfig,
+    ) -> Result<Self> {
+        let noise_suppressor = noise_suppression
+            .map(NoiseSuppressionProcessor::new)
+            .transpose()?;
+        let echo_canceller = echo_cancellation
+            .map(EchoCancellationProcessor::new)
+            .transpose()?;
+
+        Ok(Self {
+            noise_suppressor,
+            echo_canceller,
+            redundancy: RedundancyEncoder::new(RedundancyConfig::default(), CodecKind::Opus, &opus)?,
+            far_end_reference: AudioFrame::silence_in(opus.format()),
+            codec: CodecKind::Opus.create(&opus)?,
+            opus,
+            encoder_control: EncoderControl::default(),
+        })
+    }
+
+    /// Encode with `kind` instead, keeping the frame duration; redundancy settings carry over
+    pub fn with_codec(mut self, kind: CodecKind) -> Result<Self> {
+        self.codec = kind.create(&self.opus)?;
+        self.redundancy = RedundancyEncoder::new(*self.redundancy.config(), kind, &self.opus)?;
+        self.far_end_reference = AudioFrame::silence_in(self.format());
+        Ok(self)
+    }
+
+    /// Repeat earlier frames in each packet as `config` prescribes
+    pub fn with_redundancy(mut self, config: RedundancyConfig) -> Result<Self> {
+        self.redundancy = RedundancyEncoder::new(config, self.codec.kind(), &self.opus)?;
+        Ok(self)
+    }
+
+    /// Take encoder changes from `control` instead of a private handle
+    pub fn with_encoder_control(mut self, control: EncoderControl) -> Self {
+        self.encoder_control = control;
+        self
+    }
+
+    /// Handle for retuning the encoder from another thread
+    pub fn encoder_control(&self) -> EncoderControl {
+        self.encoder_control.clone()
+    }
+
+    /// Format of the frames the pipeline encodes
+    pub fn format(&self) -> AudioFormat {
+        self.codec.format()
+    }
+
+    /// Codec the pipeline encodes with
+    pub fn codec(&self) -> CodecKind {
+        self.codec.kind()
+    }
+
+    /// Update the far-end reference signal used by echo cancellation
+    pub fn set_far_end_reference(&mut self, frame: &AudioFrame) {
+        self.far_end_reference.samples.clone_from(&frame.samples);
+        self.far_end_reference.timestamp = frame.timestamp;
+        self.far_end_reference.sequence = frame.sequence;
+        self.far_end_reference.format = frame.format;
+    }
+
+    /// Run a captured frame through all stages and return the encoded packet,
+    /// in the RFC 2198 layout with any redundant frames ahead of the primary
+    pub fn process(&mut self, frame: &mut AudioFrame) -> Result<Vec<u8>> {
+        if frame.format != self.format() {
+            return Err(anyhow!("Captured frame is {}, but the encoder expects {}", frame.format, self.format()));
+        }
+
+        if let Some(noise_suppressor) = self.noise_suppressor.as_mut() {
+            noise_suppressor.process_frame(frame)?;
+        }
+
+        if let Some(echo_canceller) = self.echo_canceller.as_mut() {
+            echo_canceller.process_frame(&self.far_end_reference, frame)?;
+        }
+
+        if let Some(target) = self.encoder_control.take()
+            && let Err(e) = self.apply_encoder_target(target) {
+            warn!("Failed to apply encoder target: {}", e);
+        }
+
+        let primary = self.codec.encode(frame)?;
+        self.redundancy.packetize(frame, primary)
+    }
+
+    fn apply_encoder_target(&mut self, target: EncoderTarget) -> Result<()> {
+        self.codec.apply_target(&target)?;
+        self.redundancy.set_active(target.redundancy);
+        self.redundancy.limit_bitrate(target.bitrate)
+    }
+
+    /// Get encoder statistics
+    pub fn codec_stats(&self) -> CodecStats {
+        self.codec.stats()
+    }
+
+    /// Whether packets currently repeat earlier frames
+    pub fn redundancy_active(&self) -> bool {
+        self.redundancy.is_active()
+    }
+
+    /// Number of packets sent with at least one earlier frame
+    pub fn packets_with_redundancy(&self) -> u64 {
+        self.redundancy.packets_with_redundancy()
     }
 }
 
+/// Receive-side chain: jitter buffer -> decoding with FEC recovery and packet loss concealment
+pub struct PlaybackPipeline {
+    jitter_buffer: AdaptiveJitterBuffer,
+    codec: Box<dyn VoiceCodec>,
+    // Settings `with_codec` builds codecs from
+    opus: OpusConfig,
+    // Sender frame number that maps to jitter buffer sequence 0
+    sequence_base: Option<u64>,
+    // Highest sender frame number queued so far
+    newest_sequence: u64,
+    frames_decoded: u64,
+    frames_concealed: u64,
+    frames_fec_decoded: u64,
+}
+
+impl PlaybackPipeline {
+    /// Create playback pipeline decoding Opus; the jitter buffer's frame counts are rescaled to the codec's frame duration
+    pub fn new(jitter_config: JitterBufferConfig, opus: OpusConfig) -> Result<Self> {
+        let jitter_config = jitter_config.with_frame_duration(opus.format().frame_duration_ms);
+        Ok(Self {
+            jitter_buffer: AdaptiveJitterBuffer::new(jitter_config)?,
+            codec: CodecKind::Opus.create(&opus)?,
+            opus,
+            sequence_base: None,
+            newest_sequence: 0,
+            frames_decoded: 0,
+            frames_concealed: 0,
+            frames_fec_decoded: 0,
+        })
+    }
+
+    /// Decode `kind` instead, keeping the frame duration
+    pub fn with_codec(mut self, kind: CodecKind) -> Result<Self> {
+        self.codec = kind.create(&self.opus)?;
+        Ok(self)
+    }
+
+    /// Fresh pipeline with the same jitter buffer and codec settings
+    pub fn fork(&self) -> Result<Self> {
+        Self::new(self.jitter_buffer.get_config(), self.opus.clone())?.with_codec(self.codec.kind())
+    }
+
+    /// Format of the frames the pipeline decodes
+    pub fn format(&self) -> AudioFormat {
+        self.codec.format()
+    }
+
+    /// Codec the pipeline decodes
+    pub fn codec(&self) -> CodecKind {
+        self.codec.kind()
+    }
+
+    /// Queue an encoded packet keyed on the sender's frame number. Earlier frames
+    /// repeated in the packet fill any gaps they cover.
+    pub fn push_packet(&mut self, sequence_number: u64, payload: Vec<u8>) -> Result<()> {
+        let payload = RedundantPayload::decode(&payload)
+            .map_err(|e| anyhow!("Malformed audio payload: {}", e))?;
+        let payload_type = self.codec.kind().payload_type();
+        if payload.payload_type != payload_type {
+            return Err(anyhow!("Unsupported audio payload type {} (expecting {})", payload.payload_type, self.codec.kind()));
+        }
+
+        // Too far behind the newest frame to be a reordered packet, so the sender
+        // started counting again, as a peer restarting its session does
+        let restart_gap = self.jitter_buffer.get_config().max_size as u64;
+        if self.sequence_base.is_some() && sequence_number + restart_gap < self.newest_sequence {
+            self.restart_stream()?;
+        }
+
+        let base = *self.sequence_base.get_or_insert(sequence_number);
+        if sequence_number < base {
+            // Reordered packet from before the first one we saw
+            return Ok(());
+        }
+        self.newest_sequence = self.newest_sequence.max(sequence_number);
+
+        let relative_sequence = (sequence_number - base) as u32;
+        let frame_duration_ms = self.format().frame_duration_ms as u64;
+        let timestamp = |sequence: u32| sequence as u64 * frame_duration_ms;
+        self.jitter_buffer.put_packet(AudioPacket::encoded(payload.primary, timestamp(relative_sequence), relative_sequence))?;
+
+        for block in payload.redundant {
+            if block.payload_type != payload_type || (block.offset as u32) > relative_sequence {
+                continue;
+            }
+            let sequence = relative_sequence - block.offset as u32;
+            self.jitter_buffer.put_recovered(AudioPacket::encoded(block.data, timestamp(sequence), sequence));
+        }
+        Ok(())
+    }
+
+    /// Drop the old stream's buffered frames and decoder state, keeping the counts
+    fn restart_stream(&mut self) -> Result<()> {
+        self.jitter_buffer.reset();
+        self.codec = self.codec.kind().create(&self.opus)?;
+        self.sequence_base = None;
+        self.newest_sequence = 0;
+        Ok(())
+    }
+
+    /// Decode the next frame for playback. A lost frame is rebuilt from the in-band FEC
+    /// of the packet after it when that one is already buffered, otherwise concealed.
+    /// Returns `None` while the jitter buffer is waiting for data.
+    pub fn next_frame(&mut self) -> Option<AudioFrame> {
+        let (mut frame, sequence) = match self.jitter_buffer.next_playout() {
+            PlayoutSlot::Packet(packet) => match self.codec.decode(&packet.payload) {
+                Ok(frame) => {
+                    self.frames_decoded += 1;
+                    (frame, packet.sequence_number)
+                }
+                Err(_) => (self.conceal()?, packet.sequence_number),
+            },
+            PlayoutSlot::Lost { sequence_number } => (self.recover(sequence_number)?, sequence_number),
+            PlayoutSlot::Empty => return None,
+        };
+
+        frame.sequence = sequence;
+        Some(frame)
+    }
+
+    fn recover(&mut self, sequence_number: u32) -> Option<AudioFrame> {
+        if let Some(next) = self.jitter_buffer.peek(sequence_number.wrapping_add(1))
+            && let Ok(frame) = self.codec.decode_fec(&next.payload) {
+            self.frames_fec_decoded += 1;
+            return Some(frame);
+        }
+        self.conceal()
+    }
+
+    fn conceal(&mut self) -> Option<AudioFrame> {
+        self.frames_concealed += 1;
+        self.codec.conceal().ok()
+    }
+
+    /// Get jitter buffer statistics
+    pub fn jitter_stats(&self) -> JitterBufferStats {
+        self.jitter_buffer.get_stats()
+    }
+
+    /// Number of frames decoded from received packets
+    pub fn frames_decoded(&self) -> u64 {
+        self.frames_decoded
+    }
+
+    /// Number of frames generated by packet loss concealment
+    pub fn frames_concealed(&self) -> u64 {
+        self.frames_concealed
+    }
+
+    /// Number of lost frames decoded from the next packet's in-band FEC
+    /// (which Opus conceals internally when that packet carries none)
+    pub fn frames_fec_decoded(&self) -> u64 {
+        self.frames_fec_decoded
+    }
+
+    /// Number of lost frames rebuilt from redundant copies in later packets
+    pub fn frames_recovered(&self) -> u64 {
+        self.jitter_buffer.get_stats().recovered_packets
+    }
+}
+
+/// Regroups device callback data into whole frames, carrying any partial
+/// frame over to the next callback
+pub struct FrameAssembler {
+    format: AudioFormat,
+    // Samples of the frame being filled
+    pending: Vec<f32>,
+}
+
+impl FrameAssembler {
+    pub fn new(format: AudioFormat) -> Self {
+        Self { format, pending: Vec::with_capacity(format.frame_samples()) }
+    }
+
+    /// Add callback data, handing each frame it completes to `on_frame`
+    pub fn push(&mut self, mut data: &[f32], mut on_frame: impl FnMut(AudioFrame)) {
+        let frame_samples = self.format.frame_samples();
+        while !data.is_empty() {
+            let take = (frame_samples - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+
+            if self.pending.len() == frame_samples {
+                let samples = std::mem::replace(&mut self.pending, Vec::with_capacity(frame_samples));
+                on_frame(AudioFrame::with_format(samples, self.format));
+            }
+        }
+    }
+
+    /// Samples waiting for the rest of their frame
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Output counterpart of [`FrameAssembler`]: spreads whole frames across device
+/// callbacks, carrying a frame's unplayed samples over to the next callback
+#[derive(Default)]
+pub struct FrameSplitter {
+    // Frame being played out
+    current: Option<AudioFrame>,
+    // Samples of it already played
+    position: usize,
+}
+
+impl FrameSplitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fill callback data, taking frames from `next_frame` as needed; silence
+    /// covers whatever is left once it runs dry
+    pub fn fill(&mut self, mut data: &mut [f32], mut next_frame: impl FnMut() -> Option<AudioFrame>) {
+        while !data.is_empty() {
+            let frame = match self.current.as_ref() {
+                Some(frame) if self.position < frame.samples.len() => frame,
+                _ => match next_frame() {
+                    Some(frame) => {
+                        self.position = 0;
+                        self.current.insert(frame)
+                    }
+                    None => {
+                        self.current = None;
+                        data.fill(0.0);
+                        return;
+                    }
+                },
+            };
+
+            let take = (frame.samples.len() - self.position).min(data.len());
+            data[..take].copy_from_slice(&frame.samples[self.position..self.position + take]);
+            self.position += take;
+            data = &mut data[take..];
+        }
+    }
+
+    /// Samples of the current frame still to be played
+    pub fn pending(&self) -> usize {
+        self.current.as_ref().map_or(0, |frame| frame.samples.len() - self.position)
+    }
+}
+
+/// Processing-thread side of the capture pipeline
+struct CapturePath {
+    pipeline: CapturePipeline,
+    encoded_producer: ringbuf::HeapProd<Vec<u8>>,
+    packets_encoded: Arc<AtomicU64>,
+    packets_dropped: Arc<AtomicU64>,
+}
+
+/// Playback state for one remote peer
+struct PeerPlayback {
+    pipeline: PlaybackPipeline,
+    last_packet: Instant,
+}
+
+/// Processing-thread side of the playback pipeline: one jitter buffer and decoder per peer, then the mixer
+struct PlaybackPath {
+    // Settings template for peers' pipelines
+    template: PlaybackPipeline,
+    peers: HashMap<SocketAddr, PeerPlayback>,
+    mixer: AudioMixer,
+    // Reused across periods to avoid reallocating
+    decoded: Vec<(SocketAddr, AudioFrame)>,
+    received_consumer: ringbuf::HeapCons<ReceivedAudioFrame>,
+    // Counts carried over from pipelines of peers that went idle
+    retired_decoded: u64,
+    retired_concealed: u64,
+    frames_decoded: Arc<AtomicU64>,
+    frames_concealed: Arc<AtomicU64>,
+}
+
+impl PlaybackPath {
+    /// Queue a received packet in its sender's pipeline, creating one for new peers
+    fn push(&mut self, received: ReceivedAudioFrame) -> Result<()> {
+        let peer = match self.peers.entry(received.peer) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(PeerPlayback {
+                pipeline: self.template.fork()?,
+                last_packet: Instant::now(),
+            }),
+        };
+        peer.last_packet = Instant::now();
+        peer.pipeline.push_packet(received.sequence_number, received.payload)
+    }
+
+    /// Decode one frame from every peer with data and mix them
+    fn next_mixed_frame(&mut self) -> Option<AudioFrame> {
+        self.decoded.clear();
+        for (&addr, peer) in self.peers.iter_mut() {
+            if let Some(frame) = peer.pipeline.next_frame() {
+                self.decoded.push((addr, frame));
+            }
+        }
+        self.mixer.mix(&self.decoded)
+    }
+
+    /// Forget peers that stopped sending
+    fn prune_idle_peers(&mut self) {
+        let (retired_decoded, retired_concealed) = (&mut self.retired_decoded, &mut self.retired_concealed);
+        let mixer = &mut self.mixer;
+        self.peers.retain(|&addr, peer| {
+            let active = peer.last_packet.elapsed() < PEER_IDLE_TIMEOUT;
+            if !active {
+                *retired_decoded += peer.pipeline.frames_decoded();
+                *retired_concealed += peer.pipeline.frames_concealed();
+                mixer.remove_participant(addr);
+            }
+            active
+        });
+    }
+
+    fn publish_stats(&self) {
+        let decoded: u64 = self.peers.values().map(|peer| peer.pipeline.frames_decoded()).sum();
+        let concealed: u64 = self.peers.values().map(|peer| peer.pipeline.frames_concealed()).sum();
+        self.frames_decoded.store(self.retired_decoded + decoded, Ordering::Relaxed);
+        self.frames_concealed.store(self.retired_concealed + concealed, Ordering::Relaxed);
+    }
+}
+
+/// Pipeline stages owned by the processing thread
+struct PipelineStages {
+    capture: Option<CapturePath>,
+    playback: Option<PlaybackPath>,
+}
+
 /// Real-time audio processor with lock-free architecture
 pub struct RealTimeAudioProcessor {
     // Audio configuration
@@ -234,7 +814,8 @@ pub struct RealTimeAudioProcessor {
 
     // Control flags
     is_running: Arc<AtomicBool>,
-    processing_thread: Option<JoinHandle<()>>,
+    // Hands the pipeline stages back when it stops
+    processing_thread: Option<JoinHandle<PipelineStages>>,
 
     // Audio devices
     input_device: Option<Device>,
@@ -254,6 +835,22 @@ pub struct RealTimeAudioProcessor {
     // Ring buffer handles for processing thread
     input_consumer: Option<ringbuf::HeapCons<AudioFrame>>,
     output_producer: Option<ringbuf::HeapProd<AudioFrame>>,
+
+    // Capture pipeline and encoded packet queue for the network layer
+    capture_pipeline: Option<CapturePipeline>,
+    encoded_producer: Option<ringbuf::HeapProd<Vec<u8>>>,
+    packets_encoded: Arc<AtomicU64>,
+    encoded_packets_dropped: Arc<AtomicU64>,
+
+    // Playback pipeline and received packet queue from the network layer
+    playback_pipeline: Option<PlaybackPipeline>,
+    received_consumer: Option<ringbuf::HeapCons<ReceivedAudioFrame>>,
+    frames_decoded: Arc<AtomicU64>,
+    frames_concealed: Arc<AtomicU64>,
+
+    // Mixer for the decoded peer streams, controlled through its handle
+    mixer_config: MixerConfig,
+    mixer: MixerHandle,
 }
 
 impl RealTimeAudioProcessor {
@@ -285,6 +882,16 @@ impl RealTimeAudioProcessor {
             output_overruns: Arc::new(AtomicU64::new(0)),
             input_consumer: None,
             output_producer: None,
+            capture_pipeline: None,
+            encoded_producer: None,
+            packets_encoded: Arc::new(AtomicU64::new(0)),
+            encoded_packets_dropped: Arc::new(AtomicU64::new(0)),
+            playback_pipeline: None,
+            received_consumer: None,
+            frames_decoded: Arc::new(AtomicU64::new(0)),
+            frames_concealed: Arc::new(AtomicU64::new(0)),
+            mixer_config: MixerConfig::default(),
+            mixer: MixerHandle::default(),
         })
     }
 
@@ -306,6 +913,111 @@ impl RealTimeAudioProcessor {
         &self.config
     }
 
+    /// Install the capture pipeline run by the processing thread.
+    /// Returns the consumer end of the encoded packet queue, which the
+    /// network layer drains and hands to `NetworkManager::send_audio_frame`.
+    pub fn enable_capture_pipeline(&mut self, pipeline: CapturePipeline) -> Result<ringbuf::HeapCons<Vec<u8>>> {
+        if self.is_running.load(Ordering::Relaxed) {
+            return Err(anyhow!("Cannot change capture pipeline while processor is running"));
+        }
+        if pipeline.format() != self.config.format() {
+            return Err(anyhow!("Capture pipeline encodes {}, but audio is captured as {}", pipeline.format(), self.config.format()));
+        }
+
+        let encoded_rb = HeapRb::<Vec<u8>>::new(self.config.buffer_capacity_multiplier);
+        let (encoded_producer, encoded_consumer) = encoded_rb.split();
+
+        self.capture_pipeline = Some(pipeline);
+        self.encoded_producer = Some(encoded_producer);
+
+        info!("Capture pipeline enabled");
+        Ok(encoded_consumer)
+    }
+
+    /// Install the playback pipeline run by the processing thread.
+    /// Returns the producer end of the received packet queue, which the
+    /// network layer fills from `NetworkManager::receive_audio_frame`.
+    pub fn enable_playback_pipeline(&mut self, pipeline: PlaybackPipeline) -> Result<ringbuf::HeapProd<ReceivedAudioFrame>> {
+        if self.is_running.load(Ordering::Relaxed) {
+            return Err(anyhow!("Cannot change playback pipeline while processor is running"));
+        }
+        if pipeline.format() != self.config.format() {
+            return Err(anyhow!("Playback pipeline decodes {}, but audio is played as {}", pipeline.format(), self.config.format()));
+        }
+
+        let received_rb = HeapRb::<ReceivedAudioFrame>::new(self.config.buffer_capacity_multiplier);
+        let (received_producer, received_consumer) = received_rb.split();
+
+        self.playback_pipeline = Some(pipeline);
+        self.received_consumer = Some(received_consumer);
+
+        info!("Playback pipeline enabled");
+        Ok(received_producer)
+    }
+
+    /// Swap in pipelines for another codec or format, such as the one a call negotiated,
+    /// keeping the queues the network layer holds. Devices are reopened at the new format
+    /// and processing restarts if it was running.
+    pub fn replace_pipelines(&mut self, capture: CapturePipeline, playback: PlaybackPipeline) -> Result<()> {
+        let format = capture.format();
+        if playback.format() != format {
+            return Err(anyhow!("Capture pipeline encodes {}, but playback pipeline decodes {}", format, playback.format()));
+        }
+        let config = self.config.clone().with_format(format);
+        config.validate()?;
+
+        let was_running = self.is_running.load(Ordering::Relaxed);
+        if was_running {
+            self.stop()?;
+        }
+
+        self.config = config;
+        if self.input_stream.is_some() || self.output_stream.is_some() {
+            self.initialize()?;
+        }
+
+        // Only stages whose queues were handed out are installed
+        if self.encoded_producer.is_some() {
+            self.capture_pipeline = Some(capture);
+        }
+        if self.received_consumer.is_some() {
+            self.playback_pipeline = Some(playback);
+        }
+        info!("Audio pipelines replaced: {}", format);
+
+        if was_running {
+            self.start()?;
+        }
+        Ok(())
+    }
+
+    /// Take back the stages of a stopped processing thread
+    fn restore_stages(&mut self, stages: PipelineStages) {
+        if let Some(capture) = stages.capture {
+            self.capture_pipeline = Some(capture.pipeline);
+            self.encoded_producer = Some(capture.encoded_producer);
+        }
+        if let Some(playback) = stages.playback {
+            self.playback_pipeline = Some(playback.template);
+            self.received_consumer = Some(playback.received_consumer);
+        }
+    }
+
+    /// Configure the mixer used for multi-party playback
+    pub fn set_mixer_config(&mut self, config: MixerConfig) -> Result<()> {
+        config.validate()?;
+        if self.is_running.load(Ordering::Relaxed) {
+            return Err(anyhow!("Cannot change mixer configuration while processor is running"));
+        }
+        self.mixer_config = config;
+        Ok(())
+    }
+
+    /// Handle for per-participant gain, mute and speaking levels
+    pub fn mixer(&self) -> MixerHandle {
+        self.mixer.clone()
+    }
+
     /// Initialize audio devices and streams
     pub fn initialize(&mut self) -> Result<()> {
         info!("Initializing audio devices");
@@ -322,17 +1034,19 @@ impl RealTimeAudioProcessor {
         info!("Output device: {}", output_device.name().unwrap_or("Unknown".to_string()));
 
         // Configure audio streams
+        let format = self.config.format();
         let config = StreamConfig {
-            channels: CHANNELS,
-            sample_rate: SampleRate(SAMPLE_RATE),
-            buffer_size: BufferSize::Fixed(FRAME_SIZE_SAMPLES as u32),
+            channels: format.channels,
+            sample_rate: SampleRate(format.sample_rate),
+            // cpal buffer sizes are in frames (samples per channel)
+            buffer_size: BufferSize::Fixed(format.frame_samples_per_channel() as u32),
         };
 
-        // Create lock-free ring buffers
-        let input_rb = HeapRb::<AudioFrame>::new(RING_BUFFER_CAPACITY);
+        // Create lock-free ring buffers, sized in frames
+        let input_rb = HeapRb::<AudioFrame>::new(self.config.buffer_capacity_multiplier);
         let (input_producer, input_consumer) = input_rb.split();
 
-        let output_rb = HeapRb::<AudioFrame>::new(RING_BUFFER_CAPACITY);
+        let output_rb = HeapRb::<AudioFrame>::new(self.config.buffer_capacity_multiplier);
         let (output_producer, output_consumer) = output_rb.split();
 
         // Store the consumer/producer for processing thread
@@ -346,10 +1060,11 @@ impl RealTimeAudioProcessor {
 
         // Create input stream with owned producer
         let mut input_producer = input_producer; // Make mutable
+        let mut assembler = FrameAssembler::new(format);
         let input_stream = input_device.build_input_stream(
             &config,
             move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                Self::input_callback(data, &mut input_producer, &frames_processed_clone, &last_input_time_clone);
+                Self::input_callback(data, &mut assembler, &mut input_producer, &frames_processed_clone, &last_input_time_clone);
             },
             |err| {
                 error!("Audio input stream error: {}", err);
@@ -359,10 +1074,11 @@ impl RealTimeAudioProcessor {
 
         // Create output stream with owned consumer
         let mut output_consumer = output_consumer; // Make mutable
+        let mut splitter = FrameSplitter::new();
         let output_stream = output_device.build_output_stream(
             &config,
             move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                Self::output_callback(data, &mut output_consumer, &last_output_time_clone);
+                Self::output_callback(data, &mut splitter, &mut output_consumer, &last_output_time_clone);
             },
             |err| {
                 error!("Audio output stream error: {}", err);
@@ -411,6 +1127,33 @@ impl RealTimeAudioProcessor {
         let output_overruns = Arc::clone(&self.output_overruns);
         let frames_processed = Arc::clone(&self.frames_processed);
 
+        // Without capture/playback pipelines the processing thread loops input back to output
+        let capture = match (self.capture_pipeline.take(), self.encoded_producer.take()) {
+            (Some(pipeline), Some(encoded_producer)) => Some(CapturePath {
+                pipeline,
+                encoded_producer,
+                packets_encoded: Arc::clone(&self.packets_encoded),
+                packets_dropped: Arc::clone(&self.encoded_packets_dropped),
+            }),
+            _ => None,
+        };
+        let playback = match (self.playback_pipeline.take(), self.received_consumer.take()) {
+            (Some(template), Some(received_consumer)) => Some(PlaybackPath {
+                template,
+                peers: HashMap::new(),
+                mixer: AudioMixer::with_handle(self.mixer_config, self.mixer.clone())?,
+                decoded: Vec::new(),
+                received_consumer,
+                // Counts carry over from before a restart
+                retired_decoded: self.frames_decoded.load(Ordering::Relaxed),
+                retired_concealed: self.frames_concealed.load(Ordering::Relaxed),
+                frames_decoded: Arc::clone(&self.frames_decoded),
+                frames_concealed: Arc::clone(&self.frames_concealed),
+            }),
+            _ => None,
+        };
+        let stages = PipelineStages { capture, playback };
+
         let processing_thread = thread::spawn(move || {
             // Set real-time thread priority for low-latency audio processing
             Self::set_realtime_priority();
@@ -423,7 +1166,8 @@ impl RealTimeAudioProcessor {
                 input_underruns,
                 output_overruns,
                 frames_processed,
-            );
+                stages,
+            )
         });
 
         self.processing_thread = Some(processing_thread);
@@ -446,10 +1190,11 @@ impl RealTimeAudioProcessor {
             output_stream.pause()?;
         }
 
-        // Wait for processing thread to finish
+        // Wait for processing thread to finish, keeping its stages for a restart
         if let Some(thread) = self.processing_thread.take() {
-            if let Err(e) = thread.join() {
-                error!("Error joining processing thread: {:?}", e);
+            match thread.join() {
+                Ok(stages) => self.restore_stages(stages),
+                Err(e) => error!("Error joining processing thread: {:?}", e),
             }
         }
 
@@ -460,11 +1205,11 @@ impl RealTimeAudioProcessor {
     /// Audio input callback - runs in real-time audio thread
     fn input_callback(
         data: &[f32],
+        assembler: &mut FrameAssembler,
         producer: &mut ringbuf::HeapProd<AudioFrame>,
         frames_processed: &std::sync::atomic::AtomicU64,
         last_input_time: &std::sync::atomic::AtomicU64,
     ) {
-        let mut frame = AudioFrame::empty();
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
@@ -472,22 +1217,23 @@ impl RealTimeAudioProcessor {
 
         last_input_time.store(now, Ordering::Relaxed);
 
-        // Copy audio data to frame (with bounds checking)
-        let copy_len = std::cmp::min(data.len(), frame.samples.len());
-        frame.samples[..copy_len].copy_from_slice(&data[..copy_len]);
-        frame.timestamp = now;
-        frame.sequence = frames_processed.fetch_add(1, Ordering::Relaxed) as u32;
+        // Split callback data into codec-sized frames; a partial frame waits for the next callback
+        assembler.push(data, |mut frame| {
+            frame.timestamp = now;
+            frame.sequence = frames_processed.fetch_add(1, Ordering::Relaxed) as u32;
 
-        // Try to push frame to ring buffer (non-blocking)
-        if producer.try_push(frame).is_err() {
-            // Buffer full - this indicates processing can't keep up
-            // In production, we might want to drop frames or implement backpressure
-        }
+            // Try to push frame to ring buffer (non-blocking)
+            if producer.try_push(frame).is_err() {
+                // Buffer full - this indicates processing can't keep up
+                // In production, we might want to drop frames or implement backpressure
+            }
+        });
     }
 
     /// Audio output callback - runs in real-time audio thread
     fn output_callback(
         data: &mut [f32],
+        splitter: &mut FrameSplitter,
         consumer: &mut ringbuf::HeapCons<AudioFrame>,
         last_output_time: &std::sync::atomic::AtomicU64,
     ) {
@@ -498,15 +1244,9 @@ impl RealTimeAudioProcessor {
 
         last_output_time.store(now, Ordering::Relaxed);
 
-        // Try to get frame from ring buffer
-        if let Some(frame) = consumer.try_pop() {
-            // Copy frame data to output buffer
-            let copy_len = std::cmp::min(data.len(), frame.samples.len());
-            data[..copy_len].copy_from_slice(&frame.samples[..copy_len]);
-        } else {
-            // No frame available - output silence
-            data.fill(0.0);
-        }
+        // Spread frames across callbacks; a frame's remainder plays at the start of the next
+        // callback, and silence fills in when the ring buffer runs dry
+        splitter.fill(data, || consumer.try_pop());
     }
 
     /// Set real-time scheduling priority for audio thread
@@ -578,7 +1318,8 @@ impl RealTimeAudioProcessor {
         input_underruns: Arc<AtomicU64>,
         output_overruns: Arc<AtomicU64>,
         frames_processed: Arc<AtomicU64>,
-    ) {
+        mut stages: PipelineStages,
+    ) -> PipelineStages {
         info!("Audio processing loop started");
 
         let mut sequence_counter = 0u32;
@@ -600,15 +1341,23 @@ impl RealTimeAudioProcessor {
                 input_frame.sequence = sequence_counter;
                 sequence_counter = sequence_counter.wrapping_add(1);
 
-                // 2. Audio processing stages (currently pass-through):
-                //    - Noise suppression (implemented in separate module)
-                //    - Echo cancellation (implemented in separate module)
-                //    - Audio compression/encoding (for network transmission)
-                //    - Network packet preparation (handled by network layer)
-
-                // 3. Push processed frame to output
-                if output_producer.try_push(input_frame).is_err() {
-                    // Output buffer full - track overrun
+                // 2. Capture pipeline: noise suppression, echo cancellation, Opus encoding
+                if let Some(capture) = stages.capture.as_mut() {
+                    match capture.pipeline.process(&mut input_frame) {
+                        Ok(packet) => {
+                            // 3. Queue encoded packet for the network layer
+                            if capture.encoded_producer.try_push(packet).is_err() {
+                                capture.packets_dropped.fetch_add(1, Ordering::Relaxed);
+                            } else {
+                                capture.packets_encoded.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                        Err(e) => {
+                            warn!("Capture pipeline failed for frame {}: {}", input_frame.sequence, e);
+                        }
+                    }
+                } else if stages.playback.is_none() && output_producer.try_push(input_frame).is_err() {
+                    // No pipelines: loop back to output. Output buffer full - track overrun
                     output_overruns.fetch_add(1, Ordering::Relaxed);
                 }
 
@@ -616,11 +1365,43 @@ impl RealTimeAudioProcessor {
                 frames_processed.fetch_add(1, Ordering::Relaxed);
             }
 
+            // Playback: received packets -> per-peer jitter buffer -> decode -> mix -> output
+            if let Some(playback) = stages.playback.as_mut() {
+                while let Some(received) = playback.received_consumer.try_pop() {
+                    let (peer, sequence_number) = (received.peer, received.sequence_number);
+                    if let Err(e) = playback.push(received) {
+                        warn!("Failed to queue received packet {} from {}: {}", sequence_number, peer, e);
+                    }
+                }
+                playback.prune_idle_peers();
+
+                // Keep a short playout queue ahead of the output callback
+                while output_producer.occupied_len() < PLAYOUT_QUEUE_FRAMES {
+                    let far_end_frame = match playback.next_mixed_frame() {
+                        Some(frame) => frame,
+                        None => break,
+                    };
+
+                    // Tap the mixed far-end signal as the echo cancellation reference
+                    if let Some(capture) = stages.capture.as_mut() {
+                        capture.pipeline.set_far_end_reference(&far_end_frame);
+                    }
+
+                    if output_producer.try_push(far_end_frame).is_err() {
+                        output_overruns.fetch_add(1, Ordering::Relaxed);
+                        break;
+                    }
+                }
+
+                playback.publish_stats();
+            }
+
             // Small sleep to prevent busy waiting
             thread::sleep(Duration::from_micros(100));
         }
 
         info!("Audio processing loop stopped");
+        stages
     }
 
     /// Get audio processing statistics
@@ -634,6 +1415,10 @@ impl RealTimeAudioProcessor {
             is_running: self.is_running.load(Ordering::Relaxed),
             input_underruns: self.input_underruns.load(Ordering::Relaxed),
             output_overruns: self.output_overruns.load(Ordering::Relaxed),
+            packets_encoded: self.packets_encoded.load(Ordering::Relaxed),
+            encoded_packets_dropped: self.encoded_packets_dropped.load(Ordering::Relaxed),
+            frames_decoded: self.frames_decoded.load(Ordering::Relaxed),
+            frames_concealed: self.frames_concealed.load(Ordering::Relaxed),
         }
     }
 
@@ -784,6 +1569,10 @@ pub struct AudioStats {
     pub is_running: bool,
     pub input_underruns: u64,
     pub output_overruns: u64,
+    pub packets_encoded: u64,
+    pub encoded_packets_dropped: u64,
+    pub frames_decoded: u64,
+    pub frames_concealed: u64,
 }
 
 impl AudioStats {
@@ -0,0 +1,271 @@
+use anyhow::{Result, anyhow};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::codec::{CodecKind, VoiceCodec};
+use crate::opus_codec::OpusConfig;
+use crate::realtime_audio::AudioFrame;
+use crate::wire::{Reader, WireError};
+
+/// Payload type of Opus blocks (the customary dynamic RTP payload type); see `CodecKind::payload_type`
+pub const PAYLOAD_TYPE_OPUS: u8 = 111;
+
+/// Most earlier frames a single packet can repeat
+pub const MAX_REDUNDANCY_DEPTH: usize = 4;
+
+/// Header bit marking a redundant block, i.e. that another header follows
+const FOLLOW_BIT: u8 = 0x80;
+/// Redundant block headers hold a 14-bit offset and a 10-bit length
+const MAX_OFFSET: u16 = 0x3FFF;
+const MAX_BLOCK_LEN: usize = 0x3FF;
+
+/// When packets repeat earlier frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedundancyMode {
+    /// Primary frame only
+    #[default]
+    Off,
+    /// Switched on by the congestion controller while reported loss is high
+    Auto,
+    /// Every packet repeats the previous frames
+    Always,
+}
+
+/// Sender-side redundancy settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RedundancyConfig {
+    pub mode: RedundancyMode,
+    /// How many previous frames each packet repeats
+    pub depth: usize,
+    /// Bitrate of the repeated copies; `None` repeats the primary encoding as is
+    pub bitrate: Option<u32>,
+}
+
+impl Default for RedundancyConfig {
+    fn default() -> Self {
+        Self {
+            mode: RedundancyMode::Off,
+            depth: 2,
+            bitrate: Some(24000),
+        }
+    }
+}
+
+impl RedundancyConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=MAX_REDUNDANCY_DEPTH).contains(&self.depth) {
+            return Err(anyhow!("Redundancy depth must be between 1 and {}", MAX_REDUNDANCY_DEPTH));
+        }
+        if let Some(bitrate) = self.bitrate
+            && !(6000..=512000).contains(&bitrate) {
+            return Err(anyhow!("Redundant bitrate must be between 6000 and 512000 bps"));
+        }
+        Ok(())
+    }
+}
+
+/// An earlier frame repeated inside a later packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedundantBlock {
+    pub payload_type: u8,
+    /// How many frames before the packet's own frame this one was
+    pub offset: u16,
+    pub data: Vec<u8>,
+}
+
+/// Audio payload carrying the current frame plus copies of earlier ones (RFC 2198)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedundantPayload {
+    /// Oldest first
+    pub redundant: Vec<RedundantBlock>,
+    pub payload_type: u8,
+    pub primary: Vec<u8>,
+}
+
+impl RedundantPayload {
+    /// Payload with no repeated frames
+    pub fn primary(payload_type: u8, primary: Vec<u8>) -> Self {
+        Self { redundant: Vec::new(), payload_type, primary }
+    }
+
+    /// Layout as in RFC 2198: per redundant block a 4-byte header F=1, payload type (7),
+    /// offset (14), length (10); then a 1-byte header F=0, payload type (7) for the primary;
+    /// then the blocks' data in header order. Offsets count frames rather than RTP
+    /// timestamp units, since our packets carry a frame number.
+    pub fn encode(&self) -> Result<Vec<u8>, WireError> {
+        let redundant_len: usize = self.redundant.iter().map(|block| 4 + block.data.len()).sum();
+        let mut payload = Vec::with_capacity(redundant_len + 1 + self.primary.len());
+
+        for block in &self.redundant {
+            check_payload_type(block.payload_type)?;
+            if block.offset == 0 || block.offset > MAX_OFFSET {
+                return Err(WireError::InvalidBody("redundant block offset out of range"));
+            }
+            if block.data.len() > MAX_BLOCK_LEN {
+                return Err(WireError::BodyTooLarge(block.data.len()));
+            }
+            let offset_and_length = (block.offset as u32) << 10 | block.data.len() as u32;
+            payload.push(FOLLOW_BIT | block.payload_type);
+            payload.extend_from_slice(&offset_and_length.to_be_bytes()[1..]);
+        }
+
+        check_payload_type(self.payload_type)?;
+        payload.push(self.payload_type);
+        for block in &self.redundant {
+            payload.extend_from_slice(&block.data);
+        }
+        payload.extend_from_slice(&self.primary);
+        Ok(payload)
+    }
+
+    pub fn decode(payload: &[u8]) -> Result<Self, WireError> {
+        let mut reader = Reader::new(payload);
+        let mut headers = Vec::new();
+        let payload_type = loop {
+            let [header] = reader.array::<1>()?;
+            if header & FOLLOW_BIT == 0 {
+                break header;
+            }
+            if headers.len() == MAX_REDUNDANCY_DEPTH {
+                return Err(WireError::InvalidBody("too many redundant blocks"));
+            }
+            let [high, middle, low] = reader.array::<3>()?;
+            let offset_and_length = u32::from_be_bytes([0, high, middle, low]);
+            let offset = (offset_and_length >> 10) as u16;
+            if offset == 0 {
+                return Err(WireError::InvalidBody("redundant block offset out of range"));
+            }
+            headers.push((header & !FOLLOW_BIT, offset, (offset_and_length & 0x3FF) as usize));
+        };
+
+        let mut redundant = Vec::with_capacity(headers.len());
+        for (block_type, offset, len) in headers {
+            redundant.push(RedundantBlock { payload_type: block_type, offset, data: reader.take(len)?.to_vec() });
+        }
+        let primary = reader.rest().to_vec();
+        if primary.is_empty() {
+            return Err(WireError::InvalidBody("missing primary block"));
+        }
+
+        Ok(Self { redundant, payload_type, primary })
+    }
+}
+
+fn check_payload_type(payload_type: u8) -> Result<(), WireError> {
+    if payload_type & FOLLOW_BIT != 0 {
+        return Err(WireError::InvalidBody("payload type must fit in 7 bits"));
+    }
+    Ok(())
+}
+
+/// Packs each encoded frame with copies of the frames before it, so a receiver can
+/// rebuild a burst of up to `depth` lost packets from the next one that arrives.
+/// Assumes every packet it produces is sent, since offsets are relative frame counts.
+pub struct RedundancyEncoder {
+    config: RedundancyConfig,
+    active: bool,
+    // Payload type of the primary and repeated blocks
+    payload_type: u8,
+    // Second encoder for lower-bitrate copies; `None` repeats the primary bytes
+    encoder: Option<Box<dyn VoiceCodec>>,
+    // Copies of the most recent frames, newest last
+    history: VecDeque<Vec<u8>>,
+    packets_with_redundancy: u64,
+}
+
+impl RedundancyEncoder {
+    /// Redundancy for `codec` packets; only codecs with an adjustable bitrate get cheaper copies
+    pub fn new(config: RedundancyConfig, codec: CodecKind, opus: &OpusConfig) -> Result<Self> {
+        config.validate()?;
+        let encoder = match (config.mode, config.bitrate) {
+            (RedundancyMode::Off, _) | (_, None) => None,
+            _ if !codec.has_adjustable_bitrate() => None,
+            (_, Some(bitrate)) => Some(codec.create(&OpusConfig {
+                bitrate: bitrate.min(opus.bitrate),
+                ..opus.clone()
+            })?),
+        };
+
+        Ok(Self {
+            active: config.mode == RedundancyMode::Always,
+            config,
+            payload_type: codec.payload_type(),
+            encoder,
+            history: VecDeque::with_capacity(config.depth + 1),
+            packets_with_redundancy: 0,
+        })
+    }
+
+    pub fn config(&self) -> &RedundancyConfig {
+        &self.config
+    }
+
+    /// Follow the congestion controller's call; only `Auto` mode listens
+    pub fn set_active(&mut self, active: bool) {
+        if self.config.mode != RedundancyMode::Auto || active == self.active {
+            return;
+        }
+        info!("Audio redundancy {}", if active { "on" } else { "off" });
+        self.active = active;
+        if !active {
+            self.history.clear();
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Keep the copies' bitrate below the primary's once congestion control lowers it
+    pub fn limit_bitrate(&mut self, primary_bitrate: u32) -> Result<()> {
+        if let Some(encoder) = self.encoder.as_mut()
+            && let Some(bitrate) = self.config.bitrate {
+            let bitrate = bitrate.min(primary_bitrate);
+            if bitrate != encoder.bitrate() {
+                encoder.set_bitrate(bitrate)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Frame `primary`, the encoding of `frame`, adding copies of earlier frames while active
+    pub fn packetize(&mut self, frame: &AudioFrame, primary: Vec<u8>) -> Result<Vec<u8>> {
+        let mut payload = RedundantPayload::primary(self.payload_type, primary);
+        if !self.active {
+            return Ok(payload.encode()?);
+        }
+
+        let newest = self.history.len();
+        payload.redundant = self.history.iter().enumerate()
+            .filter(|(_, data)| data.len() <= MAX_BLOCK_LEN)
+            .map(|(index, data)| RedundantBlock {
+                payload_type: self.payload_type,
+                offset: (newest - index) as u16,
+                data: data.clone(),
+            })
+            .collect();
+        if !payload.redundant.is_empty() {
+            self.packets_with_redundancy += 1;
+        }
+
+        let copy = match self.encoder.as_mut() {
+            Some(encoder) => encoder.encode(frame)?,
+            None => payload.primary.clone(),
+        };
+        self.history.push_back(copy);
+        if self.history.len() > self.config.depth {
+            self.history.pop_front();
+        }
+
+        let encoded = payload.encode()?;
+        debug!("Packet carries {} redundant frames, {} bytes", payload.redundant.len(), encoded.len());
+        Ok(encoded)
+    }
+
+    /// Number of packets sent with at least one earlier frame
+    pub fn packets_with_redundancy(&self) -> u64 {
+        self.packets_with_redundancy
+    }
+}
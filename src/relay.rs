@@ -0,0 +1,1069 @@
+use anyhow::{Result, anyhow};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, aead::{Aead, KeyInit, Payload}};
+use ed25519_dalek::{Signature, Signer, Verifier, VerifyingKey};
+use hkdf::Hkdf;
+use log::{debug, info, warn};
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use x25519_dalek::{EphemeralSecret, PublicKey as X25519PublicKey, ReusableSecret};
+
+use crate::discovery::RoomInfo;
+use crate::security::{ReplayWindow, SecurityConfig};
+use crate::wire::{Reader, WireError};
+
+/// Current relay protocol version
+pub const RELAY_WIRE_VERSION: u8 = 1;
+
+/// Header: version (1) + message type (1) + body length (2), as in [`crate::wire`]
+const HEADER_LEN: usize = 4;
+
+/// Default UDP port for `humr-relay`
+pub const DEFAULT_RELAY_PORT: u16 = 8090;
+
+/// Room capacity when no `RoomInfo` was registered, matching `RoomInfo::max_participants`
+pub const DEFAULT_MAX_PARTICIPANTS: u8 = 10;
+
+/// Participants that send nothing for this long are dropped from the roster
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a join challenge stays valid
+const CHALLENGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outstanding challenges kept before new join requests are ignored
+const MAX_PENDING_CHALLENGES: usize = 1024;
+
+/// Interval between join retransmissions on the client
+const JOIN_RETRANSMIT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long a client waits for the relay to admit it
+const JOIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Domain separation for join signatures
+const JOIN_LABEL: &[u8] = b"HUMR_RELAY_JOIN_V1";
+
+/// Domain separation for sender key delivery
+const SENDER_KEY_LABEL: &[u8] = b"HUMR_SENDER_KEY_V1";
+
+const SIGNATURE_LEN: usize = 64;
+const PUBLIC_KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Relay message type byte carried in every packet header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RelayMessageType {
+    JoinRequest = 0x20,
+    Challenge = 0x21,
+    Join = 0x22,
+    JoinAccepted = 0x23,
+    JoinRejected = 0x24,
+    ParticipantJoined = 0x25,
+    ParticipantLeft = 0x26,
+    SenderKey = 0x27,
+    KeyRequest = 0x28,
+    Media = 0x29,
+    Leave = 0x2A,
+}
+
+impl RelayMessageType {
+    /// Type byte used for a given message
+    pub fn of(message: &RelayMessage) -> Self {
+        match message {
+            RelayMessage::JoinRequest { .. } => RelayMessageType::JoinRequest,
+            RelayMessage::Challenge { .. } => RelayMessageType::Challenge,
+            RelayMessage::Join { .. } => RelayMessageType::Join,
+            RelayMessage::JoinAccepted { .. } => RelayMessageType::JoinAccepted,
+            RelayMessage::JoinRejected { .. } => RelayMessageType::JoinRejected,
+            RelayMessage::ParticipantJoined { .. } => RelayMessageType::ParticipantJoined,
+            RelayMessage::ParticipantLeft { .. } => RelayMessageType::ParticipantLeft,
+            RelayMessage::SenderKey { .. } => RelayMessageType::SenderKey,
+            RelayMessage::KeyRequest { .. } => RelayMessageType::KeyRequest,
+            RelayMessage::Media { .. } => RelayMessageType::Media,
+            RelayMessage::Leave => RelayMessageType::Leave,
+        }
+    }
+}
+
+impl TryFrom<u8> for RelayMessageType {
+    type Error = WireError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x20 => Ok(RelayMessageType::JoinRequest),
+            0x21 => Ok(RelayMessageType::Challenge),
+            0x22 => Ok(RelayMessageType::Join),
+            0x23 => Ok(RelayMessageType::JoinAccepted),
+            0x24 => Ok(RelayMessageType::JoinRejected),
+            0x25 => Ok(RelayMessageType::ParticipantJoined),
+            0x26 => Ok(RelayMessageType::ParticipantLeft),
+            0x27 => Ok(RelayMessageType::SenderKey),
+            0x28 => Ok(RelayMessageType::KeyRequest),
+            0x29 => Ok(RelayMessageType::Media),
+            0x2A => Ok(RelayMessageType::Leave),
+            other => Err(WireError::UnknownMessageType(other)),
+        }
+    }
+}
+
+/// Room member as announced by the relay.
+/// The join signature lets other members check the agreement key belongs to the identity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Participant {
+    pub id: u32,
+    pub identity_public_key: [u8; 32],
+    /// X25519 key that sender keys are sealed to
+    pub agreement_public_key: [u8; 32],
+    pub join_nonce: [u8; 32],
+    pub join_signature: [u8; 64],
+}
+
+impl Participant {
+    /// Check the join signature over the room, both keys and the relay's challenge
+    pub fn verify(&self, room_id: &str) -> Result<VerifyingKey> {
+        let identity = VerifyingKey::from_bytes(&self.identity_public_key)
+            .map_err(|_| anyhow!("Invalid participant identity key"))?;
+        let digest = join_digest(room_id, &self.identity_public_key, &self.agreement_public_key, &self.join_nonce);
+        identity.verify(&digest, &Signature::from_bytes(&self.join_signature))
+            .map_err(|_| anyhow!("Invalid join signature for participant {}", self.id))?;
+        Ok(identity)
+    }
+}
+
+/// Messages exchanged between relay clients and `humr-relay`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelayMessage {
+    /// Client asks for a challenge to join a room
+    JoinRequest { room_id: String },
+    /// Relay nonce the client must sign
+    Challenge { nonce: [u8; 32] },
+    /// Signed join answering a challenge
+    Join {
+        room_id: String,
+        identity_public_key: [u8; 32],
+        agreement_public_key: [u8; 32],
+        nonce: [u8; 32],
+        signature: [u8; 64],
+    },
+    JoinAccepted { participant_id: u32, max_participants: u8 },
+    JoinRejected { reason: String },
+    ParticipantJoined { participant: Participant },
+    ParticipantLeft { participant_id: u32 },
+    /// Sender key sealed to one recipient; the relay only routes it
+    SenderKey {
+        from: u32,
+        to: u32,
+        ephemeral_public_key: [u8; 32],
+        ciphertext: Vec<u8>,
+        signature: [u8; 64],
+    },
+    /// Ask a member to resend its current sender key
+    KeyRequest { from: u32, to: u32 },
+    /// Opus packet encrypted under the sender's key, forwarded as-is
+    Media {
+        sender: u32,
+        key_id: u32,
+        sequence: u64,
+        nonce: [u8; 12],
+        ciphertext: Vec<u8>,
+    },
+    Leave,
+}
+
+/// Encode a relay message into a versioned, length-prefixed packet.
+///
+/// Layout (all integers big-endian, strings as length (2) + UTF-8):
+/// - `JoinRequest`: room id
+/// - `Challenge`: nonce (32)
+/// - `Join`: room id, identity key (32), agreement key (32), nonce (32), signature (64)
+/// - `JoinAccepted`: participant id (4), max participants (1)
+/// - `JoinRejected`: reason
+/// - `ParticipantJoined`: id (4), identity key (32), agreement key (32), join nonce (32), join signature (64)
+/// - `ParticipantLeft`: participant id (4)
+/// - `SenderKey`: from (4), to (4), ephemeral key (32), signature (64), ciphertext (rest of body)
+/// - `KeyRequest`: from (4), to (4)
+/// - `Media`: sender (4), key id (4), sequence (8), nonce (12), ciphertext (rest of body)
+/// - `Leave`: empty
+pub fn encode_relay_message(message: &RelayMessage) -> Result<Vec<u8>, WireError> {
+    let mut body = Vec::new();
+
+    match message {
+        RelayMessage::JoinRequest { room_id } => put_string(&mut body, room_id)?,
+        RelayMessage::Challenge { nonce } => body.extend_from_slice(nonce),
+        RelayMessage::Join { room_id, identity_public_key, agreement_public_key, nonce, signature } => {
+            put_string(&mut body, room_id)?;
+            body.extend_from_slice(identity_public_key);
+            body.extend_from_slice(agreement_public_key);
+            body.extend_from_slice(nonce);
+            body.extend_from_slice(signature);
+        }
+        RelayMessage::JoinAccepted { participant_id, max_participants } => {
+            body.extend_from_slice(&participant_id.to_be_bytes());
+            body.push(*max_participants);
+        }
+        RelayMessage::JoinRejected { reason } => put_string(&mut body, reason)?,
+        RelayMessage::ParticipantJoined { participant } => {
+            body.extend_from_slice(&participant.id.to_be_bytes());
+            body.extend_from_slice(&participant.identity_public_key);
+            body.extend_from_slice(&participant.agreement_public_key);
+            body.extend_from_slice(&participant.join_nonce);
+            body.extend_from_slice(&participant.join_signature);
+        }
+        RelayMessage::ParticipantLeft { participant_id } => body.extend_from_slice(&participant_id.to_be_bytes()),
+        RelayMessage::SenderKey { from, to, ephemeral_public_key, ciphertext, signature } => {
+            body.extend_from_slice(&from.to_be_bytes());
+            body.extend_from_slice(&to.to_be_bytes());
+            body.extend_from_slice(ephemeral_public_key);
+            body.extend_from_slice(signature);
+            body.extend_from_slice(ciphertext);
+        }
+        RelayMessage::KeyRequest { from, to } => {
+            body.extend_from_slice(&from.to_be_bytes());
+            body.extend_from_slice(&to.to_be_bytes());
+        }
+        RelayMessage::Media { sender, key_id, sequence, nonce, ciphertext } => {
+            body.reserve(16 + NONCE_LEN + ciphertext.len());
+            body.extend_from_slice(&media_header(*sender, *key_id, *sequence));
+            body.extend_from_slice(nonce);
+            body.extend_from_slice(ciphertext);
+        }
+        RelayMessage::Leave => {}
+    }
+
+    let body_len = u16::try_from(body.len())
+        .map_err(|_| WireError::BodyTooLarge(body.len()))?;
+
+    let mut packet = Vec::with_capacity(HEADER_LEN + body.len());
+    packet.push(RELAY_WIRE_VERSION);
+    packet.push(RelayMessageType::of(message) as u8);
+    packet.extend_from_slice(&body_len.to_be_bytes());
+    packet.extend_from_slice(&body);
+    Ok(packet)
+}
+
+/// Decode a packet produced by [`encode_relay_message`]
+pub fn decode_relay_message(packet: &[u8]) -> Result<RelayMessage, WireError> {
+    if packet.len() < HEADER_LEN {
+        return Err(WireError::Truncated { needed: HEADER_LEN, available: packet.len() });
+    }
+    if packet[0] != RELAY_WIRE_VERSION {
+        return Err(WireError::UnsupportedVersion(packet[0]));
+    }
+
+    let message_type = RelayMessageType::try_from(packet[1])?;
+    let declared = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+    let body = &packet[HEADER_LEN..];
+    if body.len() != declared {
+        return Err(WireError::LengthMismatch { declared, actual: body.len() });
+    }
+
+    let mut reader = Reader::new(body);
+    let message = match message_type {
+        RelayMessageType::JoinRequest => RelayMessage::JoinRequest { room_id: read_string(&mut reader)? },
+        RelayMessageType::Challenge => RelayMessage::Challenge { nonce: reader.array::<32>()? },
+        RelayMessageType::Join => RelayMessage::Join {
+            room_id: read_string(&mut reader)?,
+            identity_public_key: reader.array::<PUBLIC_KEY_LEN>()?,
+            agreement_public_key: reader.array::<PUBLIC_KEY_LEN>()?,
+            nonce: reader.array::<32>()?,
+            signature: reader.array::<SIGNATURE_LEN>()?,
+        },
+        RelayMessageType::JoinAccepted => RelayMessage::JoinAccepted {
+            participant_id: reader.u32()?,
+            max_participants: reader.array::<1>()?[0],
+        },
+        RelayMessageType::JoinRejected => RelayMessage::JoinRejected { reason: read_string(&mut reader)? },
+        RelayMessageType::ParticipantJoined => RelayMessage::ParticipantJoined {
+            participant: Participant {
+                id: reader.u32()?,
+                identity_public_key: reader.array::<PUBLIC_KEY_LEN>()?,
+                agreement_public_key: reader.array::<PUBLIC_KEY_LEN>()?,
+                join_nonce: reader.array::<32>()?,
+                join_signature: reader.array::<SIGNATURE_LEN>()?,
+            },
+        },
+        RelayMessageType::ParticipantLeft => RelayMessage::ParticipantLeft { participant_id: reader.u32()? },
+        RelayMessageType::SenderKey => {
+            let from = reader.u32()?;
+            let to = reader.u32()?;
+            let ephemeral_public_key = reader.array::<PUBLIC_KEY_LEN>()?;
+            let signature = reader.array::<SIGNATURE_LEN>()?;
+            let ciphertext = reader.rest().to_vec();
+            if ciphertext.len() < TAG_LEN {
+                return Err(WireError::InvalidBody("ciphertext shorter than authentication tag"));
+            }
+            RelayMessage::SenderKey { from, to, ephemeral_public_key, ciphertext, signature }
+        }
+        RelayMessageType::KeyRequest => RelayMessage::KeyRequest { from: reader.u32()?, to: reader.u32()? },
+        RelayMessageType::Media => {
+            let sender = reader.u32()?;
+            let key_id = reader.u32()?;
+            let sequence = reader.u64()?;
+            let nonce = reader.array::<NONCE_LEN>()?;
+            let ciphertext = reader.rest().to_vec();
+            if ciphertext.len() < TAG_LEN {
+                return Err(WireError::InvalidBody("ciphertext shorter than authentication tag"));
+            }
+            RelayMessage::Media { sender, key_id, sequence, nonce, ciphertext }
+        }
+        RelayMessageType::Leave => RelayMessage::Leave,
+    };
+
+    if reader.remaining() > 0 {
+        return Err(WireError::TrailingBytes(reader.remaining()));
+    }
+
+    Ok(message)
+}
+
+fn put_string(body: &mut Vec<u8>, value: &str) -> Result<(), WireError> {
+    let len = u16::try_from(value.len())
+        .map_err(|_| WireError::BodyTooLarge(value.len()))?;
+    body.extend_from_slice(&len.to_be_bytes());
+    body.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn read_string(reader: &mut Reader<'_>) -> Result<String, WireError> {
+    let len = reader.u16()? as usize;
+    std::str::from_utf8(reader.take(len)?)
+        .map(str::to_string)
+        .map_err(|_| WireError::InvalidBody("string is not valid UTF-8"))
+}
+
+/// Media fields authenticated alongside the payload
+fn media_header(sender: u32, key_id: u32, sequence: u64) -> [u8; 16] {
+    let mut header = [0u8; 16];
+    header[..4].copy_from_slice(&sender.to_be_bytes());
+    header[4..8].copy_from_slice(&key_id.to_be_bytes());
+    header[8..].copy_from_slice(&sequence.to_be_bytes());
+    header
+}
+
+fn join_digest(room_id: &str, identity: &[u8; 32], agreement: &[u8; 32], nonce: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(JOIN_LABEL);
+    hasher.update((room_id.len() as u16).to_be_bytes());
+    hasher.update(room_id.as_bytes());
+    hasher.update(identity);
+    hasher.update(agreement);
+    hasher.update(nonce);
+    hasher.finalize().into()
+}
+
+fn sender_key_digest(from: &[u8; 32], to: &[u8; 32], ephemeral: &[u8; 32], ciphertext: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(SENDER_KEY_LABEL);
+    hasher.update(from);
+    hasher.update(to);
+    hasher.update(ephemeral);
+    hasher.update(ciphertext);
+    hasher.finalize().into()
+}
+
+/// Cipher for one sender key delivery, bound to both identities and the ephemeral key
+fn sender_key_cipher(shared_secret: &[u8], from: &[u8; 32], to: &[u8; 32], ephemeral: &[u8; 32]) -> Result<ChaCha20Poly1305> {
+    let hkdf = Hkdf::<Sha256>::new(Some(SENDER_KEY_LABEL), shared_secret);
+    let mut key = [0u8; 32];
+    hkdf.expand(&[from.as_slice(), to.as_slice(), ephemeral.as_slice()].concat(), &mut key)
+        .map_err(|_| anyhow!("Sender key derivation failed"))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Relay settings
+#[derive(Debug, Clone)]
+pub struct RelayConfig {
+    /// Capacity of rooms created on first join
+    pub default_max_participants: u8,
+    /// Only admit joins for rooms added with [`RelayServer::register_room`]
+    pub require_registered_rooms: bool,
+    /// Identity keys allowed to join; empty admits any key that passes the challenge
+    pub allowed_identities: Vec<VerifyingKey>,
+    pub idle_timeout: Duration,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            default_max_participants: DEFAULT_MAX_PARTICIPANTS,
+            require_registered_rooms: false,
+            allowed_identities: Vec::new(),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        }
+    }
+}
+
+struct PendingChallenge {
+    room_id: String,
+    nonce: [u8; 32],
+    issued: Instant,
+}
+
+struct RoomMember {
+    participant: Participant,
+    last_seen: Instant,
+}
+
+struct RelayRoom {
+    max_participants: u8,
+    // Registered rooms outlive their last member
+    registered: bool,
+    members: HashMap<SocketAddr, RoomMember>,
+}
+
+impl RelayRoom {
+    fn new(max_participants: u8, registered: bool) -> Self {
+        Self { max_participants, registered, members: HashMap::new() }
+    }
+
+    fn addr_of(&self, participant_id: u32) -> Option<SocketAddr> {
+        self.members.iter()
+            .find(|(_, member)| member.participant.id == participant_id)
+            .map(|(&addr, _)| addr)
+    }
+}
+
+/// Datagrams to send after handling a packet
+type Outgoing = Vec<(SocketAddr, Vec<u8>)>;
+
+#[derive(Default)]
+struct RelayState {
+    rooms: HashMap<String, RelayRoom>,
+    // Room each joined address belongs to
+    memberships: HashMap<SocketAddr, String>,
+    pending: HashMap<SocketAddr, PendingChallenge>,
+    next_participant_id: u32,
+}
+
+impl RelayState {
+    fn handle(&mut self, config: &RelayConfig, addr: SocketAddr, packet: &[u8]) -> Outgoing {
+        let message = match decode_relay_message(packet) {
+            Ok(message) => message,
+            Err(e) => {
+                debug!("Dropping malformed relay packet from {}: {}", addr, e);
+                return Vec::new();
+            }
+        };
+
+        // Joined members refresh their liveness with any packet
+        if let Some(room_id) = self.memberships.get(&addr)
+            && let Some(member) = self.rooms.get_mut(room_id).and_then(|room| room.members.get_mut(&addr)) {
+            member.last_seen = Instant::now();
+        }
+
+        match message {
+            RelayMessage::JoinRequest { room_id } => self.issue_challenge(addr, room_id),
+            RelayMessage::Join { room_id, identity_public_key, agreement_public_key, nonce, signature } => {
+                let participant = Participant {
+                    id: 0,
+                    identity_public_key,
+                    agreement_public_key,
+                    join_nonce: nonce,
+                    join_signature: signature,
+                };
+                self.admit(config, addr, &room_id, participant)
+            }
+            RelayMessage::Media { sender, .. } => self.forward_media(addr, sender, packet),
+            RelayMessage::SenderKey { from, to, .. } | RelayMessage::KeyRequest { from, to } => {
+                self.route_to_member(addr, from, to, packet)
+            }
+            RelayMessage::Leave => self.remove_member(addr),
+            _ => Vec::new(),
+        }
+    }
+
+    fn issue_challenge(&mut self, addr: SocketAddr, room_id: String) -> Outgoing {
+        if self.pending.len() >= MAX_PENDING_CHALLENGES {
+            self.pending.retain(|_, pending| pending.issued.elapsed() < CHALLENGE_TIMEOUT);
+            if self.pending.len() >= MAX_PENDING_CHALLENGES {
+                warn!("Too many pending joins, ignoring request from {}", addr);
+                return Vec::new();
+            }
+        }
+
+        // Reuse the outstanding challenge so retransmitted requests don't invalidate a Join in flight
+        let nonce = match self.pending.get(&addr) {
+            Some(pending) if pending.room_id == room_id && pending.issued.elapsed() < CHALLENGE_TIMEOUT => pending.nonce,
+            _ => {
+                let nonce: [u8; 32] = rand::random();
+                self.pending.insert(addr, PendingChallenge { room_id, nonce, issued: Instant::now() });
+                nonce
+            }
+        };
+
+        reply(addr, &RelayMessage::Challenge { nonce })
+    }
+
+    fn admit(&mut self, config: &RelayConfig, addr: SocketAddr, room_id: &str, mut participant: Participant) -> Outgoing {
+        // Retransmitted Join after we already admitted this address
+        if self.memberships.get(&addr).is_some_and(|joined| joined == room_id)
+            && let Some(member) = self.rooms.get(room_id).and_then(|room| room.members.get(&addr))
+            && member.participant.join_nonce == participant.join_nonce {
+            let max_participants = self.rooms.get(room_id).map_or(0, |room| room.max_participants);
+            return reply(addr, &RelayMessage::JoinAccepted { participant_id: member.participant.id, max_participants });
+        }
+
+        match self.pending.get(&addr) {
+            Some(pending) if pending.room_id == room_id
+                && pending.nonce == participant.join_nonce
+                && pending.issued.elapsed() < CHALLENGE_TIMEOUT => {}
+            _ => return reject(addr, "No valid challenge for this join"),
+        }
+
+        let identity = match participant.verify(room_id) {
+            Ok(identity) => identity,
+            Err(e) => {
+                warn!("Rejected join from {}: {}", addr, e);
+                return reject(addr, "Invalid join signature");
+            }
+        };
+        if !config.allowed_identities.is_empty() && !config.allowed_identities.contains(&identity) {
+            return reject(addr, "Identity not allowed on this relay");
+        }
+        self.pending.remove(&addr);
+
+        let mut outgoing = Vec::new();
+        // A member rejoining from a new address replaces its old entry
+        let stale = self.rooms.get(room_id).and_then(|room| {
+            room.members.iter()
+                .find(|(_, member)| member.participant.identity_public_key == participant.identity_public_key)
+                .map(|(&stale_addr, _)| stale_addr)
+        });
+        if let Some(stale_addr) = stale {
+            outgoing.extend(self.remove_member(stale_addr));
+        }
+        if self.memberships.contains_key(&addr) {
+            outgoing.extend(self.remove_member(addr));
+        }
+
+        let room = match self.rooms.get_mut(room_id) {
+            Some(room) => room,
+            None if config.require_registered_rooms => return reject(addr, "Unknown room"),
+            None => self.rooms.entry(room_id.to_string())
+                .or_insert_with(|| RelayRoom::new(config.default_max_participants, false)),
+        };
+        if room.members.len() >= room.max_participants as usize {
+            return reject(addr, "Room is full");
+        }
+
+        self.next_participant_id = self.next_participant_id.wrapping_add(1).max(1);
+        participant.id = self.next_participant_id;
+
+        outgoing.extend(reply(addr, &RelayMessage::JoinAccepted {
+            participant_id: participant.id,
+            max_participants: room.max_participants,
+        }));
+        // Existing roster to the newcomer, the newcomer to everyone else
+        let announcement = RelayMessage::ParticipantJoined { participant: participant.clone() };
+        for (&member_addr, member) in &room.members {
+            outgoing.extend(reply(addr, &RelayMessage::ParticipantJoined { participant: member.participant.clone() }));
+            outgoing.extend(reply(member_addr, &announcement));
+        }
+
+        info!("Participant {} joined room {} from {}", participant.id, room_id, addr);
+        room.members.insert(addr, RoomMember { participant, last_seen: Instant::now() });
+        self.memberships.insert(addr, room_id.to_string());
+        outgoing
+    }
+
+    /// Forward a media packet to the rest of the room without touching its payload
+    fn forward_media(&self, addr: SocketAddr, sender: u32, packet: &[u8]) -> Outgoing {
+        let room = match self.memberships.get(&addr).and_then(|room_id| self.rooms.get(room_id)) {
+            Some(room) => room,
+            None => return Vec::new(),
+        };
+        // The sender id must be the one we assigned to this address
+        if room.members.get(&addr).is_none_or(|member| member.participant.id != sender) {
+            debug!("Dropping media from {} claiming participant {}", addr, sender);
+            return Vec::new();
+        }
+
+        room.members.keys()
+            .filter(|&&member_addr| member_addr != addr)
+            .map(|&member_addr| (member_addr, packet.to_vec()))
+            .collect()
+    }
+
+    /// Deliver a point-to-point control message between two members of the same room
+    fn route_to_member(&self, addr: SocketAddr, from: u32, to: u32, packet: &[u8]) -> Outgoing {
+        let room = match self.memberships.get(&addr).and_then(|room_id| self.rooms.get(room_id)) {
+            Some(room) => room,
+            None => return Vec::new(),
+        };
+        if room.members.get(&addr).is_none_or(|member| member.participant.id != from) {
+            return Vec::new();
+        }
+
+        room.addr_of(to)
+            .map(|target| vec![(target, packet.to_vec())])
+            .unwrap_or_default()
+    }
+
+    fn remove_member(&mut self, addr: SocketAddr) -> Outgoing {
+        let room_id = match self.memberships.remove(&addr) {
+            Some(room_id) => room_id,
+            None => return Vec::new(),
+        };
+        let room = match self.rooms.get_mut(&room_id) {
+            Some(room) => room,
+            None => return Vec::new(),
+        };
+
+        let mut outgoing = Vec::new();
+        if let Some(member) = room.members.remove(&addr) {
+            info!("Participant {} left room {}", member.participant.id, room_id);
+            let departure = RelayMessage::ParticipantLeft { participant_id: member.participant.id };
+            for &member_addr in room.members.keys() {
+                outgoing.extend(reply(member_addr, &departure));
+            }
+        }
+
+        if room.members.is_empty() && !room.registered {
+            self.rooms.remove(&room_id);
+        }
+        outgoing
+    }
+
+    fn expire_idle(&mut self, idle_timeout: Duration) -> Outgoing {
+        let idle: Vec<SocketAddr> = self.rooms.values()
+            .flat_map(|room| room.members.iter())
+            .filter(|(_, member)| member.last_seen.elapsed() >= idle_timeout)
+            .map(|(&addr, _)| addr)
+            .collect();
+        self.pending.retain(|_, pending| pending.issued.elapsed() < CHALLENGE_TIMEOUT);
+
+        idle.into_iter().flat_map(|addr| self.remove_member(addr)).collect()
+    }
+}
+
+fn reply(addr: SocketAddr, message: &RelayMessage) -> Outgoing {
+    match encode_relay_message(message) {
+        Ok(packet) => vec![(addr, packet)],
+        Err(e) => {
+            warn!("Failed to encode relay message: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn reject(addr: SocketAddr, reason: &str) -> Outgoing {
+    info!("Rejected join from {}: {}", addr, reason);
+    reply(addr, &RelayMessage::JoinRejected { reason: reason.to_string() })
+}
+
+/// Selective forwarding relay: authenticates members and fans out their
+/// encrypted media without holding any key that decrypts it
+pub struct RelayServer {
+    socket: UdpSocket,
+    config: RelayConfig,
+    state: Mutex<RelayState>,
+}
+
+impl RelayServer {
+    pub async fn bind(addr: &str, config: RelayConfig) -> Result<Self> {
+        let socket = UdpSocket::bind(addr).await
+            .map_err(|e| anyhow!("Failed to bind relay socket to {}: {}", addr, e))?;
+
+        Ok(Self {
+            socket,
+            config,
+            state: Mutex::new(RelayState::default()),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Create a room ahead of time, taking its capacity from the advertised room info
+    pub async fn register_room(&self, room: &RoomInfo) {
+        let mut state = self.state.lock().await;
+        let entry = state.rooms.entry(room.room_id.clone())
+            .or_insert_with(|| RelayRoom::new(room.max_participants, true));
+        entry.max_participants = room.max_participants;
+        entry.registered = true;
+    }
+
+    /// Current members of a room
+    pub async fn roster(&self, room_id: &str) -> Vec<Participant> {
+        let state = self.state.lock().await;
+        let mut roster: Vec<Participant> = state.rooms.get(room_id)
+            .map(|room| room.members.values().map(|member| member.participant.clone()).collect())
+            .unwrap_or_default();
+        roster.sort_by_key(|participant| participant.id);
+        roster
+    }
+
+    /// Serve until the socket fails
+    pub async fn run(&self) -> Result<()> {
+        info!("Relay listening on {}", self.local_addr()?);
+        let mut buffer = vec![0u8; 2048];
+        let sweep_interval = (self.config.idle_timeout / 4).max(Duration::from_millis(100));
+        let mut next_sweep = Instant::now() + sweep_interval;
+
+        loop {
+            let received = tokio::time::timeout_at(next_sweep, self.socket.recv_from(&mut buffer)).await;
+
+            let outgoing = match received {
+                Ok(Ok((len, addr))) => self.state.lock().await.handle(&self.config, addr, &buffer[..len]),
+                // ICMP port unreachable from a departed client surfaces here on some platforms
+                Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionReset => continue,
+                Ok(Err(e)) => return Err(anyhow!("Relay receive error: {}", e)),
+                Err(_) => {
+                    next_sweep = Instant::now() + sweep_interval;
+                    self.state.lock().await.expire_idle(self.config.idle_timeout)
+                }
+            };
+
+            for (addr, packet) in outgoing {
+                if let Err(e) = self.socket.send_to(&packet, addr).await {
+                    debug!("Failed to send to {}: {}", addr, e);
+                }
+            }
+        }
+    }
+}
+
+/// Events surfaced by [`RelayClient::next_event`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelayEvent {
+    Audio { sender: u32, sequence: u64, payload: Vec<u8> },
+    ParticipantJoined { participant_id: u32, identity: VerifyingKey },
+    ParticipantLeft { participant_id: u32 },
+}
+
+/// Sender key received from another member
+struct ReceivedSenderKey {
+    key_id: u32,
+    cipher: ChaCha20Poly1305,
+    replay_window: ReplayWindow,
+}
+
+struct RemoteMember {
+    identity: VerifyingKey,
+    agreement_public_key: X25519PublicKey,
+    // Current and previous keys, so packets in flight across a rotation still decrypt
+    keys: Vec<ReceivedSenderKey>,
+    // Key id we last asked this member to resend
+    requested_key: Option<u32>,
+}
+
+impl RemoteMember {
+    fn key(&mut self, key_id: u32) -> Option<&mut ReceivedSenderKey> {
+        self.keys.iter_mut().find(|key| key.key_id == key_id)
+    }
+}
+
+/// Our outgoing media key
+struct OwnSenderKey {
+    key_id: u32,
+    key: [u8; 32],
+    cipher: ChaCha20Poly1305,
+}
+
+impl OwnSenderKey {
+    fn generate(key_id: u32) -> Self {
+        let key: [u8; 32] = rand::random();
+        Self { key_id, key, cipher: ChaCha20Poly1305::new(Key::from_slice(&key)) }
+    }
+}
+
+/// Room member connected through `humr-relay`.
+/// Media is encrypted once under our sender key, which is sealed individually to every other member.
+pub struct RelayClient {
+    socket: UdpSocket,
+    relay_addr: SocketAddr,
+    identity: SecurityConfig,
+    agreement_secret: ReusableSecret,
+    participant_id: u32,
+    max_participants: u8,
+    members: HashMap<u32, RemoteMember>,
+    sender_key: OwnSenderKey,
+    sequence: u64,
+    room_id: String,
+}
+
+impl RelayClient {
+    /// Join a room on the relay, answering its challenge with our identity key
+    pub async fn join(relay_addr: SocketAddr, room_id: &str, identity: SecurityConfig) -> Result<Self> {
+        let bind_addr = if relay_addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(bind_addr).await?;
+        let agreement_secret = ReusableSecret::random_from_rng(OsRng);
+        let agreement_public_key = X25519PublicKey::from(&agreement_secret).to_bytes();
+        let identity_public_key = identity.get_public_identity().to_bytes();
+
+        let join_request = encode_relay_message(&RelayMessage::JoinRequest { room_id: room_id.to_string() })?;
+        let mut outstanding = join_request.clone();
+        socket.send_to(&outstanding, relay_addr).await?;
+
+        let deadline = Instant::now() + JOIN_TIMEOUT;
+        let mut buffer = vec![0u8; 2048];
+
+        let (participant_id, max_participants) = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(anyhow!("Relay join timeout - relay may not be reachable"));
+            }
+
+            let (len, addr) = match tokio::time::timeout(JOIN_RETRANSMIT_INTERVAL.min(remaining), socket.recv_from(&mut buffer)).await {
+                Ok(received) => received?,
+                Err(_) => {
+                    socket.send_to(&outstanding, relay_addr).await?;
+                    continue;
+                }
+            };
+            if addr != relay_addr {
+                continue;
+            }
+
+            match decode_relay_message(&buffer[..len]) {
+                Ok(RelayMessage::Challenge { nonce }) => {
+                    let digest = join_digest(room_id, &identity_public_key, &agreement_public_key, &nonce);
+                    let signature = identity.identity_signing_key.sign(&digest).to_bytes();
+                    outstanding = encode_relay_message(&RelayMessage::Join {
+                        room_id: room_id.to_string(),
+                        identity_public_key,
+                        agreement_public_key,
+                        nonce,
+                        signature,
+                    })?;
+                    socket.send_to(&outstanding, relay_addr).await?;
+                }
+                Ok(RelayMessage::JoinAccepted { participant_id, max_participants }) => break (participant_id, max_participants),
+                Ok(RelayMessage::JoinRejected { reason }) => return Err(anyhow!("Relay rejected join: {}", reason)),
+                _ => {}
+            }
+        };
+
+        info!("Joined relay room {} as participant {}", room_id, participant_id);
+        Ok(Self {
+            socket,
+            relay_addr,
+            identity,
+            agreement_secret,
+            participant_id,
+            max_participants,
+            members: HashMap::new(),
+            sender_key: OwnSenderKey::generate(1),
+            sequence: 0,
+            room_id: room_id.to_string(),
+        })
+    }
+
+    /// Id the relay assigned to us
+    pub fn participant_id(&self) -> u32 {
+        self.participant_id
+    }
+
+    /// Room capacity reported by the relay
+    pub fn max_participants(&self) -> u8 {
+        self.max_participants
+    }
+
+    /// Other members we know of, with their identity keys
+    pub fn participants(&self) -> Vec<(u32, VerifyingKey)> {
+        let mut participants: Vec<(u32, VerifyingKey)> = self.members.iter()
+            .map(|(&id, member)| (id, member.identity))
+            .collect();
+        participants.sort_by_key(|(id, _)| *id);
+        participants
+    }
+
+    /// Id of the sender key currently used for our media
+    pub fn sender_key_id(&self) -> u32 {
+        self.sender_key.key_id
+    }
+
+    /// Encrypt a payload under our sender key and hand it to the relay for fan-out
+    pub async fn send_audio(&mut self, payload: &[u8]) -> Result<()> {
+        self.sequence += 1;
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let header = media_header(self.participant_id, self.sender_key.key_id, self.sequence);
+        let ciphertext = self.sender_key.cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: payload, aad: &header })
+            .map_err(|_| anyhow!("Media encryption failed"))?;
+
+        self.send(&RelayMessage::Media {
+            sender: self.participant_id,
+            key_id: self.sender_key.key_id,
+            sequence: self.sequence,
+            nonce,
+            ciphertext,
+        }).await
+    }
+
+    /// Process relay traffic until something the caller cares about happens
+    pub async fn next_event(&mut self) -> Result<RelayEvent> {
+        let mut buffer = vec![0u8; 2048];
+        loop {
+            let (len, addr) = self.socket.recv_from(&mut buffer).await?;
+            if addr != self.relay_addr {
+                continue;
+            }
+
+            let message = match decode_relay_message(&buffer[..len]) {
+                Ok(message) => message,
+                Err(e) => {
+                    debug!("Dropping malformed relay packet: {}", e);
+                    continue;
+                }
+            };
+
+            match self.handle_message(message).await {
+                Ok(Some(event)) => return Ok(event),
+                Ok(None) => {}
+                Err(e) => debug!("Ignoring relay message: {}", e),
+            }
+        }
+    }
+
+    /// Tell the relay we are leaving
+    pub async fn leave(self) -> Result<()> {
+        self.send(&RelayMessage::Leave).await
+    }
+
+    /// Switch to a fresh sender key and deliver it to every member
+    pub async fn rotate_sender_key(&mut self) -> Result<()> {
+        self.sender_key = OwnSenderKey::generate(self.sender_key.key_id.wrapping_add(1));
+        let member_ids: Vec<u32> = self.members.keys().copied().collect();
+        for member_id in member_ids {
+            self.send_sender_key(member_id).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_message(&mut self, message: RelayMessage) -> Result<Option<RelayEvent>> {
+        match message {
+            RelayMessage::ParticipantJoined { participant } => {
+                let identity = participant.verify(&self.room_id)?;
+                self.members.insert(participant.id, RemoteMember {
+                    identity,
+                    agreement_public_key: X25519PublicKey::from(participant.agreement_public_key),
+                    keys: Vec::new(),
+                    requested_key: None,
+                });
+                self.send_sender_key(participant.id).await?;
+                Ok(Some(RelayEvent::ParticipantJoined { participant_id: participant.id, identity }))
+            }
+            RelayMessage::ParticipantLeft { participant_id } => {
+                if self.members.remove(&participant_id).is_none() {
+                    return Ok(None);
+                }
+                // The departed member must not be able to follow the rest of the call
+                self.rotate_sender_key().await?;
+                Ok(Some(RelayEvent::ParticipantLeft { participant_id }))
+            }
+            RelayMessage::SenderKey { from, to, ephemeral_public_key, ciphertext, signature } if to == self.participant_id => {
+                self.accept_sender_key(from, ephemeral_public_key, &ciphertext, signature)?;
+                Ok(None)
+            }
+            RelayMessage::KeyRequest { from, to } if to == self.participant_id && self.members.contains_key(&from) => {
+                self.send_sender_key(from).await?;
+                Ok(None)
+            }
+            RelayMessage::Media { sender, key_id, sequence, nonce, ciphertext } => {
+                let member = self.members.get_mut(&sender)
+                    .ok_or_else(|| anyhow!("Media from unknown participant {}", sender))?;
+
+                let key = match member.key(key_id) {
+                    Some(key) => key,
+                    None => {
+                        // Lost or not yet delivered: ask once per key id
+                        if member.requested_key != Some(key_id) {
+                            member.requested_key = Some(key_id);
+                            self.send(&RelayMessage::KeyRequest { from: self.participant_id, to: sender }).await?;
+                        }
+                        return Err(anyhow!("No sender key {} for participant {}", key_id, sender));
+                    }
+                };
+
+                key.replay_window.check(sequence)?;
+                let header = media_header(sender, key_id, sequence);
+                let payload = key.cipher
+                    .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &header })
+                    .map_err(|_| anyhow!("Media authentication failed for participant {}", sender))?;
+                key.replay_window.accept(sequence);
+
+                Ok(Some(RelayEvent::Audio { sender, sequence, payload }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Seal our sender key to one member and sign the delivery
+    async fn send_sender_key(&self, member_id: u32) -> Result<()> {
+        let member = self.members.get(&member_id)
+            .ok_or_else(|| anyhow!("Unknown participant {}", member_id))?;
+
+        let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+        let ephemeral_public_key = X25519PublicKey::from(&ephemeral).to_bytes();
+        let shared_secret = ephemeral.diffie_hellman(&member.agreement_public_key);
+
+        let our_identity = self.identity.get_public_identity().to_bytes();
+        let their_identity = member.identity.to_bytes();
+        let cipher = sender_key_cipher(shared_secret.as_bytes(), &our_identity, &their_identity, &ephemeral_public_key)?;
+
+        let mut plaintext = Vec::with_capacity(36);
+        plaintext.extend_from_slice(&self.sender_key.key_id.to_be_bytes());
+        plaintext.extend_from_slice(&self.sender_key.key);
+        // Each delivery uses a fresh key, so a fixed nonce is safe
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&[0u8; NONCE_LEN]), plaintext.as_slice())
+            .map_err(|_| anyhow!("Sender key encryption failed"))?;
+
+        let digest = sender_key_digest(&our_identity, &their_identity, &ephemeral_public_key, &ciphertext);
+        let signature = self.identity.identity_signing_key.sign(&digest).to_bytes();
+
+        self.send(&RelayMessage::SenderKey {
+            from: self.participant_id,
+            to: member_id,
+            ephemeral_public_key,
+            ciphertext,
+            signature,
+        }).await
+    }
+
+    fn accept_sender_key(&mut self, from: u32, ephemeral_public_key: [u8; 32], ciphertext: &[u8], signature: [u8; 64]) -> Result<()> {
+        let our_identity = self.identity.get_public_identity().to_bytes();
+        let member = self.members.get_mut(&from)
+            .ok_or_else(|| anyhow!("Sender key from unknown participant {}", from))?;
+        let their_identity = member.identity.to_bytes();
+
+        let digest = sender_key_digest(&their_identity, &our_identity, &ephemeral_public_key, ciphertext);
+        member.identity.verify(&digest, &Signature::from_bytes(&signature))
+            .map_err(|_| anyhow!("Invalid sender key signature from participant {}", from))?;
+
+        let shared_secret = self.agreement_secret.diffie_hellman(&X25519PublicKey::from(ephemeral_public_key));
+        let cipher = sender_key_cipher(shared_secret.as_bytes(), &their_identity, &our_identity, &ephemeral_public_key)?;
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&[0u8; NONCE_LEN]), ciphertext)
+            .map_err(|_| anyhow!("Sender key decryption failed"))?;
+        if plaintext.len() != 36 {
+            return Err(anyhow!("Invalid sender key length"));
+        }
+
+        let key_id = u32::from_be_bytes([plaintext[0], plaintext[1], plaintext[2], plaintext[3]]);
+        if member.key(key_id).is_some() {
+            return Ok(());
+        }
+        member.keys.insert(0, ReceivedSenderKey {
+            key_id,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&plaintext[4..])),
+            replay_window: ReplayWindow::new(),
+        });
+        member.keys.truncate(2);
+        Ok(())
+    }
+
+    async fn send(&self, message: &RelayMessage) -> Result<()> {
+        let packet = encode_relay_message(message)?;
+        self.socket.send_to(&packet, self.relay_addr).await?;
+        Ok(())
+    }
+}
@@ -1,12 +1,17 @@
 use anyhow::{Result, anyhow};
-use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, aead::{Aead, AeadCore, KeyInit}};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, aead::{Aead, KeyInit, Payload}};
 use ed25519_dalek::{SigningKey, VerifyingKey, Signature, Signer, Verifier};
-use x25519_dalek::{EphemeralSecret, PublicKey as X25519PublicKey, SharedSecret};
+use x25519_dalek::{EphemeralSecret, PublicKey as X25519PublicKey};
 use sha2::{Sha256, Digest};
+use hkdf::Hkdf;
 use rand::rngs::OsRng;
 use serde::{Serialize, Deserialize};
 use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
-use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::capabilities::{CapabilityOffer, NegotiatedAudio, NegotiationError};
+use crate::known_peers::{KnownPeers, TrustDecision, TrustPolicy};
 
 /// Security error types
 #[derive(Debug)]
@@ -61,11 +66,17 @@ pub struct SecurityConfig {
     pub identity_verifying_key: VerifyingKey,
     /// Trusted public keys (contacts/peers)
     pub trusted_keys: Vec<VerifyingKey>,
-    /// Current session encryption cipher
-    pub session_cipher: Option<ChaCha20Poly1305>,
     /// Enable/disable security features
     pub encryption_enabled: bool,
     pub authentication_required: bool,
+    /// Interval between in-band rekeys; `None` disables them
+    pub rekey_interval: Option<Duration>,
+    /// How unknown peers are treated during handshakes
+    pub trust_policy: TrustPolicy,
+    /// Pinned peer identities, shared by every session using this config
+    pub known_peers: Arc<Mutex<KnownPeers>>,
+    /// Audio we offer in handshakes
+    pub capabilities: CapabilityOffer,
 }
 
 impl SecurityConfig {
@@ -78,9 +89,12 @@ impl SecurityConfig {
             identity_signing_key,
             identity_verifying_key,
             trusted_keys: Vec::new(),
-            session_cipher: None,
             encryption_enabled: true,
             authentication_required: true,
+            rekey_interval: Some(DEFAULT_REKEY_INTERVAL),
+            trust_policy: TrustPolicy::default(),
+            known_peers: Arc::new(Mutex::new(KnownPeers::in_memory())),
+            capabilities: CapabilityOffer::default(),
         })
     }
 
@@ -99,9 +113,12 @@ impl SecurityConfig {
             identity_signing_key,
             identity_verifying_key,
             trusted_keys: Vec::new(),
-            session_cipher: None,
             encryption_enabled: true,
             authentication_required: true,
+            rekey_interval: Some(DEFAULT_REKEY_INTERVAL),
+            trust_policy: TrustPolicy::default(),
+            known_peers: Arc::new(Mutex::new(KnownPeers::in_memory())),
+            capabilities: CapabilityOffer::default(),
         })
     }
 
@@ -126,47 +143,328 @@ impl SecurityConfig {
     pub fn export_identity(&self) -> String {
         BASE64.encode(self.identity_signing_key.to_bytes())
     }
+
+    /// Human-comparable fingerprint of our public identity
+    pub fn fingerprint(&self) -> String {
+        identity_fingerprint(&self.identity_verifying_key)
+    }
 }
 
-/// Message types for secure communication protocol
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(tag = "type")]
+/// SHA-256 fingerprint of an identity key as grouped hex, e.g. `3f2a 91c0 ...`
+pub fn identity_fingerprint(public_key: &VerifyingKey) -> String {
+    let digest = Sha256::digest(public_key.as_bytes());
+    digest
+        .chunks(2)
+        .map(|pair| format!("{:02x}{:02x}", pair[0], pair[1]))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Domain separation label for short authentication strings
+const SAS_LABEL: &[u8] = b"HUMR_SAS_V1";
+
+/// Short authentication string derived from the handshake transcript.
+/// Both sides see the same code only if they share one transcript, i.e. no one sits in between.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShortAuthString {
+    /// Adjective, noun, adjective, noun from the room-name word lists (5 bits each)
+    pub words: [&'static str; 4],
+    /// Six-digit alternative to the words
+    pub safety_number: u32,
+}
+
+impl ShortAuthString {
+    fn from_transcript(transcript_hash: &[u8; 32]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(SAS_LABEL);
+        hasher.update(transcript_hash);
+        let digest: [u8; 32] = hasher.finalize().into();
+
+        // Both lists hold 32 unique words, so a byte modulo 32 is unbiased
+        let adjectives = &crate::lighthouse::RoomName::ADJECTIVES;
+        let nouns = &crate::lighthouse::RoomName::NOUNS;
+        let words = [
+            adjectives[digest[0] as usize % adjectives.len()],
+            nouns[digest[1] as usize % nouns.len()],
+            adjectives[digest[2] as usize % adjectives.len()],
+            nouns[digest[3] as usize % nouns.len()],
+        ];
+        let safety_number = u32::from_be_bytes([digest[4], digest[5], digest[6], digest[7]]) % 1_000_000;
+
+        Self { words, safety_number }
+    }
+
+    /// Words separated by spaces, e.g. `swift falcon amber wolf`
+    pub fn words_string(&self) -> String {
+        self.words.join(" ")
+    }
+
+    /// Safety number in two groups of three digits, e.g. `042 917`
+    pub fn safety_number_string(&self) -> String {
+        format!("{:03} {:03}", self.safety_number / 1000, self.safety_number % 1000)
+    }
+}
+
+impl std::fmt::Display for ShortAuthString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.words_string(), self.safety_number_string())
+    }
+}
+
+/// Message types for secure communication protocol.
+/// Keys, nonces and signatures are raw bytes; `crate::wire` defines the packet encoding.
+#[derive(Debug, Clone, PartialEq)]
 pub enum SecureMessage {
-    /// Initial handshake with identity and ephemeral key
+    /// Initial handshake with identity, ephemeral key and audio offer
     Handshake {
-        identity_public_key: String, // base64 encoded
-        ephemeral_public_key: String, // base64 encoded x25519
-        signature: String, // base64 encoded signature
+        identity_public_key: [u8; 32], // ed25519
+        ephemeral_public_key: [u8; 32], // x25519
+        signature: [u8; 64],
         timestamp: u64,
+        capabilities: CapabilityOffer,
     },
-    /// Handshake response with peer's ephemeral key
+    /// Handshake response with the responder's identity, ephemeral key and audio offer
     HandshakeResponse {
-        ephemeral_public_key: String,
-        signature: String,
+        identity_public_key: [u8; 32],
+        ephemeral_public_key: [u8; 32],
+        signature: [u8; 64],
         timestamp: u64,
+        capabilities: CapabilityOffer,
     },
     /// Encrypted audio frame
     EncryptedAudio {
-        nonce: String, // base64 encoded
-        ciphertext: String, // base64 encoded
         frame_number: u64,
+        timestamp: u64, // sender clock, milliseconds
+        nonce: [u8; 12],
+        ciphertext: Vec<u8>,
+    },
+    /// In-band rekey request carrying a fresh ephemeral key
+    Rekey {
+        generation: u32,
+        ephemeral_public_key: [u8; 32],
+        signature: [u8; 64],
+    },
+    /// Answer to a rekey request with the responder's fresh ephemeral key
+    RekeyResponse {
+        generation: u32,
+        ephemeral_public_key: [u8; 32],
+        signature: [u8; 64],
+    },
+    /// Encrypted control payload, such as receiver feedback, outside the audio sequence
+    EncryptedControl {
+        counter: u64,
+        nonce: [u8; 12],
+        ciphertext: Vec<u8>,
     },
     /// Connection termination
     Disconnect {
         reason: String,
-        signature: String,
+        signature: [u8; 64],
     },
 }
 
+/// Domain separation label for the handshake transcript hash
+const TRANSCRIPT_LABEL: &[u8] = b"HUMR_HANDSHAKE";
+
+/// Handshake protocol version bound into every transcript
+pub const HANDSHAKE_VERSION: u8 = 2;
+
+/// Role labels so neither side's signature can be reflected as the other's
+const INITIATOR_ROLE: &[u8] = b"humr initiator";
+const RESPONDER_ROLE: &[u8] = b"humr responder";
+
+/// Domain separation label for rekey signatures and transcripts
+const REKEY_LABEL: &[u8] = b"HUMR_REKEY_V1";
+
+/// Domain separation label for Disconnect signatures
+const DISCONNECT_LABEL: &[u8] = b"HUMR_DISCONNECT_V1";
+
+/// Handshakes older than this are refused; answered ones are remembered this long to
+/// refuse replays
+pub const HANDSHAKE_MAX_AGE: Duration = Duration::from_secs(300);
+
+/// How far ahead of our clock a handshake timestamp may run
+const HANDSHAKE_MAX_CLOCK_SKEW_SECS: u64 = 30;
+
+/// Refuse a handshake timestamp outside the window around our clock that replays are checked in
+fn check_handshake_timestamp(timestamp: u64, message: &str) -> Result<()> {
+    let current_time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    if current_time.saturating_sub(timestamp) > HANDSHAKE_MAX_AGE.as_secs() {
+        return Err(anyhow!("{} timestamp too old", message));
+    }
+    if timestamp > current_time + HANDSHAKE_MAX_CLOCK_SKEW_SECS {
+        return Err(anyhow!("{} timestamp is in the future", message));
+    }
+    Ok(())
+}
+
+/// Default interval between in-band rekeys
+pub const DEFAULT_REKEY_INTERVAL: Duration = Duration::from_secs(300);
+
+/// How long retired receive keys stay usable for in-flight packets
+const REKEY_OVERLAP: Duration = Duration::from_secs(2);
+
+/// How long to wait for a RekeyResponse before resending the request
+const REKEY_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Control packets count from here so their nonces never collide with audio counters
+pub const CONTROL_COUNTER_BASE: u64 = 1 << 63;
+
+/// Number of packet counters tracked behind the highest one seen
+pub const REPLAY_WINDOW_SIZE: u64 = 1024;
+
+/// Sliding-window replay filter over a direction's packet counters (RFC 6479 style).
+/// Memory is fixed regardless of call length.
+#[derive(Debug, Clone)]
+pub struct ReplayWindow {
+    highest: u64,
+    bitmap: [u64; (REPLAY_WINDOW_SIZE / 64) as usize],
+}
+
+impl Default for ReplayWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReplayWindow {
+    pub fn new() -> Self {
+        Self {
+            highest: 0,
+            bitmap: [0; (REPLAY_WINDOW_SIZE / 64) as usize],
+        }
+    }
+
+    /// Check a counter without recording it; call `accept` once the packet authenticates
+    pub fn check(&self, counter: u64) -> Result<()> {
+        if counter == 0 {
+            return Err(anyhow!("Invalid packet counter"));
+        }
+        if counter > self.highest {
+            return Ok(());
+        }
+        if self.highest - counter >= REPLAY_WINDOW_SIZE {
+            return Err(anyhow!("Packet counter {} too old", counter));
+        }
+        if self.is_marked(counter) {
+            return Err(anyhow!("Replayed packet counter {}", counter));
+        }
+        Ok(())
+    }
+
+    /// Record an authenticated counter, sliding the window forward if needed
+    pub fn accept(&mut self, counter: u64) {
+        if counter > self.highest {
+            // Clear the slots the window slides over
+            let advance = counter - self.highest;
+            if advance >= REPLAY_WINDOW_SIZE {
+                self.bitmap = [0; (REPLAY_WINDOW_SIZE / 64) as usize];
+            } else {
+                for skipped in self.highest + 1..=counter {
+                    let (word, bit) = Self::slot(skipped);
+                    self.bitmap[word] &= !(1 << bit);
+                }
+            }
+            self.highest = counter;
+        }
+
+        let (word, bit) = Self::slot(counter);
+        self.bitmap[word] |= 1 << bit;
+    }
+
+    /// Highest counter accepted so far
+    pub fn highest(&self) -> u64 {
+        self.highest
+    }
+
+    fn is_marked(&self, counter: u64) -> bool {
+        let (word, bit) = Self::slot(counter);
+        self.bitmap[word] & (1 << bit) != 0
+    }
+
+    fn slot(counter: u64) -> (usize, u64) {
+        let index = counter % REPLAY_WINDOW_SIZE;
+        ((index / 64) as usize, index % 64)
+    }
+}
+
+/// Traffic key and nonce prefix for one direction of a session
+struct DirectionKeys {
+    cipher: ChaCha20Poly1305,
+    nonce_prefix: [u8; 4],
+}
+
+/// Independent keys for each direction, derived from one handshake or rekey
+struct SessionKeys {
+    send: DirectionKeys,
+    receive: DirectionKeys,
+    /// Secret carried forward into the next key generation
+    chain_key: [u8; 32],
+    /// Transcript this generation is bound to
+    transcript_hash: [u8; 32],
+}
+
+/// Receive keys of the previous generation, kept until in-flight packets drain
+struct RetiredKeys {
+    keys: DirectionKeys,
+    expires_at: Instant,
+}
+
+/// Rekey we started and are waiting to have answered
+struct OutstandingRekey {
+    generation: u32,
+    ephemeral_secret: EphemeralSecret,
+    request: SecureMessage,
+    sent_at: Instant,
+}
+
+/// Our answer to the peer's latest rekey, repeated if the request is retransmitted
+struct AnsweredRekey {
+    generation: u32,
+    peer_ephemeral: [u8; 32],
+    response: SecureMessage,
+}
+
+/// Which key generation an incoming packet belongs to
+#[derive(Clone, Copy, PartialEq)]
+enum ReceiveSlot {
+    Current,
+    Pending,
+    Previous,
+}
+
 /// Secure session manager for end-to-end encrypted communication
 pub struct SecureSession {
     config: SecurityConfig,
     pub(crate) peer_identity: Option<VerifyingKey>,
-    shared_secret: Option<SharedSecret>,
     ephemeral_secret: Option<EphemeralSecret>,
-    session_key: Option<[u8; 32]>,
+    // Initiator's half of the transcript, kept until the response arrives
+    initiator_transcript: Option<[u8; 32]>,
+    // Ephemeral key of the last Handshake we answered, to reject replays
+    answered_handshake: Option<[u8; 32]>,
+    keys: Option<SessionKeys>,
+    // Rekey state: responder keys awaiting confirmation, and retired receive keys
+    pending_keys: Option<SessionKeys>,
+    previous_receive: Option<RetiredKeys>,
+    outstanding_rekey: Option<OutstandingRekey>,
+    answered_rekey: Option<AnsweredRekey>,
+    key_generation: u32,
+    last_rekey: Instant,
     frame_counter: u64,
+    replay_window: ReplayWindow,
+    // Control packets run their own counter so audio frame numbers stay contiguous
+    control_counter: u64,
+    control_replay_window: ReplayWindow,
     is_initiator: bool,
+    // Name we addressed the peer by, used for known-peers lookups
+    peer_name: Option<String>,
+    // Transcript of the handshake that opened the session; rekeys leave it unchanged
+    handshake_transcript: Option<[u8; 32]>,
+    // Audio settled in the handshake, or why the offers did not match
+    negotiated_audio: Option<NegotiatedAudio>,
+    audio_mismatch: Option<NegotiationError>,
 }
 
 impl SecureSession {
@@ -175,45 +473,127 @@ impl SecureSession {
         Self {
             config,
             peer_identity: None,
-            shared_secret: None,
             ephemeral_secret: None,
-            session_key: None,
+            initiator_transcript: None,
+            answered_handshake: None,
+            keys: None,
+            pending_keys: None,
+            previous_receive: None,
+            outstanding_rekey: None,
+            answered_rekey: None,
+            key_generation: 0,
+            last_rekey: Instant::now(),
             frame_counter: 0,
+            replay_window: ReplayWindow::new(),
+            control_counter: CONTROL_COUNTER_BASE,
+            control_replay_window: ReplayWindow::new(),
             is_initiator: false,
+            peer_name: None,
+            handshake_transcript: None,
+            negotiated_audio: None,
+            audio_mismatch: None,
         }
     }
 
     /// Initiate secure handshake with a peer
     pub fn initiate_handshake(&mut self) -> Result<SecureMessage> {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        self.initiate_handshake_at(timestamp)
+    }
+
+    /// Handshake stamped with `timestamp`, in seconds since the epoch
+    pub(crate) fn initiate_handshake_at(&mut self, timestamp: u64) -> Result<SecureMessage> {
         self.is_initiator = true;
 
         // Generate ephemeral keypair for this session
         let ephemeral_secret = EphemeralSecret::random_from_rng(OsRng);
         let ephemeral_public = X25519PublicKey::from(&ephemeral_secret);
 
-        // Create handshake message
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)?
-            .as_secs();
-
-        // Sign the ephemeral public key to prove identity
-        let mut hasher = Sha256::new();
-        hasher.update(ephemeral_public.as_bytes());
-        hasher.update(timestamp.to_be_bytes());
-        let hash = hasher.finalize();
-
-        let signature = self.config.identity_signing_key.sign(&hash);
+        // Sign our half of the transcript; the responder's signature covers both halves
+        let initiator_transcript = initiator_transcript_hash(
+            &self.config.identity_verifying_key,
+            &ephemeral_public,
+            timestamp,
+            &self.config.capabilities,
+        )?;
+        let signature = self.config.identity_signing_key
+            .sign(&transcript_signing_digest(INITIATOR_ROLE, &initiator_transcript));
 
         self.ephemeral_secret = Some(ephemeral_secret);
+        self.initiator_transcript = Some(initiator_transcript);
 
         Ok(SecureMessage::Handshake {
-            identity_public_key: BASE64.encode(self.config.identity_verifying_key.as_bytes()),
-            ephemeral_public_key: BASE64.encode(ephemeral_public.as_bytes()),
-            signature: BASE64.encode(signature.to_bytes()),
+            identity_public_key: self.config.identity_verifying_key.to_bytes(),
+            ephemeral_public_key: ephemeral_public.to_bytes(),
+            signature: signature.to_bytes(),
             timestamp,
+            capabilities: self.config.capabilities.clone(),
         })
     }
 
+    /// Set the name (e.g. `host:port`) the peer's key is pinned under in known peers
+    pub fn set_peer_name(&mut self, name: Option<String>) {
+        self.peer_name = name;
+    }
+
+    /// Apply the trust policy to a peer identity whose signature has been verified
+    fn check_peer_trust(&self, peer_identity: &VerifyingKey) -> Result<()> {
+        let policy = if self.config.authentication_required {
+            self.config.trust_policy
+        } else {
+            TrustPolicy::Permissive
+        };
+
+        let mut known_peers = self.config.known_peers.lock()
+            .map_err(|_| anyhow!("Known peers store poisoned"))?;
+        let decision = known_peers.verify(
+            self.peer_name.as_deref(),
+            peer_identity,
+            &self.config.trusted_keys,
+            policy,
+        )?;
+
+        if decision == TrustDecision::Accepted {
+            println!("WARNING: Accepting unverified peer {}", identity_fingerprint(peer_identity));
+        }
+        Ok(())
+    }
+
+    /// Code both users compare out of band; a man in the middle ends up with two different codes
+    pub fn short_auth_string(&self) -> Option<ShortAuthString> {
+        if !self.is_session_active() {
+            return None;
+        }
+        self.handshake_transcript.as_ref().map(ShortAuthString::from_transcript)
+    }
+
+    /// Record the peer as verified once both users have confirmed their codes match
+    pub fn mark_peer_verified(&self) -> Result<()> {
+        let peer_identity = self.peer_identity
+            .filter(|_| self.is_session_active())
+            .ok_or_else(|| anyhow!("No established session to verify"))?;
+
+        let mut known_peers = self.config.known_peers.lock()
+            .map_err(|_| anyhow!("Known peers store poisoned"))?;
+        known_peers.mark_verified(self.peer_name.as_deref(), &peer_identity)
+    }
+
+    /// Whether the current peer has been verified by comparing codes
+    pub fn is_peer_verified(&self) -> bool {
+        match (self.peer_identity, self.config.known_peers.lock()) {
+            (Some(peer_identity), Ok(known_peers)) => known_peers.is_verified(&peer_identity),
+            _ => false,
+        }
+    }
+
+    /// Decide which side answers when both peers sent a Handshake at once.
+    /// The side with the lower identity key abandons its own attempt and responds.
+    pub fn yields_to_peer(&self, peer_identity_key: &[u8; 32]) -> bool {
+        self.config.identity_verifying_key.as_bytes() < peer_identity_key
+    }
+
     /// Process incoming handshake and generate response
     pub fn process_handshake(&mut self, handshake: SecureMessage) -> Result<Option<SecureMessage>> {
         match handshake {
@@ -222,84 +602,79 @@ impl SecureSession {
                 ephemeral_public_key,
                 signature,
                 timestamp,
+                capabilities,
             } => {
-                // Verify timestamp is recent (within 5 minutes)
-                let current_time = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)?
-                    .as_secs();
-                if current_time.saturating_sub(timestamp) > 300 {
-                    return Err(anyhow!("Handshake timestamp too old"));
+                if self.answered_handshake == Some(ephemeral_public_key) {
+                    return Err(anyhow!("Replayed handshake"));
                 }
 
+                // Verify timestamp is recent (within 5 minutes) and not ahead of our clock
+                check_handshake_timestamp(timestamp, "Handshake")?;
+
                 // Parse peer's identity
-                let peer_identity_bytes = BASE64.decode(identity_public_key)?;
-                let peer_identity_array: [u8; 32] = peer_identity_bytes.try_into()
-                    .map_err(|_| anyhow!("Invalid public key length"))?;
-                let peer_identity = VerifyingKey::from_bytes(&peer_identity_array)
+                let peer_identity = VerifyingKey::from_bytes(&identity_public_key)
                     .map_err(|e| anyhow!("Invalid public key: {}", e))?;
 
-                // ASSUMPTION: In production, you might want configurable trust models
-                // For now, we'll add any connecting peer to trusted list automatically
-                // Real implementation should require manual verification or PKI
-                if !self.config.is_peer_trusted(&peer_identity) {
-                    println!("WARNING: Peer {} not in trusted list, adding automatically",
-                             BASE64.encode(peer_identity.as_bytes()));
-                    // In production: return Err(anyhow!("Untrusted peer"));
-                }
-
                 // Parse ephemeral key
-                let peer_ephemeral_bytes = BASE64.decode(ephemeral_public_key)?;
-                let peer_ephemeral_array: [u8; 32] = peer_ephemeral_bytes.try_into()
-                    .map_err(|_| anyhow!("Invalid ephemeral key length"))?;
-                let peer_ephemeral = X25519PublicKey::from(peer_ephemeral_array);
-
-                // Verify signature
-                let signature_bytes = BASE64.decode(signature)?;
-                let signature_array: [u8; 64] = signature_bytes.try_into()
-                    .map_err(|_| anyhow!("Invalid signature length"))?;
-                let signature = Signature::from_bytes(&signature_array);
-
-                let mut hasher = Sha256::new();
-                hasher.update(peer_ephemeral.as_bytes());
-                hasher.update(timestamp.to_be_bytes());
-                let hash = hasher.finalize();
-
-                peer_identity.verify(&hash, &signature)
+                let peer_ephemeral = X25519PublicKey::from(ephemeral_public_key);
+
+                // Verify signature over the initiator's half of the transcript
+                let signature = Signature::from_bytes(&signature);
+                let initiator_transcript = initiator_transcript_hash(&peer_identity, &peer_ephemeral, timestamp, &capabilities)?;
+
+                peer_identity.verify(&transcript_signing_digest(INITIATOR_ROLE, &initiator_transcript), &signature)
                     .map_err(|e| anyhow!("Signature verification failed: {}", e))?;
 
+                // Only a key proven by its signature may be checked against (and pinned in) known peers
+                self.check_peer_trust(&peer_identity)?;
+
+                // Answering a peer abandons any handshake we initiated ourselves
+                self.is_initiator = false;
+                self.ephemeral_secret = None;
+                self.initiator_transcript = None;
+
                 // Generate our ephemeral keypair and shared secret
                 let ephemeral_secret = EphemeralSecret::random_from_rng(OsRng);
                 let ephemeral_public = X25519PublicKey::from(&ephemeral_secret);
                 let shared_secret = ephemeral_secret.diffie_hellman(&peer_ephemeral);
 
-                // Derive session key using HKDF-like approach
-                let session_key = self.derive_session_key(&shared_secret, &peer_identity)?;
-
-                // Store session state
-                self.peer_identity = Some(peer_identity);
-                self.shared_secret = Some(shared_secret);
-                self.session_key = Some(session_key);
-
-                // Create cipher for this session
-                let key = Key::from_slice(&session_key);
-                self.config.session_cipher = Some(ChaCha20Poly1305::new(key));
-
-                // Generate response
                 let response_timestamp = std::time::SystemTime::now()
                     .duration_since(std::time::UNIX_EPOCH)?
                     .as_secs();
 
-                let mut response_hasher = Sha256::new();
-                response_hasher.update(ephemeral_public.as_bytes());
-                response_hasher.update(response_timestamp.to_be_bytes());
-                let response_hash = response_hasher.finalize();
+                // Full transcript: both identities, both ephemerals, both offers, version and timestamps
+                let transcript_hash = full_transcript_hash(
+                    &initiator_transcript,
+                    &self.config.identity_verifying_key,
+                    &ephemeral_public,
+                    response_timestamp,
+                    &self.config.capabilities,
+                )?;
+
+                // Without common audio we still answer, so the initiator learns why, but
+                // start no session
+                match self.config.capabilities.negotiate(&capabilities, false) {
+                    Ok(negotiated) => {
+                        // Derive directional keys salted with the full transcript
+                        let keys = self.derive_keys(shared_secret.as_bytes(), transcript_hash)?;
+                        self.start_session(keys);
+                        self.negotiated_audio = Some(negotiated);
+                    }
+                    Err(mismatch) => self.audio_mismatch = Some(mismatch),
+                }
+                self.peer_identity = Some(peer_identity);
+                self.answered_handshake = Some(ephemeral_public_key);
 
-                let response_signature = self.config.identity_signing_key.sign(&response_hash);
+                // Sign the full transcript so the response only fits this initiator's handshake
+                let response_signature = self.config.identity_signing_key
+                    .sign(&transcript_signing_digest(RESPONDER_ROLE, &transcript_hash));
 
                 Ok(Some(SecureMessage::HandshakeResponse {
-                    ephemeral_public_key: BASE64.encode(ephemeral_public.as_bytes()),
-                    signature: BASE64.encode(response_signature.to_bytes()),
+                    identity_public_key: self.config.identity_verifying_key.to_bytes(),
+                    ephemeral_public_key: ephemeral_public.to_bytes(),
+                    signature: response_signature.to_bytes(),
                     timestamp: response_timestamp,
+                    capabilities: self.config.capabilities.clone(),
                 }))
             }
             _ => Err(anyhow!("Expected handshake message")),
@@ -310,58 +685,53 @@ impl SecureSession {
     pub fn process_handshake_response(&mut self, response: SecureMessage) -> Result<()> {
         match response {
             SecureMessage::HandshakeResponse {
+                identity_public_key,
                 ephemeral_public_key,
                 signature,
                 timestamp,
+                capabilities,
             } => {
-                let peer_identity = self.peer_identity
-                    .ok_or_else(|| anyhow!("No peer identity established"))?;
+                let peer_identity = VerifyingKey::from_bytes(&identity_public_key)
+                    .map_err(|e| anyhow!("Invalid public key: {}", e))?;
 
-                // Verify timestamp
-                let current_time = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)?
-                    .as_secs();
-                if current_time.saturating_sub(timestamp) > 300 {
-                    return Err(anyhow!("Response timestamp too old"));
+                // If we dialled a known peer, the responder must be that peer
+                if let Some(expected) = self.peer_identity
+                    && expected != peer_identity {
+                    return Err(anyhow!("Responder identity does not match expected peer"));
                 }
 
+                // Verify timestamp
+                check_handshake_timestamp(timestamp, "Response")?;
+
                 // Parse peer's ephemeral key
-                let peer_ephemeral_bytes = BASE64.decode(ephemeral_public_key)?;
-                let peer_ephemeral_array: [u8; 32] = peer_ephemeral_bytes.try_into()
-                    .map_err(|_| anyhow!("Invalid ephemeral key length"))?;
-                let peer_ephemeral = X25519PublicKey::from(peer_ephemeral_array);
-
-                // Verify signature
-                let signature_bytes = BASE64.decode(signature)?;
-                let signature_array: [u8; 64] = signature_bytes.try_into()
-                    .map_err(|_| anyhow!("Invalid signature length"))?;
-                let signature = Signature::from_bytes(&signature_array);
-
-                let mut hasher = Sha256::new();
-                hasher.update(peer_ephemeral.as_bytes());
-                hasher.update(timestamp.to_be_bytes());
-                let hash = hasher.finalize();
-
-                peer_identity.verify(&hash, &signature)
+                let peer_ephemeral = X25519PublicKey::from(ephemeral_public_key);
+
+                // Verify the signature covers our own handshake as well as the response
+                let initiator_transcript = self.initiator_transcript
+                    .ok_or_else(|| anyhow!("No handshake in progress"))?;
+                let transcript_hash = full_transcript_hash(&initiator_transcript, &peer_identity, &peer_ephemeral, timestamp, &capabilities)?;
+                let signature = Signature::from_bytes(&signature);
+
+                peer_identity.verify(&transcript_signing_digest(RESPONDER_ROLE, &transcript_hash), &signature)
                     .map_err(|e| anyhow!("Signature verification failed: {}", e))?;
+                self.check_peer_trust(&peer_identity)?;
+
+                // The responder answers even when our offers share nothing; both sides reach the same verdict
+                let negotiated = self.config.capabilities.negotiate(&capabilities, true)?;
 
                 // Complete DH exchange
                 let ephemeral_secret = self.ephemeral_secret.take()
                     .ok_or_else(|| anyhow!("No ephemeral secret"))?;
                 let shared_secret = ephemeral_secret.diffie_hellman(&peer_ephemeral);
+                self.initiator_transcript = None;
 
-                // Derive session key
-                let session_key = self.derive_session_key(&shared_secret, &peer_identity)?;
-
-                // Store session state
-                self.shared_secret = Some(shared_secret);
-                self.session_key = Some(session_key);
-
-                // Create cipher
-                let key = Key::from_slice(&session_key);
-                self.config.session_cipher = Some(ChaCha20Poly1305::new(key));
+                // Derive directional keys salted with the full transcript
+                let keys = self.derive_keys(shared_secret.as_bytes(), transcript_hash)?;
+                self.start_session(keys);
+                self.peer_identity = Some(peer_identity);
+                self.negotiated_audio = Some(negotiated);
 
-                println!("Secure session established with peer");
+                println!("Secure session established with peer ({})", negotiated);
                 Ok(())
             }
             _ => Err(anyhow!("Expected handshake response")),
@@ -370,79 +740,420 @@ impl SecureSession {
 
     /// Encrypt audio frame for transmission
     pub fn encrypt_audio_frame(&mut self, audio_data: &[u8]) -> Result<SecureMessage> {
-        let cipher = self.config.session_cipher.as_ref()
-            .ok_or_else(|| anyhow!("No active session"))?;
-
-        // Generate unique nonce for this frame
-        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        // Audio counters must stay below the control range
+        let frame_number = self.frame_counter.checked_add(1)
+            .filter(|&counter| counter < CONTROL_COUNTER_BASE)
+            .ok_or_else(|| anyhow!("Packet counter exhausted, rekey required"))?;
+        let (nonce, ciphertext) = self.seal(frame_number, audio_data)?;
+        self.frame_counter = frame_number;
 
-        // Encrypt the audio data
-        let ciphertext = cipher.encrypt(&nonce, audio_data)
-            .map_err(|_| anyhow!("Encryption failed"))?;
-
-        self.frame_counter += 1;
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
 
         Ok(SecureMessage::EncryptedAudio {
-            nonce: BASE64.encode(nonce.as_slice()),
-            ciphertext: BASE64.encode(ciphertext),
-            frame_number: self.frame_counter,
+            frame_number,
+            timestamp,
+            nonce,
+            ciphertext,
         })
     }
 
     /// Decrypt received audio frame
-    pub fn decrypt_audio_frame(&self, message: SecureMessage) -> Result<Vec<u8>> {
+    pub fn decrypt_audio_frame(&mut self, message: SecureMessage) -> Result<Vec<u8>> {
         match message {
-            SecureMessage::EncryptedAudio { nonce, ciphertext, frame_number: _ } => {
-                let cipher = self.config.session_cipher.as_ref()
-                    .ok_or_else(|| anyhow!("No active session"))?;
+            SecureMessage::EncryptedAudio { frame_number, nonce, ciphertext, .. } => {
+                if frame_number >= CONTROL_COUNTER_BASE {
+                    return Err(anyhow!("Audio packet counter in the control range"));
+                }
+                self.open(frame_number, nonce, &ciphertext, false)
+            }
+            _ => Err(anyhow!("Expected encrypted audio message")),
+        }
+    }
 
-                let nonce_bytes = BASE64.decode(nonce)?;
-                let nonce = Nonce::from_slice(&nonce_bytes);
+    /// Encrypt a control payload under the session keys, outside the audio sequence
+    pub fn encrypt_control(&mut self, payload: &[u8]) -> Result<SecureMessage> {
+        let counter = self.control_counter.checked_add(1)
+            .ok_or_else(|| anyhow!("Control counter exhausted, rekey required"))?;
+        let (nonce, ciphertext) = self.seal(counter, payload)?;
+        self.control_counter = counter;
+        Ok(SecureMessage::EncryptedControl { counter, nonce, ciphertext })
+    }
 
-                let ciphertext_bytes = BASE64.decode(ciphertext)?;
+    /// Decrypt a received control payload
+    pub fn decrypt_control(&mut self, message: SecureMessage) -> Result<Vec<u8>> {
+        match message {
+            SecureMessage::EncryptedControl { counter, nonce, ciphertext } => {
+                if counter <= CONTROL_COUNTER_BASE {
+                    return Err(anyhow!("Control packet counter outside the control range"));
+                }
+                self.open(counter, nonce, &ciphertext, true)
+            }
+            _ => Err(anyhow!("Expected encrypted control message")),
+        }
+    }
 
-                let plaintext = cipher.decrypt(nonce, ciphertext_bytes.as_slice())
-                    .map_err(|_| anyhow!("Decryption failed"))?;
+    /// Encrypt under the current send key; the nonce is our direction's prefix followed by
+    /// the packet counter, which is also authenticated alongside the data
+    fn seal(&self, counter: u64, plaintext: &[u8]) -> Result<([u8; 12], Vec<u8>)> {
+        let send = &self.keys.as_ref()
+            .ok_or_else(|| anyhow!("No active session"))?
+            .send;
+        let nonce = counter_nonce(send.nonce_prefix, counter);
+        let aad = counter.to_be_bytes();
+        let ciphertext = send.cipher.encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &aad })
+            .map_err(|_| anyhow!("Encryption failed"))?;
+        Ok((nonce, ciphertext))
+    }
 
-                Ok(plaintext)
-            }
-            _ => Err(anyhow!("Expected encrypted audio message")),
+    /// Decrypt under whichever key generation the nonce names, checking the audio or
+    /// control replay window
+    fn open(&mut self, counter: u64, nonce: [u8; 12], ciphertext: &[u8], control: bool) -> Result<Vec<u8>> {
+        let slot = self.receive_slot(&nonce[..4])?;
+        let receive = match slot {
+            ReceiveSlot::Current => self.keys.as_ref().map(|keys| &keys.receive),
+            ReceiveSlot::Pending => self.pending_keys.as_ref().map(|keys| &keys.receive),
+            ReceiveSlot::Previous => self.previous_receive.as_ref().map(|retired| &retired.keys),
+        }.ok_or_else(|| anyhow!("No active session"))?;
+
+        if nonce != counter_nonce(receive.nonce_prefix, counter) {
+            return Err(anyhow!("Nonce does not match peer direction and counter"));
+        }
+
+        let replay_window = if control { &mut self.control_replay_window } else { &mut self.replay_window };
+
+        // Reject replays before spending time on decryption
+        replay_window.check(counter)?;
+
+        let aad = counter.to_be_bytes();
+        let plaintext = receive.cipher.decrypt(Nonce::from_slice(&nonce), Payload { msg: ciphertext, aad: &aad })
+            .map_err(|_| anyhow!("Decryption failed"))?;
+
+        // Only authenticated packets advance the window
+        replay_window.accept(counter);
+
+        // Traffic under the pending keys proves the peer switched over
+        if slot == ReceiveSlot::Pending {
+            self.promote_pending_keys();
         }
+
+        Ok(plaintext)
     }
 
-    /// Derive session key from shared secret and peer identity
-    fn derive_session_key(&self, shared_secret: &SharedSecret, peer_identity: &VerifyingKey) -> Result<[u8; 32]> {
-        // HKDF-like key derivation with consistent ordering
-        let mut hasher = Sha256::new();
-        hasher.update(shared_secret.as_bytes());
+    /// Derive directional traffic keys with HKDF-SHA256.
+    /// The transcript hash is the salt, so keys are bound to this exact handshake or rekey.
+    fn derive_keys(&self, input_key_material: &[u8], transcript_hash: [u8; 32]) -> Result<SessionKeys> {
+        let hkdf = Hkdf::<Sha256>::new(Some(&transcript_hash), input_key_material);
+
+        let expand_direction = |key_label: &[u8], nonce_label: &[u8]| -> Result<DirectionKeys> {
+            let mut key = [0u8; 32];
+            let mut nonce_prefix = [0u8; 4];
+            hkdf.expand(key_label, &mut key)
+                .map_err(|_| anyhow!("Key derivation failed"))?;
+            hkdf.expand(nonce_label, &mut nonce_prefix)
+                .map_err(|_| anyhow!("Key derivation failed"))?;
+            Ok(DirectionKeys { cipher: ChaCha20Poly1305::new(Key::from_slice(&key)), nonce_prefix })
+        };
 
-        // Ensure both parties derive the same key by ordering identities consistently
-        let self_bytes = self.config.identity_verifying_key.as_bytes();
-        let peer_bytes = peer_identity.as_bytes();
+        let initiator_to_responder = expand_direction(b"humr i2r key", b"humr i2r nonce")?;
+        let responder_to_initiator = expand_direction(b"humr r2i key", b"humr r2i nonce")?;
 
-        if self_bytes < peer_bytes {
-            hasher.update(self_bytes);
-            hasher.update(peer_bytes);
+        let mut chain_key = [0u8; 32];
+        hkdf.expand(b"humr chain key", &mut chain_key)
+            .map_err(|_| anyhow!("Key derivation failed"))?;
+
+        let (send, receive) = if self.is_initiator {
+            (initiator_to_responder, responder_to_initiator)
         } else {
-            hasher.update(peer_bytes);
-            hasher.update(self_bytes);
+            (responder_to_initiator, initiator_to_responder)
+        };
+
+        Ok(SessionKeys { send, receive, chain_key, transcript_hash })
+    }
+
+    /// Install the first key generation of a new handshake and reset all per-session state
+    fn start_session(&mut self, keys: SessionKeys) {
+        self.handshake_transcript = Some(keys.transcript_hash);
+        self.keys = Some(keys);
+        self.pending_keys = None;
+        self.previous_receive = None;
+        self.outstanding_rekey = None;
+        self.answered_rekey = None;
+        self.key_generation = 0;
+        self.last_rekey = Instant::now();
+        self.frame_counter = 0;
+        self.replay_window = ReplayWindow::new();
+        self.control_counter = CONTROL_COUNTER_BASE;
+        self.control_replay_window = ReplayWindow::new();
+    }
+
+    /// Switch to a new key generation, keeping the old receive key for in-flight packets.
+    /// Packet counters keep running so the jitter buffer sees one continuous sequence.
+    fn activate_keys(&mut self, keys: SessionKeys) {
+        if let Some(old) = self.keys.replace(keys) {
+            self.previous_receive = Some(RetiredKeys {
+                keys: old.receive,
+                expires_at: Instant::now() + REKEY_OVERLAP,
+            });
         }
+        self.last_rekey = Instant::now();
+    }
 
-        hasher.update(b"HUMR_SESSION_KEY_V1");
+    /// Adopt keys we offered in a RekeyResponse once the peer is known to use them
+    fn promote_pending_keys(&mut self) {
+        if let Some(keys) = self.pending_keys.take() {
+            self.activate_keys(keys);
+        }
+    }
+
+    /// Pick the key generation a packet was sent under by its nonce prefix
+    fn receive_slot(&mut self, nonce_prefix: &[u8]) -> Result<ReceiveSlot> {
+        if let Some(ref retired) = self.previous_receive
+            && retired.expires_at <= Instant::now() {
+            self.previous_receive = None;
+        }
 
-        let hash = hasher.finalize();
-        Ok(hash.into())
+        let current = self.keys.as_ref()
+            .ok_or_else(|| anyhow!("No active session"))?;
+
+        if current.receive.nonce_prefix == nonce_prefix {
+            Ok(ReceiveSlot::Current)
+        } else if self.pending_keys.as_ref().is_some_and(|keys| keys.receive.nonce_prefix == nonce_prefix) {
+            Ok(ReceiveSlot::Pending)
+        } else if self.previous_receive.as_ref().is_some_and(|retired| retired.keys.nonce_prefix == nonce_prefix) {
+            Ok(ReceiveSlot::Previous)
+        } else {
+            Err(anyhow!("Nonce prefix does not match any active key"))
+        }
+    }
+
+    /// Advance both directions to the next key generation with a one-way ratchet.
+    /// Both peers must ratchet in lockstep; no messages are exchanged.
+    pub fn ratchet_keys(&mut self) -> Result<()> {
+        let current = self.keys.as_ref()
+            .ok_or_else(|| anyhow!("No active session"))?;
+        let next = self.derive_keys(&current.chain_key, current.transcript_hash)?;
+
+        self.activate_keys(next);
+        self.key_generation += 1;
+        Ok(())
+    }
+
+    /// Start a rekey when the interval has elapsed, or resend one that went unanswered.
+    /// Only the handshake initiator rekeys on a timer, so the two sides never collide.
+    pub fn poll_rekey(&mut self) -> Result<Option<SecureMessage>> {
+        if !self.is_session_active() {
+            return Ok(None);
+        }
+
+        if let Some(ref mut outstanding) = self.outstanding_rekey {
+            if outstanding.sent_at.elapsed() >= REKEY_RETRY_INTERVAL {
+                outstanding.sent_at = Instant::now();
+                return Ok(Some(outstanding.request.clone()));
+            }
+            return Ok(None);
+        }
+
+        match self.config.rekey_interval {
+            Some(interval) if self.is_initiator && self.last_rekey.elapsed() >= interval => {
+                self.initiate_rekey().map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Begin an in-band rekey with a fresh X25519 exchange
+    pub fn initiate_rekey(&mut self) -> Result<SecureMessage> {
+        let transcript_hash = self.keys.as_ref()
+            .ok_or_else(|| anyhow!("No active session"))?
+            .transcript_hash;
+        let generation = self.key_generation.checked_add(1)
+            .ok_or_else(|| anyhow!("Key generation exhausted"))?;
+
+        let ephemeral_secret = EphemeralSecret::random_from_rng(OsRng);
+        let ephemeral_public = X25519PublicKey::from(&ephemeral_secret).to_bytes();
+
+        let digest = rekey_digest(&transcript_hash, generation, &ephemeral_public, None);
+        let request = SecureMessage::Rekey {
+            generation,
+            ephemeral_public_key: ephemeral_public,
+            signature: self.config.identity_signing_key.sign(&digest).to_bytes(),
+        };
+
+        self.outstanding_rekey = Some(OutstandingRekey {
+            generation,
+            ephemeral_secret,
+            request: request.clone(),
+            sent_at: Instant::now(),
+        });
+
+        Ok(request)
+    }
+
+    /// Handle a peer's Rekey or RekeyResponse, returning any reply to send
+    pub fn process_rekey(&mut self, message: SecureMessage) -> Result<Option<SecureMessage>> {
+        let peer_identity = self.peer_identity
+            .ok_or_else(|| anyhow!("No peer identity established"))?;
+
+        match message {
+            SecureMessage::Rekey { generation, ephemeral_public_key, signature } => {
+                // Retransmitted request: repeat our earlier answer
+                if let Some(ref answered) = self.answered_rekey
+                    && answered.generation == generation
+                    && answered.peer_ephemeral == ephemeral_public_key {
+                    return Ok(Some(answered.response.clone()));
+                }
+
+                // A newer rekey means the peer already runs on the keys we last offered
+                self.promote_pending_keys();
+
+                if generation != self.key_generation.wrapping_add(1) {
+                    return Err(anyhow!("Unexpected rekey generation {}", generation));
+                }
+
+                let transcript_hash = self.keys.as_ref()
+                    .ok_or_else(|| anyhow!("No active session"))?
+                    .transcript_hash;
+
+                let digest = rekey_digest(&transcript_hash, generation, &ephemeral_public_key, None);
+                peer_identity.verify(&digest, &Signature::from_bytes(&signature))
+                    .map_err(|e| anyhow!("Rekey signature verification failed: {}", e))?;
+
+                let ephemeral_secret = EphemeralSecret::random_from_rng(OsRng);
+                let ephemeral_public = X25519PublicKey::from(&ephemeral_secret).to_bytes();
+                let shared_secret = ephemeral_secret.diffie_hellman(&X25519PublicKey::from(ephemeral_public_key));
+
+                let next = self.derive_rekeyed(shared_secret.as_bytes(), generation, &ephemeral_public_key, &ephemeral_public)?;
+
+                // Keep sending under the current keys until the peer proves it has the new ones
+                self.pending_keys = Some(next);
+                self.key_generation = generation;
+
+                let response_digest = rekey_digest(&transcript_hash, generation, &ephemeral_public_key, Some(&ephemeral_public));
+                let response = SecureMessage::RekeyResponse {
+                    generation,
+                    ephemeral_public_key: ephemeral_public,
+                    signature: self.config.identity_signing_key.sign(&response_digest).to_bytes(),
+                };
+
+                self.answered_rekey = Some(AnsweredRekey {
+                    generation,
+                    peer_ephemeral: ephemeral_public_key,
+                    response: response.clone(),
+                });
+
+                Ok(Some(response))
+            }
+            SecureMessage::RekeyResponse { generation, ephemeral_public_key, signature } => {
+                let outstanding = match self.outstanding_rekey {
+                    Some(ref outstanding) if outstanding.generation == generation => outstanding,
+                    // Late duplicate of a response we already applied
+                    _ if generation <= self.key_generation => return Ok(None),
+                    _ => return Err(anyhow!("Unexpected rekey response generation {}", generation)),
+                };
+
+                let transcript_hash = self.keys.as_ref()
+                    .ok_or_else(|| anyhow!("No active session"))?
+                    .transcript_hash;
+                let our_ephemeral = X25519PublicKey::from(&outstanding.ephemeral_secret).to_bytes();
+
+                let digest = rekey_digest(&transcript_hash, generation, &our_ephemeral, Some(&ephemeral_public_key));
+                peer_identity.verify(&digest, &Signature::from_bytes(&signature))
+                    .map_err(|e| anyhow!("Rekey signature verification failed: {}", e))?;
+
+                let outstanding = self.outstanding_rekey.take()
+                    .ok_or_else(|| anyhow!("No rekey in progress"))?;
+                let shared_secret = outstanding.ephemeral_secret.diffie_hellman(&X25519PublicKey::from(ephemeral_public_key));
+
+                let next = self.derive_rekeyed(shared_secret.as_bytes(), generation, &our_ephemeral, &ephemeral_public_key)?;
+                self.activate_keys(next);
+                self.key_generation = generation;
+
+                Ok(None)
+            }
+            _ => Err(anyhow!("Expected rekey message")),
+        }
+    }
+
+    /// Derive the next generation from the current chain key and a fresh DH secret
+    fn derive_rekeyed(
+        &self,
+        shared_secret: &[u8],
+        generation: u32,
+        requester_ephemeral: &[u8; 32],
+        responder_ephemeral: &[u8; 32],
+    ) -> Result<SessionKeys> {
+        let current = self.keys.as_ref()
+            .ok_or_else(|| anyhow!("No active session"))?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(REKEY_LABEL);
+        hasher.update(current.transcript_hash);
+        hasher.update(generation.to_be_bytes());
+        hasher.update(requester_ephemeral);
+        hasher.update(responder_ephemeral);
+        let transcript_hash: [u8; 32] = hasher.finalize().into();
+
+        let mut input_key_material = current.chain_key.to_vec();
+        input_key_material.extend_from_slice(shared_secret);
+
+        self.derive_keys(&input_key_material, transcript_hash)
+    }
+
+    /// Signed notice that we are leaving, valid only within this session
+    pub fn create_disconnect(&self, reason: &str) -> Result<SecureMessage> {
+        let transcript_hash = self.handshake_transcript
+            .filter(|_| self.is_session_active())
+            .ok_or_else(|| anyhow!("No active session"))?;
+
+        Ok(SecureMessage::Disconnect {
+            reason: reason.to_string(),
+            signature: self.config.identity_signing_key.sign(&disconnect_digest(&transcript_hash, reason)).to_bytes(),
+        })
+    }
+
+    /// Check a peer's Disconnect against its identity key and this session, returning the reason
+    pub fn verify_disconnect(&self, message: &SecureMessage) -> Result<String> {
+        let (reason, signature) = match message {
+            SecureMessage::Disconnect { reason, signature } => (reason, signature),
+            _ => return Err(anyhow!("Expected disconnect message")),
+        };
+        let (peer_identity, transcript_hash) = self.peer_identity
+            .zip(self.handshake_transcript)
+            .filter(|_| self.is_session_active())
+            .ok_or_else(|| anyhow!("No active session"))?;
+
+        peer_identity.verify(&disconnect_digest(&transcript_hash, reason), &Signature::from_bytes(signature))
+            .map_err(|e| anyhow!("Disconnect signature verification failed: {}", e))?;
+        Ok(reason.clone())
+    }
+
+    /// Number of rekeys or ratchet steps since the handshake
+    pub fn key_generation(&self) -> u32 {
+        self.key_generation
     }
 
     /// Check if session is active and secure
     pub fn is_session_active(&self) -> bool {
-        self.config.session_cipher.is_some() && self.peer_identity.is_some()
+        self.keys.is_some() && self.peer_identity.is_some()
     }
 
     /// Get peer's verified identity
     pub fn get_peer_identity(&self) -> Option<&VerifyingKey> {
         self.peer_identity.as_ref()
     }
+
+    /// Codec and format both sides settled on in the handshake
+    pub fn negotiated_audio(&self) -> Option<NegotiatedAudio> {
+        self.negotiated_audio
+    }
+
+    /// Why we answered a handshake without starting a session: the offers share no audio format
+    pub fn audio_mismatch(&self) -> Option<&NegotiationError> {
+        self.audio_mismatch.as_ref()
+    }
 }
 
 /// High-level security manager for the tests
@@ -450,7 +1161,6 @@ pub struct SecurityManager {
     session: SecureSession,
     config: SecurityConfig,
     stats: SecurityStats,
-    used_nonces: HashSet<Vec<u8>>, // Track used nonces to prevent replay attacks
 }
 
 #[derive(Debug, Clone)]
@@ -470,6 +1180,7 @@ pub struct KeyExchangeMessage {
     pub identity_public_key: Vec<u8>,
     pub signature: Vec<u8>,
     pub timestamp: u64,
+    pub capabilities: CapabilityOffer,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -502,7 +1213,6 @@ impl SecurityManager {
             session,
             config,
             stats,
-            used_nonces: HashSet::new(),
         })
     }
 
@@ -534,15 +1244,13 @@ impl SecurityManager {
             .map_err(|_| SecurityError::KeyDerivationFailed)?;
 
         match handshake {
-            SecureMessage::Handshake { ephemeral_public_key, identity_public_key, signature, timestamp } => {
+            SecureMessage::Handshake { ephemeral_public_key, identity_public_key, signature, timestamp, capabilities } => {
                 Ok(KeyExchangeMessage {
-                    ephemeral_public_key: BASE64.decode(ephemeral_public_key)
-                        .map_err(|_| SecurityError::InvalidHandshake)?,
-                    identity_public_key: BASE64.decode(identity_public_key)
-                        .map_err(|_| SecurityError::InvalidHandshake)?,
-                    signature: BASE64.decode(signature)
-                        .map_err(|_| SecurityError::InvalidHandshake)?,
+                    ephemeral_public_key: ephemeral_public_key.to_vec(),
+                    identity_public_key: identity_public_key.to_vec(),
+                    signature: signature.to_vec(),
                     timestamp,
+                    capabilities,
                 })
             }
             _ => Err(SecurityError::InvalidHandshake),
@@ -551,24 +1259,24 @@ impl SecurityManager {
 
     pub fn process_key_exchange(&mut self, exchange: &KeyExchangeMessage) -> Result<KeyExchangeMessage, SecurityError> {
         let handshake = SecureMessage::Handshake {
-            ephemeral_public_key: BASE64.encode(&exchange.ephemeral_public_key),
-            identity_public_key: BASE64.encode(&exchange.identity_public_key),
-            signature: BASE64.encode(&exchange.signature),
+            ephemeral_public_key: fixed_bytes(&exchange.ephemeral_public_key)?,
+            identity_public_key: fixed_bytes(&exchange.identity_public_key)?,
+            signature: fixed_bytes(&exchange.signature)?,
             timestamp: exchange.timestamp,
+            capabilities: exchange.capabilities.clone(),
         };
 
         let response = self.session.process_handshake(handshake)
             .map_err(|_| SecurityError::InvalidHandshake)?;
 
         match response {
-            Some(SecureMessage::HandshakeResponse { ephemeral_public_key, signature, timestamp }) => {
+            Some(SecureMessage::HandshakeResponse { identity_public_key, ephemeral_public_key, signature, timestamp, capabilities }) => {
                 Ok(KeyExchangeMessage {
-                    ephemeral_public_key: BASE64.decode(ephemeral_public_key)
-                        .map_err(|_| SecurityError::InvalidHandshake)?,
-                    identity_public_key: self.config.identity_verifying_key.as_bytes().to_vec(),
-                    signature: BASE64.decode(signature)
-                        .map_err(|_| SecurityError::InvalidHandshake)?,
+                    ephemeral_public_key: ephemeral_public_key.to_vec(),
+                    identity_public_key: identity_public_key.to_vec(),
+                    signature: signature.to_vec(),
                     timestamp,
+                    capabilities,
                 })
             }
             _ => Err(SecurityError::InvalidHandshake),
@@ -587,9 +1295,11 @@ impl SecurityManager {
         self.session.peer_identity = Some(peer_identity);
 
         let handshake_response = SecureMessage::HandshakeResponse {
-            ephemeral_public_key: BASE64.encode(&response.ephemeral_public_key),
-            signature: BASE64.encode(&response.signature),
+            identity_public_key: peer_identity_array,
+            ephemeral_public_key: fixed_bytes(&response.ephemeral_public_key)?,
+            signature: fixed_bytes(&response.signature)?,
             timestamp: response.timestamp,
+            capabilities: response.capabilities.clone(),
         };
 
         self.session.process_handshake_response(handshake_response)
@@ -602,55 +1312,41 @@ impl SecurityManager {
     pub fn encrypt_message(&mut self, data: &[u8]) -> Result<EncryptedMessage, SecurityError> {
         self.stats.messages_encrypted += 1;
 
-        // Get the session cipher directly for better performance
-        let cipher = self.session.config.session_cipher.as_ref()
-            .ok_or(SecurityError::SessionNotEstablished)?;
-
-        // Use frame counter for nonce generation (faster than OsRng for bulk operations)
-        self.session.frame_counter += 1;
-        let mut nonce_bytes = [0u8; 12];
-        nonce_bytes[4..].copy_from_slice(&self.session.frame_counter.to_le_bytes());
-
-        // Include session generation counter to ensure nonces differ across key rotations
-        let session_gen = self.stats.key_exchanges_completed; // Use as session identifier
-        nonce_bytes[0..4].copy_from_slice(&(session_gen as u32).to_le_bytes());
-
-        let nonce = Nonce::from_slice(&nonce_bytes);
-
-        // Encrypt directly without base64 encoding/decoding
-        let ciphertext = cipher.encrypt(nonce, data)
-            .map_err(|_| SecurityError::EncryptionFailed("Encryption failed".to_string()))?;
+        if !self.session.is_session_active() {
+            return Err(SecurityError::SessionNotEstablished);
+        }
 
-        Ok(EncryptedMessage {
-            ciphertext,
-            nonce: nonce.as_slice().to_vec(),
-            tag: vec![], // ChaCha20Poly1305 includes auth tag in ciphertext
-        })
+        // Counter-based nonce from the session (faster than OsRng for bulk operations)
+        match self.session.encrypt_audio_frame(data) {
+            Ok(SecureMessage::EncryptedAudio { nonce, ciphertext, .. }) => Ok(EncryptedMessage {
+                ciphertext,
+                nonce: nonce.to_vec(),
+                tag: vec![], // ChaCha20Poly1305 includes auth tag in ciphertext
+            }),
+            Ok(_) => Err(SecurityError::EncryptionFailed("Unexpected message type".to_string())),
+            Err(e) => Err(SecurityError::EncryptionFailed(e.to_string())),
+        }
     }
 
     pub fn decrypt_message(&mut self, encrypted: &EncryptedMessage) -> Result<Vec<u8>, SecurityError> {
-        // Check for nonce reuse (replay attack protection)
-        if self.used_nonces.contains(&encrypted.nonce) {
-            return Err(SecurityError::DecryptionFailed("Nonce reuse detected (replay attack)".to_string()));
-        }
-
         self.stats.messages_decrypted += 1;
 
-        let message = SecureMessage::EncryptedAudio {
-            nonce: BASE64.encode(&encrypted.nonce),
-            ciphertext: BASE64.encode(&encrypted.ciphertext),
-            frame_number: 0,
-        };
+        let nonce: [u8; 12] = encrypted.nonce.as_slice().try_into()
+            .map_err(|_| SecurityError::DecryptionFailed("Invalid nonce length".to_string()))?;
 
-        let result = self.session.decrypt_audio_frame(message)
-            .map_err(|e| SecurityError::DecryptionFailed(e.to_string()));
+        // The counter travels in the nonce; the session's replay window rejects reuse
+        let mut counter_bytes = [0u8; 8];
+        counter_bytes.copy_from_slice(&nonce[4..]);
 
-        // Only add nonce to used set if decryption was successful
-        if result.is_ok() {
-            self.used_nonces.insert(encrypted.nonce.clone());
-        }
+        let message = SecureMessage::EncryptedAudio {
+            frame_number: u64::from_be_bytes(counter_bytes),
+            timestamp: 0,
+            nonce,
+            ciphertext: encrypted.ciphertext.clone(),
+        };
 
-        result
+        self.session.decrypt_audio_frame(message)
+            .map_err(|e| SecurityError::DecryptionFailed(e.to_string()))
     }
 
     pub fn encrypt_audio_frame(&mut self, frame: &crate::realtime_audio::AudioFrame) -> Result<EncryptedAudioFrame, SecurityError> {
@@ -666,12 +1362,10 @@ impl SecurityManager {
             .map_err(|e| SecurityError::EncryptionFailed(e.to_string()))?;
 
         match encrypted {
-            SecureMessage::EncryptedAudio { nonce, ciphertext, frame_number } => {
+            SecureMessage::EncryptedAudio { nonce, ciphertext, frame_number, .. } => {
                 Ok(EncryptedAudioFrame {
-                    encrypted_data: BASE64.decode(ciphertext)
-                        .map_err(|_| SecurityError::EncryptionFailed("Base64 decode failed".to_string()))?,
-                    nonce: BASE64.decode(nonce)
-                        .map_err(|_| SecurityError::EncryptionFailed("Base64 decode failed".to_string()))?,
+                    encrypted_data: ciphertext,
+                    nonce: nonce.to_vec(),
                     frame_number,
                 })
             }
@@ -680,25 +1374,20 @@ impl SecurityManager {
     }
 
     pub fn decrypt_audio_frame(&mut self, encrypted: &EncryptedAudioFrame) -> Result<crate::realtime_audio::AudioFrame, SecurityError> {
-        // Check for nonce reuse (replay attack protection)
-        if self.used_nonces.contains(&encrypted.nonce) {
-            return Err(SecurityError::DecryptionFailed("Nonce reuse detected (replay attack)".to_string()));
-        }
-
         self.stats.audio_frames_decrypted += 1;
 
         let message = SecureMessage::EncryptedAudio {
-            nonce: BASE64.encode(&encrypted.nonce),
-            ciphertext: BASE64.encode(&encrypted.encrypted_data),
             frame_number: encrypted.frame_number,
+            timestamp: 0,
+            nonce: encrypted.nonce.as_slice().try_into()
+                .map_err(|_| SecurityError::DecryptionFailed("Invalid nonce length".to_string()))?,
+            ciphertext: encrypted.encrypted_data.clone(),
         };
 
+        // Replay protection happens in the session's sliding window
         let audio_bytes = self.session.decrypt_audio_frame(message)
             .map_err(|e| SecurityError::DecryptionFailed(e.to_string()))?;
 
-        // Only add nonce to used set if decryption was successful
-        self.used_nonces.insert(encrypted.nonce.clone());
-
         // Convert bytes back to audio frame
         let mut samples = Vec::new();
         for chunk in audio_bytes.chunks_exact(4) {
@@ -715,34 +1404,109 @@ impl SecurityManager {
             return Err(SecurityError::SessionNotEstablished);
         }
 
-        // Generate new session key using existing shared secret and peer identity
-        if let (Some(shared_secret), Some(peer_identity)) =
-            (&self.session.shared_secret, &self.session.peer_identity) {
-
-            // Derive new session key with incremented frame counter for uniqueness
-            self.session.frame_counter += 1;
-            let new_key = self.session.derive_session_key(shared_secret, peer_identity)?;
-
-            // Create new cipher with rotated key
-            let new_cipher = ChaCha20Poly1305::new(Key::from_slice(&new_key));
-            self.session.config.session_cipher = Some(new_cipher);
-
-            // Reset frame counter for new session
-            self.session.frame_counter = 0;
-
-            // Clear used nonces since they're not relevant to the new session
-            self.used_nonces.clear();
+        // Ratchet both directions forward; the old keys cannot be recovered from the new ones
+        self.session.ratchet_keys()?;
 
-            // Increment key rotation counter for nonce uniqueness
-            self.stats.key_exchanges_completed += 1;
+        // Increment key rotation counter
+        self.stats.key_exchanges_completed += 1;
 
-            Ok(())
-        } else {
-            Err(SecurityError::SessionNotEstablished)
-        }
+        Ok(())
     }
 
     pub fn get_stats(&self) -> &SecurityStats {
         &self.stats
     }
-}
\ No newline at end of file
+}
+
+/// Convert a variable-length key or signature field into its fixed-size form
+fn fixed_bytes<const N: usize>(bytes: &[u8]) -> Result<[u8; N], SecurityError> {
+    bytes.try_into().map_err(|_| SecurityError::InvalidHandshake)
+}
+
+/// Digest signed by each side of a rekey, bound to the current transcript and generation
+fn rekey_digest(
+    transcript_hash: &[u8; 32],
+    generation: u32,
+    requester_ephemeral: &[u8; 32],
+    responder_ephemeral: Option<&[u8; 32]>,
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(REKEY_LABEL);
+    hasher.update(if responder_ephemeral.is_some() { b"response" as &[u8] } else { b"request" });
+    hasher.update(transcript_hash);
+    hasher.update(generation.to_be_bytes());
+    hasher.update(requester_ephemeral);
+    if let Some(responder_ephemeral) = responder_ephemeral {
+        hasher.update(responder_ephemeral);
+    }
+    hasher.finalize().into()
+}
+
+/// Digest a peer signs to leave: bound to the handshake transcript so it cannot end another session
+fn disconnect_digest(transcript_hash: &[u8; 32], reason: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(DISCONNECT_LABEL);
+    hasher.update(transcript_hash);
+    hasher.update(reason.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Build a 96-bit nonce from a direction's prefix and a 64-bit packet counter
+fn counter_nonce(prefix: [u8; 4], counter: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..4].copy_from_slice(&prefix);
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+/// Initiator's half of the transcript: version, identity, ephemeral key, timestamp and audio offer
+fn initiator_transcript_hash(
+    initiator_identity: &VerifyingKey,
+    initiator_ephemeral: &X25519PublicKey,
+    timestamp: u64,
+    capabilities: &CapabilityOffer,
+) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    hasher.update(TRANSCRIPT_LABEL);
+    hasher.update([HANDSHAKE_VERSION]);
+    hasher.update(initiator_identity.as_bytes());
+    hasher.update(initiator_ephemeral.as_bytes());
+    hasher.update(timestamp.to_be_bytes());
+    hasher.update(encoded_offer(capabilities)?);
+    Ok(hasher.finalize().into())
+}
+
+/// Full transcript: the initiator's half extended with the responder's identity, ephemeral key
+/// and audio offer
+fn full_transcript_hash(
+    initiator_transcript: &[u8; 32],
+    responder_identity: &VerifyingKey,
+    responder_ephemeral: &X25519PublicKey,
+    timestamp: u64,
+    capabilities: &CapabilityOffer,
+) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    hasher.update(TRANSCRIPT_LABEL);
+    hasher.update([HANDSHAKE_VERSION]);
+    hasher.update(initiator_transcript);
+    hasher.update(responder_identity.as_bytes());
+    hasher.update(responder_ephemeral.as_bytes());
+    hasher.update(timestamp.to_be_bytes());
+    hasher.update(encoded_offer(capabilities)?);
+    Ok(hasher.finalize().into())
+}
+
+/// An offer as it appears on the wire, so the signature covers exactly what is sent
+fn encoded_offer(capabilities: &CapabilityOffer) -> Result<Vec<u8>> {
+    let mut encoded = Vec::new();
+    capabilities.encode(&mut encoded)?;
+    Ok(encoded)
+}
+
+/// Digest a party signs: its role label followed by the transcript hash
+fn transcript_signing_digest(role: &[u8], transcript_hash: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(role);
+    hasher.update(transcript_hash);
+    hasher.finalize().into()
+}
@@ -0,0 +1,376 @@
+use anyhow::{Result, anyhow};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// Fixed value in every RFC 5389 header, used to tell STUN apart from other traffic on the socket
+pub const MAGIC_COOKIE: u32 = 0x2112_A442;
+
+/// Default STUN port
+pub const DEFAULT_STUN_PORT: u16 = 3478;
+
+/// Header: type (2) + length (2) + magic cookie (4) + transaction id (12)
+const HEADER_LEN: usize = 20;
+
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const ATTR_ICE_CONTROLLING: u16 = 0x802A;
+
+const FAMILY_IPV4: u8 = 0x01;
+const FAMILY_IPV6: u8 = 0x02;
+
+/// First retransmission timeout; doubled after every unanswered request (RFC 5389 §7.2.1)
+pub(crate) const STUN_INITIAL_RTO: Duration = Duration::from_millis(250);
+
+/// Requests sent before a binding transaction gives up
+pub(crate) const STUN_MAX_ATTEMPTS: u32 = 4;
+
+/// Identifies a request and its response
+pub type TransactionId = [u8; 12];
+
+/// STUN decoding error types
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StunError {
+    Truncated { needed: usize, available: usize },
+    NotStun,
+    LengthMismatch { declared: usize, actual: usize },
+    UnsupportedMessageType(u16),
+    MissingMappedAddress,
+    InvalidAttribute(&'static str),
+}
+
+impl std::fmt::Display for StunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StunError::Truncated { needed, available } => write!(f, "Truncated STUN message: need {} bytes, have {}", needed, available),
+            StunError::NotStun => write!(f, "Not a STUN message"),
+            StunError::LengthMismatch { declared, actual } => write!(f, "STUN length mismatch: header declares {} bytes, message has {}", declared, actual),
+            StunError::UnsupportedMessageType(kind) => write!(f, "Unsupported STUN message type: {:#06x}", kind),
+            StunError::MissingMappedAddress => write!(f, "Binding response carries no mapped address"),
+            StunError::InvalidAttribute(msg) => write!(f, "Invalid STUN attribute: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for StunError {}
+
+/// The subset of RFC 5389 Humr speaks: binding requests and their success responses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StunMessage {
+    /// Connectivity checks carry ICE-CONTROLLING with the sender's tie-breaker (RFC 8445 §7.1.3)
+    BindingRequest { transaction_id: TransactionId, tie_breaker: Option<u64> },
+    BindingSuccess { transaction_id: TransactionId, mapped_address: SocketAddr },
+}
+
+impl StunMessage {
+    pub fn transaction_id(&self) -> TransactionId {
+        match self {
+            StunMessage::BindingRequest { transaction_id, .. } |
+            StunMessage::BindingSuccess { transaction_id, .. } => *transaction_id,
+        }
+    }
+}
+
+pub fn new_transaction_id() -> TransactionId {
+    rand::random()
+}
+
+/// Cheap check for demultiplexing STUN from protocol packets on a shared socket
+pub fn is_stun_message(packet: &[u8]) -> bool {
+    packet.len() >= HEADER_LEN
+        && packet[0] & 0xC0 == 0
+        && packet[4..8] == MAGIC_COOKIE.to_be_bytes()
+        && u16::from_be_bytes([packet[2], packet[3]]) as usize == packet.len() - HEADER_LEN
+}
+
+pub fn encode_stun_message(message: &StunMessage) -> Vec<u8> {
+    let mut attributes = Vec::new();
+    let message_type = match message {
+        StunMessage::BindingRequest { tie_breaker, .. } => {
+            if let Some(tie_breaker) = tie_breaker {
+                put_attribute(&mut attributes, ATTR_ICE_CONTROLLING, &tie_breaker.to_be_bytes());
+            }
+            BINDING_REQUEST
+        }
+        StunMessage::BindingSuccess { transaction_id, mapped_address } => {
+            put_attribute(&mut attributes, ATTR_XOR_MAPPED_ADDRESS, &encode_address(*mapped_address, Some(transaction_id)));
+            BINDING_SUCCESS
+        }
+    };
+
+    let mut packet = Vec::with_capacity(HEADER_LEN + attributes.len());
+    packet.extend_from_slice(&message_type.to_be_bytes());
+    packet.extend_from_slice(&(attributes.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    packet.extend_from_slice(&message.transaction_id());
+    packet.extend_from_slice(&attributes);
+    packet
+}
+
+pub fn decode_stun_message(packet: &[u8]) -> Result<StunMessage, StunError> {
+    if packet.len() < HEADER_LEN {
+        return Err(StunError::Truncated { needed: HEADER_LEN, available: packet.len() });
+    }
+    if packet[0] & 0xC0 != 0 || packet[4..8] != MAGIC_COOKIE.to_be_bytes() {
+        return Err(StunError::NotStun);
+    }
+
+    let declared = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+    if declared != packet.len() - HEADER_LEN || !declared.is_multiple_of(4) {
+        return Err(StunError::LengthMismatch { declared, actual: packet.len() - HEADER_LEN });
+    }
+
+    let message_type = u16::from_be_bytes([packet[0], packet[1]]);
+    let mut transaction_id = [0u8; 12];
+    transaction_id.copy_from_slice(&packet[8..HEADER_LEN]);
+
+    let mut xor_mapped = None;
+    let mut mapped = None;
+    let mut tie_breaker = None;
+    let mut attributes = &packet[HEADER_LEN..];
+
+    while !attributes.is_empty() {
+        if attributes.len() < 4 {
+            return Err(StunError::Truncated { needed: 4, available: attributes.len() });
+        }
+        let kind = u16::from_be_bytes([attributes[0], attributes[1]]);
+        let len = u16::from_be_bytes([attributes[2], attributes[3]]) as usize;
+        // Values are padded to a multiple of four bytes
+        let padded = len.div_ceil(4) * 4;
+        if attributes.len() < 4 + padded {
+            return Err(StunError::Truncated { needed: 4 + padded, available: attributes.len() });
+        }
+        let value = &attributes[4..4 + len];
+
+        match kind {
+            ATTR_XOR_MAPPED_ADDRESS => xor_mapped = Some(decode_address(value, Some(&transaction_id))?),
+            ATTR_MAPPED_ADDRESS => mapped = Some(decode_address(value, None)?),
+            ATTR_ICE_CONTROLLING => {
+                let value: [u8; 8] = value.try_into()
+                    .map_err(|_| StunError::InvalidAttribute("ICE-CONTROLLING must be 8 bytes"))?;
+                tie_breaker = Some(u64::from_be_bytes(value));
+            }
+            // Servers may add SOFTWARE, FINGERPRINT and the like
+            _ => {}
+        }
+        attributes = &attributes[4 + padded..];
+    }
+
+    match message_type {
+        BINDING_REQUEST => Ok(StunMessage::BindingRequest { transaction_id, tie_breaker }),
+        BINDING_SUCCESS => {
+            let mapped_address = xor_mapped.or(mapped).ok_or(StunError::MissingMappedAddress)?;
+            Ok(StunMessage::BindingSuccess { transaction_id, mapped_address })
+        }
+        other => Err(StunError::UnsupportedMessageType(other)),
+    }
+}
+
+fn put_attribute(attributes: &mut Vec<u8>, kind: u16, value: &[u8]) {
+    attributes.extend_from_slice(&kind.to_be_bytes());
+    attributes.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    attributes.extend_from_slice(value);
+    attributes.resize(attributes.len().div_ceil(4) * 4, 0);
+}
+
+/// MAPPED-ADDRESS value, XORed with the cookie and transaction id for XOR-MAPPED-ADDRESS
+fn encode_address(addr: SocketAddr, xor_with: Option<&TransactionId>) -> Vec<u8> {
+    let mask = address_mask(xor_with);
+    let port_mask = if xor_with.is_some() { (MAGIC_COOKIE >> 16) as u16 } else { 0 };
+
+    let mut value = vec![0u8];
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            value.push(FAMILY_IPV4);
+            value.extend_from_slice(&(addr.port() ^ port_mask).to_be_bytes());
+            value.extend(ip.octets().iter().zip(&mask).map(|(byte, mask)| byte ^ mask));
+        }
+        IpAddr::V6(ip) => {
+            value.push(FAMILY_IPV6);
+            value.extend_from_slice(&(addr.port() ^ port_mask).to_be_bytes());
+            value.extend(ip.octets().iter().zip(&mask).map(|(byte, mask)| byte ^ mask));
+        }
+    }
+    value
+}
+
+fn decode_address(value: &[u8], xor_with: Option<&TransactionId>) -> Result<SocketAddr, StunError> {
+    if value.len() < 4 {
+        return Err(StunError::InvalidAttribute("address too short"));
+    }
+    let mask = address_mask(xor_with);
+    let port_mask = if xor_with.is_some() { (MAGIC_COOKIE >> 16) as u16 } else { 0 };
+    let port = u16::from_be_bytes([value[2], value[3]]) ^ port_mask;
+    let octets = &value[4..];
+
+    let ip = match value[1] {
+        FAMILY_IPV4 if octets.len() == 4 => {
+            let mut ip = [0u8; 4];
+            for (out, (byte, mask)) in ip.iter_mut().zip(octets.iter().zip(&mask)) {
+                *out = byte ^ mask;
+            }
+            IpAddr::V4(Ipv4Addr::from(ip))
+        }
+        FAMILY_IPV6 if octets.len() == 16 => {
+            let mut ip = [0u8; 16];
+            for (out, (byte, mask)) in ip.iter_mut().zip(octets.iter().zip(&mask)) {
+                *out = byte ^ mask;
+            }
+            IpAddr::V6(Ipv6Addr::from(ip))
+        }
+        _ => return Err(StunError::InvalidAttribute("unknown address family or length")),
+    };
+
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Magic cookie followed by the transaction id; all zeros for the plain MAPPED-ADDRESS
+fn address_mask(xor_with: Option<&TransactionId>) -> [u8; 16] {
+    let mut mask = [0u8; 16];
+    if let Some(transaction_id) = xor_with {
+        mask[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        mask[4..].copy_from_slice(transaction_id);
+    }
+    mask
+}
+
+/// Ask a STUN server for the address `socket` is seen from.
+/// Only for sockets nothing else is reading; a [`crate::network::NetworkManager`] uses
+/// `discover_reflexive_address` instead so its receive task sees the response.
+pub async fn binding_request(socket: &UdpSocket, server: SocketAddr) -> Result<SocketAddr> {
+    let transaction_id = new_transaction_id();
+    let request = encode_stun_message(&StunMessage::BindingRequest { transaction_id, tie_breaker: None });
+    let mut buffer = vec![0u8; 512];
+    let mut rto = STUN_INITIAL_RTO;
+
+    for _ in 0..STUN_MAX_ATTEMPTS {
+        socket.send_to(&request, server).await?;
+        let deadline = tokio::time::Instant::now() + rto;
+
+        while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await {
+            let (len, from) = received?;
+            if from != server {
+                continue;
+            }
+            if let Ok(StunMessage::BindingSuccess { transaction_id: id, mapped_address }) = decode_stun_message(&buffer[..len])
+                && id == transaction_id {
+                return Ok(mapped_address);
+            }
+        }
+        rto *= 2;
+    }
+
+    Err(anyhow!("STUN server {} did not answer", server))
+}
+
+/// Answer a binding request with the address it came from
+pub fn binding_response(packet: &[u8], from: SocketAddr) -> Option<Vec<u8>> {
+    match decode_stun_message(packet) {
+        Ok(StunMessage::BindingRequest { transaction_id, .. }) => {
+            Some(encode_stun_message(&StunMessage::BindingSuccess { transaction_id, mapped_address: from }))
+        }
+        _ => None,
+    }
+}
+
+/// Minimal STUN responder: answers binding requests and nothing else.
+/// Enough to find reflexive addresses on a LAN or in tests.
+pub struct StunServer {
+    socket: UdpSocket,
+}
+
+impl StunServer {
+    pub async fn bind(addr: &str) -> Result<Self> {
+        let socket = UdpSocket::bind(addr).await
+            .map_err(|e| anyhow!("Failed to bind STUN socket to {}: {}", addr, e))?;
+        Ok(Self { socket })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Serve until the socket fails
+    pub async fn run(&self) -> Result<()> {
+        info!("STUN responder listening on {}", self.local_addr()?);
+        let mut buffer = vec![0u8; 512];
+
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(e) if e.kind() == std::io::ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(anyhow!("STUN receive error: {}", e)),
+            };
+
+            if let Some(response) = binding_response(&buffer[..len], from)
+                && let Err(e) = self.socket.send_to(&response, from).await {
+                debug!("Failed to answer STUN request from {}: {}", from, e);
+            }
+        }
+    }
+}
+
+/// Where a candidate address came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CandidateKind {
+    /// Address of a local interface
+    Host,
+    /// Public address a STUN server saw us at
+    ServerReflexive,
+    /// Address allocated on a TURN-style relay, tried last
+    Relayed,
+}
+
+impl CandidateKind {
+    /// RFC 8445 type preference: direct paths first
+    fn type_preference(self) -> u32 {
+        match self {
+            CandidateKind::Host => 126,
+            CandidateKind::ServerReflexive => 100,
+            CandidateKind::Relayed => 0,
+        }
+    }
+}
+
+/// Transport address a peer can try to reach us at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Candidate {
+    pub kind: CandidateKind,
+    pub addr: SocketAddr,
+    /// Higher is tried first
+    pub priority: u32,
+}
+
+impl Candidate {
+    pub fn new(kind: CandidateKind, addr: SocketAddr) -> Self {
+        // RFC 8445 §5.1.2.1 with a single component and one address per type
+        let priority = (kind.type_preference() << 24) + (65535 << 8) + (256 - 1);
+        Self { kind, addr, priority }
+    }
+
+    pub fn host(addr: SocketAddr) -> Self {
+        Self::new(CandidateKind::Host, addr)
+    }
+
+    pub fn server_reflexive(addr: SocketAddr) -> Self {
+        Self::new(CandidateKind::ServerReflexive, addr)
+    }
+
+    pub fn relayed(addr: SocketAddr) -> Self {
+        Self::new(CandidateKind::Relayed, addr)
+    }
+}
+
+/// Address of the interface that routes to the internet, without sending anything
+pub fn local_interface_ip() -> Option<IpAddr> {
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0").ok()?;
+    // Connecting a UDP socket only selects a route; the address is from TEST-NET-1
+    socket.connect("192.0.2.1:9").ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_unspecified()).then_some(ip)
+}
@@ -15,6 +15,10 @@ use ratatui::{
     Frame, Terminal,
 };
 use std::io;
+use std::sync::{Arc, Mutex};
+
+use crate::known_peers::TrustError;
+use crate::security::ShortAuthString;
 use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
@@ -57,6 +61,10 @@ pub struct TerminalApp {
     pub is_muted: bool,
     pub show_help: bool,
     pub last_update: Instant,
+    /// Code the user reads to the peer to rule out a man in the middle
+    pub safety_code: Option<ShortAuthString>,
+    pub peer_verified: bool,
+    verification_requested: bool,
     peer_list_state: ListState,
 }
 
@@ -76,6 +84,9 @@ impl Default for TerminalApp {
             is_muted: false,
             show_help: false,
             last_update: Instant::now(),
+            safety_code: None,
+            peer_verified: false,
+            verification_requested: false,
             peer_list_state: ListState::default(),
         }
     }
@@ -129,6 +140,48 @@ impl TerminalApp {
         }
     }
 
+    /// Switch to the call view once a peer is connected, showing its safety code
+    pub fn enter_call(&mut self, code: Option<ShortAuthString>, verified: bool) {
+        self.mode = AppMode::Connected;
+        self.connection_status = ConnectionStatus::Connected;
+        self.set_safety_code(code, verified);
+    }
+
+    /// Show the session's safety code and whether this peer was verified before
+    pub fn set_safety_code(&mut self, code: Option<ShortAuthString>, verified: bool) {
+        self.safety_code = code;
+        self.peer_verified = verified;
+        self.verification_requested = false;
+    }
+
+    /// User confirmed the codes match; the caller persists it via `take_verification_request`
+    pub fn confirm_safety_code(&mut self) {
+        if self.safety_code.is_some() && !self.peer_verified {
+            self.peer_verified = true;
+            self.verification_requested = true;
+        }
+    }
+
+    /// Returns true once after the user confirmed the safety code
+    pub fn take_verification_request(&mut self) -> bool {
+        std::mem::take(&mut self.verification_requested)
+    }
+
+    /// Show a failed connection, calling out identity key changes explicitly
+    pub fn report_connection_error(&mut self, error: &anyhow::Error) {
+        let message = match error.downcast_ref::<TrustError>() {
+            Some(TrustError::KeyChanged { name, was_verified: true, .. }) => {
+                format!("⚠️  VERIFIED identity key for {} has CHANGED - likely impersonation. {}", name, error)
+            }
+            Some(TrustError::KeyChanged { name, .. }) => {
+                format!("⚠️  Identity key for {} has CHANGED - possible impersonation. {}", name, error)
+            }
+            Some(trust_error) => format!("🔒 {}", trust_error),
+            None => error.to_string(),
+        };
+        self.connection_status = ConnectionStatus::Failed(message);
+    }
+
     pub fn toggle_help(&mut self) {
         self.show_help = !self.show_help;
     }
@@ -167,9 +220,11 @@ impl TerminalApp {
                 _ => {}
             },
             AppMode::Connected => match key {
+                KeyCode::Char('v') => self.confirm_safety_code(),
                 KeyCode::Backspace => {
                     self.mode = AppMode::MainMenu;
                     self.connection_status = ConnectionStatus::Disconnected;
+                    self.set_safety_code(None, false);
                 }
                 _ => {}
             },
@@ -197,6 +252,11 @@ impl TerminalApp {
 }
 
 pub fn run_terminal_ui() -> Result<()> {
+    run_shared_terminal_ui(Arc::new(Mutex::new(TerminalApp::new())))
+}
+
+/// Run the terminal UI over state the application also updates, e.g. with the call's safety code
+pub fn run_shared_terminal_ui(app: Arc<Mutex<TerminalApp>>) -> Result<()> {
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -204,9 +264,7 @@ pub fn run_terminal_ui() -> Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Create app state
-    let mut app = TerminalApp::new();
-    let res = run_app(&mut terminal, &mut app);
+    let res = run_app(&mut terminal, &app);
 
     // Restore terminal
     disable_raw_mode()?;
@@ -224,21 +282,26 @@ pub fn run_terminal_ui() -> Result<()> {
     Ok(())
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut TerminalApp) -> io::Result<()> {
+fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &Mutex<TerminalApp>) -> io::Result<()> {
+    // Hold the state only while drawing and handling input, so the application can update it
+    let lock = || app.lock().map_err(|_| io::Error::other("Terminal UI state poisoned"));
     loop {
-        terminal.draw(|f| ui(f, app))?;
+        {
+            let mut state = lock()?;
+            terminal.draw(|f| ui(f, &mut state))?;
+        }
 
         if event::poll(Duration::from_millis(100))? {
             if let Event::Key(key) = event::read()? {
                 if key.kind == KeyEventKind::Press {
-                    if !app.handle_key_event(key.code) {
+                    if !lock()?.handle_key_event(key.code) {
                         return Ok(());
                     }
                 }
             }
         }
 
-        app.update();
+        lock()?.update();
     }
 }
 
@@ -275,7 +338,7 @@ fn ui(f: &mut Frame, app: &mut TerminalApp) {
         AppMode::MainMenu => "1: Start Voice Chat | 2: Join Voice Chat | q: Quit | h: Help",
         AppMode::HostMode => "Share room code with others | Backspace: Back | q: Quit",
         AppMode::JoinMode => "↑↓: Select | Enter: Connect | Backspace: Back | q: Quit",
-        AppMode::Connected => "m: Mute/Unmute | Backspace: Disconnect | q: Quit",
+        AppMode::Connected => "m: Mute/Unmute | v: Codes match | Backspace: Disconnect | q: Quit",
     };
 
     let footer = Paragraph::new(footer_text)
@@ -465,7 +528,7 @@ fn render_connected_mode(f: &mut Frame, area: ratatui::layout::Rect, app: &Termi
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(6),  // Connection info
+            Constraint::Length(8),  // Connection info
             Constraint::Length(4),  // Audio levels
             Constraint::Min(0),     // Chat area
         ])
@@ -488,6 +551,8 @@ fn render_connected_mode(f: &mut Frame, area: ratatui::layout::Rect, app: &Termi
             Span::styled("🔒 Encrypted", Style::default().fg(Color::Green)),
         ]),
         Line::from(""),
+        safety_code_line(app),
+        Line::from(""),
         Line::from(if app.is_muted {
             vec![Span::styled("🔇 MUTED", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))]
         } else {
@@ -537,6 +602,23 @@ fn render_connected_mode(f: &mut Frame, area: ratatui::layout::Rect, app: &Termi
     f.render_widget(chat_block, chunks[2]);
 }
 
+/// Safety code with verification state, or a notice while the session is unauthenticated
+fn safety_code_line(app: &TerminalApp) -> Line<'static> {
+    match (&app.safety_code, app.peer_verified) {
+        (Some(code), true) => Line::from(vec![
+            Span::raw("Safety code: "),
+            Span::styled(code.to_string(), Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::styled("  ✅ Verified", Style::default().fg(Color::Green)),
+        ]),
+        (Some(code), false) => Line::from(vec![
+            Span::raw("Safety code: "),
+            Span::styled(code.to_string(), Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::styled("  Compare with your peer, press v if it matches", Style::default().fg(Color::Yellow)),
+        ]),
+        (None, _) => Line::from(Span::styled("Safety code: unavailable", Style::default().fg(Color::DarkGray))),
+    }
+}
+
 fn render_help_overlay(f: &mut Frame) {
     let popup_area = centered_rect(80, 80, f.size());
     f.render_widget(Clear, popup_area);
@@ -562,6 +644,7 @@ fn render_help_overlay(f: &mut Frame) {
         Line::from(""),
         Line::from(Span::styled("Connected Mode:", Style::default().add_modifier(Modifier::BOLD))),
         Line::from("  m - Toggle mute/unmute"),
+        Line::from("  v - Confirm the safety code matches your peer's"),
         Line::from("  • Real-time audio quality monitoring"),
         Line::from("  • Automatic noise suppression"),
         Line::from(""),
@@ -0,0 +1,144 @@
+#[cfg(test)]
+mod capabilities_tests {
+    use crate::capabilities::*;
+    use crate::codec::CodecKind;
+    use crate::config::AppConfig;
+    use crate::opus_codec::OpusConfig;
+    use crate::realtime_audio::{AudioFormat, AudioProfile};
+    use crate::wire::Reader;
+
+    fn offer(codecs: &[CodecKind], sample_rates: &[u32], channels: &[u16], frame_durations_ms: &[u32]) -> CapabilityOffer {
+        CapabilityOffer {
+            protocol_version: AUDIO_PROTOCOL_VERSION,
+            codecs: codecs.to_vec(),
+            sample_rates: sample_rates.to_vec(),
+            channels: channels.to_vec(),
+            frame_durations_ms: frame_durations_ms.to_vec(),
+            fec: true,
+            dtx: false,
+        }
+    }
+
+    #[test]
+    fn test_offer_round_trip() {
+        let original = offer(&CodecKind::ALL, &[48000, 16000, 8000], &[2, 1], &[20, 60, 10]);
+        let mut encoded = Vec::new();
+        original.encode(&mut encoded).unwrap();
+        assert_eq!(encoded.len(), 1 + 5 + 13 + 3 + 4 + 1);
+
+        let mut reader = Reader::new(&encoded);
+        assert_eq!(CapabilityOffer::decode(&mut reader).unwrap(), original);
+        assert_eq!(reader.remaining(), 0);
+
+        // Cut short anywhere, it fails to decode rather than misreading
+        for len in 1..encoded.len() {
+            assert!(CapabilityOffer::decode(&mut Reader::new(&encoded[..len])).is_err());
+        }
+    }
+
+    #[test]
+    fn test_unknown_codecs_passed_over() {
+        // A newer peer offering a codec we have never heard of alongside one we run
+        let mut encoded = Vec::new();
+        offer(&[CodecKind::Pcma, CodecKind::G722], &[16000], &[1], &[20]).encode(&mut encoded).unwrap();
+        encoded[2] = 127;
+        let theirs = CapabilityOffer::decode(&mut Reader::new(&encoded)).unwrap();
+        assert_eq!(theirs.codecs, vec![CodecKind::G722]);
+
+        // Offering only codecs we do not know leaves negotiation to explain the mismatch
+        encoded[3] = 126;
+        let theirs = CapabilityOffer::decode(&mut Reader::new(&encoded)).unwrap();
+        assert!(theirs.codecs.is_empty());
+        let ours = offer(&[CodecKind::Opus], &[48000], &[2], &[20]);
+        assert!(matches!(ours.negotiate(&theirs, true), Err(NegotiationError::NoCommonCodec { .. })));
+    }
+
+    #[test]
+    fn test_initiator_preferences_win() {
+        let alice = offer(&[CodecKind::Opus, CodecKind::G722], &[48000, 16000], &[2, 1], &[20, 60]);
+        let bob = offer(&[CodecKind::G722, CodecKind::Opus], &[16000, 48000], &[1, 2], &[60, 20]);
+
+        // Both sides agree whoever dialled
+        let alice_calls = alice.negotiate(&bob, true).unwrap();
+        assert_eq!(bob.negotiate(&alice, false).unwrap(), alice_calls);
+        assert_eq!((alice_calls.codec, alice_calls.format), (CodecKind::Opus, AudioFormat::STANDARD));
+
+        let bob_calls = bob.negotiate(&alice, true).unwrap();
+        assert_eq!(alice.negotiate(&bob, false).unwrap(), bob_calls);
+        assert_eq!(bob_calls.codec, CodecKind::G722);
+        assert_eq!(bob_calls.format, AudioFormat { sample_rate: 16000, channels: 1, frame_duration_ms: 60 });
+        assert!(!bob_calls.fec, "FEC is an Opus feature");
+    }
+
+    #[test]
+    fn test_settles_on_common_format() {
+        // Opus drops to the best rate and channel count both run
+        let alice = offer(&[CodecKind::Opus], &[48000, 16000], &[2, 1], &[20]);
+        let bob = offer(&[CodecKind::Opus], &[16000], &[1], &[20]);
+        let audio = alice.negotiate(&bob, true).unwrap();
+        assert_eq!(audio.format, AudioFormat { sample_rate: 16000, channels: 1, frame_duration_ms: 20 });
+        assert!(audio.fec && !audio.dtx);
+
+        // G.722 is passed over when one side cannot run 16 kHz mono
+        let alice = offer(&[CodecKind::G722, CodecKind::Opus], &[48000], &[2], &[20]);
+        let bob = offer(&[CodecKind::G722, CodecKind::Opus], &[48000, 16000], &[2, 1], &[20]);
+        assert_eq!(alice.negotiate(&bob, true).unwrap().codec, CodecKind::Opus);
+
+        // FEC and DTX only when both sides support them
+        let bob = CapabilityOffer { fec: false, dtx: true, ..bob };
+        let alice = CapabilityOffer { dtx: true, ..alice };
+        let audio = alice.negotiate(&bob, true).unwrap();
+        assert!(!audio.fec && audio.dtx);
+        assert_eq!(audio.to_string(), "Opus, 48 kHz stereo in 20 ms frames, FEC off, DTX on");
+    }
+
+    #[test]
+    fn test_mismatches_explain_themselves() {
+        let ours = offer(&[CodecKind::Opus, CodecKind::G722], &[48000], &[2], &[20]);
+
+        let newer = CapabilityOffer { protocol_version: AUDIO_PROTOCOL_VERSION + 1, ..ours.clone() };
+        let error = ours.negotiate(&newer, true).unwrap_err();
+        assert_eq!(error, NegotiationError::ProtocolVersion { ours: AUDIO_PROTOCOL_VERSION, theirs: AUDIO_PROTOCOL_VERSION + 1 });
+        assert_eq!(error.to_string(), "Peer speaks audio protocol version 2, we speak version 1");
+
+        let error = ours.negotiate(&offer(&[CodecKind::Pcma], &[8000], &[1], &[20]), false).unwrap_err();
+        assert_eq!(error.to_string(), "No codec in common: we offer Opus, G.722; peer offers G.711 A-law");
+
+        let error = ours.negotiate(&offer(&[CodecKind::Opus], &[48000], &[2], &[60]), true).unwrap_err();
+        assert_eq!(error.to_string(), "No frame size in common: we use 20 ms; peer uses 60 ms");
+
+        let error = ours.negotiate(&offer(&[CodecKind::G722], &[48000], &[2], &[20]), true).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No audio format in common for G.722: we run 48000 Hz with 2 channels; peer runs 48000 Hz with 2 channels",
+        );
+    }
+
+    #[test]
+    fn test_config_offers_all_runnable_audio() {
+        let mut config = AppConfig::default();
+        assert_eq!(config.to_capability_offer(), CapabilityOffer {
+            fec: config.processing.codec.fec_enabled,
+            dtx: config.processing.codec.dtx_enabled,
+            ..offer(&CodecKind::ALL, &[48000, 24000, 16000, 12000, 8000], &[2, 1], &[20, 10, 40, 60])
+        });
+
+        // The profile's format leads; everything else the pipelines run follows
+        config.processing.codec.profile = AudioProfile::LowBandwidth;
+        config.processing.codec.codecs = vec![CodecKind::Pcmu, CodecKind::Opus];
+        let offered = config.to_capability_offer();
+        assert_eq!(offered.codecs, [CodecKind::Pcmu, CodecKind::Opus]);
+        assert_eq!(offered.sample_rates, [16000, 48000, 24000, 12000, 8000]);
+        assert_eq!(offered.channels, [1, 2]);
+        assert_eq!(offered.frame_durations_ms, [60, 10, 20, 40]);
+
+        // Against itself, the offer settles on the audio the config prefers
+        assert_eq!(offered.negotiate(&offered, true).unwrap(), config.preferred_audio());
+
+        // A peer that only runs Opus at 48 kHz stereo in 20 ms frames is still reachable
+        let peer = CapabilityOffer::for_format(CodecKind::Opus, AudioFormat::STANDARD, &OpusConfig::default());
+        let audio = offered.negotiate(&peer, true).unwrap();
+        assert_eq!((audio.codec, audio.format), (CodecKind::Opus, AudioFormat::STANDARD));
+        assert_eq!(peer.negotiate(&offered, false).unwrap(), audio);
+    }
+}
@@ -0,0 +1,197 @@
+#[cfg(test)]
+mod codec_tests {
+    use crate::codec::*;
+    use crate::g711::*;
+    use crate::g722::G722Codec;
+    use crate::jitter_buffer::JitterBufferConfig;
+    use crate::opus_codec::{OpusCodec, OpusConfig};
+    use crate::realtime_audio::{AudioFormat, AudioFrame, CapturePipeline, PlaybackPipeline};
+    use crate::redundancy::{RedundancyConfig, RedundancyMode, RedundantPayload};
+
+    fn tone(format: AudioFormat, frequency: f32, index: usize) -> AudioFrame {
+        let samples = format.frame_samples();
+        AudioFrame::with_format((0..samples)
+            .map(|n| {
+                let t = (n + index * samples) as f32 / format.sample_rate as f32;
+                0.4 * (2.0 * std::f32::consts::PI * frequency * t).sin()
+            })
+            .collect(), format)
+    }
+
+    /// Signal-to-noise ratio of `output` against `input`, at the codec delay that fits best
+    fn snr_db(input: &[f32], output: &[f32], max_delay: usize) -> f32 {
+        (0..max_delay)
+            .map(|delay| {
+                let pairs = input.iter().zip(&output[delay..]);
+                let (signal, noise) = pairs.fold((0.0, 0.0), |(signal, noise), (x, y)| {
+                    (signal + x * x, noise + (x - y) * (x - y))
+                });
+                10.0 * (signal / noise.max(1e-12)).log10()
+            })
+            .fold(f32::MIN, f32::max)
+    }
+
+    #[test]
+    fn test_g711_companding() {
+        assert_eq!(linear_to_ulaw(0), 0xFF);
+        assert_eq!(linear_to_alaw(0), 0xD5);
+        assert_eq!(ulaw_to_linear(linear_to_ulaw(i16::MAX)), 32124);
+        assert_eq!(alaw_to_linear(linear_to_alaw(i16::MIN)), -32256);
+
+        // Every code decodes to a value that encodes back to it (bar μ-law's negative zero)
+        for code in 0..=255u8 {
+            assert_eq!(linear_to_alaw(alaw_to_linear(code)), code, "A-law code {:#x}", code);
+            if code != 0x7F {
+                assert_eq!(linear_to_ulaw(ulaw_to_linear(code)), code, "μ-law code {:#x}", code);
+            }
+        }
+
+        // Logarithmic steps: the error stays within a small fraction of the sample
+        for sample in (-32000..32000i16).step_by(97) {
+            for decoded in [ulaw_to_linear(linear_to_ulaw(sample)), alaw_to_linear(linear_to_alaw(sample))] {
+                let error = (sample as i32 - decoded as i32).abs();
+                assert!(error <= (sample as i32).abs() / 16 + 16, "{} came back as {}", sample, decoded);
+            }
+        }
+    }
+
+    #[test]
+    fn test_g711_codec_frames() {
+        for (law, kind) in [(G711Law::MuLaw, CodecKind::Pcmu), (G711Law::ALaw, CodecKind::Pcma)] {
+            let mut codec = G711Codec::new(law, 20).unwrap();
+            let format = codec.format();
+            assert_eq!((codec.kind(), format.sample_rate, format.channels), (kind, 8000, 1));
+
+            let frame = tone(format, 440.0, 0);
+            let encoded = codec.encode(&frame).unwrap();
+            assert_eq!(encoded.len(), 160);
+            let decoded = codec.decode(&encoded).unwrap();
+            assert_eq!(decoded.format, format);
+            assert!(snr_db(&frame.samples, &decoded.samples, 1) > 30.0);
+
+            // Concealment repeats the last frame, fading
+            let first = codec.conceal().unwrap();
+            let second = codec.conceal().unwrap();
+            assert!(first.peak() > second.peak() && second.peak() > 0.0);
+
+            // Frames of the wrong size are refused and counted
+            assert!(codec.encode(&AudioFrame::silence()).is_err());
+            assert!(codec.decode(&encoded[..100]).is_err());
+            assert!(codec.decode_fec(&encoded).is_err());
+
+            // Fixed rate: bitrate requests change nothing
+            codec.set_bitrate(16000).unwrap();
+            let stats = codec.stats();
+            assert_eq!((stats.codec, stats.bitrate), (kind, 64000));
+            assert_eq!((stats.frames_encoded, stats.frames_decoded), (1, 1));
+            assert_eq!((stats.encoding_errors, stats.decoding_errors), (1, 1));
+            assert_eq!(stats.average_compression_ratio, 4.0);
+        }
+    }
+
+    #[test]
+    fn test_g722_codes_wideband_speech_band() {
+        let mut codec = G722Codec::new(20).unwrap();
+        let format = codec.format();
+        assert_eq!((format.sample_rate, format.channels), (16000, 1));
+
+        // Tones in the lower and the higher sub-band both survive
+        for frequency in [500.0, 1000.0, 5000.0] {
+            codec.reset().unwrap();
+            let (mut input, mut output) = (Vec::new(), Vec::new());
+            for index in 0..10 {
+                let frame = tone(format, frequency, index);
+                let encoded = codec.encode(&frame).unwrap();
+                assert_eq!(encoded.len(), 160);
+                input.extend_from_slice(&frame.samples);
+                output.extend(codec.decode(&encoded).unwrap().samples);
+            }
+
+            // Skip the adaptation at the start
+            let snr = snr_db(&input[1600..3000], &output[1600..], 48);
+            assert!(snr > 15.0, "{} Hz tone at {:.1} dB SNR", frequency, snr);
+        }
+
+        let stats = codec.stats();
+        assert_eq!((stats.codec, stats.bitrate, stats.frames_encoded), (CodecKind::G722, 64000, 30));
+        assert!(codec.decode(&[0; 10]).is_err());
+    }
+
+    #[test]
+    fn test_codec_kind_selection() {
+        for kind in CodecKind::ALL {
+            assert_eq!(CodecKind::from_payload_type(kind.payload_type()), Some(kind));
+        }
+        assert_eq!(CodecKind::from_payload_type(100), None);
+
+        let low = AudioFormat::LOW_BANDWIDTH;
+        assert_eq!(CodecKind::Opus.format(low), low);
+        assert_eq!(CodecKind::G722.format(low), AudioFormat { sample_rate: 16000, channels: 1, frame_duration_ms: 60 });
+        assert_eq!(CodecKind::Pcmu.format(AudioFormat::STANDARD), AudioFormat { sample_rate: 8000, channels: 1, frame_duration_ms: 20 });
+
+        // Every codec behind the trait codes frames in its own format
+        for kind in CodecKind::ALL {
+            let mut codec = kind.create(&OpusConfig::default()).unwrap();
+            let frame = tone(codec.format(), 440.0, 0);
+            let encoded = codec.encode(&frame).unwrap();
+            assert_eq!(codec.decode(&encoded).unwrap().samples.len(), frame.samples.len());
+            assert_eq!(codec.stats().frames_decoded, 1);
+        }
+    }
+
+    #[test]
+    fn test_opus_through_trait() {
+        let mut codec: Box<dyn VoiceCodec> = Box::new(OpusCodec::new(OpusConfig::default()).unwrap());
+        assert!(CodecKind::Opus.has_adjustable_bitrate());
+
+        codec.set_bitrate(32000).unwrap();
+        let encoded = codec.encode(&tone(codec.format(), 440.0, 0)).unwrap();
+        codec.decode(&encoded).unwrap();
+        assert_eq!(codec.conceal().unwrap().samples.len(), codec.format().frame_samples());
+
+        let stats = codec.stats();
+        assert_eq!((stats.codec, stats.bitrate, stats.frame_duration_ms), (CodecKind::Opus, 32000, 20));
+        assert_eq!((stats.frames_encoded, stats.frames_decoded), (1, 1));
+    }
+
+    #[test]
+    fn test_pipelines_carry_chosen_codec() {
+        let redundancy = RedundancyConfig { mode: RedundancyMode::Always, depth: 1, ..RedundancyConfig::default() };
+        let jitter_config = JitterBufferConfig { initial_target_size: 1, ..JitterBufferConfig::default() };
+
+        for kind in [CodecKind::G722, CodecKind::Pcma] {
+            let mut capture = CapturePipeline::new(None, None, OpusConfig::default()).unwrap()
+                .with_codec(kind).unwrap()
+                .with_redundancy(redundancy).unwrap();
+            let mut playback = PlaybackPipeline::new(jitter_config, OpusConfig::default()).unwrap()
+                .with_codec(kind).unwrap();
+            assert_eq!((capture.codec(), playback.codec()), (kind, kind));
+            assert_eq!(capture.format(), kind.format(AudioFormat::STANDARD));
+
+            // Frame 2 is lost and rebuilt from the copy in frame 3
+            let mut played = Vec::new();
+            for sequence in 0..6 {
+                let packet = capture.process(&mut tone(capture.format(), 440.0, sequence)).unwrap();
+                let payload = RedundantPayload::decode(&packet).unwrap();
+                assert_eq!(payload.payload_type, kind.payload_type());
+                if sequence != 2 {
+                    playback.push_packet(sequence as u64, packet).unwrap();
+                }
+                while let Some(frame) = playback.next_frame() {
+                    assert_eq!(frame.format, capture.format());
+                    played.push(frame.sequence);
+                }
+            }
+            assert_eq!(played, (0..6).collect::<Vec<u32>>());
+            assert_eq!(playback.frames_recovered(), 1);
+            assert_eq!(capture.codec_stats().codec, kind);
+
+            // A fork decodes the same codec; Opus packets are not mistaken for it
+            let mut fork = playback.fork().unwrap();
+            assert_eq!(fork.codec(), kind);
+            let mut opus = CapturePipeline::new(None, None, OpusConfig::default()).unwrap();
+            let opus_packet = opus.process(&mut tone(opus.format(), 440.0, 0)).unwrap();
+            assert!(fork.push_packet(0, opus_packet).is_err());
+        }
+    }
+}
@@ -0,0 +1,264 @@
+#[cfg(test)]
+mod congestion_tests {
+    use crate::congestion::*;
+    use crate::netsim::{Impairment, ImpairmentConfig, LossModel};
+    use crate::opus_codec::OpusConfig;
+    use crate::realtime_audio::{AudioFrame, CapturePipeline};
+    use std::net::SocketAddr;
+    use std::time::{Duration, Instant};
+
+    const FRAME: Duration = Duration::from_millis(20);
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([192, 0, 2, 1], port))
+    }
+
+    fn report(loss_fraction: f32, delay_trend: f32) -> FeedbackReport {
+        FeedbackReport { highest_sequence: 50, loss_fraction, jitter_ms: 2.0, delay_trend, received_bitrate: 48000 }
+    }
+
+    /// Receive statistics for one second of 20ms frames, arriving `extra_delay(i)` late
+    fn one_interval(stats: &mut ReceiveStatistics, start: Instant, lost: &[u64], extra_delay: impl Fn(u64) -> Duration) -> FeedbackReport {
+        for sequence in 1..=50u64 {
+            if lost.contains(&sequence) {
+                continue;
+            }
+            let sent = FRAME * sequence as u32;
+            stats.record(sequence, 1_000_000 + sent.as_millis() as u64, 120, start + sent + extra_delay(sequence));
+        }
+        assert!(stats.report(start + FRAME * 25).is_none(), "reported before the interval ended");
+        stats.report(start + FEEDBACK_INTERVAL + FRAME * 2).expect("report due")
+    }
+
+    #[test]
+    fn test_feedback_report_round_trip() {
+        let original = FeedbackReport { highest_sequence: 123_456, loss_fraction: 0.25, jitter_ms: 3.5, delay_trend: -1.25, received_bitrate: 40_000 };
+        let decoded = FeedbackReport::decode(&original.encode()).unwrap();
+        assert_eq!(decoded.highest_sequence, original.highest_sequence);
+        assert!((decoded.loss_fraction - 0.25).abs() < 0.001);
+        assert_eq!((decoded.jitter_ms, decoded.delay_trend, decoded.received_bitrate), (3.5, -1.25, 40_000));
+
+        let mut encoded = original.encode();
+        assert!(FeedbackReport::decode(&encoded[..encoded.len() - 1]).is_err());
+        encoded.push(0);
+        assert!(FeedbackReport::decode(&encoded).is_err());
+        encoded.pop();
+        encoded[0] = 0x7F;
+        assert!(FeedbackReport::decode(&encoded).is_err());
+    }
+
+    #[test]
+    fn test_receive_statistics_measure_loss_and_delay_trend() {
+        let start = Instant::now();
+        let mut stats = ReceiveStatistics::new(start);
+
+        // Steady link: nothing lost, no jitter, flat delay
+        let steady = one_interval(&mut stats, start, &[], |_| Duration::from_millis(30));
+        assert_eq!(steady.highest_sequence, 50);
+        assert_eq!(steady.loss_fraction, 0.0);
+        assert!(steady.jitter_ms < 0.5 && steady.delay_trend.abs() < 0.5, "{:?}", steady);
+        assert!(steady.received_bitrate > 0);
+
+        // Five frames lost while a queue adds 2ms per 20ms frame, arriving every 22ms
+        let mut stats = ReceiveStatistics::new(start);
+        let congested = one_interval(&mut stats, start, &[3, 9, 17, 28, 40], |sequence| Duration::from_millis(2 * sequence));
+        assert!((congested.loss_fraction - 0.1).abs() < 0.001, "{:?}", congested);
+        assert!((congested.delay_trend - 2000.0 / 22.0).abs() < 5.0, "{:?}", congested);
+        assert!(congested.jitter_ms > 1.0);
+    }
+
+    #[test]
+    fn test_clean_link_probes_up_to_the_ceiling() {
+        let config = CongestionConfig { initial_bitrate: 80_000, ..CongestionConfig::default() };
+        let mut controller = CongestionController::new(config).unwrap();
+        let start = Instant::now();
+
+        let decision = controller.on_report(peer(1), report(0.0, 0.0), start).unwrap();
+        assert_eq!(decision.reason, RateChangeReason::Headroom);
+        assert_eq!(decision.target.bitrate, 84_000);
+
+        // At most one increase per interval
+        assert!(controller.on_report(peer(1), report(0.0, 0.0), start + FRAME).is_none());
+
+        for i in 1..10 {
+            controller.on_report(peer(1), report(0.0, 0.0), start + FEEDBACK_INTERVAL * i);
+        }
+        assert_eq!(controller.target().bitrate, config.max_bitrate);
+        assert!(controller.on_report(peer(1), report(0.0, 0.0), start + FEEDBACK_INTERVAL * 20).is_none());
+    }
+
+    #[test]
+    fn test_loss_and_queue_growth_back_off() {
+        let mut controller = CongestionController::new(CongestionConfig::default()).unwrap();
+        let now = Instant::now();
+
+        let decision = controller.on_report(peer(1), report(0.2, 0.0), now).unwrap();
+        assert_eq!(decision.reason, RateChangeReason::PacketLoss);
+        assert_eq!(decision.target.bitrate, 57_600);
+        assert_eq!(decision.target.complexity, CongestionConfig::default().degraded_complexity);
+
+        let decision = controller.on_report(peer(1), report(0.0, 25.0), now).unwrap();
+        assert_eq!(decision.reason, RateChangeReason::QueueGrowth);
+        assert_eq!(decision.target.bitrate, 48_960);
+
+        // Sustained heavy loss bottoms out at the floor, in mono
+        for _ in 0..20 {
+            controller.on_report(peer(1), report(0.5, 0.0), now);
+        }
+        let target = controller.target();
+        assert_eq!(target.bitrate, CongestionConfig::default().min_bitrate);
+        assert!(target.mono);
+    }
+
+    #[test]
+    fn test_complexity_recovers_only_once_loss_clears() {
+        let mut controller = CongestionController::new(CongestionConfig::default()).unwrap();
+        let now = Instant::now();
+        let full = CongestionConfig::default().complexity;
+
+        controller.on_report(peer(1), report(0.06, 0.0), now);
+        assert!(controller.target().complexity < full);
+
+        // Moderate loss keeps it down, a clean report restores it
+        assert!(controller.on_report(peer(1), report(0.03, 0.0), now + FEEDBACK_INTERVAL).is_none());
+        let decision = controller.on_report(peer(1), report(0.0, 0.0), now + FEEDBACK_INTERVAL * 2).unwrap();
+        assert_eq!(decision.target.complexity, full);
+        assert!(!decision.target.mono);
+    }
+
+    #[test]
+    fn test_redundancy_follows_loss_with_hysteresis() {
+        let mut controller = CongestionController::new(CongestionConfig::default()).unwrap();
+        let now = Instant::now();
+
+        // Moderate loss only turns redundancy on, which counts as a loss response
+        let decision = controller.on_report(peer(1), report(0.04, 0.0), now).unwrap();
+        assert!(decision.target.redundancy && !decision.previous.redundancy);
+        assert_eq!(decision.reason, RateChangeReason::PacketLoss);
+
+        // It stays on through lighter loss and goes once the link is clean
+        assert!(controller.on_report(peer(1), report(0.025, 0.0), now).is_none());
+        assert!(!controller.on_report(peer(1), report(0.0, 0.0), now).unwrap().target.redundancy);
+
+        // Without a threshold it is never requested
+        let config = CongestionConfig { redundancy_above_loss: None, ..CongestionConfig::default() };
+        let mut controller = CongestionController::new(config).unwrap();
+        controller.on_report(peer(1), report(0.3, 0.0), now);
+        assert!(!controller.target().redundancy);
+    }
+
+    #[test]
+    fn test_redundancy_switch_at_fixed_bitrate() {
+        let fixed = EncoderTarget { bitrate: 32_000, complexity: 5, mono: false, redundancy: false, packet_loss_percent: 5 };
+        let mut switch = RedundancySwitch::new(0.03, fixed);
+        let now = Instant::now();
+
+        // Same hysteresis as the controller; only the redundancy flag ever changes
+        assert!(switch.on_report(peer(1), &report(0.01, 0.0), now).is_none());
+        assert_eq!(switch.on_report(peer(1), &report(0.04, 0.0), now), Some(EncoderTarget { redundancy: true, ..fixed }));
+        assert!(switch.on_report(peer(1), &report(0.025, 0.0), now).is_none());
+        assert_eq!(switch.on_report(peer(1), &report(0.0, 0.0), now), Some(fixed));
+
+        // The worst peer decides until its reports expire
+        switch.on_report(peer(2), &report(0.2, 0.0), now);
+        assert!(switch.on_report(peer(1), &report(0.0, 0.0), now).is_none());
+        assert_eq!(switch.on_report(peer(1), &report(0.0, 0.0), now + Duration::from_secs(5)), Some(fixed));
+    }
+
+    #[test]
+    fn test_expected_loss_tracks_reports_for_fec() {
+        let mut controller = CongestionController::new(CongestionConfig::default()).unwrap();
+        let now = Instant::now();
+        assert_eq!(controller.target().packet_loss_percent, 5);
+
+        // Rounded up to the next 5% as soon as it is reported
+        let decision = controller.on_report(peer(1), report(0.12, 0.0), now).unwrap();
+        assert_eq!(decision.target.packet_loss_percent, 15);
+        assert_eq!(decision.reason, RateChangeReason::PacketLoss);
+
+        // Held through lighter loss, then eased off a step per clean report
+        controller.on_report(peer(1), report(0.03, 0.0), now);
+        assert_eq!(controller.target().packet_loss_percent, 15);
+        let steps: Vec<u8> = (1..=4)
+            .map(|i| {
+                controller.on_report(peer(1), report(0.0, 0.0), now + FEEDBACK_INTERVAL * i);
+                controller.target().packet_loss_percent
+            })
+            .collect();
+        assert_eq!(steps, vec![10, 5, 0, 0]);
+    }
+
+    #[test]
+    fn test_worst_peer_decides_until_its_reports_expire() {
+        let mut controller = CongestionController::new(CongestionConfig::default()).unwrap();
+        let now = Instant::now();
+
+        controller.on_report(peer(1), report(0.3, 0.0), now);
+        let degraded = controller.target().bitrate;
+
+        // A healthy second peer cannot raise the rate while the first still struggles
+        assert!(controller.on_report(peer(2), report(0.0, 0.0), now + FEEDBACK_INTERVAL).unwrap().target.bitrate < degraded);
+
+        // Once the lossy peer stops reporting, the healthy one lets it climb
+        let later = now + Duration::from_secs(10);
+        let decision = controller.on_report(peer(2), report(0.0, 0.0), later).unwrap();
+        assert_eq!(decision.reason, RateChangeReason::Headroom);
+    }
+
+    #[test]
+    fn test_invalid_bounds_rejected() {
+        assert!(CongestionController::new(CongestionConfig { min_bitrate: 70_000, ..CongestionConfig::default() }).is_err());
+        assert!(CongestionController::new(CongestionConfig { max_bitrate: 600_000, ..CongestionConfig::default() }).is_err());
+        assert!(CongestionController::new(CongestionConfig { degraded_complexity: 9, ..CongestionConfig::default() }).is_err());
+    }
+
+    #[test]
+    fn test_simulated_lossy_link_lowers_bitrate() {
+        // Thirty seconds of audio over a 25% loss link, replayed identically every run
+        let mut impairment = Impairment::new(ImpairmentConfig {
+            loss: LossModel::Random { probability: 0.25 },
+            delay: Duration::from_millis(40),
+            jitter: Duration::from_millis(10),
+            seed: 99,
+            ..ImpairmentConfig::default()
+        }).unwrap();
+        let sends: Vec<(Duration, Vec<u8>)> = (1..=1500u64)
+            .map(|sequence| (FRAME * sequence as u32, sequence.to_be_bytes().to_vec()))
+            .collect();
+
+        let start = Instant::now();
+        let mut stats = ReceiveStatistics::new(start);
+        let mut controller = CongestionController::new(CongestionConfig::default()).unwrap();
+        let mut next_report = FEEDBACK_INTERVAL;
+        for (arrival, packet) in impairment.simulate(sends) {
+            while arrival >= next_report {
+                if let Some(report) = stats.report(start + next_report) {
+                    controller.on_report(peer(1), report, start + next_report);
+                }
+                next_report += FEEDBACK_INTERVAL;
+            }
+            let sequence = u64::from_be_bytes(packet.try_into().unwrap());
+            let sent_ms = 1_000_000 + (FRAME * sequence as u32).as_millis() as u64;
+            stats.record(sequence, sent_ms, 100, start + arrival);
+        }
+
+        let target = controller.target();
+        assert_eq!(target.bitrate, CongestionConfig::default().min_bitrate);
+        assert!(target.mono && target.complexity == CongestionConfig::default().degraded_complexity);
+    }
+
+    #[test]
+    fn test_capture_pipeline_applies_encoder_target() {
+        let control = EncoderControl::default();
+        let mut pipeline = CapturePipeline::new(None, None, OpusConfig::default()).unwrap()
+            .with_encoder_control(control.clone());
+
+        control.request(EncoderTarget { bitrate: 24_000, complexity: 2, mono: true, redundancy: false, packet_loss_percent: 20 });
+        pipeline.process(&mut AudioFrame::silence()).unwrap();
+
+        let stats = pipeline.codec_stats();
+        assert_eq!((stats.bitrate, stats.complexity), (24_000, 2));
+        assert_eq!(stats.packet_loss_percent, 20);
+        assert!(control.take().is_none());
+    }
+}
@@ -8,6 +8,8 @@ mod integration_tests {
     use crate::echo_cancellation::{EchoCancellationProcessor, EchoCancellationConfig};
     use crate::security::{SecurityManager, SecurityConfig};
     use crate::app::VocalCommunicationApp;
+    use crate::config::AppConfig;
+    use crate::transport::{LoopbackTransport, Transport, TransportKind};
     use std::time::{Duration, Instant};
     use std::sync::{Arc, Mutex};
 
@@ -242,6 +244,148 @@ mod integration_tests {
         println!("App integration test passed (smoke test)");
     }
 
+    #[tokio::test]
+    async fn test_two_apps_call_over_loopback() {
+        let (alice_transport, bob_transport) = LoopbackTransport::pair();
+        let bob_addr = bob_transport.local_addr().unwrap();
+        let mut alice = VocalCommunicationApp::with_transport(AppConfig::default(), SecurityConfig::new().unwrap(), Arc::new(alice_transport));
+        let mut bob = VocalCommunicationApp::with_transport(AppConfig::default(), SecurityConfig::new().unwrap(), Arc::new(bob_transport));
+
+        // Bob answers while Alice dials: full handshake over in-process channels
+        let bob_host = bob_addr.ip().to_string();
+        let (answered, dialled) = tokio::join!(
+            bob.accept_peer(),
+            alice.connect_to_peer(&bob_host, bob_addr.port()),
+        );
+        dialled.unwrap();
+        let alice_addr = answered.unwrap();
+
+        let alice_network = alice.network_manager();
+        let bob_network = bob.network_manager();
+        assert!(alice_network.lock().unwrap().is_secure_session_active().await);
+        assert!(bob_network.lock().unwrap().is_secure_session_active().await);
+        assert_eq!(alice_network.lock().unwrap().transport_kind(), Some(TransportKind::Loopback));
+
+        // Both directions through the pipelines the app builds from its configuration
+        for (sender, receiver, expected_from) in [(&alice, &bob, alice_addr), (&bob, &alice, bob_addr)] {
+            let mut capture = VocalCommunicationApp::build_capture_pipeline(sender.get_config(), &sender.call_audio()).unwrap();
+            let mut playback = VocalCommunicationApp::build_playback_pipeline(receiver.get_config(), &receiver.call_audio()).unwrap();
+            let sender_network = sender.network_manager();
+            let receiver_network = receiver.network_manager();
+
+            let mut played = Vec::new();
+            for _ in 0..25 {
+                let mut frame = generate_speech_frame();
+                let packet = capture.process(&mut frame).unwrap();
+                sender_network.lock().unwrap().send_audio_frame(&packet).await.unwrap();
+                tokio::task::yield_now().await;
+
+                while let Some(received) = receiver_network.lock().unwrap().receive_audio_frame().unwrap() {
+                    assert_eq!(received.peer, expected_from);
+                    playback.push_packet(received.sequence_number, received.payload).unwrap();
+                }
+                if let Some(frame) = playback.next_frame() {
+                    played.push(frame);
+                }
+            }
+
+            // Nothing is lost in-process, so everything decodes without concealment
+            assert!(played.len() >= 20, "Only {} frames played", played.len());
+            assert_eq!(playback.frames_concealed(), 0);
+            let rms = played.last().map(|frame| {
+                (frame.samples.iter().map(|s| s * s).sum::<f32>() / frame.samples.len() as f32).sqrt()
+            }).unwrap();
+            assert!(rms > 0.01, "Decoded audio is silent: rms {:.4}", rms);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apps_run_the_audio_they_negotiate() {
+        use crate::codec::CodecKind;
+        use crate::realtime_audio::AudioFormat;
+
+        // Alice prefers G.722; Bob runs Opus by default but offers G.722 too
+        let (alice_transport, bob_transport) = LoopbackTransport::pair();
+        let bob_addr = bob_transport.local_addr().unwrap();
+        let mut alice_config = AppConfig::default();
+        alice_config.processing.codec.codecs = vec![CodecKind::G722, CodecKind::Opus];
+        let mut alice = VocalCommunicationApp::with_transport(alice_config, SecurityConfig::new().unwrap(), Arc::new(alice_transport));
+        let mut bob = VocalCommunicationApp::with_transport(AppConfig::default(), SecurityConfig::new().unwrap(), Arc::new(bob_transport));
+        assert_eq!(bob.call_audio().codec, CodecKind::Opus);
+
+        // Alice dials, so her preference wins, and both apps switch to it
+        let bob_host = bob_addr.ip().to_string();
+        let (answered, dialled) = tokio::join!(
+            bob.accept_peer(),
+            alice.connect_to_peer(&bob_host, bob_addr.port()),
+        );
+        dialled.unwrap();
+        answered.unwrap();
+        let audio = alice.call_audio();
+        assert_eq!(bob.call_audio(), audio);
+        assert_eq!((audio.codec, audio.format), (CodecKind::G722, AudioFormat { sample_rate: 16000, channels: 1, frame_duration_ms: 20 }));
+
+        // Bob's rebuilt pipelines talk to Alice's
+        let mut capture = VocalCommunicationApp::build_capture_pipeline(bob.get_config(), &bob.call_audio()).unwrap();
+        let mut playback = VocalCommunicationApp::build_playback_pipeline(alice.get_config(), &alice.call_audio()).unwrap();
+        assert_eq!((capture.format(), playback.format()), (audio.format, audio.format));
+
+        let samples = audio.format.frame_samples();
+        let mut played = 0;
+        for index in 0..10 {
+            let mut frame = AudioFrame::with_format((0..samples)
+                .map(|n| 0.3 * (((n + index * samples) as f32) * 0.1).sin())
+                .collect(), audio.format);
+            let packet = capture.process(&mut frame).unwrap();
+            bob.network_manager().lock().unwrap().send_audio_frame(&packet).await.unwrap();
+            tokio::task::yield_now().await;
+
+            while let Some(received) = alice.network_manager().lock().unwrap().receive_audio_frame().unwrap() {
+                playback.push_packet(received.sequence_number, received.payload).unwrap();
+            }
+            while let Some(frame) = playback.next_frame() {
+                assert_eq!(frame.format, audio.format);
+                played += 1;
+            }
+        }
+        assert!(played >= 5, "Only {} frames played", played);
+        assert_eq!(playback.frames_concealed(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_app_shows_and_persists_safety_code() {
+        let (alice_transport, bob_transport) = LoopbackTransport::pair();
+        let bob_addr = bob_transport.local_addr().unwrap();
+        let mut alice = VocalCommunicationApp::with_transport(AppConfig::default(), SecurityConfig::new().unwrap(), Arc::new(alice_transport));
+        let mut bob = VocalCommunicationApp::with_transport(AppConfig::default(), SecurityConfig::new().unwrap(), Arc::new(bob_transport));
+
+        let bob_host = bob_addr.ip().to_string();
+        let (answered, dialled) = tokio::join!(
+            bob.accept_peer(),
+            alice.connect_to_peer(&bob_host, bob_addr.port()),
+        );
+        dialled.unwrap();
+        answered.unwrap();
+
+        // Both UIs show the same code for a new, unverified peer
+        let alice_ui = alice.terminal_app();
+        let bob_ui = bob.terminal_app();
+        let code = alice_ui.lock().unwrap().safety_code.clone().expect("No safety code shown");
+        assert_eq!(bob_ui.lock().unwrap().safety_code.as_ref(), Some(&code));
+        assert!(!alice_ui.lock().unwrap().peer_verified);
+        assert!(!alice.persist_verification().await.unwrap());
+
+        // Alice presses 'v': the app pins Bob as verified, once
+        alice_ui.lock().unwrap().handle_key_event(crossterm::event::KeyCode::Char('v'));
+        assert!(alice.persist_verification().await.unwrap());
+        assert!(!alice.persist_verification().await.unwrap());
+        assert!(alice.network_manager().lock().unwrap().is_peer_verified().await);
+        assert!(!bob.network_manager().lock().unwrap().is_peer_verified().await);
+
+        alice.disconnect_from_peer();
+        assert!(alice_ui.lock().unwrap().safety_code.is_none());
+    }
+
     #[test]
     fn test_performance_under_load() {
         // Test system performance with continuous processing
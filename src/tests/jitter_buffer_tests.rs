@@ -3,6 +3,7 @@ mod jitter_buffer_tests {
     use crate::jitter_buffer::*;
     use crate::jitter_buffer::{AdaptiveJitterBuffer, AudioPacket};
     use crate::realtime_audio::AudioFrame;
+    use crate::transport::TransportKind;
     use std::time::{Duration, Instant};
 
     #[test]
@@ -364,6 +365,90 @@ mod jitter_buffer_tests {
         }
     }
 
+    #[test]
+    fn test_playout_reports_lost_packets() {
+        let config = JitterBufferConfig::default();
+        let mut buffer = AdaptiveJitterBuffer::new(config).unwrap();
+
+        // Sequence 2 never arrives; 3, 4 and 5 are waiting behind it
+        for seq in [0u32, 1, 3, 4, 5] {
+            let packet = AudioPacket::encoded(vec![seq as u8; 10], seq as u64 * 20, seq);
+            buffer.add_packet(packet).unwrap();
+        }
+
+        let mut slots = Vec::new();
+        for _ in 0..7 {
+            slots.push(match buffer.next_playout() {
+                PlayoutSlot::Packet(packet) => {
+                    assert_eq!(packet.payload, vec![packet.sequence_number as u8; 10]);
+                    format!("packet {}", packet.sequence_number)
+                }
+                PlayoutSlot::Lost { sequence_number } => format!("lost {}", sequence_number),
+                PlayoutSlot::Empty => "empty".to_string(),
+            });
+        }
+
+        assert_eq!(slots, vec![
+            "packet 0", "packet 1", "lost 2", "packet 3", "packet 4", "packet 5", "empty",
+        ]);
+    }
+
+    #[test]
+    fn test_playout_waits_for_reordered_packet() {
+        let config = JitterBufferConfig::default();
+        let mut buffer = AdaptiveJitterBuffer::new(config).unwrap();
+
+        // Only one packet behind the gap: below target depth, so keep waiting
+        buffer.add_packet(AudioPacket::encoded(vec![1], 20, 1)).unwrap();
+        assert!(matches!(buffer.next_playout(), PlayoutSlot::Empty));
+
+        // The reordered packet shows up late and is played in order
+        buffer.add_packet(AudioPacket::encoded(vec![0], 0, 0)).unwrap();
+        assert!(matches!(buffer.next_playout(), PlayoutSlot::Packet(p) if p.sequence_number == 0));
+        assert!(matches!(buffer.next_playout(), PlayoutSlot::Packet(p) if p.sequence_number == 1));
+    }
+
+    #[test]
+    fn test_stream_transport_discards_stall_backlog() {
+        let config = JitterBufferConfig::for_transport(TransportKind::Tcp);
+        assert!(config.discard_stall_backlog);
+        assert!(config.initial_target_size > JitterBufferConfig::default().initial_target_size);
+        assert_eq!(JitterBufferConfig::for_transport(TransportKind::Udp), JitterBufferConfig::default());
+
+        // A retransmission stall releases 20 frames at once; resume near the newest
+        let mut buffer = AdaptiveJitterBuffer::new(config).unwrap();
+        for seq in 0u32..20 {
+            buffer.add_packet(AudioPacket::encoded(vec![seq as u8], seq as u64 * 20, seq)).unwrap();
+        }
+
+        let target = config.initial_target_size as u32;
+        assert!(matches!(buffer.next_playout(), PlayoutSlot::Packet(p) if p.sequence_number == 20 - target));
+        assert_eq!(buffer.get_stats().late_packets, (20 - target) as u64);
+
+        // The same burst over UDP is played out in full
+        let mut buffer = AdaptiveJitterBuffer::new(JitterBufferConfig::default()).unwrap();
+        for seq in 0u32..20 {
+            buffer.add_packet(AudioPacket::encoded(vec![seq as u8], seq as u64 * 20, seq)).unwrap();
+        }
+        assert!(matches!(buffer.next_playout(), PlayoutSlot::Packet(p) if p.sequence_number == 0));
+    }
+
+    #[test]
+    fn test_frame_duration_keeps_buffering_depth() {
+        let config = JitterBufferConfig::default();
+        assert_eq!(config.with_frame_duration(config.frame_duration_ms), config);
+
+        // 60 ms frames: the same 60 ms target is one frame, 400 ms maximum rounds up to 7
+        let long = config.with_frame_duration(60);
+        assert_eq!(long.frame_duration_ms, 60);
+        assert_eq!((long.initial_target_size, long.min_size, long.max_size), (1, 1, 7));
+
+        // 10 ms frames double every count
+        let short = config.with_frame_duration(10);
+        assert_eq!((short.initial_target_size, short.min_size, short.max_size), (6, 2, 40));
+        assert_eq!(short.with_frame_duration(20), config);
+    }
+
     #[test]
     fn test_jitter_measurement() {
         let config = JitterBufferConfig::default();
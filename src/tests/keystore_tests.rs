@@ -0,0 +1,117 @@
+#[cfg(test)]
+mod keystore_tests {
+    use crate::keystore::*;
+    use crate::security::SecurityConfig;
+    use std::path::{Path, PathBuf};
+
+    // Keep tests fast; the default cost is meant for real passphrases
+    const TEST_KDF_ITERATIONS: u32 = 1_000;
+
+    fn temp_keystore_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("humr-keystore-{}", uuid::Uuid::new_v4()))
+            .join(IDENTITY_FILE_NAME)
+    }
+
+    fn cleanup(path: &Path) {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+
+    #[test]
+    fn test_identity_created_once_and_reloaded() {
+        let path = temp_keystore_path();
+        let keystore = IdentityKeystore::new(&path);
+        assert!(!keystore.exists());
+
+        let created = keystore.load_or_create(None).unwrap();
+        assert!(keystore.exists());
+        assert!(!keystore.is_encrypted().unwrap());
+
+        let reloaded = keystore.load_or_create(None).unwrap();
+        assert_eq!(created.get_public_identity(), reloaded.get_public_identity());
+        assert_eq!(created.fingerprint(), reloaded.fingerprint());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_passphrase_encrypted_identity() {
+        let path = temp_keystore_path();
+        let keystore = IdentityKeystore::new(&path).with_kdf_iterations(TEST_KDF_ITERATIONS);
+        let identity = SecurityConfig::new().unwrap();
+
+        keystore.save(&identity, Some("correct horse")).unwrap();
+        assert!(keystore.is_encrypted().unwrap());
+
+        // Secret key must not appear in the file
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains(&identity.export_identity()));
+
+        assert!(keystore.load(None).is_err());
+        assert!(keystore.load(Some("wrong passphrase")).is_err());
+
+        let loaded = keystore.load(Some("correct horse")).unwrap();
+        assert_eq!(loaded.get_public_identity(), identity.get_public_identity());
+
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_rejects_tampered_keystore() {
+        let path = temp_keystore_path();
+        let keystore = IdentityKeystore::new(&path).with_kdf_iterations(TEST_KDF_ITERATIONS);
+        keystore.save(&SecurityConfig::new().unwrap(), Some("passphrase")).unwrap();
+
+        // Swapping the public key breaks the authenticated encryption
+        let other = base64::Engine::encode(
+            &base64::engine::general_purpose::STANDARD,
+            SecurityConfig::new().unwrap().get_public_identity().to_bytes(),
+        );
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let public_line = contents.lines().find(|line| line.starts_with("public_key")).unwrap();
+        std::fs::write(&path, contents.replace(public_line, &format!("public_key = \"{}\"", other))).unwrap();
+
+        assert!(keystore.load(Some("passphrase")).is_err());
+
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let home = IdentityKeystore::new(temp_keystore_path()).with_kdf_iterations(TEST_KDF_ITERATIONS);
+        let backup = IdentityKeystore::new(temp_keystore_path()).with_kdf_iterations(TEST_KDF_ITERATIONS);
+        let restored = IdentityKeystore::new(temp_keystore_path());
+
+        let identity = home.load_or_create(None).unwrap();
+        backup.save(&identity, Some("backup passphrase")).unwrap();
+
+        let imported = backup.load(Some("backup passphrase")).unwrap();
+        restored.save(&imported, None).unwrap();
+        assert_eq!(restored.load(None).unwrap().fingerprint(), identity.fingerprint());
+
+        for keystore in [&home, &backup, &restored] {
+            cleanup(keystore.path());
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_format() {
+        let identity = SecurityConfig::new().unwrap();
+        let fingerprint = identity.fingerprint();
+
+        // 32-byte SHA-256 digest as 16 groups of 4 hex digits
+        let groups: Vec<&str> = fingerprint.split(' ').collect();
+        assert_eq!(groups.len(), 16);
+        assert!(groups.iter().all(|g| g.len() == 4 && g.chars().all(|c| c.is_ascii_hexdigit())));
+        assert_ne!(fingerprint, SecurityConfig::new().unwrap().fingerprint());
+    }
+}
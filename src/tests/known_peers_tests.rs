@@ -0,0 +1,176 @@
+#[cfg(test)]
+mod known_peers_tests {
+    use crate::known_peers::*;
+    use crate::security::{SecureSession, SecurityConfig};
+    use std::path::PathBuf;
+
+    fn temp_known_peers_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("humr-known-peers-{}", uuid::Uuid::new_v4()))
+            .join(KNOWN_PEERS_FILE_NAME)
+    }
+
+    fn config_with_policy(policy: TrustPolicy) -> SecurityConfig {
+        let mut config = SecurityConfig::new().unwrap();
+        config.trust_policy = policy;
+        config
+    }
+
+    /// Dial `responder` as `name`, returning the initiator's handshake result
+    fn dial(initiator_config: &SecurityConfig, name: &str, responder_config: SecurityConfig) -> anyhow::Result<()> {
+        let mut initiator = SecureSession::new(initiator_config.clone());
+        let mut responder = SecureSession::new(responder_config);
+        initiator.set_peer_name(Some(name.to_string()));
+
+        let handshake = initiator.initiate_handshake()?;
+        let response = responder.process_handshake(handshake)?.unwrap();
+        initiator.process_handshake_response(response)
+    }
+
+    #[test]
+    fn test_tofu_pins_and_detects_key_change() {
+        let alice = config_with_policy(TrustPolicy::Tofu);
+        let bob = SecurityConfig::new().unwrap();
+
+        dial(&alice, "bob.example:8080", bob.clone()).unwrap();
+        {
+            let known = alice.known_peers.lock().unwrap();
+            let pinned = known.get("bob.example:8080").unwrap();
+            assert_eq!(pinned.public_key, bob.get_public_identity());
+            assert_eq!(pinned.first_seen.len(), "2024-01-01T00:00:00Z".len());
+        }
+
+        // Same peer again is fine
+        dial(&alice, "bob.example:8080", bob).unwrap();
+
+        // A new key at the same name is a hard failure the caller can identify
+        let error = dial(&alice, "bob.example:8080", SecurityConfig::new().unwrap()).unwrap_err();
+        match error.downcast_ref::<TrustError>() {
+            Some(TrustError::KeyChanged { name, .. }) => assert_eq!(name, "bob.example:8080"),
+            other => panic!("Expected key change error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_strict_policy_requires_known_key() {
+        let mut alice = config_with_policy(TrustPolicy::Strict);
+        let bob = SecurityConfig::new().unwrap();
+
+        let error = dial(&alice, "bob", bob.clone()).unwrap_err();
+        assert!(matches!(error.downcast_ref::<TrustError>(), Some(TrustError::UnknownPeer { .. })));
+        assert!(alice.known_peers.lock().unwrap().peers().is_empty());
+
+        // Configured trusted keys are accepted
+        alice.add_trusted_peer(bob.get_public_identity());
+        dial(&alice, "bob", bob).unwrap();
+
+        // So are keys already pinned in known peers
+        let carol = SecurityConfig::new().unwrap();
+        alice.known_peers.lock().unwrap().insert("carol", carol.get_public_identity()).unwrap();
+        dial(&alice, "carol", carol).unwrap();
+    }
+
+    #[test]
+    fn test_strict_listener_rejects_unknown_initiator() {
+        let mallory = SecurityConfig::new().unwrap();
+        let mut listener = SecureSession::new(config_with_policy(TrustPolicy::Strict));
+
+        let handshake = SecureSession::new(mallory).initiate_handshake().unwrap();
+        assert!(listener.process_handshake(handshake).is_err());
+        assert!(!listener.is_session_active());
+    }
+
+    #[test]
+    fn test_permissive_policy_does_not_pin() {
+        let alice = config_with_policy(TrustPolicy::Permissive);
+        dial(&alice, "bob", SecurityConfig::new().unwrap()).unwrap();
+        assert!(alice.known_peers.lock().unwrap().peers().is_empty());
+
+        // Disabling authentication behaves permissively whatever the policy
+        let mut relaxed = config_with_policy(TrustPolicy::Strict);
+        relaxed.authentication_required = false;
+        dial(&relaxed, "bob", SecurityConfig::new().unwrap()).unwrap();
+
+        // A pinned key still cannot change
+        let bob = SecurityConfig::new().unwrap();
+        alice.known_peers.lock().unwrap().insert("bob", bob.get_public_identity()).unwrap();
+        assert!(dial(&alice, "bob", SecurityConfig::new().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_known_peers_file_round_trip() {
+        let path = temp_known_peers_path();
+        let peer_key = SecurityConfig::new().unwrap().get_public_identity();
+
+        let mut store = KnownPeers::load(&path).unwrap();
+        assert!(store.peers().is_empty());
+        let decision = store.verify(Some("[::1]:9000"), &peer_key, &[], TrustPolicy::Tofu).unwrap();
+        assert_eq!(decision, TrustDecision::Pinned);
+
+        // Pinning persists the entry immediately
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.lines().any(|line| line.starts_with("[::1]:9000 ed25519 ")));
+
+        let reloaded = KnownPeers::load(&path).unwrap();
+        assert_eq!(reloaded.peers(), store.peers());
+        assert_eq!(reloaded.find_by_key(&peer_key).unwrap().name, "[::1]:9000");
+
+        // Listeners pin by key under a generated name
+        let caller_key = SecurityConfig::new().unwrap().get_public_identity();
+        store.verify(None, &caller_key, &[], TrustPolicy::Tofu).unwrap();
+        assert!(store.find_by_key(&caller_key).unwrap().name.starts_with("peer-"));
+        assert_eq!(store.verify(None, &caller_key, &[], TrustPolicy::Strict).unwrap(), TrustDecision::Known);
+
+        assert!(store.remove("[::1]:9000"));
+        assert!(store.get("[::1]:9000").is_none());
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_verified_flag_persists_and_flags_key_change() {
+        let path = temp_known_peers_path();
+        let peer_key = SecurityConfig::new().unwrap().get_public_identity();
+
+        let mut store = KnownPeers::load(&path).unwrap();
+        store.verify(Some("bob"), &peer_key, &[], TrustPolicy::Tofu).unwrap();
+        assert!(!store.is_verified(&peer_key));
+
+        store.mark_verified(Some("bob"), &peer_key).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.lines().any(|line| line.starts_with("bob ed25519 ") && line.ends_with(" verified")));
+
+        let mut reloaded = KnownPeers::load(&path).unwrap();
+        assert!(reloaded.get("bob").unwrap().verified);
+
+        let impostor = SecurityConfig::new().unwrap().get_public_identity();
+        match reloaded.verify(Some("bob"), &impostor, &[], TrustPolicy::Tofu) {
+            Err(TrustError::KeyChanged { was_verified, .. }) => assert!(was_verified),
+            other => panic!("Expected key change error, got {:?}", other),
+        }
+
+        // A listener can verify a caller it only knows by key
+        let caller = SecurityConfig::new().unwrap().get_public_identity();
+        reloaded.mark_verified(None, &caller).unwrap();
+        assert!(reloaded.is_verified(&caller));
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_rejects_malformed_known_peers() {
+        let path = temp_known_peers_path();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        for contents in ["bob ed25519", "bob rsa AAAA 2024-01-01T00:00:00Z", "bob ed25519 not-base64!"] {
+            std::fs::write(&path, contents).unwrap();
+            assert!(KnownPeers::load(&path).is_err(), "should reject {:?}", contents);
+        }
+
+        // Comments and blank lines are ignored
+        std::fs::write(&path, "# comment\n\n").unwrap();
+        assert!(KnownPeers::load(&path).unwrap().peers().is_empty());
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::lighthouse::*;
 use crate::discovery::ConnectionMethod;
+use crate::stun::Candidate;
 use std::time::{Duration, Instant};
 use tokio::time::timeout;
 
@@ -121,6 +122,8 @@ mod lighthouse_tdd_tests {
         // REQUIREMENT: Lighthouse must provide multiple connection methods per UX specs
         let mut lighthouse = LighthouseService::new();
         lighthouse.start_lighthouse(8080).await.unwrap();
+        // The internet method is only advertised once STUN has found a public address
+        lighthouse.set_candidates(vec![Candidate::server_reflexive("198.51.100.7:8080".parse().unwrap())]);
 
         let methods = lighthouse.get_connection_methods();
 
@@ -0,0 +1,92 @@
+#[cfg(test)]
+mod mixer_tests {
+    use crate::mixer::*;
+    use crate::realtime_audio::AudioFrame;
+    use std::net::SocketAddr;
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn constant_frame(value: f32) -> AudioFrame {
+        AudioFrame { samples: vec![value; 960], ..AudioFrame::empty() }
+    }
+
+    #[test]
+    fn test_mix_sums_participants_with_gain() {
+        let mut mixer = AudioMixer::new(MixerConfig::default()).unwrap();
+        let handle = mixer.handle();
+        handle.set_gain(peer(1), 0.5);
+
+        let mixed = mixer.mix(&[(peer(1), constant_frame(0.4)), (peer(2), constant_frame(0.1))]).unwrap();
+        assert!(mixed.samples.iter().all(|&sample| (sample - 0.3).abs() < 1e-6));
+
+        assert!(mixer.mix(&[]).is_none());
+    }
+
+    #[test]
+    fn test_muted_participant_is_metered_but_not_mixed() {
+        let mut mixer = AudioMixer::new(MixerConfig::default()).unwrap();
+        let handle = mixer.handle();
+        handle.set_muted(peer(1), true);
+
+        let mixed = mixer.mix(&[(peer(1), constant_frame(0.5)), (peer(2), constant_frame(0.0))]).unwrap();
+        assert!(mixed.samples.iter().all(|&sample| sample == 0.0));
+
+        let levels = handle.levels();
+        let muted = levels.iter().find(|level| level.peer == peer(1)).unwrap();
+        assert!(muted.muted && muted.is_speaking);
+        assert_eq!(handle.active_speakers(), vec![peer(1)]);
+    }
+
+    #[test]
+    fn test_soft_clip_limits_loud_mixes() {
+        let threshold = 0.8;
+        assert_eq!(soft_clip(0.5, threshold), 0.5);
+        assert_eq!(soft_clip(-0.8, threshold), -0.8);
+        assert!(soft_clip(3.0, threshold) <= 1.0);
+        assert!(soft_clip(-3.0, threshold) >= -1.0);
+        assert!(soft_clip(0.9, threshold) > soft_clip(0.85, threshold));
+
+        // Five loud participants stay within full scale
+        let mut mixer = AudioMixer::new(MixerConfig::default()).unwrap();
+        let inputs: Vec<_> = (1..=5).map(|port| (peer(port), constant_frame(0.7))).collect();
+        let mixed = mixer.mix(&inputs).unwrap();
+        assert!(mixed.samples.iter().all(|&sample| sample > 0.8 && sample <= 1.0));
+        assert_eq!(mixer.get_stats().samples_limited, 960);
+    }
+
+    #[test]
+    fn test_levels_track_who_is_speaking() {
+        let mut mixer = AudioMixer::new(MixerConfig::default()).unwrap();
+        let handle = mixer.handle();
+
+        mixer.mix(&[(peer(1), constant_frame(0.3)), (peer(2), constant_frame(0.001))]).unwrap();
+        let levels = handle.levels();
+        assert_eq!(levels[0].peer, peer(1), "loudest participant first");
+        assert!((levels[0].level - 0.3).abs() < 1e-4);
+        assert_eq!(handle.active_speakers(), vec![peer(1)]);
+
+        // Level decays once the participant stops sending
+        for _ in 0..30 {
+            mixer.mix(&[(peer(2), constant_frame(0.001))]).unwrap();
+        }
+        assert!(handle.active_speakers().is_empty());
+
+        mixer.remove_participant(peer(1));
+        assert_eq!(handle.levels().len(), 1);
+    }
+
+    #[test]
+    fn test_gain_is_clamped_and_config_validated() {
+        let handle = MixerHandle::default();
+        handle.set_gain(peer(1), 100.0);
+        assert_eq!(handle.control(peer(1)).gain, MAX_PARTICIPANT_GAIN);
+        handle.set_gain(peer(1), -1.0);
+        assert_eq!(handle.control(peer(1)).gain, 0.0);
+        assert_eq!(handle.control(peer(2)), ParticipantControl::default());
+
+        assert!(AudioMixer::new(MixerConfig { limiter_threshold: 1.0, ..MixerConfig::default() }).is_err());
+        assert!(AudioMixer::new(MixerConfig { level_release: 1.0, ..MixerConfig::default() }).is_err());
+    }
+}
@@ -7,4 +7,18 @@ mod security_tests;
 mod integration_tests;
 mod cross_platform_tests;
 mod error_recovery_tests;
-mod lighthouse_tests;
\ No newline at end of file
+mod lighthouse_tests;
+mod wire_tests;
+mod network_tests;
+mod keystore_tests;
+mod known_peers_tests;
+mod relay_tests;
+mod mixer_tests;
+mod stun_tests;
+mod turn_tests;
+mod transport_tests;
+mod netsim_tests;
+mod congestion_tests;
+mod redundancy_tests;
+mod codec_tests;
+mod capabilities_tests;
@@ -0,0 +1,264 @@
+#[cfg(test)]
+mod netsim_tests {
+    use crate::netsim::*;
+    use crate::jitter_buffer::{AdaptiveJitterBuffer, AudioPacket, JitterBufferConfig, PlayoutSlot};
+    use crate::opus_codec::{OpusCodec, OpusConfig};
+    use crate::realtime_audio::{AudioFrame, FRAME_SIZE_SAMPLES, SAMPLE_RATE};
+    use crate::transport::{LoopbackTransport, Transport};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::net::UdpSocket;
+
+    const FRAME: Duration = Duration::from_millis(20);
+
+    fn bursty(seed: u64) -> ImpairmentConfig {
+        ImpairmentConfig {
+            loss: LossModel::GilbertElliott { good_to_bad: 0.05, bad_to_good: 0.3, loss_in_good: 0.0, loss_in_bad: 1.0 },
+            delay: Duration::from_millis(30),
+            jitter: Duration::from_millis(25),
+            reorder_probability: 0.02,
+            duplicate_probability: 0.01,
+            seed,
+            ..ImpairmentConfig::default()
+        }
+    }
+
+    /// Lengths of consecutive runs of lost packets
+    fn loss_bursts(impairment: &mut Impairment, packets: usize) -> Vec<usize> {
+        let mut bursts = Vec::new();
+        let mut current = 0;
+        for i in 0..packets {
+            if impairment.schedule(FRAME * i as u32, 100).is_empty() {
+                current += 1;
+            } else if current > 0 {
+                bursts.push(current);
+                current = 0;
+            }
+        }
+        bursts
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(ImpairmentConfig::default().validate().is_ok());
+        assert!(Impairment::new(ImpairmentConfig { loss: LossModel::Random { probability: 1.5 }, ..ImpairmentConfig::default() }).is_err());
+        assert!(Impairment::new(ImpairmentConfig { duplicate_probability: -0.1, ..ImpairmentConfig::default() }).is_err());
+        assert!(Impairment::new(ImpairmentConfig { bandwidth_bps: Some(0), ..ImpairmentConfig::default() }).is_err());
+    }
+
+    #[test]
+    fn test_same_seed_replays_exactly() {
+        let run = |seed| {
+            let mut impairment = Impairment::new(bursty(seed)).unwrap();
+            let schedule: Vec<Vec<Duration>> = (0..500).map(|i| impairment.schedule(FRAME * i, 160)).collect();
+            (schedule, impairment.stats())
+        };
+
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42).0, run(43).0);
+    }
+
+    #[test]
+    fn test_unimpaired_link_delivers_everything_immediately() {
+        let mut impairment = Impairment::new(ImpairmentConfig::default()).unwrap();
+        for i in 0..100 {
+            assert_eq!(impairment.schedule(FRAME * i, 160), vec![Duration::ZERO]);
+        }
+        let stats = impairment.stats();
+        assert_eq!((stats.packets_in, stats.packets_out), (100, 100));
+    }
+
+    #[test]
+    fn test_random_loss_rate() {
+        let mut impairment = Impairment::new(ImpairmentConfig {
+            loss: LossModel::Random { probability: 0.1 },
+            seed: 7,
+            ..ImpairmentConfig::default()
+        }).unwrap();
+        for i in 0..10_000 {
+            impairment.schedule(FRAME * i, 160);
+        }
+        let rate = impairment.stats().dropped_loss as f64 / 10_000.0;
+        assert!((0.08..0.12).contains(&rate), "loss rate {}", rate);
+    }
+
+    #[test]
+    fn test_gilbert_elliott_loss_is_bursty() {
+        // About 14% loss either way, but Gilbert-Elliott loses it in runs averaging 1 / 0.3 packets
+        let mut bursty = Impairment::new(ImpairmentConfig { seed: 3, ..bursty(3) }).unwrap();
+        let mut random = Impairment::new(ImpairmentConfig {
+            loss: LossModel::Random { probability: 0.143 },
+            seed: 3,
+            ..ImpairmentConfig::default()
+        }).unwrap();
+
+        let mean = |bursts: &[usize]| bursts.iter().sum::<usize>() as f64 / bursts.len() as f64;
+        let bursty_mean = mean(&loss_bursts(&mut bursty, 20_000));
+        let random_mean = mean(&loss_bursts(&mut random, 20_000));
+        assert!((2.8..3.9).contains(&bursty_mean), "mean burst {}", bursty_mean);
+        assert!(random_mean < 1.3, "mean burst {}", random_mean);
+    }
+
+    #[test]
+    fn test_delay_jitter_reordering_and_duplication() {
+        let mut impairment = Impairment::new(ImpairmentConfig {
+            delay: Duration::from_millis(50),
+            jitter: Duration::from_millis(10),
+            reorder_probability: 0.1,
+            reorder_delay: Duration::from_millis(40),
+            duplicate_probability: 0.1,
+            seed: 11,
+            ..ImpairmentConfig::default()
+        }).unwrap();
+
+        let sends: Vec<(Duration, Vec<u8>)> = (0..1000u32).map(|i| (FRAME * i, i.to_be_bytes().to_vec())).collect();
+        let arrivals = impairment.simulate(sends);
+        let sequence = |packet: &[u8]| u32::from_be_bytes(packet.try_into().unwrap());
+
+        for (arrival, packet) in &arrivals {
+            let latency = *arrival - FRAME * sequence(packet);
+            assert!(latency >= Duration::from_millis(50) && latency <= Duration::from_millis(100), "latency {:?}", latency);
+        }
+
+        let stats = impairment.stats();
+        assert!(stats.reordered > 50 && stats.duplicated > 50);
+        assert_eq!(arrivals.len() as u64, 1000 + stats.duplicated);
+        let out_of_order = arrivals.windows(2).filter(|pair| sequence(&pair[1].1) < sequence(&pair[0].1)).count();
+        assert!(out_of_order > 0);
+    }
+
+    #[test]
+    fn test_bandwidth_cap_queues_then_drops() {
+        // 1000-byte packets every 1ms on an 800 kbps link: 10ms each to serialise
+        let mut impairment = Impairment::new(ImpairmentConfig {
+            bandwidth_bps: Some(800_000),
+            max_queue_delay: Duration::from_millis(100),
+            ..ImpairmentConfig::default()
+        }).unwrap();
+
+        let sends: Vec<(Duration, Vec<u8>)> = (0..200).map(|i| (Duration::from_millis(i), vec![0u8; 1000])).collect();
+        let arrivals = impairment.simulate(sends);
+
+        for pair in arrivals.windows(2) {
+            assert!(pair[1].0 - pair[0].0 >= Duration::from_millis(10));
+        }
+        let stats = impairment.stats();
+        assert!(stats.dropped_queue > 100, "dropped {}", stats.dropped_queue);
+        assert_eq!(stats.packets_out as usize, arrivals.len());
+    }
+
+    /// Play an Opus call through the impaired link in virtual time, concealing losses
+    /// with PLC, and return the decoded audio plus what the jitter buffer saw
+    fn impaired_call(seed: u64) -> (Vec<f32>, u64, u64) {
+        let mut encoder = OpusCodec::new(OpusConfig::default()).unwrap();
+        let mut decoder = OpusCodec::new(OpusConfig::default()).unwrap();
+        // Each packet is a sequence number followed by the Opus payload
+        let sends: Vec<(Duration, Vec<u8>)> = (0..150u32).map(|sequence| {
+            let samples: Vec<f32> = (0..FRAME_SIZE_SAMPLES).map(|i| {
+                let t = (sequence as usize * FRAME_SIZE_SAMPLES + i) as f32 / SAMPLE_RATE as f32;
+                0.4 * (2.0 * std::f32::consts::PI * 440.0 * t).sin()
+            }).collect();
+            let mut packet = sequence.to_be_bytes().to_vec();
+            packet.extend(encoder.encode(&AudioFrame::new(samples)).unwrap());
+            (FRAME * sequence, packet)
+        }).collect();
+
+        let mut impairment = Impairment::new(bursty(seed)).unwrap();
+        let mut arrivals = impairment.simulate(sends).into_iter().peekable();
+
+        let mut buffer = AdaptiveJitterBuffer::new(JitterBufferConfig { adaptive: false, ..JitterBufferConfig::default() }).unwrap();
+        let (mut output, mut played, mut concealed) = (Vec::new(), 0, 0);
+        for tick in 0..200u32 {
+            let now = FRAME * tick;
+            while let Some((arrival, _)) = arrivals.peek() && *arrival <= now {
+                let (_, packet) = arrivals.next().unwrap();
+                let sequence = u32::from_be_bytes(packet[..4].try_into().unwrap());
+                buffer.put_packet(AudioPacket::encoded(packet[4..].to_vec(), sequence as u64 * 20, sequence)).unwrap();
+            }
+            match buffer.next_playout() {
+                PlayoutSlot::Packet(packet) => {
+                    played += 1;
+                    output.extend(decoder.decode(&packet.payload).unwrap().samples);
+                }
+                PlayoutSlot::Lost { .. } => {
+                    concealed += 1;
+                    output.extend(decoder.decode_lost_packet().unwrap().samples);
+                }
+                PlayoutSlot::Empty => {}
+            }
+        }
+        (output, played, concealed)
+    }
+
+    #[test]
+    fn test_impaired_call_is_reproducible() {
+        let (output, played, concealed) = impaired_call(2024);
+        assert!(concealed > 0, "seed produced no loss to conceal");
+        assert!(played > 100);
+        assert_eq!(impaired_call(2024), (output, played, concealed));
+    }
+
+    #[tokio::test]
+    async fn test_simulated_transport_impairs_sends() {
+        let (alice, bob) = LoopbackTransport::pair();
+        let bob_addr = bob.local_addr().unwrap();
+        let lossy = SimulatedTransport::new(Arc::new(alice), ImpairmentConfig {
+            delay: Duration::from_millis(60),
+            duplicate_probability: 1.0,
+            ..ImpairmentConfig::default()
+        }).unwrap();
+
+        let sent = tokio::time::Instant::now();
+        assert_eq!(lossy.send_to(b"hello", bob_addr).await.unwrap(), 5);
+
+        let mut buffer = [0u8; 64];
+        for _ in 0..2 {
+            let (len, _) = tokio::time::timeout(Duration::from_secs(2), bob.recv_from(&mut buffer)).await.unwrap().unwrap();
+            assert_eq!(&buffer[..len], b"hello");
+        }
+        assert!(sent.elapsed() >= Duration::from_millis(60));
+        assert_eq!(lossy.stats().duplicated, 1);
+
+        // Total loss still reports the send as successful, like UDP
+        let (alice, bob) = LoopbackTransport::pair();
+        let bob_addr = bob.local_addr().unwrap();
+        let dead = SimulatedTransport::new(Arc::new(alice), ImpairmentConfig {
+            loss: LossModel::Random { probability: 1.0 },
+            ..ImpairmentConfig::default()
+        }).unwrap();
+        assert!(dead.send_to(b"gone", bob_addr).await.is_ok());
+        assert!(tokio::time::timeout(Duration::from_millis(200), bob.recv_from(&mut buffer)).await.is_err());
+        assert_eq!(dead.stats().dropped_loss, 1);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_forwards_both_directions() {
+        let echo = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let echo_addr = echo.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 1500];
+            while let Ok((len, from)) = echo.recv_from(&mut buffer).await {
+                let _ = echo.send_to(&buffer[..len], from).await;
+            }
+        });
+
+        let upstream = ImpairmentConfig { delay: Duration::from_millis(20), ..ImpairmentConfig::default() };
+        let downstream = ImpairmentConfig { duplicate_probability: 1.0, ..ImpairmentConfig::default() };
+        let proxy = Arc::new(NetsimProxy::bind("127.0.0.1:0", echo_addr, upstream, downstream).await.unwrap());
+        let proxy_addr = proxy.local_addr().unwrap();
+        let runner = Arc::clone(&proxy);
+        tokio::spawn(async move { runner.run().await });
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(b"ping", proxy_addr).await.unwrap();
+
+        let mut buffer = [0u8; 64];
+        for _ in 0..2 {
+            let (len, from) = tokio::time::timeout(Duration::from_secs(2), client.recv_from(&mut buffer)).await.unwrap().unwrap();
+            assert_eq!(&buffer[..len], b"ping");
+            assert_eq!(from, proxy_addr);
+        }
+        assert_eq!(proxy.upstream_stats().packets_out, 1);
+        assert_eq!(proxy.downstream_stats().duplicated, 1);
+    }
+}
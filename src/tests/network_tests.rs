@@ -0,0 +1,538 @@
+#[cfg(test)]
+mod network_tests {
+    use crate::network::*;
+    use crate::security::{SecureSession, SecurityConfig};
+    use crate::transport::{self, Transport, TransportFuture, TransportKind};
+    use crate::wire;
+    use crate::known_peers::TrustError;
+    use crate::capabilities::{CapabilityOffer, NegotiationError};
+    use crate::codec::CodecKind;
+    use crate::opus_codec::OpusConfig;
+    use crate::realtime_audio::AudioFormat;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    fn secure_config(remote_port: u16, local_port: u16) -> ConnectionConfig {
+        ConnectionConfig {
+            remote_host: "127.0.0.1".to_string(),
+            port: remote_port,
+            local_port: Some(local_port),
+            use_encryption: true,
+            security_config: Some(SecurityConfig::new().unwrap()),
+            max_peers: DEFAULT_MAX_PEERS,
+            turn_server: None,
+            transport: TransportKind::Udp,
+        }
+    }
+
+    fn identity_of(config: &ConnectionConfig) -> String {
+        let identity = config.security_config.as_ref().unwrap().get_public_identity();
+        base64::Engine::encode(&base64::engine::general_purpose::STANDARD, identity.as_bytes())
+    }
+
+    fn free_udp_port() -> u16 {
+        std::net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+    }
+
+    async fn wait_for_frame(manager: &mut NetworkManager) -> ReceivedAudioFrame {
+        for _ in 0..100 {
+            if let Some(frame) = manager.receive_audio_frame().unwrap() {
+                return frame;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("No audio frame received");
+    }
+
+    /// Transport that also receives packets the test forges, with any source address
+    struct ForgingTransport {
+        inner: Arc<dyn Transport>,
+        forged: tokio::sync::Mutex<mpsc::UnboundedReceiver<(SocketAddr, Vec<u8>)>>,
+    }
+
+    impl ForgingTransport {
+        async fn bind() -> (Arc<Self>, mpsc::UnboundedSender<(SocketAddr, Vec<u8>)>) {
+            let inner = transport::bind_transport("127.0.0.1:0", TransportKind::Udp).await.unwrap();
+            let (forge, forged) = mpsc::unbounded_channel();
+            (Arc::new(Self { inner, forged: tokio::sync::Mutex::new(forged) }), forge)
+        }
+    }
+
+    impl Transport for ForgingTransport {
+        fn send_to<'a>(&'a self, packet: &'a [u8], target: SocketAddr) -> TransportFuture<'a, usize> {
+            self.inner.send_to(packet, target)
+        }
+
+        fn recv_from<'a>(&'a self, buffer: &'a mut [u8]) -> TransportFuture<'a, (usize, SocketAddr)> {
+            Box::pin(async move {
+                let mut forged = self.forged.lock().await;
+                let mut received = vec![0u8; buffer.len()];
+                let (packet, from) = tokio::select! {
+                    result = self.inner.recv_from(&mut received) => {
+                        let (len, from) = result?;
+                        (&received[..len], from)
+                    }
+                    Some((from, packet)) = forged.recv() => {
+                        received = packet;
+                        (&received[..], from)
+                    }
+                };
+                let len = packet.len().min(buffer.len());
+                buffer[..len].copy_from_slice(&packet[..len]);
+                Ok((len, from))
+            })
+        }
+
+        fn local_addr(&self) -> std::io::Result<SocketAddr> {
+            self.inner.local_addr()
+        }
+
+        fn kind(&self) -> TransportKind {
+            self.inner.kind()
+        }
+    }
+
+    /// Handshake signed by a fresh identity offering `capabilities`
+    fn forged_handshake(capabilities: CapabilityOffer) -> Vec<u8> {
+        let mut session = SecureSession::new(SecurityConfig { capabilities, ..SecurityConfig::new().unwrap() });
+        wire::encode_message(&session.initiate_handshake().unwrap()).unwrap()
+    }
+
+    async fn assert_audio_flows(a: &mut NetworkManager, b: &mut NetworkManager) {
+        a.send_audio_frame(b"from a").await.unwrap();
+        assert_eq!(wait_for_frame(b).await.payload, b"from a");
+
+        b.send_audio_frame(b"from b").await.unwrap();
+        assert_eq!(wait_for_frame(a).await.payload, b"from b");
+    }
+
+    #[tokio::test]
+    async fn test_listener_accepts_handshake() {
+        let mut host = NetworkManager::new(secure_config(0, 0));
+        let host_port = host.bind().await.unwrap().port();
+
+        let mut client = NetworkManager::new(secure_config(host_port, 0));
+
+        let accept = tokio::spawn(async move {
+            host.accept_connection().await.unwrap();
+            host
+        });
+        client.establish_connection().await.unwrap();
+        let mut host = accept.await.unwrap();
+
+        assert!(host.is_connected() && client.is_connected());
+        assert!(host.is_secure_session_active().await);
+        assert!(client.is_secure_session_active().await);
+        assert_eq!(client.negotiated_audio().await.map(|audio| audio.codec), Some(CodecKind::Opus));
+        assert_eq!(host.negotiated_audio().await, client.negotiated_audio().await);
+
+        assert_audio_flows(&mut client, &mut host).await;
+    }
+
+    #[tokio::test]
+    async fn test_control_messages_alongside_audio() {
+        let mut host = NetworkManager::new(secure_config(0, 0));
+        let host_port = host.bind().await.unwrap().port();
+        let mut client = NetworkManager::new(secure_config(host_port, 0));
+
+        let accept = tokio::spawn(async move {
+            host.accept_connection().await.unwrap();
+            host
+        });
+        client.establish_connection().await.unwrap();
+        let mut host = accept.await.unwrap();
+        let host_addr = std::net::SocketAddr::from(([127, 0, 0, 1], host_port));
+
+        client.send_audio_frame(b"one").await.unwrap();
+        client.send_control(host_addr, b"report").await.unwrap();
+        client.send_audio_frame(b"two").await.unwrap();
+
+        let first = wait_for_frame(&mut host).await;
+        let second = wait_for_frame(&mut host).await;
+        assert_eq!(second.sequence_number, first.sequence_number + 1);
+        assert!(first.timestamp > 0);
+
+        let control = host.receive_control().unwrap().expect("control message queued");
+        assert_eq!(control.payload, b"report");
+        assert!(host.receive_control().unwrap().is_none());
+
+        // Control messages only go to established peers
+        assert!(client.send_control("127.0.0.1:9".parse().unwrap(), b"report").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_restarted_peer_replaces_session() {
+        let mut host = NetworkManager::new(secure_config(0, 0));
+        let host_port = host.bind().await.unwrap().port();
+
+        let client_port = free_udp_port();
+        let client_config = secure_config(host_port, client_port);
+        let mut client = NetworkManager::new(client_config.clone());
+        let accept = tokio::spawn(async move {
+            host.accept_connection().await.unwrap();
+            host
+        });
+        client.establish_connection().await.unwrap();
+        let mut host = accept.await.unwrap();
+        client.disconnect();
+        drop(client);
+        // Let the aborted receive task release the port
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Same identity and address, fresh handshake: the host answers it as a new session
+        let mut restarted = NetworkManager::new(client_config);
+        restarted.establish_connection().await.unwrap();
+        assert_eq!(host.peer_count().await, 1);
+        assert_audio_flows(&mut restarted, &mut host).await;
+    }
+
+    #[tokio::test]
+    async fn test_handshake_from_other_key_keeps_session() {
+        let (transport, forge) = ForgingTransport::bind().await;
+        let mut host = NetworkManager::with_transport(secure_config(0, 0), transport);
+        let host_port = host.bind().await.unwrap().port();
+
+        let client_port = free_udp_port();
+        let client_config = secure_config(host_port, client_port);
+        let client_identity = identity_of(&client_config);
+        let mut client = NetworkManager::new(client_config);
+        client.establish_connection().await.unwrap();
+        host.accept_connection().await.unwrap();
+
+        // Someone else's key, sent from the client's address: one with matching audio,
+        // and one that has nothing in common with the host
+        let client_addr = SocketAddr::from(([127, 0, 0, 1], client_port));
+        let narrowband = CodecKind::Pcmu.format(AudioFormat::STANDARD);
+        forge.send((client_addr, forged_handshake(CapabilityOffer::default()))).unwrap();
+        forge.send((client_addr, forged_handshake(CapabilityOffer::for_format(CodecKind::Pcmu, narrowband, &OpusConfig::default())))).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(host.peer_count().await, 1);
+        assert_eq!(host.get_peer_identity().await, Some(client_identity));
+        assert_audio_flows(&mut client, &mut host).await;
+    }
+
+    #[tokio::test]
+    async fn test_replayed_handshake_not_answered() {
+        let (transport, forge) = ForgingTransport::bind().await;
+        let mut host = NetworkManager::with_transport(secure_config(0, 0), transport);
+        host.bind().await.unwrap();
+
+        // A caller's Handshake is answered once; the same packet again, from its own
+        // address or any other, starts nothing
+        let handshake = forged_handshake(CapabilityOffer::default());
+        let caller = SocketAddr::from(([127, 0, 0, 1], free_udp_port()));
+        forge.send((caller, handshake.clone())).unwrap();
+        host.accept_connection().await.unwrap();
+        assert!(host.remove_peer(caller).await);
+
+        for replayed_from in [caller, SocketAddr::from(([127, 0, 0, 1], free_udp_port()))] {
+            forge.send((replayed_from, handshake.clone())).unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(host.peers().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_simultaneous_open_settles_roles() {
+        let (port_a, port_b) = (free_udp_port(), free_udp_port());
+        let (config_a, config_b) = (secure_config(port_b, port_a), secure_config(port_a, port_b));
+        let (identity_a, identity_b) = (identity_of(&config_a), identity_of(&config_b));
+        let mut peer_a = NetworkManager::new(config_a);
+        let mut peer_b = NetworkManager::new(config_b);
+
+        // Both sides dial each other at once
+        let dial_b = tokio::spawn(async move {
+            peer_b.establish_connection().await.unwrap();
+            peer_b
+        });
+        peer_a.establish_connection().await.unwrap();
+        let mut peer_b = dial_b.await.unwrap();
+
+        assert!(peer_a.is_secure_session_active().await);
+        assert!(peer_b.is_secure_session_active().await);
+        assert_eq!(peer_a.get_peer_identity().await, Some(identity_b));
+        assert_eq!(peer_b.get_peer_identity().await, Some(identity_a));
+
+        assert_audio_flows(&mut peer_a, &mut peer_b).await;
+    }
+
+    #[tokio::test]
+    async fn test_initiator_aborts_on_changed_peer_key() {
+        let mut host = NetworkManager::new(secure_config(0, 0));
+        let host_port = host.bind().await.unwrap().port();
+
+        // Pin a different key for the host's address
+        let client_config = secure_config(host_port, 0);
+        let stale_key = SecurityConfig::new().unwrap().get_public_identity();
+        client_config.security_config.as_ref().unwrap().known_peers.lock().unwrap()
+            .insert(&format!("127.0.0.1:{}", host_port), stale_key).unwrap();
+        let mut client = NetworkManager::new(client_config);
+
+        let accept = tokio::spawn(async move { host.accept_connection().await });
+        let started = std::time::Instant::now();
+        let error = client.establish_connection().await.unwrap_err();
+
+        assert!(matches!(error.downcast_ref::<TrustError>(), Some(TrustError::KeyChanged { .. })));
+        assert!(started.elapsed() < Duration::from_secs(2), "key change should fail fast, not time out");
+        assert!(!client.is_connected());
+        accept.abort();
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_audio_fails_fast() {
+        // The host runs G.711 at 8 kHz; the client only Opus
+        let mut host_config = secure_config(0, 0);
+        let narrowband = CodecKind::Pcmu.format(AudioFormat::STANDARD);
+        host_config.security_config.as_mut().unwrap().capabilities =
+            CapabilityOffer::for_format(CodecKind::Pcmu, narrowband, &OpusConfig::default());
+        let mut host = NetworkManager::new(host_config);
+        let host_port = host.bind().await.unwrap().port();
+
+        let mut client = NetworkManager::new(secure_config(host_port, 0));
+        let accept = tokio::spawn(async move { host.accept_connection().await });
+        let started = std::time::Instant::now();
+        let error = client.establish_connection().await.unwrap_err();
+
+        assert!(matches!(error.downcast_ref::<NegotiationError>(), Some(NegotiationError::NoCommonCodec { .. })));
+        assert!(error.to_string().contains("G.711 μ-law"));
+        assert!(started.elapsed() < Duration::from_secs(2), "a mismatch should fail fast, not time out");
+        assert!(!client.is_connected());
+        assert!(!accept.is_finished());
+        accept.abort();
+    }
+
+    #[tokio::test]
+    async fn test_other_wire_version_rejected_explicitly() {
+        let mut host = NetworkManager::new(secure_config(0, 0));
+        let host_port = host.bind().await.unwrap().port();
+
+        // A caller on the previous version hears which version the host speaks
+        let caller = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut old_handshake = forged_handshake(CapabilityOffer::default());
+        old_handshake[0] = wire::WIRE_VERSION - 1;
+        caller.send_to(&old_handshake, ("127.0.0.1", host_port)).await.unwrap();
+        let mut reply = [0u8; 64];
+        let (len, _) = tokio::time::timeout(Duration::from_secs(2), caller.recv_from(&mut reply)).await.unwrap().unwrap();
+        assert_eq!(&reply[..len], wire::version_notice().as_slice());
+        assert!(host.peers().await.is_empty());
+
+        // Dialling a host on another version fails fast with that reason
+        let newer_host = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let newer_port = newer_host.local_addr().unwrap().port();
+        let answer = std::thread::spawn(move || {
+            let mut packet = [0u8; 2048];
+            let (_, client) = newer_host.recv_from(&mut packet).unwrap();
+            let mut notice = wire::version_notice();
+            notice[0] = wire::WIRE_VERSION + 1;
+            newer_host.send_to(&notice, client).unwrap();
+        });
+        let mut client = NetworkManager::new(secure_config(newer_port, 0));
+        let started = std::time::Instant::now();
+        let error = client.establish_connection().await.unwrap_err();
+        answer.join().unwrap();
+
+        assert!(error.to_string().contains("wire protocol version"));
+        assert!(started.elapsed() < Duration::from_secs(2), "a version mismatch should fail fast, not time out");
+        assert!(!client.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_initiator_times_out_without_listener() {
+        let silent_port = free_udp_port();
+        let _silent = std::net::UdpSocket::bind(("127.0.0.1", silent_port)).unwrap();
+
+        let mut client = NetworkManager::new(secure_config(silent_port, 0));
+        assert!(client.establish_connection().await.is_err());
+        assert!(!client.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_host_admits_multiple_peers() {
+        let mut host = NetworkManager::new(secure_config(0, 0));
+        let host_port = host.bind().await.unwrap().port();
+
+        let mut alice = NetworkManager::new(secure_config(host_port, 0));
+        let mut bob = NetworkManager::new(secure_config(host_port, 0));
+        alice.establish_connection().await.unwrap();
+        bob.establish_connection().await.unwrap();
+
+        let first = host.accept_connection().await.unwrap();
+        let second = host.accept_connection().await.unwrap();
+        assert_ne!(first, second);
+        assert_eq!(host.peer_count().await, 2);
+
+        // Each sender is tagged with its own address and decrypted under its own session
+        alice.send_audio_frame(b"from alice").await.unwrap();
+        bob.send_audio_frame(b"from bob").await.unwrap();
+        let mut received = vec![wait_for_frame(&mut host).await, wait_for_frame(&mut host).await];
+        received.sort_by_key(|frame| frame.payload.clone());
+        assert_eq!(received[0].payload, b"from alice");
+        assert_eq!(received[1].payload, b"from bob");
+        assert_ne!(received[0].peer, received[1].peer);
+
+        // The host fans out to both peers
+        host.send_audio_frame(b"from host").await.unwrap();
+        assert_eq!(wait_for_frame(&mut alice).await.payload, b"from host");
+        assert_eq!(wait_for_frame(&mut bob).await.payload, b"from host");
+
+        let peers = host.peers().await;
+        assert_eq!(peers.len(), 2);
+        for peer in &peers {
+            assert!(peer.is_established && peer.identity.is_some());
+            assert_eq!(peer.stats.packets_sent, 1);
+            assert!(peer.stats.packets_received >= 2, "handshake and audio counted per peer");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_later_peers_join_settled_audio() {
+        let mut host = NetworkManager::new(secure_config(0, 0));
+        let host_port = host.bind().await.unwrap().port();
+        let mut first = NetworkManager::new(secure_config(host_port, 0));
+        first.establish_connection().await.unwrap();
+        host.accept_connection().await.unwrap();
+        let settled = host.negotiated_audio().await.unwrap();
+
+        // A caller preferring G.722 still gets the call's Opus, as it dialled in second
+        let offering = |capabilities: CapabilityOffer| {
+            let mut config = secure_config(host_port, 0);
+            config.security_config.as_mut().unwrap().capabilities = capabilities;
+            NetworkManager::new(config)
+        };
+        let mut second = offering(CapabilityOffer::new(vec![CodecKind::G722, CodecKind::Opus], AudioFormat::STANDARD, &OpusConfig::default()));
+        second.establish_connection().await.unwrap();
+        host.accept_connection().await.unwrap();
+        assert_eq!(second.negotiated_audio().await, Some(settled));
+        assert!(host.peers().await.iter().all(|peer| peer.audio == Some(settled)));
+
+        // One that cannot run it is turned away with the reason
+        let mut third = offering(CapabilityOffer::new(vec![CodecKind::G722], AudioFormat::STANDARD, &OpusConfig::default()));
+        let error = third.establish_connection().await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<NegotiationError>(), Some(NegotiationError::NoCommonCodec { .. })));
+        assert_eq!(host.peer_count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_peer_table_limit() {
+        let mut config = secure_config(0, 0);
+        config.max_peers = 1;
+        let mut host = NetworkManager::new(config);
+        let host_port = host.bind().await.unwrap().port();
+
+        let mut alice = NetworkManager::new(secure_config(host_port, 0));
+        alice.establish_connection().await.unwrap();
+        host.accept_connection().await.unwrap();
+
+        let mut bob = NetworkManager::new(secure_config(host_port, 0));
+        assert!(bob.establish_connection().await.is_err());
+        assert_eq!(host.peer_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_audio_from_unknown_address_dropped() {
+        let mut host = NetworkManager::new(secure_config(0, 0));
+        let host_port = host.bind().await.unwrap().port();
+
+        let mut client = NetworkManager::new(secure_config(host_port, 0));
+        client.establish_connection().await.unwrap();
+        host.accept_connection().await.unwrap();
+
+        // A valid-looking audio packet from an address that never completed a handshake
+        let mut stranger = crate::security::SecureSession::new(SecurityConfig::new().unwrap());
+        let mut responder = crate::security::SecureSession::new(SecurityConfig::new().unwrap());
+        let response = responder.process_handshake(stranger.initiate_handshake().unwrap()).unwrap().unwrap();
+        stranger.process_handshake_response(response).unwrap();
+        let packet = crate::wire::encode_message(&stranger.encrypt_audio_frame(b"injected").unwrap()).unwrap();
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.send_to(&packet, ("127.0.0.1", host_port)).unwrap();
+        socket.send_to(b"plaintext injection", ("127.0.0.1", host_port)).unwrap();
+
+        client.send_audio_frame(b"from client").await.unwrap();
+        assert_eq!(wait_for_frame(&mut host).await.payload, b"from client");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(host.receive_audio_frame().unwrap().is_none());
+        assert_eq!(host.peers().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_peers_numbered_separately() {
+        let mut host = NetworkManager::new(ConnectionConfig { use_encryption: false, security_config: None, ..secure_config(0, 0) });
+        let host_port = host.bind().await.unwrap().port();
+
+        let alice = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let bob = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut received = Vec::new();
+        for socket in [&alice, &alice, &bob, &alice, &bob] {
+            socket.send_to(b"frame", ("127.0.0.1", host_port)).unwrap();
+            let frame = wait_for_frame(&mut host).await;
+            received.push((frame.peer.port(), frame.sequence_number));
+        }
+
+        // Each sender's frames count from one, whoever else is talking
+        let (alice_port, bob_port) = (alice.local_addr().unwrap().port(), bob.local_addr().unwrap().port());
+        assert_eq!(received, vec![(alice_port, 1), (alice_port, 2), (bob_port, 1), (alice_port, 3), (bob_port, 2)]);
+    }
+
+    #[tokio::test]
+    async fn test_send_failure_isolated_to_peer() {
+        let mut manager = NetworkManager::new(ConnectionConfig { use_encryption: false, security_config: None, ..secure_config(0, 0) });
+        let listener = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+
+        // An IPv6 peer cannot be reached from our IPv4 socket
+        let unreachable: std::net::SocketAddr = "[::1]:9".parse().unwrap();
+        manager.dial_peer(unreachable).await.unwrap();
+        manager.dial_peer(listener.local_addr().unwrap()).await.unwrap();
+
+        manager.send_audio_frame(b"frame").await.unwrap();
+        let mut buffer = [0u8; 16];
+        let (len, _) = listener.recv_from(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"frame");
+
+        let peers = manager.peers().await;
+        let errors = |addr| peers.iter().find(|peer| peer.addr == addr).unwrap().stats.send_errors;
+        assert_eq!(errors(unreachable), 1);
+        assert_eq!(errors(listener.local_addr().unwrap()), 0);
+        assert_eq!(manager.stats().await.totals.send_errors, 1);
+    }
+
+    #[tokio::test]
+    async fn test_signed_disconnect_removes_peer() {
+        let mut host = NetworkManager::new(secure_config(0, 0));
+        let host_addr = host.bind().await.unwrap();
+        let mut client = NetworkManager::new(secure_config(host_addr.port(), 0));
+        client.establish_connection().await.unwrap();
+        host.accept_connection().await.unwrap();
+        assert_eq!(host.peers().await.len(), 1);
+
+        let host_addr = format!("127.0.0.1:{}", host_addr.port()).parse().unwrap();
+        assert!(client.remove_peer(host_addr).await);
+        for _ in 0..100 {
+            if host.peers().await.is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Host kept the peer after its disconnect");
+    }
+
+    #[tokio::test]
+    async fn test_idle_peers_expire() {
+        let mut host = NetworkManager::new(secure_config(0, 0));
+        let host_port = host.bind().await.unwrap().port();
+        let mut client = NetworkManager::new(secure_config(host_port, 0));
+        client.establish_connection().await.unwrap();
+        host.accept_connection().await.unwrap();
+        assert_audio_flows(&mut client, &mut host).await;
+
+        // The client falls silent past the idle timeout
+        tokio::time::pause();
+        tokio::time::advance(PEER_IDLE_TIMEOUT).await;
+        assert!(host.send_audio_frame(b"anyone there?").await.is_err());
+        assert!(host.peers().await.is_empty());
+    }
+}
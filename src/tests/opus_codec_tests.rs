@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod opus_codec_tests {
     use crate::opus_codec::*;
-    use crate::realtime_audio::{AudioFrame, SAMPLE_RATE, CHANNELS, FRAME_SIZE_SAMPLES};
+    use crate::realtime_audio::{AudioFrame, AudioFormat, SAMPLE_RATE, CHANNELS, FRAME_SIZE_SAMPLES};
     use std::time::Instant;
 
     #[test]
@@ -281,6 +281,76 @@ mod opus_codec_tests {
                silent_encoded.len(), signal_encoded.len());
     }
 
+    #[test]
+    fn test_inband_fec_recovers_lost_frame() {
+        // Speech with a wandering pitch, so the encoder codes it with SILK and adds LBRR data
+        let speech = |index: usize| AudioFrame::new((0..FRAME_SIZE_SAMPLES).map(|n| {
+            let t = (index * FRAME_SIZE_SAMPLES / 2 + n / 2) as f32 / SAMPLE_RATE as f32;
+            let f0 = 140.0 + 30.0 * (t * 3.0).sin();
+            (1..=6).map(|h| 0.25 / h as f32 * (2.0 * std::f32::consts::PI * f0 * h as f32 * t).sin()).sum::<f32>()
+        }).collect());
+        let error = |a: &AudioFrame, b: &AudioFrame| -> f32 {
+            a.samples.iter().zip(&b.samples).map(|(x, y)| (x - y) * (x - y)).sum()
+        };
+
+        for fec_enabled in [true, false] {
+            let mut encoder = OpusCodec::new(OpusConfig { fec_enabled, packet_loss_percent: 20, ..OpusConfig::default() }).unwrap();
+            let packets: Vec<Vec<u8>> = (0..22).map(|index| encoder.encode(&speech(index)).unwrap()).collect();
+
+            // Three decoders in step; frame 20 is lost for two of them
+            let mut decoders: Vec<OpusCodec> = (0..3).map(|_| OpusCodec::new(OpusConfig::default()).unwrap()).collect();
+            for packet in &packets[..20] {
+                for decoder in decoders.iter_mut() {
+                    decoder.decode(packet).unwrap();
+                }
+            }
+            let reference = decoders[0].decode(&packets[20]).unwrap();
+            let recovered = decoders[1].decode_fec(&packets[21]).unwrap();
+            let concealed = decoders[2].decode_lost_packet().unwrap();
+            assert_eq!(recovered.samples.len(), FRAME_SIZE_SAMPLES);
+
+            if fec_enabled {
+                assert!(error(&recovered, &reference) * 4.0 < error(&concealed, &reference),
+                        "FEC error {} vs PLC error {}", error(&recovered, &reference), error(&concealed, &reference));
+            } else {
+                // Without LBRR data the decoder can only conceal
+                assert_eq!(recovered.samples, concealed.samples);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dtx_setting_reaches_encoder() {
+        let silence = AudioFrame::new(vec![0.0; FRAME_SIZE_SAMPLES]);
+        for dtx_enabled in [true, false] {
+            let mut codec = OpusCodec::new(OpusConfig { dtx_enabled, ..OpusConfig::default() }).unwrap();
+            let sizes: Vec<usize> = (0..30).map(|_| codec.encode(&silence).unwrap().len()).collect();
+
+            // After a short hangover DTX sends bare TOC bytes through sustained silence
+            let tail_max = sizes[20..].iter().copied().max().unwrap();
+            if dtx_enabled {
+                assert!(tail_max <= 2, "DTX packets: {:?}", sizes);
+                let packet = codec.encode(&silence).unwrap();
+                assert_eq!(codec.decode(&packet).unwrap().samples.len(), FRAME_SIZE_SAMPLES);
+            } else {
+                assert!(tail_max > 2, "Packets without DTX: {:?}", sizes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_packet_loss_percent_update() {
+        let mut codec = OpusCodec::new(OpusConfig::default()).unwrap();
+        assert_eq!(codec.get_stats().packet_loss_percent, 5);
+
+        codec.set_packet_loss_percent(30).unwrap();
+        assert_eq!(codec.get_stats().packet_loss_percent, 30);
+        assert!(codec.set_packet_loss_percent(101).is_err());
+        assert_eq!(codec.get_config().packet_loss_percent, 30);
+
+        assert!(OpusConfig { packet_loss_percent: 101, ..OpusConfig::default() }.validate().is_err());
+    }
+
     #[test]
     fn test_opus_complexity_levels() {
         let complexities = vec![0, 5, 10];
@@ -321,28 +391,60 @@ mod opus_codec_tests {
     #[test]
     fn test_opus_frame_size_variations() {
         // Test different frame sizes supported by Opus
-        let frame_sizes_ms = vec![10, 20, 40];
+        let frame_sizes_ms = vec![10, 20, 40, 60];
 
         for frame_size_ms in frame_sizes_ms {
-            let mut config = OpusConfig::default();
-            config.frame_size_ms = frame_size_ms;
+            let format = AudioFormat { frame_duration_ms: frame_size_ms, ..AudioFormat::STANDARD };
+            let config = OpusConfig::default().with_format(format);
 
             let mut codec = OpusCodec::new(config).unwrap();
 
-            let frame_samples = (SAMPLE_RATE as f32 * frame_size_ms as f32 / 1000.0) as usize;
+            let frame_samples = format.frame_samples();
             let samples = generate_test_signal(frame_samples);
-            let frame = AudioFrame::new(samples);
+            let frame = AudioFrame::with_format(samples, format);
 
             let encoded_data = codec.encode(&frame).unwrap();
             let decoded_frame = codec.decode(&encoded_data).unwrap();
 
             assert_eq!(decoded_frame.samples.len(), frame_samples);
+            assert_eq!(decoded_frame.format, format);
+            assert_eq!(codec.decode_lost_packet().unwrap().samples.len(), frame_samples);
 
             println!("Frame size {}ms: {} samples, {} bytes encoded",
                     frame_size_ms, frame_samples, encoded_data.len());
         }
     }
 
+    #[test]
+    fn test_low_bandwidth_format_round_trip() {
+        let format = AudioFormat::LOW_BANDWIDTH;
+        let config = OpusConfig { bitrate: 16000, ..OpusConfig::default() }.with_format(format);
+        assert_eq!(config.format(), format);
+        let mut codec = OpusCodec::new(config).unwrap();
+
+        // 60 ms of 16 kHz mono is 960 samples, the same count as 20 ms of 48 kHz per channel
+        let frame = AudioFrame::with_format(generate_test_signal(format.frame_samples()), format);
+        assert_eq!(frame.samples.len(), 960);
+        assert_eq!((frame.channels(), frame.sample_rate()), (1, 16000));
+
+        let encoded = codec.encode(&frame).unwrap();
+        let decoded = codec.decode(&encoded).unwrap();
+        assert_eq!(decoded.samples.len(), 960);
+        assert_eq!(decoded.format, format);
+
+        // FEC and concealment rebuild a whole 60 ms frame
+        assert_eq!(codec.decode_fec(&encoded).unwrap().samples.len(), 960);
+        assert_eq!(codec.decode_lost_packet().unwrap().samples.len(), 960);
+
+        let stats = codec.get_stats();
+        assert_eq!((stats.sample_rate, stats.channels, stats.frame_duration_ms), (16000, 1, 60));
+        assert!(stats.average_compression_ratio > 1.0);
+
+        // Formats Opus cannot code are refused up front
+        assert!(OpusCodec::new(OpusConfig::default().with_format(AudioFormat { frame_duration_ms: 30, ..format })).is_err());
+        assert!(OpusCodec::new(OpusConfig::default().with_format(AudioFormat { sample_rate: 44100, ..format })).is_err());
+    }
+
     #[test]
     fn test_opus_error_handling() {
         let config = OpusConfig::default();
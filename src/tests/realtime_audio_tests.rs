@@ -97,6 +97,61 @@ mod realtime_audio_tests {
         assert_eq!(silent_frame.peak(), 0.0);
     }
 
+    #[test]
+    fn test_frame_assembler_carries_partial_frames() {
+        let format = AudioFormat::STANDARD;
+        let mut assembler = FrameAssembler::new(format);
+        let mut frames = Vec::new();
+
+        // Callbacks that do not line up with frames: 441 samples at a time
+        let signal: Vec<f32> = (1..=5000).map(|n| n as f32).collect();
+        for chunk in signal.chunks(441) {
+            assembler.push(chunk, |frame| frames.push(frame));
+        }
+
+        // Only whole frames come out, continuous and unpadded
+        assert_eq!(frames.len(), 5000 / format.frame_samples());
+        let emitted: Vec<f32> = frames.iter().flat_map(|frame| frame.samples.iter().copied()).collect();
+        assert_eq!(emitted, signal[..emitted.len()]);
+        assert!(frames.iter().all(|frame| frame.format == format));
+        assert_eq!(assembler.pending(), 5000 % format.frame_samples());
+
+        // The carried samples open the next frame
+        assembler.push(&vec![0.0; format.frame_samples()], |frame| frames.push(frame));
+        assert_eq!(frames.last().unwrap().samples[0], signal[emitted.len()]);
+    }
+
+    #[test]
+    fn test_frame_splitter_carries_partial_frames() {
+        let format = AudioFormat::STANDARD;
+        let signal: Vec<f32> = (1..=3 * format.frame_samples()).map(|n| n as f32).collect();
+        let mut frames: std::collections::VecDeque<AudioFrame> = signal
+            .chunks(format.frame_samples())
+            .map(|samples| AudioFrame::with_format(samples.to_vec(), format))
+            .collect();
+        let mut splitter = FrameSplitter::new();
+
+        // Callbacks that do not line up with frames: 441 samples at a time
+        let mut played = Vec::new();
+        for _ in 0..signal.len() / 441 {
+            let mut data = [f32::NAN; 441];
+            splitter.fill(&mut data, || frames.pop_front());
+            played.extend_from_slice(&data);
+        }
+
+        // Every sample plays once, in order, with the remainder carried over
+        assert_eq!(played, signal[..played.len()]);
+        assert_eq!(splitter.pending(), signal.len() - played.len());
+
+        // Once the frames run out the rest of the callback is silence
+        let mut data = [f32::NAN; 441];
+        splitter.fill(&mut data, || frames.pop_front());
+        let tail = signal.len() - played.len();
+        assert_eq!(data[..tail], signal[played.len()..]);
+        assert!(data[tail..].iter().all(|&sample| sample == 0.0));
+        assert_eq!(splitter.pending(), 0);
+    }
+
     #[test]
     fn test_realtime_audio_processor_creation() {
         let processor = RealTimeAudioProcessor::new();
@@ -119,6 +174,221 @@ mod realtime_audio_tests {
         assert_eq!(stats.output_overruns, 0);
     }
 
+    #[test]
+    fn test_capture_pipeline_produces_decodable_packets() {
+        use crate::noise_suppression::NoiseSuppressionConfig;
+        use crate::echo_cancellation::EchoCancellationConfig;
+        use crate::opus_codec::{OpusCodec, OpusConfig};
+        use crate::redundancy::{RedundantPayload, PAYLOAD_TYPE_OPUS};
+
+        let mut pipeline = CapturePipeline::new(
+            Some(NoiseSuppressionConfig::default()),
+            Some(EchoCancellationConfig::default()),
+            OpusConfig::default(),
+        ).unwrap();
+        let mut decoder = OpusCodec::new(OpusConfig::default()).unwrap();
+
+        // Far-end reference is silence until the receive path provides one
+        pipeline.set_far_end_reference(&AudioFrame::silence());
+
+        for i in 0..5 {
+            let mut frame = AudioFrame::new((0..FRAME_SIZE_SAMPLES)
+                .map(|n| 0.3 * ((n + i * FRAME_SIZE_SAMPLES) as f32 * 0.05).sin())
+                .collect());
+
+            let packet = pipeline.process(&mut frame).unwrap();
+            assert!(!packet.is_empty());
+            assert!(packet.len() < FRAME_SIZE_SAMPLES * 4, "Packet should be compressed");
+
+            // Redundancy is off by default, so the packet is the primary frame alone
+            let payload = RedundantPayload::decode(&packet).unwrap();
+            assert_eq!(payload.payload_type, PAYLOAD_TYPE_OPUS);
+            assert!(payload.redundant.is_empty());
+            let decoded = decoder.decode(&payload.primary).unwrap();
+            assert_eq!(decoded.samples.len(), FRAME_SIZE_SAMPLES);
+        }
+
+        assert_eq!(pipeline.codec_stats().frames_encoded, 5);
+    }
+
+    #[test]
+    fn test_playback_pipeline_decodes_and_conceals() {
+        use crate::jitter_buffer::JitterBufferConfig;
+        use crate::opus_codec::{OpusCodec, OpusConfig};
+        use crate::redundancy::{RedundantPayload, PAYLOAD_TYPE_OPUS};
+
+        let mut encoder = OpusCodec::new(OpusConfig::default()).unwrap();
+        let mut jitter_config = JitterBufferConfig::default();
+        jitter_config.initial_target_size = 1;
+        let mut pipeline = PlaybackPipeline::new(jitter_config, OpusConfig::default()).unwrap();
+
+        // Nothing to play before any packet arrives
+        assert!(pipeline.next_frame().is_none());
+
+        // Sender frame numbers start mid-stream; frame 102 is lost in transit
+        for sender_sequence in [100u64, 101, 103] {
+            let frame = AudioFrame::new(vec![0.1; FRAME_SIZE_SAMPLES]);
+            let packet = RedundantPayload::primary(PAYLOAD_TYPE_OPUS, encoder.encode(&frame).unwrap());
+            pipeline.push_packet(sender_sequence, packet.encode().unwrap()).unwrap();
+        }
+
+        let mut played = Vec::new();
+        while let Some(frame) = pipeline.next_frame() {
+            assert_eq!(frame.samples.len(), FRAME_SIZE_SAMPLES);
+            played.push(frame.sequence);
+        }
+
+        // Frame 103 was already buffered, so 102 comes from its in-band FEC rather than PLC
+        assert_eq!(played, vec![0, 1, 2, 3]);
+        assert_eq!(pipeline.frames_decoded(), 3);
+        assert_eq!(pipeline.frames_fec_decoded(), 1);
+        assert_eq!(pipeline.frames_concealed(), 0);
+    }
+
+    #[test]
+    fn test_playback_pipeline_follows_restarted_sender() {
+        use crate::jitter_buffer::JitterBufferConfig;
+        use crate::opus_codec::{OpusCodec, OpusConfig};
+        use crate::redundancy::{RedundantPayload, PAYLOAD_TYPE_OPUS};
+
+        let mut encoder = OpusCodec::new(OpusConfig::default()).unwrap();
+        let mut jitter_config = JitterBufferConfig::default();
+        jitter_config.initial_target_size = 1;
+        let mut pipeline = PlaybackPipeline::new(jitter_config, OpusConfig::default()).unwrap();
+        let mut send = |pipeline: &mut PlaybackPipeline, sender_sequence: u64| {
+            let frame = AudioFrame::new(vec![0.1; FRAME_SIZE_SAMPLES]);
+            let packet = RedundantPayload::primary(PAYLOAD_TYPE_OPUS, encoder.encode(&frame).unwrap());
+            pipeline.push_packet(sender_sequence, packet.encode().unwrap()).unwrap();
+            let mut played = 0;
+            while pipeline.next_frame().is_some() {
+                played += 1;
+            }
+            played
+        };
+
+        for sender_sequence in 1..=100 {
+            send(&mut pipeline, sender_sequence);
+        }
+        let decoded = pipeline.frames_decoded();
+
+        // The sender restarts its session at the same address and counts from 1 again
+        let played: usize = (1..=10).map(|sender_sequence| send(&mut pipeline, sender_sequence)).sum();
+        assert_eq!(played, 10);
+        assert_eq!(pipeline.frames_decoded(), decoded + 10);
+
+        // A packet merely delayed in transit is dropped, not mistaken for a restart
+        assert_eq!(send(&mut pipeline, 3), 0);
+        assert_eq!(send(&mut pipeline, 11), 1);
+        assert_eq!(pipeline.frames_decoded(), decoded + 11);
+    }
+
+    #[test]
+    fn test_low_bandwidth_profile_end_to_end() {
+        use crate::noise_suppression::NoiseSuppressionConfig;
+        use crate::echo_cancellation::EchoCancellationConfig;
+        use crate::jitter_buffer::JitterBufferConfig;
+        use crate::opus_codec::OpusConfig;
+
+        let format = AudioFormat::LOW_BANDWIDTH;
+        let opus = OpusConfig { bitrate: 16000, ..OpusConfig::default() }.with_format(format);
+        let mut capture = CapturePipeline::new(
+            Some(NoiseSuppressionConfig::default()),
+            Some(EchoCancellationConfig::default()),
+            opus.clone(),
+        ).unwrap();
+        let mut playback = PlaybackPipeline::new(JitterBufferConfig::default(), opus).unwrap();
+        assert_eq!((capture.format(), playback.format()), (format, format));
+
+        // A frame captured in another format is refused rather than mis-encoded
+        assert!(capture.process(&mut AudioFrame::silence()).is_err());
+
+        // Frame 3 is lost in transit
+        let samples = format.frame_samples();
+        let mut played = Vec::new();
+        for sequence in 0..8u64 {
+            let mut frame = AudioFrame::with_format((0..samples)
+                .map(|n| 0.3 * ((n as u64 + sequence * samples as u64) as f32 * 0.05).sin())
+                .collect(), format);
+            let packet = capture.process(&mut frame).unwrap();
+            if sequence != 3 {
+                playback.push_packet(sequence, packet).unwrap();
+            }
+            while let Some(frame) = playback.next_frame() {
+                assert_eq!(frame.samples.len(), samples);
+                assert_eq!(frame.format, format);
+                played.push(frame.sequence);
+            }
+        }
+
+        assert_eq!(played, (0..8).collect::<Vec<u32>>());
+        assert_eq!(playback.frames_decoded(), 7);
+        assert_eq!(playback.frames_fec_decoded() + playback.frames_concealed(), 1);
+
+        // The processor captures in one format, and only takes pipelines that match it
+        let mut processor = RealTimeAudioProcessor::new().unwrap();
+        assert!(processor.enable_capture_pipeline(capture).is_err());
+        let mut processor = RealTimeAudioProcessor::with_config(AudioConfiguration::default().with_format(format)).unwrap();
+        assert_eq!(processor.get_config().format(), format);
+        assert!(processor.enable_playback_pipeline(playback).is_ok());
+    }
+
+    #[test]
+    fn test_enable_playback_pipeline() {
+        use crate::jitter_buffer::JitterBufferConfig;
+        use crate::opus_codec::OpusConfig;
+
+        let mut processor = RealTimeAudioProcessor::new().unwrap();
+        let pipeline = PlaybackPipeline::new(JitterBufferConfig::default(), OpusConfig::default()).unwrap();
+
+        let received_producer = processor.enable_playback_pipeline(pipeline);
+        assert!(received_producer.is_ok());
+
+        let stats = processor.get_stats();
+        assert_eq!(stats.frames_decoded, 0);
+        assert_eq!(stats.frames_concealed, 0);
+    }
+
+    #[test]
+    fn test_enable_capture_pipeline() {
+        use crate::opus_codec::OpusConfig;
+
+        let mut processor = RealTimeAudioProcessor::new().unwrap();
+        let pipeline = CapturePipeline::new(None, None, OpusConfig::default()).unwrap();
+
+        let encoded_consumer = processor.enable_capture_pipeline(pipeline);
+        assert!(encoded_consumer.is_ok());
+
+        let stats = processor.get_stats();
+        assert_eq!(stats.packets_encoded, 0);
+        assert_eq!(stats.encoded_packets_dropped, 0);
+    }
+
+    #[test]
+    fn test_replace_pipelines_switches_format() {
+        use crate::codec::CodecKind;
+        use crate::jitter_buffer::JitterBufferConfig;
+        use crate::opus_codec::OpusConfig;
+
+        let mut processor = RealTimeAudioProcessor::new().unwrap();
+        processor.enable_capture_pipeline(CapturePipeline::new(None, None, OpusConfig::default()).unwrap()).unwrap();
+        processor.enable_playback_pipeline(PlaybackPipeline::new(JitterBufferConfig::default(), OpusConfig::default()).unwrap()).unwrap();
+
+        // A call settles on G.722: the processor now runs its format
+        let opus = OpusConfig::default().with_format(AudioFormat::LOW_BANDWIDTH);
+        let capture = CapturePipeline::new(None, None, opus.clone()).unwrap().with_codec(CodecKind::G722).unwrap();
+        let playback = PlaybackPipeline::new(JitterBufferConfig::default(), opus.clone()).unwrap().with_codec(CodecKind::G722).unwrap();
+        processor.replace_pipelines(capture, playback).unwrap();
+        let format = processor.get_config().format();
+        assert_eq!(format, AudioFormat { sample_rate: 16000, channels: 1, frame_duration_ms: 60 });
+        assert_eq!(processor.get_config().frame_size_samples(), format.frame_samples());
+
+        // Both directions must agree on the format
+        let capture = CapturePipeline::new(None, None, OpusConfig::default()).unwrap();
+        let playback = PlaybackPipeline::new(JitterBufferConfig::default(), opus).unwrap();
+        assert!(processor.replace_pipelines(capture, playback).is_err());
+        assert_eq!(processor.get_config().format(), format);
+    }
+
     #[test]
     fn test_audio_buffer_pool_creation() {
         let pool = AudioBufferPool::new(10);
@@ -0,0 +1,176 @@
+#[cfg(test)]
+mod redundancy_tests {
+    use crate::redundancy::*;
+    use crate::congestion::{EncoderControl, EncoderTarget};
+    use crate::jitter_buffer::JitterBufferConfig;
+    use crate::netsim::{Impairment, ImpairmentConfig, LossModel};
+    use crate::opus_codec::OpusConfig;
+    use crate::realtime_audio::{AudioFrame, CapturePipeline, PlaybackPipeline, FRAME_SIZE_SAMPLES};
+    use std::time::Duration;
+
+    fn tone(index: usize) -> AudioFrame {
+        AudioFrame::new((0..FRAME_SIZE_SAMPLES)
+            .map(|n| 0.3 * ((n + index * FRAME_SIZE_SAMPLES) as f32 * 0.03).sin())
+            .collect())
+    }
+
+    fn capture(config: RedundancyConfig) -> CapturePipeline {
+        CapturePipeline::new(None, None, OpusConfig::default()).unwrap()
+            .with_redundancy(config).unwrap()
+    }
+
+    fn always(depth: usize) -> RedundancyConfig {
+        RedundancyConfig { mode: RedundancyMode::Always, depth, ..RedundancyConfig::default() }
+    }
+
+    /// Packets for `count` consecutive frames, by frame number
+    fn encode_frames(pipeline: &mut CapturePipeline, count: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|index| pipeline.process(&mut tone(index)).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_payload_layout_round_trip() {
+        let payload = RedundantPayload {
+            redundant: vec![
+                RedundantBlock { payload_type: PAYLOAD_TYPE_OPUS, offset: 2, data: vec![0xAA; 3] },
+                RedundantBlock { payload_type: PAYLOAD_TYPE_OPUS, offset: 1, data: vec![0xBB; 2] },
+            ],
+            payload_type: PAYLOAD_TYPE_OPUS,
+            primary: vec![0xCC; 4],
+        };
+
+        let encoded = payload.encode().unwrap();
+        // F=1|PT, then offset (14 bits) and length (10 bits)
+        assert_eq!(&encoded[..4], &[0x80 | PAYLOAD_TYPE_OPUS, 0x00, 0x08, 0x03]);
+        assert_eq!(&encoded[4..8], &[0x80 | PAYLOAD_TYPE_OPUS, 0x00, 0x04, 0x02]);
+        assert_eq!(encoded[8], PAYLOAD_TYPE_OPUS);
+        assert_eq!(encoded.len(), 9 + 3 + 2 + 4);
+        assert_eq!(RedundantPayload::decode(&encoded).unwrap(), payload);
+
+        let plain = RedundantPayload::primary(PAYLOAD_TYPE_OPUS, vec![1, 2, 3]);
+        assert_eq!(plain.encode().unwrap(), vec![PAYLOAD_TYPE_OPUS, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_malformed_payloads_rejected() {
+        // Empty, header only, and a block longer than the packet
+        assert!(RedundantPayload::decode(&[]).is_err());
+        assert!(RedundantPayload::decode(&[PAYLOAD_TYPE_OPUS]).is_err());
+        assert!(RedundantPayload::decode(&[0x80 | PAYLOAD_TYPE_OPUS, 0x00, 0x04, 0x10, PAYLOAD_TYPE_OPUS, 1, 2]).is_err());
+        // Zero offset
+        assert!(RedundantPayload::decode(&[0x80 | PAYLOAD_TYPE_OPUS, 0x00, 0x00, 0x01, PAYLOAD_TYPE_OPUS, 1, 2]).is_err());
+
+        let mut payload = RedundantPayload::primary(0x80, vec![1]);
+        assert!(payload.encode().is_err());
+        payload.payload_type = PAYLOAD_TYPE_OPUS;
+        payload.redundant.push(RedundantBlock { payload_type: PAYLOAD_TYPE_OPUS, offset: 1, data: vec![0; 1024] });
+        assert!(payload.encode().is_err());
+
+        assert!(RedundancyConfig { depth: 0, ..RedundancyConfig::default() }.validate().is_err());
+        assert!(RedundancyConfig { depth: MAX_REDUNDANCY_DEPTH + 1, ..RedundancyConfig::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_packets_repeat_previous_frames_at_lower_bitrate() {
+        let mut pipeline = capture(always(2));
+        let packets = encode_frames(&mut pipeline, 5);
+
+        let offsets: Vec<Vec<u16>> = packets.iter()
+            .map(|packet| RedundantPayload::decode(packet).unwrap().redundant.iter().map(|block| block.offset).collect())
+            .collect();
+        assert_eq!(offsets, vec![vec![], vec![1], vec![2, 1], vec![2, 1], vec![2, 1]]);
+        assert_eq!(pipeline.packets_with_redundancy(), 4);
+
+        // Copies come from the 24 kbps encoder, so they are smaller than the 64 kbps primary
+        let last = RedundantPayload::decode(&packets[4]).unwrap();
+        let primary_total: usize = packets.iter().map(|packet| RedundantPayload::decode(packet).unwrap().primary.len()).sum();
+        assert!(last.redundant[1].data.len() < primary_total / packets.len());
+    }
+
+    #[test]
+    fn test_auto_mode_follows_encoder_target() {
+        let control = EncoderControl::default();
+        let config = RedundancyConfig { mode: RedundancyMode::Auto, ..RedundancyConfig::default() };
+        let mut pipeline = capture(config).with_encoder_control(control.clone());
+        let target = |redundancy| EncoderTarget { bitrate: 64_000, complexity: 5, mono: false, redundancy, packet_loss_percent: 5 };
+
+        encode_frames(&mut pipeline, 3);
+        assert!(!pipeline.redundancy_active());
+        assert_eq!(pipeline.packets_with_redundancy(), 0);
+
+        control.request(target(true));
+        encode_frames(&mut pipeline, 3);
+        assert!(pipeline.redundancy_active());
+        assert_eq!(pipeline.packets_with_redundancy(), 2);
+
+        control.request(target(false));
+        let packets = encode_frames(&mut pipeline, 2);
+        assert!(RedundantPayload::decode(&packets[1]).unwrap().redundant.is_empty());
+
+        // A fixed mode ignores the controller
+        let mut fixed = capture(always(1)).with_encoder_control(control.clone());
+        control.request(target(false));
+        encode_frames(&mut fixed, 3);
+        assert!(fixed.redundancy_active());
+    }
+
+    #[test]
+    fn test_playback_rebuilds_lost_burst_from_later_packet() {
+        let packets = encode_frames(&mut capture(always(2)), 12);
+        let jitter_config = JitterBufferConfig { initial_target_size: 1, ..JitterBufferConfig::default() };
+
+        // Two lost frames are covered; with a third the oldest falls outside the copies
+        for (lost, recovered, unrecovered) in [(&[5u64, 6][..], 2, 0), (&[5, 6, 7][..], 2, 1)] {
+            let mut playback = PlaybackPipeline::new(jitter_config, OpusConfig::default()).unwrap();
+            let mut played = Vec::new();
+            for (sequence, packet) in packets.iter().enumerate() {
+                if !lost.contains(&(sequence as u64)) {
+                    playback.push_packet(1000 + sequence as u64, packet.clone()).unwrap();
+                }
+                while let Some(frame) = playback.next_frame() {
+                    played.push(frame.sequence);
+                }
+            }
+
+            assert_eq!(played, (0..12).collect::<Vec<u32>>());
+            assert_eq!(playback.frames_recovered(), recovered);
+            assert_eq!(playback.frames_fec_decoded() + playback.frames_concealed(), unrecovered);
+        }
+    }
+
+    #[test]
+    fn test_redundancy_reduces_concealment_on_bursty_link() {
+        // Ten seconds over a Gilbert-Elliott link that loses packets in short bursts
+        let link = ImpairmentConfig {
+            loss: LossModel::GilbertElliott { good_to_bad: 0.05, bad_to_good: 0.5, loss_in_good: 0.0, loss_in_bad: 0.8 },
+            delay: Duration::from_millis(30),
+            seed: 2198,
+            ..ImpairmentConfig::default()
+        };
+
+        let unrecovered = |config: RedundancyConfig| {
+            let packets = encode_frames(&mut capture(config), 500);
+            let sends: Vec<(Duration, Vec<u8>)> = packets.into_iter().enumerate()
+                .map(|(sequence, packet)| {
+                    let mut tagged = (sequence as u32).to_be_bytes().to_vec();
+                    tagged.extend_from_slice(&packet);
+                    (Duration::from_millis(20 * sequence as u64), tagged)
+                })
+                .collect();
+
+            let mut playback = PlaybackPipeline::new(JitterBufferConfig::default(), OpusConfig::default()).unwrap();
+            for (_, tagged) in Impairment::new(link.clone()).unwrap().simulate(sends) {
+                let sequence = u32::from_be_bytes(tagged[..4].try_into().unwrap());
+                playback.push_packet(sequence as u64, tagged[4..].to_vec()).unwrap();
+                while playback.next_frame().is_some() {}
+            }
+            (playback.frames_fec_decoded() + playback.frames_concealed(), playback.frames_recovered())
+        };
+
+        let (without, _) = unrecovered(RedundancyConfig::default());
+        let (with, recovered) = unrecovered(always(2));
+        assert!(without > 20, "Link lost too little to compare: {}", without);
+        assert!(with * 3 < without, "Missed {} frames with redundancy, {} without", with, without);
+        assert!(recovered > 0);
+    }
+}
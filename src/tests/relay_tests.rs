@@ -0,0 +1,223 @@
+#[cfg(test)]
+mod relay_tests {
+    use crate::relay::*;
+    use crate::discovery::RoomInfo;
+    use crate::security::SecurityConfig;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    async fn start_relay(config: RelayConfig) -> (Arc<RelayServer>, SocketAddr) {
+        let server = Arc::new(RelayServer::bind("127.0.0.1:0", config).await.unwrap());
+        let addr = server.local_addr().unwrap();
+        let running = Arc::clone(&server);
+        tokio::spawn(async move { running.run().await });
+        (server, addr)
+    }
+
+    async fn join(relay: SocketAddr, room: &str) -> RelayClient {
+        RelayClient::join(relay, room, SecurityConfig::new().unwrap()).await.unwrap()
+    }
+
+    async fn next_event(client: &mut RelayClient) -> RelayEvent {
+        tokio::time::timeout(Duration::from_secs(2), client.next_event()).await
+            .expect("no relay event")
+            .unwrap()
+    }
+
+    /// Wait until the given participants have been announced, in any order
+    async fn await_joined(client: &mut RelayClient, expected: &[u32]) {
+        let mut pending = expected.to_vec();
+        while !pending.is_empty() {
+            if let RelayEvent::ParticipantJoined { participant_id, .. } = next_event(client).await {
+                pending.retain(|&id| id != participant_id);
+            }
+        }
+    }
+
+    async fn next_audio(client: &mut RelayClient) -> (u32, Vec<u8>) {
+        loop {
+            if let RelayEvent::Audio { sender, payload, .. } = next_event(client).await {
+                return (sender, payload);
+            }
+        }
+    }
+
+    fn room_info(room_id: &str, max_participants: u8) -> RoomInfo {
+        RoomInfo {
+            room_id: room_id.to_string(),
+            display_name: "Test room".to_string(),
+            host_name: "relay".to_string(),
+            port: DEFAULT_RELAY_PORT,
+            is_encrypted: true,
+            max_participants,
+            current_participants: 0,
+            created_at: Instant::now(),
+            connection_methods: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_relay_messages_round_trip() {
+        let participant = Participant {
+            id: 7,
+            identity_public_key: [1u8; 32],
+            agreement_public_key: [2u8; 32],
+            join_nonce: [3u8; 32],
+            join_signature: [4u8; 64],
+        };
+        let messages = [
+            RelayMessage::JoinRequest { room_id: "room".to_string() },
+            RelayMessage::Challenge { nonce: [5u8; 32] },
+            RelayMessage::Join {
+                room_id: "room".to_string(),
+                identity_public_key: [1u8; 32],
+                agreement_public_key: [2u8; 32],
+                nonce: [3u8; 32],
+                signature: [4u8; 64],
+            },
+            RelayMessage::JoinAccepted { participant_id: 7, max_participants: 10 },
+            RelayMessage::JoinRejected { reason: "Room is full".to_string() },
+            RelayMessage::ParticipantJoined { participant },
+            RelayMessage::ParticipantLeft { participant_id: 7 },
+            RelayMessage::SenderKey { from: 1, to: 2, ephemeral_public_key: [6u8; 32], ciphertext: vec![7u8; 52], signature: [8u8; 64] },
+            RelayMessage::KeyRequest { from: 2, to: 1 },
+            RelayMessage::Media { sender: 1, key_id: 3, sequence: 99, nonce: [9u8; 12], ciphertext: vec![10u8; 40] },
+            RelayMessage::Leave,
+        ];
+
+        for message in messages {
+            let packet = encode_relay_message(&message).unwrap();
+            assert_eq!(decode_relay_message(&packet).unwrap(), message);
+        }
+
+        // Protocol packets are not relay packets
+        let handshake = crate::wire::encode_message(&crate::security::SecureMessage::Disconnect {
+            reason: String::new(),
+            signature: [0u8; 64],
+        }).unwrap();
+        assert!(decode_relay_message(&handshake).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_relay_forwards_between_room_members() {
+        let (server, relay) = start_relay(RelayConfig::default()).await;
+
+        let mut alice = join(relay, "group").await;
+        let mut bob = join(relay, "group").await;
+        await_joined(&mut alice, &[bob.participant_id()]).await;
+        let mut carol = join(relay, "group").await;
+        await_joined(&mut alice, &[carol.participant_id()]).await;
+        await_joined(&mut bob, &[alice.participant_id(), carol.participant_id()]).await;
+        await_joined(&mut carol, &[alice.participant_id(), bob.participant_id()]).await;
+
+        assert_eq!(server.roster("group").await.len(), 3);
+        assert_eq!(carol.participants().len(), 2);
+
+        // One upload reaches every other member
+        alice.send_audio(b"hello from alice").await.unwrap();
+        assert_eq!(next_audio(&mut bob).await, (alice.participant_id(), b"hello from alice".to_vec()));
+        assert_eq!(next_audio(&mut carol).await, (alice.participant_id(), b"hello from alice".to_vec()));
+
+        carol.send_audio(b"hello from carol").await.unwrap();
+        assert_eq!(next_audio(&mut alice).await, (carol.participant_id(), b"hello from carol".to_vec()));
+        assert_eq!(next_audio(&mut bob).await, (carol.participant_id(), b"hello from carol".to_vec()));
+
+        // Members of another room hear nothing
+        let mut outsider = join(relay, "other").await;
+        alice.send_audio(b"not for you").await.unwrap();
+        assert!(tokio::time::timeout(Duration::from_millis(200), outsider.next_event()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_relay_enforces_room_capacity() {
+        let (server, relay) = start_relay(RelayConfig { require_registered_rooms: true, ..RelayConfig::default() }).await;
+        server.register_room(&room_info("small", 2)).await;
+
+        let alice = join(relay, "small").await;
+        assert_eq!(alice.max_participants(), 2);
+        let _bob = join(relay, "small").await;
+
+        let error = RelayClient::join(relay, "small", SecurityConfig::new().unwrap()).await.err().unwrap();
+        assert!(error.to_string().contains("full"));
+        let error = RelayClient::join(relay, "unregistered", SecurityConfig::new().unwrap()).await.err().unwrap();
+        assert!(error.to_string().contains("Unknown room"));
+        assert_eq!(server.roster("small").await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_relay_rejects_unauthenticated_join_and_spoofed_media() {
+        let allowed = SecurityConfig::new().unwrap();
+        let (server, relay) = start_relay(RelayConfig {
+            allowed_identities: vec![allowed.get_public_identity()],
+            ..RelayConfig::default()
+        }).await;
+
+        let error = RelayClient::join(relay, "room", SecurityConfig::new().unwrap()).await.err().unwrap();
+        assert!(error.to_string().contains("not allowed"));
+
+        // A Join without a challenge is refused
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let forged = encode_relay_message(&RelayMessage::Join {
+            room_id: "room".to_string(),
+            identity_public_key: allowed.get_public_identity().to_bytes(),
+            agreement_public_key: [0u8; 32],
+            nonce: [0u8; 32],
+            signature: [0u8; 64],
+        }).unwrap();
+        socket.send_to(&forged, relay).await.unwrap();
+        let mut buffer = [0u8; 512];
+        let (len, _) = socket.recv_from(&mut buffer).await.unwrap();
+        assert!(matches!(decode_relay_message(&buffer[..len]).unwrap(), RelayMessage::JoinRejected { .. }));
+
+        let mut member = RelayClient::join(relay, "room", allowed).await.unwrap();
+        assert_eq!(server.roster("room").await.len(), 1);
+
+        // Media claiming to be the member from an unjoined address is not forwarded
+        let spoofed = encode_relay_message(&RelayMessage::Media {
+            sender: member.participant_id(),
+            key_id: 1,
+            sequence: 1,
+            nonce: [0u8; 12],
+            ciphertext: vec![0u8; 32],
+        }).unwrap();
+        socket.send_to(&spoofed, relay).await.unwrap();
+        assert!(tokio::time::timeout(Duration::from_millis(200), member.next_event()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sender_key_rotates_when_member_leaves() {
+        let (server, relay) = start_relay(RelayConfig::default()).await;
+
+        let mut alice = join(relay, "room").await;
+        let mut bob = join(relay, "room").await;
+        await_joined(&mut alice, &[bob.participant_id()]).await;
+        let carol = join(relay, "room").await;
+        let carol_id = carol.participant_id();
+        await_joined(&mut alice, &[carol_id]).await;
+        await_joined(&mut bob, &[alice.participant_id(), carol_id]).await;
+
+        let first_key = alice.sender_key_id();
+        carol.leave().await.unwrap();
+
+        assert_eq!(next_event(&mut alice).await, RelayEvent::ParticipantLeft { participant_id: carol_id });
+        assert_eq!(next_event(&mut bob).await, RelayEvent::ParticipantLeft { participant_id: carol_id });
+        assert_ne!(alice.sender_key_id(), first_key);
+        assert_eq!(server.roster("room").await.len(), 2);
+
+        // Bob picked up the new key and still hears Alice
+        alice.send_audio(b"after rotation").await.unwrap();
+        assert_eq!(next_audio(&mut bob).await, (alice.participant_id(), b"after rotation".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_idle_participants_expire() {
+        let (server, relay) = start_relay(RelayConfig { idle_timeout: Duration::from_millis(300), ..RelayConfig::default() }).await;
+
+        let _idle = join(relay, "room").await;
+        assert_eq!(server.roster("room").await.len(), 1);
+
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        assert!(server.roster("room").await.is_empty());
+    }
+}
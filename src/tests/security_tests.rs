@@ -1,6 +1,10 @@
 #[cfg(test)]
 mod security_tests {
     use crate::security::*;
+    use crate::capabilities::{CapabilityOffer, NegotiationError};
+    use crate::codec::CodecKind;
+    use crate::opus_codec::OpusConfig;
+    use crate::realtime_audio::AudioFormat;
     use crate::realtime_audio::AudioFrame;
     use ed25519_dalek::{SigningKey, VerifyingKey};
     use x25519_dalek::{EphemeralSecret, PublicKey};
@@ -15,7 +19,7 @@ mod security_tests {
         assert!(config.encryption_enabled);
         assert!(config.authentication_required);
         assert!(config.trusted_keys.is_empty());
-        assert!(config.session_cipher.is_none());
+        assert!(!SecureSession::new(config).is_session_active());
     }
 
     #[test]
@@ -250,6 +254,448 @@ mod security_tests {
         println!("Forward secrecy test passed");
     }
 
+    #[test]
+    fn test_directional_keys_reject_reflection() {
+        let mut alice = SecureSession::new(SecurityConfig::new().unwrap());
+        let mut bob = SecureSession::new(SecurityConfig::new().unwrap());
+
+        let handshake = alice.initiate_handshake().unwrap();
+        let response = bob.process_handshake(handshake).unwrap().unwrap();
+        alice.process_handshake_response(response).unwrap();
+
+        let to_bob = alice.encrypt_audio_frame(b"alice to bob").unwrap();
+        let to_alice = bob.encrypt_audio_frame(b"bob to alice").unwrap();
+
+        // Each direction decrypts only at the intended receiver
+        assert_eq!(bob.decrypt_audio_frame(to_bob.clone()).unwrap(), b"alice to bob");
+        assert_eq!(alice.decrypt_audio_frame(to_alice.clone()).unwrap(), b"bob to alice");
+
+        // Reflecting a packet back at its sender must fail
+        assert!(alice.decrypt_audio_frame(to_bob).is_err());
+        assert!(bob.decrypt_audio_frame(to_alice).is_err());
+    }
+
+    #[test]
+    fn test_replay_window_sliding() {
+        let mut window = ReplayWindow::new();
+
+        // Out-of-order packets inside the window are accepted once
+        for counter in [1, 3, 2, 10] {
+            assert!(window.check(counter).is_ok());
+            window.accept(counter);
+        }
+        assert!(window.check(3).is_err(), "Duplicate counter should be rejected");
+        assert!(window.check(5).is_ok(), "Unseen counter inside window should pass");
+
+        // Jump far ahead: everything behind the window is too old
+        window.accept(10 + REPLAY_WINDOW_SIZE);
+        assert_eq!(window.highest(), 10 + REPLAY_WINDOW_SIZE);
+        assert!(window.check(10).is_err());
+        assert!(window.check(11).is_ok());
+        assert!(window.check(0).is_err());
+    }
+
+    #[test]
+    fn test_session_rejects_replayed_and_stale_frames() {
+        let mut alice = SecureSession::new(SecurityConfig::new().unwrap());
+        let mut bob = SecureSession::new(SecurityConfig::new().unwrap());
+
+        let handshake = alice.initiate_handshake().unwrap();
+        let response = bob.process_handshake(handshake).unwrap().unwrap();
+        alice.process_handshake_response(response).unwrap();
+
+        let first = alice.encrypt_audio_frame(b"first").unwrap();
+        let second = alice.encrypt_audio_frame(b"second").unwrap();
+
+        // Reordered delivery is fine, replays are not
+        assert_eq!(bob.decrypt_audio_frame(second.clone()).unwrap(), b"second");
+        assert_eq!(bob.decrypt_audio_frame(first.clone()).unwrap(), b"first");
+        assert!(bob.decrypt_audio_frame(first).is_err());
+        assert!(bob.decrypt_audio_frame(second).is_err());
+
+        // A frame that falls behind the window is dropped
+        let stale = alice.encrypt_audio_frame(b"stale").unwrap();
+        for _ in 0..REPLAY_WINDOW_SIZE {
+            let frame = alice.encrypt_audio_frame(b"fresh").unwrap();
+            bob.decrypt_audio_frame(frame).unwrap();
+        }
+        assert!(bob.decrypt_audio_frame(stale).is_err());
+    }
+
+    #[test]
+    fn test_frame_counter_is_authenticated() {
+        let mut alice = SecureSession::new(SecurityConfig::new().unwrap());
+        let mut bob = SecureSession::new(SecurityConfig::new().unwrap());
+
+        let handshake = alice.initiate_handshake().unwrap();
+        let response = bob.process_handshake(handshake).unwrap().unwrap();
+        alice.process_handshake_response(response).unwrap();
+
+        let frame = alice.encrypt_audio_frame(b"audio").unwrap();
+        let (frame_number, timestamp, mut nonce, ciphertext) = match frame {
+            SecureMessage::EncryptedAudio { frame_number, timestamp, nonce, ciphertext } => (frame_number, timestamp, nonce, ciphertext),
+            _ => panic!("Expected encrypted audio"),
+        };
+        assert_eq!(frame_number, 1);
+
+        // Moving the counter, with or without a matching nonce, must fail
+        let shifted = SecureMessage::EncryptedAudio { frame_number: 7, timestamp, nonce, ciphertext: ciphertext.clone() };
+        assert!(bob.decrypt_audio_frame(shifted).is_err());
+
+        nonce[4..].copy_from_slice(&7u64.to_be_bytes());
+        let forged = SecureMessage::EncryptedAudio { frame_number: 7, timestamp, nonce, ciphertext };
+        assert!(bob.decrypt_audio_frame(forged).is_err());
+    }
+
+    #[test]
+    fn test_control_messages_use_separate_counters() {
+        let (mut alice, mut bob) = establish_session_pair(SecurityConfig::new().unwrap());
+
+        let control = alice.encrypt_control(b"feedback").unwrap();
+        let audio = alice.encrypt_audio_frame(b"audio").unwrap();
+
+        // Control traffic does not consume audio frame numbers
+        assert!(matches!(audio, SecureMessage::EncryptedAudio { frame_number: 1, .. }));
+        assert!(matches!(control, SecureMessage::EncryptedControl { counter, .. } if counter == CONTROL_COUNTER_BASE + 1));
+
+        assert_eq!(bob.decrypt_control(control.clone()).unwrap(), b"feedback");
+        assert_eq!(bob.decrypt_audio_frame(audio).unwrap(), b"audio");
+        assert!(bob.decrypt_control(control.clone()).is_err());
+
+        // A control packet cannot be passed off as audio, or the other way round
+        let (counter, nonce, ciphertext) = match alice.encrypt_control(b"more").unwrap() {
+            SecureMessage::EncryptedControl { counter, nonce, ciphertext } => (counter, nonce, ciphertext),
+            _ => panic!("Expected encrypted control"),
+        };
+        let disguised = SecureMessage::EncryptedAudio { frame_number: counter, timestamp: 0, nonce, ciphertext };
+        assert!(bob.decrypt_audio_frame(disguised).is_err());
+
+        let audio = alice.encrypt_audio_frame(b"audio").unwrap();
+        let (frame_number, nonce, ciphertext) = match audio {
+            SecureMessage::EncryptedAudio { frame_number, nonce, ciphertext, .. } => (frame_number, nonce, ciphertext),
+            _ => panic!("Expected encrypted audio"),
+        };
+        assert!(bob.decrypt_control(SecureMessage::EncryptedControl { counter: frame_number, nonce, ciphertext }).is_err());
+    }
+
+    fn establish_session_pair(alice_config: SecurityConfig) -> (SecureSession, SecureSession) {
+        let mut alice = SecureSession::new(alice_config);
+        let mut bob = SecureSession::new(SecurityConfig::new().unwrap());
+
+        let handshake = alice.initiate_handshake().unwrap();
+        let response = bob.process_handshake(handshake).unwrap().unwrap();
+        alice.process_handshake_response(response).unwrap();
+        (alice, bob)
+    }
+
+    fn nonce_prefix(message: &SecureMessage) -> [u8; 4] {
+        match message {
+            SecureMessage::EncryptedAudio { nonce, .. } => nonce[..4].try_into().unwrap(),
+            _ => panic!("Expected encrypted audio"),
+        }
+    }
+
+    #[test]
+    fn test_in_band_rekey_with_overlap() {
+        let (mut alice, mut bob) = establish_session_pair(SecurityConfig::new().unwrap());
+
+        // Frames still in flight when the rekey happens
+        let alice_old = alice.encrypt_audio_frame(b"alice old").unwrap();
+        let bob_old = bob.encrypt_audio_frame(b"bob old").unwrap();
+
+        let request = alice.initiate_rekey().unwrap();
+        let response = bob.process_rekey(request).unwrap().unwrap();
+        assert!(alice.process_rekey(response).unwrap().is_none());
+        assert_eq!(alice.key_generation(), 1);
+        assert_eq!(bob.key_generation(), 1);
+
+        // Alice switched immediately; her new frames use a fresh key
+        let alice_new = alice.encrypt_audio_frame(b"alice new").unwrap();
+        assert_ne!(nonce_prefix(&alice_old), nonce_prefix(&alice_new));
+
+        // Bob keeps sending on the old key until Alice's new traffic confirms the switch
+        let bob_unconfirmed = bob.encrypt_audio_frame(b"bob unconfirmed").unwrap();
+        assert_eq!(nonce_prefix(&bob_old), nonce_prefix(&bob_unconfirmed));
+
+        assert_eq!(bob.decrypt_audio_frame(alice_new).unwrap(), b"alice new");
+        assert_eq!(bob.decrypt_audio_frame(alice_old).unwrap(), b"alice old");
+
+        let bob_new = bob.encrypt_audio_frame(b"bob new").unwrap();
+        assert_ne!(nonce_prefix(&bob_old), nonce_prefix(&bob_new));
+
+        // Old-generation packets still decrypt during the overlap
+        assert_eq!(alice.decrypt_audio_frame(bob_new).unwrap(), b"bob new");
+        assert_eq!(alice.decrypt_audio_frame(bob_old).unwrap(), b"bob old");
+        assert_eq!(alice.decrypt_audio_frame(bob_unconfirmed).unwrap(), b"bob unconfirmed");
+    }
+
+    #[test]
+    fn test_rekey_rejects_forged_and_stale_requests() {
+        let (mut alice, mut bob) = establish_session_pair(SecurityConfig::new().unwrap());
+
+        // Tampered signature
+        let forged = match alice.initiate_rekey().unwrap() {
+            SecureMessage::Rekey { generation, ephemeral_public_key, mut signature } => {
+                signature[0] ^= 0xFF;
+                SecureMessage::Rekey { generation, ephemeral_public_key, signature }
+            }
+            _ => panic!("Expected rekey request"),
+        };
+        assert!(bob.process_rekey(forged).is_err());
+
+        // Complete two rekeys, then replay the first request
+        let first = alice.initiate_rekey().unwrap();
+        let response = bob.process_rekey(first.clone()).unwrap().unwrap();
+        alice.process_rekey(response).unwrap();
+
+        let frame = alice.encrypt_audio_frame(b"confirm").unwrap();
+        bob.decrypt_audio_frame(frame).unwrap();
+
+        let second = alice.initiate_rekey().unwrap();
+        let response = bob.process_rekey(second).unwrap().unwrap();
+        alice.process_rekey(response).unwrap();
+
+        assert!(bob.process_rekey(first).is_err());
+        assert_eq!(bob.key_generation(), 2);
+    }
+
+    #[test]
+    fn test_rekey_interval_drives_initiator_only() {
+        let mut alice_config = SecurityConfig::new().unwrap();
+        alice_config.rekey_interval = Some(std::time::Duration::ZERO);
+        let (mut alice, mut bob) = establish_session_pair(alice_config);
+
+        let request = alice.poll_rekey().unwrap();
+        assert!(matches!(request, Some(SecureMessage::Rekey { generation: 1, .. })));
+        assert!(bob.poll_rekey().unwrap().is_none());
+
+        // While unanswered, the request is only resent after the retry interval
+        assert!(alice.poll_rekey().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_handshake_response_bound_to_initiation() {
+        let mut alice = SecureSession::new(SecurityConfig::new().unwrap());
+        let mut bob = SecureSession::new(SecurityConfig::new().unwrap());
+
+        // Response to an earlier initiation must not complete a fresh one
+        let first = alice.initiate_handshake().unwrap();
+        let stale_response = bob.process_handshake(first).unwrap().unwrap();
+        alice.initiate_handshake().unwrap();
+        assert!(alice.process_handshake_response(stale_response).is_err());
+        assert!(!alice.is_session_active());
+
+        // Response Bob gave Carol cannot be fed to Alice
+        let mut carol = SecureSession::new(SecurityConfig::new().unwrap());
+        let mut bob_for_carol = SecureSession::new(SecurityConfig::new().unwrap());
+        let cross_response = bob_for_carol.process_handshake(carol.initiate_handshake().unwrap()).unwrap().unwrap();
+        let alice_handshake = alice.initiate_handshake().unwrap();
+        assert!(alice.process_handshake_response(cross_response).is_err());
+
+        // The genuine response still completes the pending handshake
+        let response = bob.process_handshake(alice_handshake).unwrap().unwrap();
+        alice.process_handshake_response(response).unwrap();
+        let encrypted = alice.encrypt_audio_frame(b"bound").unwrap();
+        assert_eq!(bob.decrypt_audio_frame(encrypted).unwrap(), b"bound");
+    }
+
+    #[test]
+    fn test_replayed_handshake_rejected() {
+        let mut alice = SecureSession::new(SecurityConfig::new().unwrap());
+        let mut bob = SecureSession::new(SecurityConfig::new().unwrap());
+
+        let handshake = alice.initiate_handshake().unwrap();
+        bob.process_handshake(handshake.clone()).unwrap();
+        assert!(bob.process_handshake(handshake).is_err());
+    }
+
+    #[test]
+    fn test_handshake_timestamp_window() {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        let answer = |timestamp: u64| {
+            let mut alice = SecureSession::new(SecurityConfig::new().unwrap());
+            let mut bob = SecureSession::new(SecurityConfig::new().unwrap());
+            bob.process_handshake(alice.initiate_handshake_at(timestamp).unwrap())
+        };
+
+        // A little clock skew either way is fine
+        assert!(answer(now - 60).is_ok());
+        assert!(answer(now + 5).is_ok());
+
+        // Too old, or signed by a clock running ahead so it could be replayed for longer
+        assert!(answer(now - HANDSHAKE_MAX_AGE.as_secs() - 60).unwrap_err().to_string().contains("too old"));
+        assert!(answer(now + 3600).unwrap_err().to_string().contains("in the future"));
+    }
+
+    #[test]
+    fn test_handshake_fields_covered_by_signature() {
+        let mut alice = SecureSession::new(SecurityConfig::new().unwrap());
+        let mut bob = SecureSession::new(SecurityConfig::new().unwrap());
+
+        // Swapping in another ephemeral key or timestamp breaks the initiator signature
+        let handshake = alice.initiate_handshake().unwrap();
+        let (identity_public_key, ephemeral_public_key, signature, timestamp, capabilities) = match handshake.clone() {
+            SecureMessage::Handshake { identity_public_key, ephemeral_public_key, signature, timestamp, capabilities } => {
+                (identity_public_key, ephemeral_public_key, signature, timestamp, capabilities)
+            }
+            other => panic!("Expected handshake, got {:?}", other),
+        };
+        let tampered_ephemeral = SecureMessage::Handshake {
+            identity_public_key,
+            ephemeral_public_key: *PublicKey::from(&EphemeralSecret::random_from_rng(OsRng)).as_bytes(),
+            signature,
+            timestamp,
+            capabilities: capabilities.clone(),
+        };
+        assert!(bob.process_handshake(tampered_ephemeral).is_err());
+        let tampered_timestamp = SecureMessage::Handshake {
+            identity_public_key, ephemeral_public_key, signature, timestamp: timestamp + 1, capabilities: capabilities.clone(),
+        };
+        assert!(bob.process_handshake(tampered_timestamp).is_err());
+
+        // Nor can the audio offer be downgraded in flight
+        let downgraded = SecureMessage::Handshake {
+            identity_public_key, ephemeral_public_key, signature, timestamp,
+            capabilities: CapabilityOffer { codecs: vec![CodecKind::Pcmu], ..capabilities.clone() },
+        };
+        assert!(bob.process_handshake(downgraded).is_err());
+
+        // An initiator signature cannot be reflected back as a responder signature
+        let reflected = SecureMessage::HandshakeResponse { identity_public_key, ephemeral_public_key, signature, timestamp, capabilities };
+        assert!(alice.process_handshake_response(reflected).is_err());
+
+        // Responder identity is part of the signed transcript
+        let response = bob.process_handshake(handshake).unwrap().unwrap();
+        let (ephemeral_public_key, signature, timestamp, capabilities) = match response.clone() {
+            SecureMessage::HandshakeResponse { ephemeral_public_key, signature, timestamp, capabilities, .. } => {
+                (ephemeral_public_key, signature, timestamp, capabilities)
+            }
+            other => panic!("Expected handshake response, got {:?}", other),
+        };
+        let impostor = SecureMessage::HandshakeResponse {
+            identity_public_key: SecurityConfig::new().unwrap().get_public_identity().to_bytes(),
+            ephemeral_public_key,
+            signature,
+            timestamp,
+            capabilities,
+        };
+        assert!(alice.process_handshake_response(impostor).is_err());
+        alice.process_handshake_response(response).unwrap();
+    }
+
+    #[test]
+    fn test_handshake_settles_audio_format() {
+        fn config_offering(capabilities: CapabilityOffer) -> SecurityConfig {
+            SecurityConfig { capabilities, ..SecurityConfig::new().unwrap() }
+        }
+
+        // Identical defaults settle on them
+        let (alice, bob) = establish_session_pair(SecurityConfig::new().unwrap());
+        let audio = alice.negotiated_audio().unwrap();
+        assert_eq!(bob.negotiated_audio(), Some(audio));
+        assert_eq!((audio.codec, audio.format), (CodecKind::Opus, AudioFormat::STANDARD));
+
+        // Both sides land on the one codec they share
+        let wideband = AudioFormat { sample_rate: 16000, channels: 1, frame_duration_ms: 20 };
+        let alice_offer = CapabilityOffer {
+            codecs: vec![CodecKind::Opus, CodecKind::G722],
+            ..CapabilityOffer::for_format(CodecKind::Opus, wideband, &OpusConfig::default())
+        };
+        let bob_offer = CapabilityOffer::for_format(CodecKind::G722, wideband, &OpusConfig::default());
+        let mut alice = SecureSession::new(config_offering(alice_offer));
+        let mut bob = SecureSession::new(config_offering(bob_offer));
+        let response = bob.process_handshake(alice.initiate_handshake().unwrap()).unwrap().unwrap();
+        alice.process_handshake_response(response).unwrap();
+        assert_eq!(alice.negotiated_audio().unwrap().codec, CodecKind::G722);
+        assert_eq!(bob.negotiated_audio(), alice.negotiated_audio());
+
+        // Nothing in common: the responder answers without starting a session, and the
+        // initiator fails with the reason
+        let mut alice = SecureSession::new(SecurityConfig::new().unwrap());
+        let narrowband = AudioFormat { sample_rate: 8000, channels: 1, frame_duration_ms: 20 };
+        let mut bob = SecureSession::new(config_offering(CapabilityOffer::for_format(CodecKind::Pcmu, narrowband, &OpusConfig::default())));
+        let response = bob.process_handshake(alice.initiate_handshake().unwrap()).unwrap().unwrap();
+        assert!(!bob.is_session_active());
+        assert!(matches!(bob.audio_mismatch(), Some(NegotiationError::NoCommonCodec { .. })));
+
+        let error = alice.process_handshake_response(response).unwrap_err();
+        assert!(error.is::<NegotiationError>());
+        assert_eq!(error.to_string(), "No codec in common: we offer Opus; peer offers G.711 μ-law");
+        assert!(!alice.is_session_active() && alice.negotiated_audio().is_none());
+    }
+
+    #[test]
+    fn test_short_auth_string_matches_and_survives_rekey() {
+        let (mut alice, mut bob) = establish_session_pair(SecurityConfig::new().unwrap());
+
+        let code = alice.short_auth_string().unwrap();
+        assert_eq!(bob.short_auth_string(), Some(code.clone()));
+        assert_eq!(code.safety_number_string().len(), 7);
+        assert!(code.words.iter().all(|word| !word.is_empty()));
+
+        // Rekeying must not change the code users already compared
+        let request = alice.initiate_rekey().unwrap();
+        let response = bob.process_rekey(request).unwrap().unwrap();
+        alice.process_rekey(response).unwrap();
+        assert_eq!(alice.short_auth_string(), Some(code));
+
+        // No session, no code
+        assert!(SecureSession::new(SecurityConfig::new().unwrap()).short_auth_string().is_none());
+    }
+
+    #[test]
+    fn test_short_auth_string_exposes_man_in_the_middle() {
+        let mut alice = SecureSession::new(SecurityConfig::new().unwrap());
+        let mut bob = SecureSession::new(SecurityConfig::new().unwrap());
+
+        // Mallory terminates Alice's handshake and opens her own to Bob
+        let mallory_config = SecurityConfig::new().unwrap();
+        let mut mallory_to_alice = SecureSession::new(mallory_config.clone());
+        let mut mallory_to_bob = SecureSession::new(mallory_config);
+
+        let response = mallory_to_alice.process_handshake(alice.initiate_handshake().unwrap()).unwrap().unwrap();
+        alice.process_handshake_response(response).unwrap();
+        let response = bob.process_handshake(mallory_to_bob.initiate_handshake().unwrap()).unwrap().unwrap();
+        mallory_to_bob.process_handshake_response(response).unwrap();
+
+        assert_ne!(alice.short_auth_string(), bob.short_auth_string());
+    }
+
+    #[test]
+    fn test_mark_peer_verified() {
+        let (alice, bob) = establish_session_pair(SecurityConfig::new().unwrap());
+        assert!(!alice.is_peer_verified());
+
+        alice.mark_peer_verified().unwrap();
+        assert!(alice.is_peer_verified());
+        assert!(!bob.is_peer_verified());
+
+        // Nothing to verify before a session exists
+        assert!(SecureSession::new(SecurityConfig::new().unwrap()).mark_peer_verified().is_err());
+    }
+
+    #[test]
+    fn test_disconnect_signed_and_bound_to_session() {
+        let alice_config = SecurityConfig::new().unwrap();
+        let (alice, bob) = establish_session_pair(alice_config.clone());
+
+        let disconnect = alice.create_disconnect("Left the call").unwrap();
+        assert_eq!(bob.verify_disconnect(&disconnect).unwrap(), "Left the call");
+
+        // Our own notice reflected back, an altered reason, or one from an earlier session
+        assert!(alice.verify_disconnect(&disconnect).is_err());
+        let altered = match disconnect.clone() {
+            SecureMessage::Disconnect { signature, .. } => SecureMessage::Disconnect { reason: "Other".to_string(), signature },
+            _ => panic!("Expected disconnect"),
+        };
+        assert!(bob.verify_disconnect(&altered).is_err());
+        let (_, bob_again) = establish_session_pair(alice_config);
+        assert!(bob_again.verify_disconnect(&disconnect).is_err());
+
+        // Nothing to end before a session exists
+        assert!(SecureSession::new(SecurityConfig::new().unwrap()).create_disconnect("Bye").is_err());
+    }
+
     #[test]
     fn test_encryption_performance() {
         let (mut alice, mut _bob) = establish_secure_session().unwrap();
@@ -0,0 +1,163 @@
+#[cfg(test)]
+mod stun_tests {
+    use crate::stun::*;
+    use crate::network::{ConnectionConfig, NetworkManager, DEFAULT_MAX_PEERS};
+    use crate::discovery::{DiscoveryManager, RoomInfo, ConnectionMethod};
+    use crate::security::SecurityConfig;
+    use crate::transport::TransportKind;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    fn secure_manager() -> NetworkManager {
+        NetworkManager::new(ConnectionConfig {
+            remote_host: "127.0.0.1".to_string(),
+            port: 0,
+            local_port: Some(0),
+            use_encryption: true,
+            security_config: Some(SecurityConfig::new().unwrap()),
+            max_peers: DEFAULT_MAX_PEERS,
+            turn_server: None,
+            transport: TransportKind::Udp,
+        })
+    }
+
+    async fn spawn_stun_server() -> SocketAddr {
+        let server = StunServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move { server.run().await });
+        addr
+    }
+
+    #[test]
+    fn test_binding_messages_round_trip() {
+        let transaction_id = new_transaction_id();
+        for tie_breaker in [None, Some(u64::MAX - 7)] {
+            let request = StunMessage::BindingRequest { transaction_id, tie_breaker };
+            let encoded = encode_stun_message(&request);
+            assert!(is_stun_message(&encoded));
+            assert_eq!(decode_stun_message(&encoded).unwrap(), request);
+        }
+
+        for mapped_address in ["203.0.113.9:40000", "[2001:db8::1]:3478"] {
+            let response = StunMessage::BindingSuccess { transaction_id, mapped_address: mapped_address.parse().unwrap() };
+            assert_eq!(decode_stun_message(&encode_stun_message(&response)).unwrap(), response);
+        }
+    }
+
+    #[test]
+    fn test_decodes_rfc5769_response() {
+        // RFC 5769 §2.2 sample IPv4 response with SOFTWARE, MESSAGE-INTEGRITY and FINGERPRINT
+        let packet: [u8; 80] = [
+            0x01, 0x01, 0x00, 0x3c, 0x21, 0x12, 0xa4, 0x42,
+            0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6, 0x86, 0xfa, 0x87, 0xdf, 0xae,
+            0x80, 0x22, 0x00, 0x0b, 0x74, 0x65, 0x73, 0x74, 0x20, 0x76, 0x65, 0x63, 0x74, 0x6f, 0x72, 0x20,
+            0x00, 0x20, 0x00, 0x08, 0x00, 0x01, 0xa1, 0x47, 0xe1, 0x12, 0xa6, 0x43,
+            0x00, 0x08, 0x00, 0x14, 0x2b, 0x91, 0xf5, 0x99, 0xfd, 0x9e, 0x90, 0xc3, 0x8c, 0x74,
+            0x89, 0xf9, 0x2a, 0xf9, 0xba, 0x53, 0xf0, 0x6b, 0xe7, 0xd7,
+            0x80, 0x28, 0x00, 0x04, 0xc0, 0x7d, 0x4c, 0x96,
+        ];
+
+        match decode_stun_message(&packet).unwrap() {
+            StunMessage::BindingSuccess { mapped_address, .. } => {
+                assert_eq!(mapped_address, "192.0.2.1:32853".parse::<SocketAddr>().unwrap());
+            }
+            other => panic!("Unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_protocol_packets_are_not_stun() {
+        let mut session = crate::security::SecureSession::new(SecurityConfig::new().unwrap());
+        let handshake = crate::wire::encode_message(&session.initiate_handshake().unwrap()).unwrap();
+        assert!(!is_stun_message(&handshake));
+        assert!(!is_stun_message(b"plaintext audio payload"));
+
+        let mut truncated = encode_stun_message(&StunMessage::BindingRequest { transaction_id: new_transaction_id(), tie_breaker: None });
+        truncated.pop();
+        assert!(!is_stun_message(&truncated));
+        assert!(decode_stun_message(&truncated).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_binding_request_reports_mapped_address() {
+        let server = spawn_stun_server().await;
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let mapped = binding_request(&socket, server).await.unwrap();
+        assert_eq!(mapped, socket.local_addr().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_network_manager_discovers_reflexive_address() {
+        let server = spawn_stun_server().await;
+        let mut manager = secure_manager();
+        let local_port = manager.bind().await.unwrap().port();
+
+        // The receive task owns the socket, so the response is routed to us through it
+        let mapped = manager.discover_reflexive_address(server).await.unwrap();
+        assert_eq!(mapped, SocketAddr::from(([127, 0, 0, 1], local_port)));
+
+        let candidates = manager.gather_candidates(&[server]).await.unwrap();
+        assert!(candidates.contains(&Candidate::server_reflexive(mapped)));
+        assert!(candidates.iter().all(|candidate| candidate.addr.port() == local_port));
+    }
+
+    #[tokio::test]
+    async fn test_hole_punching_via_exchanged_candidates() {
+        let server = spawn_stun_server().await;
+        let mut alice = secure_manager();
+        let mut bob = secure_manager();
+        let alice_candidates = alice.gather_candidates(&[server]).await.unwrap();
+        let mut bob_candidates = bob.gather_candidates(&[server]).await.unwrap();
+
+        // A higher-priority candidate that never answers must not block the others
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        bob_candidates.push(Candidate { priority: u32::MAX, ..Candidate::host(silent.local_addr().unwrap()) });
+
+        // Both sides punch at once, as they would after swapping invites
+        let punch_bob = tokio::spawn(async move {
+            bob.connect_via_candidates(&alice_candidates).await.unwrap();
+            bob
+        });
+        let reached = alice.connect_via_candidates(&bob_candidates).await.unwrap();
+        let mut bob = punch_bob.await.unwrap();
+
+        assert_ne!(reached, silent.local_addr().unwrap());
+        assert!(alice.is_secure_session_active().await);
+        assert!(bob.is_secure_session_active().await);
+
+        alice.send_audio_frame(b"through the nat").await.unwrap();
+        for _ in 0..100 {
+            if let Some(frame) = bob.receive_audio_frame().unwrap() {
+                assert_eq!(frame.payload, b"through the nat");
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("No audio frame received");
+    }
+
+    #[tokio::test]
+    async fn test_room_info_carries_candidates() {
+        let mut discovery = DiscoveryManager::new();
+        discovery.start().await.unwrap();
+
+        let reflexive = Candidate::server_reflexive("198.51.100.7:40000".parse().unwrap());
+        let host = Candidate::host("192.168.1.20:8080".parse().unwrap());
+        let room = discovery.create_room_with_candidates("Host".to_string(), 8080, &[host, reflexive]).await.unwrap();
+
+        assert!(room.connection_methods.iter().any(|method| matches!(
+            method,
+            ConnectionMethod::Internet { public_ip, port: 40000 } if *public_ip == reflexive.addr.ip()
+        )));
+
+        // Candidates survive the JSON used for QR invites
+        let invite: RoomInfo = serde_json::from_str(&serde_json::to_string(&room).unwrap()).unwrap();
+        assert_eq!(invite.candidates(), vec![host, reflexive]);
+        assert!(host.priority > reflexive.priority);
+
+        // Without STUN there is no public address to advertise
+        let local_only = discovery.create_room("Host".to_string(), 8080).await.unwrap();
+        assert!(!local_only.connection_methods.iter().any(|method| matches!(method, ConnectionMethod::Internet { .. })));
+    }
+}
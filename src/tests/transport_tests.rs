@@ -0,0 +1,146 @@
+#[cfg(test)]
+mod transport_tests {
+    use crate::transport::*;
+    use crate::network::{ConnectionConfig, NetworkManager, DEFAULT_MAX_PEERS};
+    use crate::security::SecurityConfig;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn stream_config(kind: TransportKind, remote_port: u16) -> ConnectionConfig {
+        ConnectionConfig {
+            remote_host: "127.0.0.1".to_string(),
+            port: remote_port,
+            local_port: Some(0),
+            use_encryption: true,
+            security_config: Some(SecurityConfig::new().unwrap()),
+            max_peers: DEFAULT_MAX_PEERS,
+            turn_server: None,
+            transport: kind,
+        }
+    }
+
+    async fn recv(transport: &dyn Transport) -> (Vec<u8>, std::net::SocketAddr) {
+        let mut buffer = vec![0u8; 2048];
+        let (len, from) = tokio::time::timeout(Duration::from_secs(2), transport.recv_from(&mut buffer)).await
+            .expect("no packet received")
+            .unwrap();
+        (buffer[..len].to_vec(), from)
+    }
+
+    #[test]
+    fn test_websocket_accept_key_matches_rfc6455() {
+        // RFC 6455 §1.3 worked example
+        assert_eq!(websocket_accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[tokio::test]
+    async fn test_frames_round_trip() {
+        for len in [0usize, 125, 126, 300, MAX_STREAM_PACKET] {
+            let payload: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            for masked in [false, true] {
+                let frame = encode_websocket_frame(0x2, &payload, masked);
+                let (opcode, decoded) = read_websocket_frame(&mut &frame[..]).await.unwrap();
+                assert_eq!(opcode, 0x2);
+                assert_eq!(decoded, payload);
+            }
+
+            let frame = encode_frame(TransportKind::Tcp, &payload, false);
+            assert_eq!(frame.len(), len + 2);
+            assert_eq!(read_length_prefixed(&mut &frame[..]).await.unwrap(), payload);
+        }
+
+        // Fragments are never sent, so a frame without FIN is refused
+        let mut fragment = encode_websocket_frame(0x2, b"partial", false);
+        fragment[0] &= 0x7F;
+        assert!(read_websocket_frame(&mut &fragment[..]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_transports_exchange_packets() {
+        for kind in [TransportKind::Tcp, TransportKind::WebSocket] {
+            let a = StreamTransport::bind("127.0.0.1:0", kind).await.unwrap();
+            let b = StreamTransport::bind("127.0.0.1:0", kind).await.unwrap();
+            let b_addr = b.local_addr().unwrap();
+
+            // The first send opens the stream; replies reuse it
+            a.send_to(b"hello", b_addr).await.unwrap();
+            let (packet, a_seen_at) = recv(&b).await;
+            assert_eq!(packet, b"hello");
+
+            b.send_to(b"hi back", a_seen_at).await.unwrap();
+            assert_eq!(recv(&a).await, (b"hi back".to_vec(), b_addr));
+            assert_eq!(a.connection_count(), 1);
+            assert_eq!(b.connection_count(), 1);
+            assert_eq!(a.kind(), kind);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_loopback_endpoints_exchange_packets() {
+        let (a, b) = LoopbackTransport::pair();
+        let (a_addr, b_addr) = (a.local_addr().unwrap(), b.local_addr().unwrap());
+
+        a.send_to(b"ping", b_addr).await.unwrap();
+        b.send_to(b"pong", a_addr).await.unwrap();
+        assert_eq!(recv(&b).await, (b"ping".to_vec(), a_addr));
+        assert_eq!(recv(&a).await, (b"pong".to_vec(), b_addr));
+        assert_eq!(a.kind(), TransportKind::Loopback);
+
+        // Like UDP, packets for an address nobody holds vanish
+        let network = LoopbackNetwork::new();
+        let c = network.endpoint("192.0.2.3:9000".parse().unwrap()).unwrap();
+        assert_eq!(c.send_to(b"nobody", b_addr).await.unwrap(), 6);
+        assert!(network.endpoint(c.local_addr().unwrap()).is_err());
+
+        // Dropping an endpoint frees its address
+        let c_addr = c.local_addr().unwrap();
+        drop(c);
+        assert!(network.endpoint(c_addr).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_websocket_listener_refuses_plain_http() {
+        let transport = StreamTransport::bind("127.0.0.1:0", TransportKind::WebSocket).await.unwrap();
+        let mut client = tokio::net::TcpStream::connect(transport.local_addr().unwrap()).await.unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\nHost: example\r\n\r\n").await.unwrap();
+
+        let mut response = Vec::new();
+        tokio::time::timeout(Duration::from_secs(2), client.read_to_end(&mut response)).await.unwrap().unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 400"));
+        assert_eq!(transport.connection_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_network_manager_over_stream_transports() {
+        for kind in [TransportKind::Tcp, TransportKind::WebSocket] {
+            let mut host = NetworkManager::new(stream_config(kind, 0));
+            let host_port = host.bind().await.unwrap().port();
+            let mut client = NetworkManager::new(stream_config(kind, host_port));
+
+            let accept = tokio::spawn(async move {
+                host.accept_connection().await.unwrap();
+                host
+            });
+            client.establish_connection().await.unwrap();
+            let mut host = accept.await.unwrap();
+            assert!(host.is_secure_session_active().await);
+
+            client.send_audio_frame(b"over a stream").await.unwrap();
+            let mut received = None;
+            for _ in 0..100 {
+                if let Some(frame) = host.receive_audio_frame().unwrap() {
+                    received = Some(frame.payload);
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            assert_eq!(received.as_deref(), Some(&b"over a stream"[..]));
+
+            let stats = client.stats().await;
+            assert_eq!(stats.transport, Some(kind));
+            assert_eq!(stats.established_peers, 1);
+            assert_eq!(stats.totals.packets_sent, 1);
+            assert_eq!(host.stats().await.transport, Some(kind));
+        }
+    }
+}
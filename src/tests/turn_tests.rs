@@ -0,0 +1,196 @@
+#[cfg(test)]
+mod turn_tests {
+    use crate::turn::*;
+    use crate::stun::{Candidate, CandidateKind};
+    use crate::network::{ConnectionConfig, NetworkManager, DEFAULT_MAX_PEERS};
+    use crate::security::SecurityConfig;
+    use crate::transport::TransportKind;
+    use ed25519_dalek::Signer;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::net::UdpSocket;
+
+    async fn start_turn(config: TurnConfig) -> (Arc<TurnServer>, SocketAddr) {
+        let server = Arc::new(TurnServer::bind("127.0.0.1:0", config).await.unwrap());
+        let addr = server.local_addr().unwrap();
+        let running = Arc::clone(&server);
+        tokio::spawn(async move { running.run().await });
+        (server, addr)
+    }
+
+    fn manager_with_relay(turn_server: SocketAddr) -> NetworkManager {
+        NetworkManager::new(ConnectionConfig {
+            remote_host: "127.0.0.1".to_string(),
+            port: 0,
+            local_port: Some(0),
+            use_encryption: true,
+            security_config: Some(SecurityConfig::new().unwrap()),
+            max_peers: DEFAULT_MAX_PEERS,
+            turn_server: Some(turn_server),
+            transport: TransportKind::Udp,
+        })
+    }
+
+    async fn send(socket: &UdpSocket, to: SocketAddr, message: &TurnMessage) {
+        socket.send_to(&encode_turn_message(message).unwrap(), to).await.unwrap();
+    }
+
+    async fn recv(socket: &UdpSocket) -> Option<(SocketAddr, Vec<u8>)> {
+        let mut buffer = vec![0u8; 2048];
+        let (len, from) = tokio::time::timeout(Duration::from_millis(300), socket.recv_from(&mut buffer)).await.ok()?.unwrap();
+        Some((from, buffer[..len].to_vec()))
+    }
+
+    async fn recv_turn(socket: &UdpSocket) -> TurnMessage {
+        let (_, packet) = recv(socket).await.expect("no reply from relay");
+        decode_turn_message(&packet).unwrap()
+    }
+
+    /// Run the challenge-response allocation by hand and return the relay's final answer
+    async fn allocate(socket: &UdpSocket, server: SocketAddr, identity: &SecurityConfig) -> TurnMessage {
+        send(socket, server, &TurnMessage::AllocateRequest).await;
+        let nonce = match recv_turn(socket).await {
+            TurnMessage::AllocateChallenge { nonce } => nonce,
+            other => panic!("Unexpected message: {:?}", other),
+        };
+        let identity_public_key = identity.get_public_identity().to_bytes();
+        let signature = identity.identity_signing_key.sign(&allocate_digest(&identity_public_key, &nonce)).to_bytes();
+        send(socket, server, &TurnMessage::Allocate { identity_public_key, nonce, signature }).await;
+        recv_turn(socket).await
+    }
+
+    #[test]
+    fn test_turn_messages_round_trip() {
+        let peer: SocketAddr = "[2001:db8::7]:40000".parse().unwrap();
+        let messages = [
+            TurnMessage::AllocateRequest,
+            TurnMessage::AllocateChallenge { nonce: [1u8; 32] },
+            TurnMessage::Allocate { identity_public_key: [2u8; 32], nonce: [3u8; 32], signature: [4u8; 64] },
+            TurnMessage::AllocateSuccess { relayed_address: "203.0.113.10:50000".parse().unwrap(), lifetime_secs: 600 },
+            TurnMessage::AllocateError { reason: "Relay is at capacity".to_string() },
+            TurnMessage::Refresh,
+            TurnMessage::CreatePermission { peers: vec!["198.51.100.7".parse().unwrap(), peer.ip()] },
+            TurnMessage::Send { peer, payload: b"ciphertext".to_vec() },
+            TurnMessage::Data { peer, payload: Vec::new() },
+        ];
+
+        for message in messages {
+            let encoded = encode_turn_message(&message).unwrap();
+            assert_eq!(encoded[0], TURN_WIRE_VERSION);
+            assert!(!crate::stun::is_stun_message(&encoded));
+            assert_eq!(decode_turn_message(&encoded).unwrap(), message);
+        }
+
+        let mut truncated = encode_turn_message(&TurnMessage::Refresh).unwrap();
+        truncated.pop();
+        assert!(decode_turn_message(&truncated).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_relay_enforces_permissions() {
+        let (server, addr) = start_turn(TurnConfig::default()).await;
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let peer_addr = peer.local_addr().unwrap();
+
+        let relayed_address = match allocate(&client, addr, &SecurityConfig::new().unwrap()).await {
+            TurnMessage::AllocateSuccess { relayed_address, .. } => relayed_address,
+            other => panic!("Unexpected message: {:?}", other),
+        };
+        assert_eq!(server.allocation_count().await, 1);
+
+        // Nothing gets through in either direction before a permission exists
+        peer.send_to(b"unsolicited", relayed_address).await.unwrap();
+        send(&client, addr, &TurnMessage::Send { peer: peer_addr, payload: b"early".to_vec() }).await;
+        assert!(recv(&client).await.is_none());
+        assert!(recv(&peer).await.is_none());
+
+        send(&client, addr, &TurnMessage::CreatePermission { peers: vec![peer_addr.ip()] }).await;
+        send(&client, addr, &TurnMessage::Send { peer: peer_addr, payload: b"outbound".to_vec() }).await;
+        let (from, payload) = recv(&peer).await.expect("relayed packet not delivered");
+        assert_eq!(from, relayed_address);
+        assert_eq!(payload, b"outbound");
+
+        peer.send_to(b"inbound", relayed_address).await.unwrap();
+        assert_eq!(recv_turn(&client).await, TurnMessage::Data { peer: peer_addr, payload: b"inbound".to_vec() });
+    }
+
+    #[tokio::test]
+    async fn test_relay_rejects_unlisted_identity() {
+        let allowed = SecurityConfig::new().unwrap();
+        let config = TurnConfig { allowed_identities: vec![allowed.get_public_identity()], ..TurnConfig::default() };
+        let (server, addr) = start_turn(config).await;
+
+        let stranger = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        assert!(matches!(
+            allocate(&stranger, addr, &SecurityConfig::new().unwrap()).await,
+            TurnMessage::AllocateError { .. }
+        ));
+        assert_eq!(server.allocation_count().await, 0);
+
+        let member = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        assert!(matches!(allocate(&member, addr, &allowed).await, TurnMessage::AllocateSuccess { .. }));
+        assert_eq!(server.allocation_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_peers_connect_through_relay_candidates() {
+        let (server, addr) = start_turn(TurnConfig::default()).await;
+        let mut alice = manager_with_relay(addr);
+        let mut bob = manager_with_relay(addr);
+
+        // Offer only relayed candidates, as if both NATs dropped direct checks
+        let relayed_only = |candidates: Vec<Candidate>| -> Vec<Candidate> {
+            candidates.into_iter().filter(|candidate| candidate.kind == CandidateKind::Relayed).collect()
+        };
+        let alice_candidates = relayed_only(alice.gather_candidates(&[]).await.unwrap());
+        let bob_candidates = relayed_only(bob.gather_candidates(&[]).await.unwrap());
+        assert_eq!(alice_candidates.len(), 1);
+        assert_eq!(bob_candidates.len(), 1);
+        assert_eq!(server.allocation_count().await, 2);
+
+        let punch_bob = tokio::spawn(async move {
+            bob.connect_via_candidates(&alice_candidates).await.unwrap();
+            bob
+        });
+        alice.connect_via_candidates(&bob_candidates).await.unwrap();
+        let mut bob = punch_bob.await.unwrap();
+
+        assert!(alice.is_secure_session_active().await);
+        assert!(bob.is_secure_session_active().await);
+        let alice_peers = alice.peers().await;
+        let bob_peers = bob.peers().await;
+        assert_eq!(alice_peers.len(), 1);
+        assert_eq!(bob_peers.len(), 1);
+        assert!(alice_peers[0].via_relay || bob_peers[0].via_relay);
+
+        alice.send_audio_frame(b"via the relay").await.unwrap();
+        for _ in 0..100 {
+            if let Some(frame) = bob.receive_audio_frame().unwrap() {
+                assert_eq!(frame.payload, b"via the relay");
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("No audio frame received");
+    }
+
+    #[tokio::test]
+    async fn test_allocation_expires_without_refresh() {
+        let config = TurnConfig { allocation_lifetime: Duration::from_secs(1), ..TurnConfig::default() };
+        let (server, addr) = start_turn(config).await;
+
+        let mut refreshed = manager_with_relay(addr);
+        refreshed.allocate_relay(addr).await.unwrap();
+        let idle = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        assert!(matches!(allocate(&idle, addr, &SecurityConfig::new().unwrap()).await, TurnMessage::AllocateSuccess { .. }));
+        assert_eq!(server.allocation_count().await, 2);
+
+        // The manager refreshes at half the lifetime; the idle client lapses
+        tokio::time::sleep(Duration::from_millis(1800)).await;
+        assert_eq!(server.allocation_count().await, 1);
+        send(&idle, addr, &TurnMessage::Refresh).await;
+        assert!(matches!(recv_turn(&idle).await, TurnMessage::AllocateError { .. }));
+    }
+}
@@ -0,0 +1,164 @@
+#[cfg(test)]
+mod wire_tests {
+    use crate::wire::*;
+    use crate::security::{SecureMessage, SecureSession, SecurityConfig};
+    use crate::capabilities::CapabilityOffer;
+
+    fn sample_audio_message() -> SecureMessage {
+        SecureMessage::EncryptedAudio {
+            frame_number: 42,
+            timestamp: 1_700_000_000_123,
+            nonce: [7u8; 12],
+            ciphertext: vec![0xAB; 96],
+        }
+    }
+
+    #[test]
+    fn test_encrypted_audio_round_trip() {
+        let message = sample_audio_message();
+        let packet = encode_message(&message).unwrap();
+
+        // Header + sequence + timestamp + nonce + ciphertext
+        assert_eq!(packet.len(), HEADER_LEN + 8 + 8 + 12 + 96);
+        assert_eq!(packet[0], WIRE_VERSION);
+        assert_eq!(packet[1], MessageType::EncryptedAudio as u8);
+
+        assert_eq!(decode_message(&packet).unwrap(), message);
+    }
+
+    #[test]
+    fn test_handshake_messages_round_trip() {
+        let handshake = SecureMessage::Handshake {
+            identity_public_key: [1u8; 32],
+            ephemeral_public_key: [2u8; 32],
+            signature: [3u8; 64],
+            timestamp: 123456,
+            capabilities: CapabilityOffer::default(),
+        };
+        let response = SecureMessage::HandshakeResponse {
+            identity_public_key: [8u8; 32],
+            ephemeral_public_key: [4u8; 32],
+            signature: [5u8; 64],
+            timestamp: 654321,
+            capabilities: CapabilityOffer::default(),
+        };
+        let disconnect = SecureMessage::Disconnect {
+            reason: "user hung up".to_string(),
+            signature: [6u8; 64],
+        };
+
+        let rekey = SecureMessage::Rekey {
+            generation: 3,
+            ephemeral_public_key: [9u8; 32],
+            signature: [10u8; 64],
+        };
+        let rekey_response = SecureMessage::RekeyResponse {
+            generation: 3,
+            ephemeral_public_key: [11u8; 32],
+            signature: [12u8; 64],
+        };
+
+        for message in [handshake, response, disconnect, rekey, rekey_response] {
+            let packet = encode_message(&message).unwrap();
+            assert_eq!(peek_message_type(&packet).unwrap(), MessageType::of(&message));
+            assert_eq!(decode_message(&packet).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn test_secure_session_packets_round_trip() {
+        let mut alice = SecureSession::new(SecurityConfig::new().unwrap());
+        let mut bob = SecureSession::new(SecurityConfig::new().unwrap());
+
+        let handshake = decode_message(&encode_message(&alice.initiate_handshake().unwrap()).unwrap()).unwrap();
+        let response = bob.process_handshake(handshake).unwrap().unwrap();
+        let response = decode_message(&encode_message(&response).unwrap()).unwrap();
+        alice.process_handshake_response(response).unwrap();
+
+        let encrypted = alice.encrypt_audio_frame(b"opus payload").unwrap();
+        let received = decode_message(&encode_message(&encrypted).unwrap()).unwrap();
+        assert_eq!(bob.decrypt_audio_frame(received).unwrap(), b"opus payload");
+
+        let control = encode_message(&alice.encrypt_control(b"feedback").unwrap()).unwrap();
+        assert_eq!(peek_message_type(&control).unwrap(), MessageType::EncryptedControl);
+        assert_eq!(control.len(), HEADER_LEN + 8 + 12 + b"feedback".len() + 16);
+        assert_eq!(bob.decrypt_control(decode_message(&control).unwrap()).unwrap(), b"feedback");
+    }
+
+    #[test]
+    fn test_rejects_truncated_packets() {
+        let packet = encode_message(&sample_audio_message()).unwrap();
+
+        assert!(matches!(decode_message(&packet[..2]), Err(WireError::Truncated { .. })));
+        assert!(matches!(
+            decode_message(&packet[..packet.len() - 1]),
+            Err(WireError::LengthMismatch { .. })
+        ));
+
+        // Header claims a body shorter than a handshake needs
+        let mut short = vec![WIRE_VERSION, MessageType::Handshake as u8, 0, 10];
+        short.extend_from_slice(&[0u8; 10]);
+        assert!(matches!(decode_message(&short), Err(WireError::Truncated { .. })));
+    }
+
+    #[test]
+    fn test_rejects_bad_header_fields() {
+        let mut packet = encode_message(&sample_audio_message()).unwrap();
+
+        packet[0] = WIRE_VERSION + 1;
+        assert_eq!(decode_message(&packet), Err(WireError::UnsupportedVersion(WIRE_VERSION + 1)));
+
+        packet[0] = WIRE_VERSION;
+        packet[1] = 0x7F;
+        assert_eq!(decode_message(&packet), Err(WireError::UnknownMessageType(0x7F)));
+    }
+
+    #[test]
+    fn test_rejects_malformed_bodies() {
+        // Ciphertext too short to carry an authentication tag
+        let mut packet = encode_message(&SecureMessage::EncryptedAudio {
+            frame_number: 1,
+            timestamp: 0,
+            nonce: [0u8; 12],
+            ciphertext: vec![1u8; 4],
+        }).unwrap();
+        assert!(matches!(decode_message(&packet), Err(WireError::InvalidBody(_))));
+
+        // Extra bytes after a handshake response's capability offer
+        packet = encode_message(&SecureMessage::HandshakeResponse {
+            identity_public_key: [0u8; 32],
+            ephemeral_public_key: [0u8; 32],
+            signature: [0u8; 64],
+            timestamp: 0,
+            capabilities: CapabilityOffer::default(),
+        }).unwrap();
+        packet.push(0xFF);
+        packet[3] += 1;
+        assert_eq!(decode_message(&packet), Err(WireError::TrailingBytes(1)));
+
+        // Handshake cut short before its capability offer
+        packet.truncate(HEADER_LEN + 136);
+        packet[2..4].copy_from_slice(&136u16.to_be_bytes());
+        assert_eq!(decode_message(&packet), Err(WireError::InvalidBody("handshake carries no capability offer")));
+
+        // Offer naming a codec we do not know: it is left out, not fatal
+        let mut offer = Vec::new();
+        CapabilityOffer::default().encode(&mut offer).unwrap();
+        offer[2] = 100;
+        let mut unknown_codec = packet.clone();
+        unknown_codec.extend_from_slice(&offer);
+        unknown_codec[2..4].copy_from_slice(&((136 + offer.len()) as u16).to_be_bytes());
+        match decode_message(&unknown_codec) {
+            Ok(SecureMessage::HandshakeResponse { capabilities, .. }) => assert!(capabilities.codecs.is_empty()),
+            other => panic!("Expected handshake response, got {:?}", other),
+        }
+
+        // Disconnect reason that is not UTF-8
+        let mut body = vec![0u8, 2, 0xFF, 0xFE];
+        body.extend_from_slice(&[0u8; 64]);
+        let mut disconnect = vec![WIRE_VERSION, MessageType::Disconnect as u8];
+        disconnect.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        disconnect.extend_from_slice(&body);
+        assert!(matches!(decode_message(&disconnect), Err(WireError::InvalidBody(_))));
+    }
+}
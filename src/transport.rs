@@ -0,0 +1,625 @@
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Largest packet a stream transport carries, matching the 16-bit length prefix
+pub const MAX_STREAM_PACKET: usize = u16::MAX as usize;
+
+/// Request path used for the WebSocket upgrade, so a reverse proxy can route it
+pub const WEBSOCKET_PATH: &str = "/humr";
+
+/// GUID from RFC 6455 §1.3 mixed into the handshake accept key
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Longest HTTP upgrade request or response we read before giving up
+const MAX_UPGRADE_HEADER: usize = 4096;
+
+/// How long to wait for a TCP connect plus upgrade before reporting the peer unreachable
+const STREAM_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Boxed future returned by [`Transport`] methods so the trait stays object safe
+pub type TransportFuture<'a, T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send + 'a>>;
+
+/// Which transport carries protocol packets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+    /// Plain UDP datagrams, the default
+    #[default]
+    Udp,
+    /// Length-prefixed packets over TCP, for networks that block outbound UDP
+    Tcp,
+    /// Binary WebSocket frames over TCP, for networks that only let HTTP out
+    WebSocket,
+    /// In-process channels between endpoints of a [`LoopbackNetwork`], for tests
+    #[serde(skip)]
+    Loopback,
+}
+
+impl TransportKind {
+    /// Whether a lost segment stalls every packet behind it until it is retransmitted
+    pub fn has_head_of_line_blocking(self) -> bool {
+        matches!(self, TransportKind::Tcp | TransportKind::WebSocket)
+    }
+}
+
+impl fmt::Display for TransportKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportKind::Udp => write!(f, "UDP"),
+            TransportKind::Tcp => write!(f, "TCP"),
+            TransportKind::WebSocket => write!(f, "WebSocket"),
+            TransportKind::Loopback => write!(f, "loopback"),
+        }
+    }
+}
+
+/// Datagram-style carrier for protocol packets, addressed by the remote socket address.
+/// Stream transports keep one connection per remote address behind the same interface.
+pub trait Transport: Send + Sync {
+    /// Send one packet to `target`, connecting first if the transport needs to
+    fn send_to<'a>(&'a self, packet: &'a [u8], target: SocketAddr) -> TransportFuture<'a, usize>;
+
+    /// Wait for the next packet from any remote, truncating it to `buffer` like UDP does
+    fn recv_from<'a>(&'a self, buffer: &'a mut [u8]) -> TransportFuture<'a, (usize, SocketAddr)>;
+
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    fn kind(&self) -> TransportKind;
+}
+
+impl Transport for UdpSocket {
+    fn send_to<'a>(&'a self, packet: &'a [u8], target: SocketAddr) -> TransportFuture<'a, usize> {
+        Box::pin(UdpSocket::send_to(self, packet, target))
+    }
+
+    fn recv_from<'a>(&'a self, buffer: &'a mut [u8]) -> TransportFuture<'a, (usize, SocketAddr)> {
+        Box::pin(UdpSocket::recv_from(self, buffer))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        UdpSocket::local_addr(self)
+    }
+
+    fn kind(&self) -> TransportKind {
+        TransportKind::Udp
+    }
+}
+
+/// Bind the transport of the given kind on `addr`
+pub async fn bind_transport(addr: &str, kind: TransportKind) -> Result<Arc<dyn Transport>> {
+    match kind {
+        TransportKind::Udp => {
+            let socket = UdpSocket::bind(addr).await
+                .map_err(|e| anyhow!("Failed to bind UDP socket to {}: {}", addr, e))?;
+            Ok(Arc::new(socket))
+        }
+        TransportKind::Tcp | TransportKind::WebSocket => Ok(Arc::new(StreamTransport::bind(addr, kind).await?)),
+        TransportKind::Loopback => Err(anyhow!("Loopback endpoints come from a LoopbackNetwork, not an address")),
+    }
+}
+
+type LoopbackInbox = mpsc::UnboundedSender<(SocketAddr, Vec<u8>)>;
+
+/// In-process network joining [`LoopbackTransport`] endpoints through channels. Packets
+/// are delivered in order and never lost; ones for an address nobody holds are dropped.
+#[derive(Clone, Default)]
+pub struct LoopbackNetwork {
+    endpoints: Arc<Mutex<HashMap<SocketAddr, LoopbackInbox>>>,
+}
+
+impl LoopbackNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach an endpoint at `addr`
+    pub fn endpoint(&self, addr: SocketAddr) -> Result<LoopbackTransport> {
+        let mut endpoints = self.endpoints.lock().map_err(|_| anyhow!("Loopback network poisoned"))?;
+        if endpoints.contains_key(&addr) {
+            return Err(anyhow!("Loopback address {} is already in use", addr));
+        }
+
+        let (inbox, inbound_rx) = mpsc::unbounded_channel();
+        endpoints.insert(addr, inbox);
+        Ok(LoopbackTransport {
+            addr,
+            network: self.clone(),
+            inbound_rx: tokio::sync::Mutex::new(inbound_rx),
+        })
+    }
+}
+
+/// Endpoint on a [`LoopbackNetwork`]; stands in for a socket in tests that need no network
+pub struct LoopbackTransport {
+    addr: SocketAddr,
+    network: LoopbackNetwork,
+    inbound_rx: tokio::sync::Mutex<mpsc::UnboundedReceiver<(SocketAddr, Vec<u8>)>>,
+}
+
+impl LoopbackTransport {
+    /// Two endpoints on a fresh network, at 192.0.2.1:9000 and 192.0.2.2:9000
+    pub fn pair() -> (Self, Self) {
+        let network = LoopbackNetwork::new();
+        let a = network.endpoint(SocketAddr::from(([192, 0, 2, 1], 9000))).expect("fresh network");
+        let b = network.endpoint(SocketAddr::from(([192, 0, 2, 2], 9000))).expect("fresh network");
+        (a, b)
+    }
+
+    fn deliver(&self, packet: &[u8], target: SocketAddr) -> usize {
+        if let Ok(endpoints) = self.network.endpoints.lock()
+            && let Some(inbox) = endpoints.get(&target) {
+            let _ = inbox.send((self.addr, packet.to_vec()));
+        }
+        packet.len()
+    }
+
+    async fn recv_packet(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (from, packet) = self.inbound_rx.lock().await.recv().await
+            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "Loopback endpoint closed"))?;
+        let len = packet.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&packet[..len]);
+        Ok((len, from))
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send_to<'a>(&'a self, packet: &'a [u8], target: SocketAddr) -> TransportFuture<'a, usize> {
+        let sent = self.deliver(packet, target);
+        Box::pin(async move { Ok(sent) })
+    }
+
+    fn recv_from<'a>(&'a self, buffer: &'a mut [u8]) -> TransportFuture<'a, (usize, SocketAddr)> {
+        Box::pin(self.recv_packet(buffer))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn kind(&self) -> TransportKind {
+        TransportKind::Loopback
+    }
+}
+
+impl Drop for LoopbackTransport {
+    fn drop(&mut self) {
+        if let Ok(mut endpoints) = self.network.endpoints.lock() {
+            endpoints.remove(&self.addr);
+        }
+    }
+}
+
+/// One open stream to a remote
+struct Connection {
+    id: u64,
+    // Already-framed bytes for the writer task
+    outbound: mpsc::UnboundedSender<Vec<u8>>,
+    // Client-to-server WebSocket frames must be masked
+    masked: bool,
+    reader: JoinHandle<()>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+#[derive(Default)]
+struct Connections {
+    by_addr: HashMap<SocketAddr, Connection>,
+    next_id: u64,
+}
+
+/// State shared by the transport, its listener and every stream's reader
+struct StreamShared {
+    kind: TransportKind,
+    connections: Mutex<Connections>,
+    inbound_tx: mpsc::UnboundedSender<(SocketAddr, Vec<u8>)>,
+}
+
+/// Stream fallback for networks that block UDP: packets are framed over TCP, one connection
+/// per remote, either with a 16-bit length prefix or as binary WebSocket frames. The
+/// packets are already encrypted by the session, so the stream itself adds no TLS.
+pub struct StreamTransport {
+    local_addr: SocketAddr,
+    shared: Arc<StreamShared>,
+    inbound_rx: tokio::sync::Mutex<mpsc::UnboundedReceiver<(SocketAddr, Vec<u8>)>>,
+    accept_task: JoinHandle<()>,
+}
+
+impl StreamTransport {
+    /// Listen for incoming streams on `addr`; outgoing streams are opened on first send
+    pub async fn bind(addr: &str, kind: TransportKind) -> Result<Self> {
+        if !kind.has_head_of_line_blocking() {
+            return Err(anyhow!("{} is not a stream transport", kind));
+        }
+
+        let listener = TcpListener::bind(addr).await
+            .map_err(|e| anyhow!("Failed to bind TCP listener to {}: {}", addr, e))?;
+        let local_addr = listener.local_addr()?;
+        let (inbound_tx, inbound_rx) = mpsc::unbounded_channel();
+        let shared = Arc::new(StreamShared { kind, connections: Mutex::new(Connections::default()), inbound_tx });
+
+        let accept_task = tokio::spawn(accept_loop(listener, Arc::clone(&shared)));
+        info!("{} transport listening on {}", kind, local_addr);
+
+        Ok(Self {
+            local_addr,
+            shared,
+            inbound_rx: tokio::sync::Mutex::new(inbound_rx),
+            accept_task,
+        })
+    }
+
+    /// Number of open streams
+    pub fn connection_count(&self) -> usize {
+        self.shared.connections.lock().map(|connections| connections.by_addr.len()).unwrap_or(0)
+    }
+
+    /// Queue a framed packet on an open stream; `false` when there is none for `target`
+    fn queue(&self, packet: &[u8], target: SocketAddr) -> bool {
+        let connections = match self.shared.connections.lock() {
+            Ok(connections) => connections,
+            Err(_) => return false,
+        };
+        match connections.by_addr.get(&target) {
+            Some(connection) => {
+                let frame = encode_frame(self.shared.kind, packet, connection.masked);
+                connection.outbound.send(frame).is_ok()
+            }
+            None => false,
+        }
+    }
+
+    async fn send_packet(&self, packet: &[u8], target: SocketAddr) -> io::Result<usize> {
+        if packet.len() > MAX_STREAM_PACKET {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Packet too large for stream transport"));
+        }
+        if self.queue(packet, target) {
+            return Ok(packet.len());
+        }
+
+        let kind = self.shared.kind;
+        let stream = tokio::time::timeout(STREAM_CONNECT_TIMEOUT, connect(target, kind)).await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, format!("Timed out connecting to {}", target)))??;
+        debug!("Opened {} stream to {}", kind, target);
+        // Client side of a WebSocket masks its frames
+        register(stream, target, kind == TransportKind::WebSocket, &self.shared);
+
+        if self.queue(packet, target) {
+            Ok(packet.len())
+        } else {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, format!("Stream to {} closed", target)))
+        }
+    }
+
+    async fn recv_packet(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (from, packet) = self.inbound_rx.lock().await.recv().await
+            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "Stream transport closed"))?;
+        let len = packet.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&packet[..len]);
+        Ok((len, from))
+    }
+}
+
+impl Transport for StreamTransport {
+    fn send_to<'a>(&'a self, packet: &'a [u8], target: SocketAddr) -> TransportFuture<'a, usize> {
+        Box::pin(self.send_packet(packet, target))
+    }
+
+    fn recv_from<'a>(&'a self, buffer: &'a mut [u8]) -> TransportFuture<'a, (usize, SocketAddr)> {
+        Box::pin(self.recv_packet(buffer))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    fn kind(&self) -> TransportKind {
+        self.shared.kind
+    }
+}
+
+impl Drop for StreamTransport {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+        // Readers hold the table too, so close the streams explicitly
+        if let Ok(mut connections) = self.shared.connections.lock() {
+            connections.by_addr.clear();
+        }
+    }
+}
+
+async fn accept_loop(listener: TcpListener, shared: Arc<StreamShared>) {
+    let kind = shared.kind;
+    loop {
+        let (stream, remote) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("TCP accept failed: {}", e);
+                continue;
+            }
+        };
+
+        let shared = Arc::clone(&shared);
+        // Upgrade off the accept loop so a slow client can't hold up others
+        tokio::spawn(async move {
+            let upgraded = match kind {
+                TransportKind::WebSocket => {
+                    tokio::time::timeout(STREAM_CONNECT_TIMEOUT, websocket_accept(stream)).await
+                        .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "Upgrade timed out")))
+                }
+                _ => Ok(stream),
+            };
+            match upgraded {
+                Ok(stream) => {
+                    debug!("Accepted {} stream from {}", kind, remote);
+                    register(stream, remote, false, &shared);
+                }
+                Err(e) => debug!("Rejected stream from {}: {}", remote, e),
+            }
+        });
+    }
+}
+
+async fn connect(target: SocketAddr, kind: TransportKind) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect(target).await?;
+    match kind {
+        TransportKind::WebSocket => websocket_connect(stream, target).await,
+        _ => Ok(stream),
+    }
+}
+
+/// Start the reader and writer for a stream and add it to the table. A stream that
+/// raced another to the same remote is dropped in favour of the one already there.
+fn register(stream: TcpStream, remote: SocketAddr, masked: bool, shared: &Arc<StreamShared>) {
+    // Voice frames are small and latency-bound; don't let Nagle batch them
+    if let Err(e) = stream.set_nodelay(true) {
+        debug!("Failed to disable Nagle on stream to {}: {}", remote, e);
+    }
+
+    let mut table = match shared.connections.lock() {
+        Ok(table) => table,
+        Err(_) => return,
+    };
+    if table.by_addr.contains_key(&remote) {
+        return;
+    }
+
+    let (read_half, write_half) = stream.into_split();
+    let (outbound, outbound_rx) = mpsc::unbounded_channel();
+    let id = table.next_id;
+    table.next_id += 1;
+
+    tokio::spawn(write_loop(write_half, outbound_rx));
+    let reader = tokio::spawn(read_loop(read_half, remote, masked, id, outbound.clone(), Arc::clone(shared)));
+    table.by_addr.insert(remote, Connection { id, outbound, masked, reader });
+}
+
+async fn write_loop(mut writer: tokio::net::tcp::OwnedWriteHalf, mut outbound_rx: mpsc::UnboundedReceiver<Vec<u8>>) {
+    while let Some(frame) = outbound_rx.recv().await {
+        if let Err(e) = writer.write_all(&frame).await {
+            debug!("Stream write failed: {}", e);
+            break;
+        }
+    }
+}
+
+async fn read_loop(
+    mut reader: tokio::net::tcp::OwnedReadHalf,
+    remote: SocketAddr,
+    masked: bool,
+    id: u64,
+    outbound: mpsc::UnboundedSender<Vec<u8>>,
+    shared: Arc<StreamShared>,
+) {
+    loop {
+        let packet = match shared.kind {
+            TransportKind::WebSocket => match read_websocket_frame(&mut reader).await {
+                Ok((OPCODE_BINARY, payload)) => payload,
+                Ok((OPCODE_PING, payload)) => {
+                    let _ = outbound.send(encode_websocket_frame(OPCODE_PONG, &payload, masked));
+                    continue;
+                }
+                Ok((OPCODE_CLOSE, _)) => break,
+                // Pongs and text frames carry nothing for us
+                Ok(_) => continue,
+                Err(e) => {
+                    debug!("WebSocket stream from {} ended: {}", remote, e);
+                    break;
+                }
+            },
+            _ => match read_length_prefixed(&mut reader).await {
+                Ok(payload) => payload,
+                Err(e) => {
+                    debug!("TCP stream from {} ended: {}", remote, e);
+                    break;
+                }
+            },
+        };
+
+        if shared.inbound_tx.send((remote, packet)).is_err() {
+            break;
+        }
+    }
+
+    // Only forget the stream if a newer one hasn't replaced it
+    if let Ok(mut table) = shared.connections.lock()
+        && table.by_addr.get(&remote).is_some_and(|connection| connection.id == id) {
+        table.by_addr.remove(&remote);
+    }
+}
+
+/// Frame a packet for the given stream transport
+pub(crate) fn encode_frame(kind: TransportKind, packet: &[u8], masked: bool) -> Vec<u8> {
+    match kind {
+        TransportKind::WebSocket => encode_websocket_frame(OPCODE_BINARY, packet, masked),
+        _ => {
+            let mut frame = Vec::with_capacity(2 + packet.len());
+            frame.extend_from_slice(&(packet.len() as u16).to_be_bytes());
+            frame.extend_from_slice(packet);
+            frame
+        }
+    }
+}
+
+/// Read one packet framed with a 16-bit big-endian length prefix
+pub(crate) async fn read_length_prefixed<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = reader.read_u16().await? as usize;
+    let mut packet = vec![0u8; len];
+    reader.read_exact(&mut packet).await?;
+    Ok(packet)
+}
+
+/// Encode a single unfragmented WebSocket frame (RFC 6455 §5.2)
+pub(crate) fn encode_websocket_frame(opcode: u8, payload: &[u8], masked: bool) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(14 + payload.len());
+    frame.push(0x80 | opcode);
+
+    let mask_bit = if masked { 0x80 } else { 0x00 };
+    match payload.len() {
+        len if len < 126 => frame.push(mask_bit | len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+
+    if masked {
+        let mask: [u8; 4] = rand::random();
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+    } else {
+        frame.extend_from_slice(payload);
+    }
+    frame
+}
+
+/// Read one WebSocket frame, returning its opcode and unmasked payload.
+/// Fragmented messages are never sent by this transport and are rejected.
+pub(crate) async fn read_websocket_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header).await?;
+    if header[0] & 0x80 == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Fragmented WebSocket frame"));
+    }
+    let opcode = header[0] & 0x0F;
+
+    let len = match header[1] & 0x7F {
+        126 => reader.read_u16().await? as usize,
+        127 => reader.read_u64().await? as usize,
+        len => len as usize,
+    };
+    if len > MAX_STREAM_PACKET {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "WebSocket frame too large"));
+    }
+
+    let mask = match header[1] & 0x80 {
+        0 => None,
+        _ => {
+            let mut mask = [0u8; 4];
+            reader.read_exact(&mut mask).await?;
+            Some(mask)
+        }
+    };
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    if let Some(mask) = mask {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+    Ok((opcode, payload))
+}
+
+/// `Sec-WebSocket-Accept` value for a client's `Sec-WebSocket-Key`
+pub fn websocket_accept_key(key: &str) -> String {
+    let digest = ring::digest::digest(
+        &ring::digest::SHA1_FOR_LEGACY_USE_ONLY,
+        format!("{}{}", key, WEBSOCKET_GUID).as_bytes(),
+    );
+    base64::engine::general_purpose::STANDARD.encode(digest.as_ref())
+}
+
+/// Read an HTTP header block byte by byte, so nothing after it is consumed
+async fn read_http_header(stream: &mut TcpStream) -> io::Result<String> {
+    let mut header = Vec::new();
+    while !header.ends_with(b"\r\n\r\n") {
+        if header.len() >= MAX_UPGRADE_HEADER {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "HTTP header too long"));
+        }
+        header.push(stream.read_u8().await?);
+    }
+    String::from_utf8(header).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "HTTP header is not UTF-8"))
+}
+
+fn header_value<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    header.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+/// Client side of the upgrade handshake
+async fn websocket_connect(mut stream: TcpStream, target: SocketAddr) -> io::Result<TcpStream> {
+    let key = base64::engine::general_purpose::STANDARD.encode(rand::random::<[u8; 16]>());
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        WEBSOCKET_PATH, target, key
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let response = read_http_header(&mut stream).await?;
+    let switching = response.lines().next().is_some_and(|status| status.split_whitespace().nth(1) == Some("101"));
+    if !switching || header_value(&response, "Sec-WebSocket-Accept") != Some(websocket_accept_key(&key).as_str()) {
+        return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "WebSocket upgrade refused"));
+    }
+    Ok(stream)
+}
+
+/// Server side of the upgrade handshake
+async fn websocket_accept(mut stream: TcpStream) -> io::Result<TcpStream> {
+    let request = read_http_header(&mut stream).await?;
+    let is_upgrade = header_value(&request, "Upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+
+    match header_value(&request, "Sec-WebSocket-Key") {
+        Some(key) if is_upgrade => {
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                websocket_accept_key(key)
+            );
+            stream.write_all(response.as_bytes()).await?;
+            Ok(stream)
+        }
+        _ => {
+            stream.write_all(b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n").await?;
+            Err(io::Error::new(io::ErrorKind::InvalidData, "Not a WebSocket upgrade"))
+        }
+    }
+}
+
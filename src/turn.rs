@@ -0,0 +1,624 @@
+use anyhow::{Result, anyhow};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::stun;
+use crate::wire::{Reader, WireError};
+
+/// Current TURN-style relay protocol version
+pub const TURN_WIRE_VERSION: u8 = 1;
+
+/// Header: version (1) + message type (1) + body length (2), as in [`crate::wire`]
+const HEADER_LEN: usize = 4;
+
+/// Default UDP port for `humr-turn`; it answers STUN binding requests on the same port
+pub const DEFAULT_TURN_PORT: u16 = stun::DEFAULT_STUN_PORT;
+
+/// Allocations the server holds at once
+pub const DEFAULT_MAX_ALLOCATIONS: usize = 64;
+
+/// Allocations that are not refreshed for this long are released
+pub const DEFAULT_ALLOCATION_LIFETIME: Duration = Duration::from_secs(600);
+
+/// How long an allocation challenge stays valid
+const CHALLENGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outstanding challenges kept before new allocation requests are ignored
+const MAX_PENDING_CHALLENGES: usize = 1024;
+
+/// Domain separation for allocation signatures
+const ALLOCATE_LABEL: &[u8] = b"HUMR_TURN_ALLOCATE_V1";
+
+const SIGNATURE_LEN: usize = 64;
+const PUBLIC_KEY_LEN: usize = 32;
+
+const FAMILY_IPV4: u8 = 4;
+const FAMILY_IPV6: u8 = 6;
+
+/// TURN message type byte carried in every packet header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TurnMessageType {
+    AllocateRequest = 0x30,
+    AllocateChallenge = 0x31,
+    Allocate = 0x32,
+    AllocateSuccess = 0x33,
+    AllocateError = 0x34,
+    Refresh = 0x35,
+    CreatePermission = 0x36,
+    Send = 0x37,
+    Data = 0x38,
+}
+
+impl TurnMessageType {
+    /// Type byte used for a given message
+    pub fn of(message: &TurnMessage) -> Self {
+        match message {
+            TurnMessage::AllocateRequest => TurnMessageType::AllocateRequest,
+            TurnMessage::AllocateChallenge { .. } => TurnMessageType::AllocateChallenge,
+            TurnMessage::Allocate { .. } => TurnMessageType::Allocate,
+            TurnMessage::AllocateSuccess { .. } => TurnMessageType::AllocateSuccess,
+            TurnMessage::AllocateError { .. } => TurnMessageType::AllocateError,
+            TurnMessage::Refresh => TurnMessageType::Refresh,
+            TurnMessage::CreatePermission { .. } => TurnMessageType::CreatePermission,
+            TurnMessage::Send { .. } => TurnMessageType::Send,
+            TurnMessage::Data { .. } => TurnMessageType::Data,
+        }
+    }
+}
+
+impl TryFrom<u8> for TurnMessageType {
+    type Error = WireError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x30 => Ok(TurnMessageType::AllocateRequest),
+            0x31 => Ok(TurnMessageType::AllocateChallenge),
+            0x32 => Ok(TurnMessageType::Allocate),
+            0x33 => Ok(TurnMessageType::AllocateSuccess),
+            0x34 => Ok(TurnMessageType::AllocateError),
+            0x35 => Ok(TurnMessageType::Refresh),
+            0x36 => Ok(TurnMessageType::CreatePermission),
+            0x37 => Ok(TurnMessageType::Send),
+            0x38 => Ok(TurnMessageType::Data),
+            other => Err(WireError::UnknownMessageType(other)),
+        }
+    }
+}
+
+/// Messages exchanged between a client and `humr-turn`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TurnMessage {
+    /// Client asks for a challenge before allocating
+    AllocateRequest,
+    /// Server nonce the client must sign
+    AllocateChallenge { nonce: [u8; 32] },
+    /// Signed allocation answering a challenge
+    Allocate {
+        identity_public_key: [u8; 32],
+        nonce: [u8; 32],
+        signature: [u8; 64],
+    },
+    /// Relayed address reserved for the client, also the answer to `Refresh`
+    AllocateSuccess { relayed_address: SocketAddr, lifetime_secs: u32 },
+    AllocateError { reason: String },
+    /// Keep the allocation alive for another lifetime
+    Refresh,
+    /// Let datagrams from these peer addresses through to the client
+    CreatePermission { peers: Vec<IpAddr> },
+    /// Packet for the server to send to `peer` from the relayed address
+    Send { peer: SocketAddr, payload: Vec<u8> },
+    /// Packet `peer` sent to the relayed address
+    Data { peer: SocketAddr, payload: Vec<u8> },
+}
+
+/// Encode a TURN message into a versioned, length-prefixed packet.
+///
+/// Layout (all integers big-endian; addresses as family (1) + IP (4 or 16) + port (2)):
+/// - `AllocateRequest`, `Refresh`: empty
+/// - `AllocateChallenge`: nonce (32)
+/// - `Allocate`: identity key (32), nonce (32), signature (64)
+/// - `AllocateSuccess`: relayed address, lifetime in seconds (4)
+/// - `AllocateError`: reason as length (2) + UTF-8
+/// - `CreatePermission`: count (1), then family (1) + IP per peer
+/// - `Send`, `Data`: peer address, payload (rest of body)
+pub fn encode_turn_message(message: &TurnMessage) -> Result<Vec<u8>, WireError> {
+    let mut body = Vec::new();
+
+    match message {
+        TurnMessage::AllocateRequest | TurnMessage::Refresh => {}
+        TurnMessage::AllocateChallenge { nonce } => body.extend_from_slice(nonce),
+        TurnMessage::Allocate { identity_public_key, nonce, signature } => {
+            body.extend_from_slice(identity_public_key);
+            body.extend_from_slice(nonce);
+            body.extend_from_slice(signature);
+        }
+        TurnMessage::AllocateSuccess { relayed_address, lifetime_secs } => {
+            put_address(&mut body, relayed_address);
+            body.extend_from_slice(&lifetime_secs.to_be_bytes());
+        }
+        TurnMessage::AllocateError { reason } => {
+            let len = u16::try_from(reason.len())
+                .map_err(|_| WireError::BodyTooLarge(reason.len()))?;
+            body.extend_from_slice(&len.to_be_bytes());
+            body.extend_from_slice(reason.as_bytes());
+        }
+        TurnMessage::CreatePermission { peers } => {
+            let count = u8::try_from(peers.len())
+                .map_err(|_| WireError::InvalidBody("too many permission addresses"))?;
+            body.push(count);
+            for ip in peers {
+                put_ip(&mut body, ip);
+            }
+        }
+        TurnMessage::Send { peer, payload } | TurnMessage::Data { peer, payload } => {
+            body.reserve(19 + payload.len());
+            put_address(&mut body, peer);
+            body.extend_from_slice(payload);
+        }
+    }
+
+    let body_len = u16::try_from(body.len())
+        .map_err(|_| WireError::BodyTooLarge(body.len()))?;
+
+    let mut packet = Vec::with_capacity(HEADER_LEN + body.len());
+    packet.push(TURN_WIRE_VERSION);
+    packet.push(TurnMessageType::of(message) as u8);
+    packet.extend_from_slice(&body_len.to_be_bytes());
+    packet.extend_from_slice(&body);
+    Ok(packet)
+}
+
+/// Decode a packet produced by [`encode_turn_message`]
+pub fn decode_turn_message(packet: &[u8]) -> Result<TurnMessage, WireError> {
+    if packet.len() < HEADER_LEN {
+        return Err(WireError::Truncated { needed: HEADER_LEN, available: packet.len() });
+    }
+    if packet[0] != TURN_WIRE_VERSION {
+        return Err(WireError::UnsupportedVersion(packet[0]));
+    }
+
+    let message_type = TurnMessageType::try_from(packet[1])?;
+    let declared = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+    let body = &packet[HEADER_LEN..];
+    if body.len() != declared {
+        return Err(WireError::LengthMismatch { declared, actual: body.len() });
+    }
+
+    let mut reader = Reader::new(body);
+    let message = match message_type {
+        TurnMessageType::AllocateRequest => TurnMessage::AllocateRequest,
+        TurnMessageType::Refresh => TurnMessage::Refresh,
+        TurnMessageType::AllocateChallenge => TurnMessage::AllocateChallenge { nonce: reader.array::<32>()? },
+        TurnMessageType::Allocate => TurnMessage::Allocate {
+            identity_public_key: reader.array::<PUBLIC_KEY_LEN>()?,
+            nonce: reader.array::<32>()?,
+            signature: reader.array::<SIGNATURE_LEN>()?,
+        },
+        TurnMessageType::AllocateSuccess => TurnMessage::AllocateSuccess {
+            relayed_address: read_address(&mut reader)?,
+            lifetime_secs: reader.u32()?,
+        },
+        TurnMessageType::AllocateError => {
+            let len = reader.u16()? as usize;
+            let reason = std::str::from_utf8(reader.take(len)?)
+                .map_err(|_| WireError::InvalidBody("string is not valid UTF-8"))?;
+            TurnMessage::AllocateError { reason: reason.to_string() }
+        }
+        TurnMessageType::CreatePermission => {
+            let count = reader.array::<1>()?[0];
+            let peers = (0..count).map(|_| read_ip(&mut reader)).collect::<Result<_, _>>()?;
+            TurnMessage::CreatePermission { peers }
+        }
+        TurnMessageType::Send => TurnMessage::Send {
+            peer: read_address(&mut reader)?,
+            payload: reader.rest().to_vec(),
+        },
+        TurnMessageType::Data => TurnMessage::Data {
+            peer: read_address(&mut reader)?,
+            payload: reader.rest().to_vec(),
+        },
+    };
+
+    if reader.remaining() > 0 {
+        return Err(WireError::TrailingBytes(reader.remaining()));
+    }
+
+    Ok(message)
+}
+
+fn put_ip(body: &mut Vec<u8>, ip: &IpAddr) {
+    match ip {
+        IpAddr::V4(ip) => {
+            body.push(FAMILY_IPV4);
+            body.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            body.push(FAMILY_IPV6);
+            body.extend_from_slice(&ip.octets());
+        }
+    }
+}
+
+fn put_address(body: &mut Vec<u8>, addr: &SocketAddr) {
+    put_ip(body, &addr.ip());
+    body.extend_from_slice(&addr.port().to_be_bytes());
+}
+
+fn read_ip(reader: &mut Reader<'_>) -> Result<IpAddr, WireError> {
+    match reader.array::<1>()?[0] {
+        FAMILY_IPV4 => Ok(IpAddr::V4(Ipv4Addr::from(reader.array::<4>()?))),
+        FAMILY_IPV6 => Ok(IpAddr::V6(Ipv6Addr::from(reader.array::<16>()?))),
+        _ => Err(WireError::InvalidBody("unknown address family")),
+    }
+}
+
+fn read_address(reader: &mut Reader<'_>) -> Result<SocketAddr, WireError> {
+    let ip = read_ip(reader)?;
+    Ok(SocketAddr::new(ip, reader.u16()?))
+}
+
+/// What a client signs to prove it holds the identity key it allocates under
+pub fn allocate_digest(identity: &[u8; 32], nonce: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(ALLOCATE_LABEL);
+    hasher.update(identity);
+    hasher.update(nonce);
+    hasher.finalize().into()
+}
+
+/// TURN-style relay settings
+#[derive(Debug, Clone)]
+pub struct TurnConfig {
+    pub max_allocations: usize,
+    /// Identity keys allowed to allocate; empty admits any key that passes the challenge
+    pub allowed_identities: Vec<VerifyingKey>,
+    pub allocation_lifetime: Duration,
+    /// Public address to report in relayed addresses, e.g. when listening on 0.0.0.0 behind a 1:1 NAT
+    pub external_ip: Option<IpAddr>,
+}
+
+impl Default for TurnConfig {
+    fn default() -> Self {
+        Self {
+            max_allocations: DEFAULT_MAX_ALLOCATIONS,
+            allowed_identities: Vec::new(),
+            allocation_lifetime: DEFAULT_ALLOCATION_LIFETIME,
+            external_ip: None,
+        }
+    }
+}
+
+struct PendingChallenge {
+    nonce: [u8; 32],
+    issued: Instant,
+}
+
+/// Relayed socket reserved for one client
+struct Allocation {
+    relayed_address: SocketAddr,
+    socket: Arc<UdpSocket>,
+    // Peer IPs whose datagrams are passed on to the client
+    permissions: Arc<Mutex<HashSet<IpAddr>>>,
+    // Nonce of the Allocate that created it, to answer retransmissions
+    nonce: [u8; 32],
+    expires: Instant,
+    // Forwards datagrams arriving on the relayed socket to the client
+    task: JoinHandle<()>,
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Default)]
+struct TurnState {
+    pending: HashMap<SocketAddr, PendingChallenge>,
+    allocations: HashMap<SocketAddr, Allocation>,
+}
+
+/// Datagrams to send from the server socket after handling a packet
+type Outgoing = Vec<(SocketAddr, Vec<u8>)>;
+
+/// Relays UDP for clients that cannot be reached directly. Each authenticated client
+/// gets its own relayed address; the server only moves already-encrypted protocol packets.
+pub struct TurnServer {
+    socket: Arc<UdpSocket>,
+    config: TurnConfig,
+    // Address relayed sockets are reported at
+    advertised_ip: IpAddr,
+    state: Mutex<TurnState>,
+}
+
+impl TurnServer {
+    pub async fn bind(addr: &str, config: TurnConfig) -> Result<Self> {
+        let socket = UdpSocket::bind(addr).await
+            .map_err(|e| anyhow!("Failed to bind TURN socket to {}: {}", addr, e))?;
+        let local_ip = socket.local_addr()?.ip();
+
+        let advertised_ip = match config.external_ip {
+            Some(ip) => ip,
+            None if !local_ip.is_unspecified() => local_ip,
+            None => stun::local_interface_ip()
+                .ok_or_else(|| anyhow!("Cannot tell which address to advertise; set an external IP"))?,
+        };
+
+        Ok(Self {
+            socket: Arc::new(socket),
+            config,
+            advertised_ip,
+            state: Mutex::new(TurnState::default()),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Number of live allocations
+    pub async fn allocation_count(&self) -> usize {
+        self.state.lock().await.allocations.len()
+    }
+
+    /// Serve until the socket fails
+    pub async fn run(&self) -> Result<()> {
+        info!("TURN relay listening on {}", self.local_addr()?);
+        let mut buffer = vec![0u8; 2048];
+        let sweep_interval = (self.config.allocation_lifetime / 4).max(Duration::from_millis(100));
+        let mut next_sweep = Instant::now() + sweep_interval;
+
+        loop {
+            // Sweep on schedule even when steady traffic keeps the receive from timing out
+            if Instant::now() >= next_sweep {
+                next_sweep = Instant::now() + sweep_interval;
+                self.expire();
+            }
+
+            let received = tokio::time::timeout_at(next_sweep, self.socket.recv_from(&mut buffer)).await;
+
+            let outgoing = match received {
+                Ok(Ok((len, addr))) => self.handle(addr, &buffer[..len]).await,
+                // ICMP port unreachable from a departed client surfaces here on some platforms
+                Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionReset => continue,
+                Ok(Err(e)) => return Err(anyhow!("TURN receive error: {}", e)),
+                Err(_) => continue,
+            };
+
+            for (addr, packet) in outgoing {
+                if let Err(e) = self.socket.send_to(&packet, addr).await {
+                    debug!("Failed to send to {}: {}", addr, e);
+                }
+            }
+        }
+    }
+
+    async fn handle(&self, addr: SocketAddr, packet: &[u8]) -> Outgoing {
+        // Doubles as a STUN server so clients can find their reflexive address here too
+        if stun::is_stun_message(packet) {
+            return stun::binding_response(packet, addr)
+                .map(|response| vec![(addr, response)])
+                .unwrap_or_default();
+        }
+
+        let message = match decode_turn_message(packet) {
+            Ok(message) => message,
+            Err(e) => {
+                debug!("Dropping malformed TURN packet from {}: {}", addr, e);
+                return Vec::new();
+            }
+        };
+
+        let mut state = self.state.lock().await;
+        match message {
+            TurnMessage::AllocateRequest => match state.allocations.get(&addr) {
+                Some(allocation) => reply(addr, &self.success(allocation)),
+                None => issue_challenge(&mut state, addr),
+            },
+            TurnMessage::Allocate { identity_public_key, nonce, signature } => {
+                self.allocate(&mut state, addr, identity_public_key, nonce, signature).await
+            }
+            TurnMessage::Refresh => match state.allocations.get_mut(&addr) {
+                Some(allocation) => {
+                    allocation.expires = Instant::now() + self.config.allocation_lifetime;
+                    reply(addr, &self.success(allocation))
+                }
+                None => reply(addr, &TurnMessage::AllocateError { reason: "No allocation".to_string() }),
+            },
+            TurnMessage::CreatePermission { peers } => {
+                if let Some(allocation) = state.allocations.get(&addr) {
+                    allocation.permissions.lock().await.extend(peers);
+                }
+                Vec::new()
+            }
+            TurnMessage::Send { peer, payload } => {
+                if let Some(allocation) = state.allocations.get(&addr) {
+                    if !allocation.permissions.lock().await.contains(&peer.ip()) {
+                        debug!("Dropping send from {} to {} without permission", addr, peer);
+                    } else if let Err(e) = allocation.socket.send_to(&payload, peer).await {
+                        debug!("Failed to relay to {}: {}", peer, e);
+                    }
+                }
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    async fn allocate(
+        &self,
+        state: &mut TurnState,
+        addr: SocketAddr,
+        identity_public_key: [u8; 32],
+        nonce: [u8; 32],
+        signature: [u8; 64],
+    ) -> Outgoing {
+        // Retransmitted Allocate after we already created the allocation
+        if let Some(allocation) = state.allocations.get(&addr)
+            && allocation.nonce == nonce {
+            return reply(addr, &self.success(allocation));
+        }
+
+        match state.pending.get(&addr) {
+            Some(pending) if pending.nonce == nonce && pending.issued.elapsed() < CHALLENGE_TIMEOUT => {}
+            _ => return reject(addr, "No valid challenge for this allocation"),
+        }
+
+        let identity = match VerifyingKey::from_bytes(&identity_public_key) {
+            Ok(identity) => identity,
+            Err(_) => return reject(addr, "Invalid identity key"),
+        };
+        let digest = allocate_digest(&identity_public_key, &nonce);
+        if identity.verify(&digest, &Signature::from_bytes(&signature)).is_err() {
+            return reject(addr, "Invalid allocation signature");
+        }
+        if !self.config.allowed_identities.is_empty() && !self.config.allowed_identities.contains(&identity) {
+            return reject(addr, "Identity not allowed on this relay");
+        }
+        state.pending.remove(&addr);
+
+        // A client allocating again replaces its old allocation
+        state.allocations.remove(&addr);
+        if state.allocations.len() >= self.config.max_allocations {
+            return reject(addr, "Relay is at capacity");
+        }
+
+        let bind_addr = SocketAddr::new(self.socket.local_addr().map(|local| local.ip()).unwrap_or(Ipv4Addr::UNSPECIFIED.into()), 0);
+        let socket = match UdpSocket::bind(bind_addr).await {
+            Ok(socket) => Arc::new(socket),
+            Err(e) => {
+                warn!("Failed to bind relayed socket: {}", e);
+                return reject(addr, "Relay could not reserve an address");
+            }
+        };
+        let relayed_address = match socket.local_addr() {
+            Ok(local) => SocketAddr::new(self.advertised_ip, local.port()),
+            Err(_) => return reject(addr, "Relay could not reserve an address"),
+        };
+
+        let permissions = Arc::new(Mutex::new(HashSet::new()));
+        let task = tokio::spawn(relay_to_client(
+            Arc::clone(&socket),
+            Arc::clone(&self.socket),
+            addr,
+            Arc::clone(&permissions),
+        ));
+
+        let allocation = Allocation {
+            relayed_address,
+            socket,
+            permissions,
+            nonce,
+            expires: Instant::now() + self.config.allocation_lifetime,
+            task,
+        };
+        let outgoing = reply(addr, &self.success(&allocation));
+        info!("Allocated {} for {}", relayed_address, addr);
+        state.allocations.insert(addr, allocation);
+        outgoing
+    }
+
+    fn success(&self, allocation: &Allocation) -> TurnMessage {
+        TurnMessage::AllocateSuccess {
+            relayed_address: allocation.relayed_address,
+            lifetime_secs: self.config.allocation_lifetime.as_secs().max(1) as u32,
+        }
+    }
+
+    fn expire(&self) {
+        // Skip a sweep rather than stall packet handling
+        if let Ok(mut state) = self.state.try_lock() {
+            let now = Instant::now();
+            state.allocations.retain(|addr, allocation| {
+                let live = allocation.expires > now;
+                if !live {
+                    info!("Allocation {} for {} expired", allocation.relayed_address, addr);
+                }
+                live
+            });
+            state.pending.retain(|_, pending| pending.issued.elapsed() < CHALLENGE_TIMEOUT);
+        }
+    }
+}
+
+fn issue_challenge(state: &mut TurnState, addr: SocketAddr) -> Outgoing {
+    if state.pending.len() >= MAX_PENDING_CHALLENGES {
+        state.pending.retain(|_, pending| pending.issued.elapsed() < CHALLENGE_TIMEOUT);
+        if state.pending.len() >= MAX_PENDING_CHALLENGES {
+            warn!("Too many pending allocations, ignoring request from {}", addr);
+            return Vec::new();
+        }
+    }
+
+    // Reuse the outstanding challenge so retransmitted requests don't invalidate an Allocate in flight
+    let nonce = match state.pending.get(&addr) {
+        Some(pending) if pending.issued.elapsed() < CHALLENGE_TIMEOUT => pending.nonce,
+        _ => {
+            let nonce: [u8; 32] = rand::random();
+            state.pending.insert(addr, PendingChallenge { nonce, issued: Instant::now() });
+            nonce
+        }
+    };
+
+    reply(addr, &TurnMessage::AllocateChallenge { nonce })
+}
+
+/// Wrap datagrams from permitted peers in `Data` and pass them to the client
+async fn relay_to_client(
+    relayed: Arc<UdpSocket>,
+    server: Arc<UdpSocket>,
+    client: SocketAddr,
+    permissions: Arc<Mutex<HashSet<IpAddr>>>,
+) {
+    let mut buffer = vec![0u8; 2048];
+
+    loop {
+        let (len, peer) = match relayed.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionReset => continue,
+            Err(e) => {
+                warn!("Relayed socket for {} failed: {}", client, e);
+                break;
+            }
+        };
+
+        if !permissions.lock().await.contains(&peer.ip()) {
+            debug!("Dropping datagram from {} without permission for {}", peer, client);
+            continue;
+        }
+
+        match encode_turn_message(&TurnMessage::Data { peer, payload: buffer[..len].to_vec() }) {
+            Ok(packet) => {
+                if let Err(e) = server.send_to(&packet, client).await {
+                    debug!("Failed to deliver relayed data to {}: {}", client, e);
+                }
+            }
+            Err(e) => debug!("Dropping oversized datagram from {}: {}", peer, e),
+        }
+    }
+}
+
+fn reply(addr: SocketAddr, message: &TurnMessage) -> Outgoing {
+    match encode_turn_message(message) {
+        Ok(packet) => vec![(addr, packet)],
+        Err(e) => {
+            warn!("Failed to encode TURN message: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn reject(addr: SocketAddr, reason: &str) -> Outgoing {
+    info!("Rejected allocation from {}: {}", addr, reason);
+    reply(addr, &TurnMessage::AllocateError { reason: reason.to_string() })
+}
@@ -0,0 +1,302 @@
+use crate::capabilities::CapabilityOffer;
+use crate::security::SecureMessage;
+
+/// Current wire protocol version; version 2 added capability offers to handshakes
+pub const WIRE_VERSION: u8 = 2;
+
+/// Header: version (1) + message type (1) + body length (2)
+pub const HEADER_LEN: usize = 4;
+
+/// Ed25519 signature length
+const SIGNATURE_LEN: usize = 64;
+/// X25519 / Ed25519 public key length
+const PUBLIC_KEY_LEN: usize = 32;
+/// ChaCha20-Poly1305 nonce length
+const NONCE_LEN: usize = 12;
+/// ChaCha20-Poly1305 authentication tag length
+const TAG_LEN: usize = 16;
+
+/// Wire decoding error types
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireError {
+    Truncated { needed: usize, available: usize },
+    UnsupportedVersion(u8),
+    UnknownMessageType(u8),
+    LengthMismatch { declared: usize, actual: usize },
+    TrailingBytes(usize),
+    BodyTooLarge(usize),
+    InvalidBody(&'static str),
+}
+
+impl std::fmt::Display for WireError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WireError::Truncated { needed, available } => write!(f, "Truncated packet: need {} bytes, have {}", needed, available),
+            WireError::UnsupportedVersion(version) => write!(f, "Unsupported wire version: {}", version),
+            WireError::UnknownMessageType(kind) => write!(f, "Unknown message type: {:#04x}", kind),
+            WireError::LengthMismatch { declared, actual } => write!(f, "Length mismatch: header declares {} body bytes, packet has {}", declared, actual),
+            WireError::TrailingBytes(count) => write!(f, "{} unexpected trailing bytes", count),
+            WireError::BodyTooLarge(len) => write!(f, "Message body too large: {} bytes", len),
+            WireError::InvalidBody(msg) => write!(f, "Invalid message body: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+/// Message type byte carried in every packet header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MessageType {
+    Handshake = 0x01,
+    HandshakeResponse = 0x02,
+    EncryptedAudio = 0x03,
+    Disconnect = 0x04,
+    Rekey = 0x05,
+    RekeyResponse = 0x06,
+    EncryptedControl = 0x07,
+}
+
+impl MessageType {
+    /// Type byte used for a given message
+    pub fn of(message: &SecureMessage) -> Self {
+        match message {
+            SecureMessage::Handshake { .. } => MessageType::Handshake,
+            SecureMessage::HandshakeResponse { .. } => MessageType::HandshakeResponse,
+            SecureMessage::EncryptedAudio { .. } => MessageType::EncryptedAudio,
+            SecureMessage::Disconnect { .. } => MessageType::Disconnect,
+            SecureMessage::Rekey { .. } => MessageType::Rekey,
+            SecureMessage::RekeyResponse { .. } => MessageType::RekeyResponse,
+            SecureMessage::EncryptedControl { .. } => MessageType::EncryptedControl,
+        }
+    }
+}
+
+impl TryFrom<u8> for MessageType {
+    type Error = WireError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(MessageType::Handshake),
+            0x02 => Ok(MessageType::HandshakeResponse),
+            0x03 => Ok(MessageType::EncryptedAudio),
+            0x04 => Ok(MessageType::Disconnect),
+            0x05 => Ok(MessageType::Rekey),
+            0x06 => Ok(MessageType::RekeyResponse),
+            0x07 => Ok(MessageType::EncryptedControl),
+            other => Err(WireError::UnknownMessageType(other)),
+        }
+    }
+}
+
+/// Encode a message into a versioned, length-prefixed packet.
+///
+/// Layout (all integers big-endian):
+/// - `Handshake`: identity key (32), ephemeral key (32), signature (64), timestamp (8), capability offer
+/// - `HandshakeResponse`: identity key (32), ephemeral key (32), signature (64), timestamp (8), capability offer
+///
+/// The capability offer layout is given by [`CapabilityOffer::encode`].
+/// - `EncryptedAudio`: sequence (8), timestamp (8), nonce (12), ciphertext (rest of body)
+/// - `Disconnect`: reason length (2), reason (UTF-8), signature (64)
+/// - `Rekey` / `RekeyResponse`: generation (4), ephemeral key (32), signature (64)
+/// - `EncryptedControl`: counter (8), nonce (12), ciphertext (rest of body)
+pub fn encode_message(message: &SecureMessage) -> Result<Vec<u8>, WireError> {
+    let mut body = Vec::new();
+
+    match message {
+        SecureMessage::Handshake { identity_public_key, ephemeral_public_key, signature, timestamp, capabilities }
+        | SecureMessage::HandshakeResponse { identity_public_key, ephemeral_public_key, signature, timestamp, capabilities } => {
+            body.extend_from_slice(identity_public_key);
+            body.extend_from_slice(ephemeral_public_key);
+            body.extend_from_slice(signature);
+            body.extend_from_slice(&timestamp.to_be_bytes());
+            capabilities.encode(&mut body)?;
+        }
+        SecureMessage::EncryptedAudio { frame_number, timestamp, nonce, ciphertext } => {
+            body.reserve(16 + NONCE_LEN + ciphertext.len());
+            body.extend_from_slice(&frame_number.to_be_bytes());
+            body.extend_from_slice(&timestamp.to_be_bytes());
+            body.extend_from_slice(nonce);
+            body.extend_from_slice(ciphertext);
+        }
+        SecureMessage::EncryptedControl { counter, nonce, ciphertext } => {
+            body.reserve(8 + NONCE_LEN + ciphertext.len());
+            body.extend_from_slice(&counter.to_be_bytes());
+            body.extend_from_slice(nonce);
+            body.extend_from_slice(ciphertext);
+        }
+        SecureMessage::Disconnect { reason, signature } => {
+            let reason_len = u16::try_from(reason.len())
+                .map_err(|_| WireError::BodyTooLarge(reason.len()))?;
+            body.extend_from_slice(&reason_len.to_be_bytes());
+            body.extend_from_slice(reason.as_bytes());
+            body.extend_from_slice(signature);
+        }
+        SecureMessage::Rekey { generation, ephemeral_public_key, signature }
+        | SecureMessage::RekeyResponse { generation, ephemeral_public_key, signature } => {
+            body.extend_from_slice(&generation.to_be_bytes());
+            body.extend_from_slice(ephemeral_public_key);
+            body.extend_from_slice(signature);
+        }
+    }
+
+    let body_len = u16::try_from(body.len())
+        .map_err(|_| WireError::BodyTooLarge(body.len()))?;
+
+    let mut packet = Vec::with_capacity(HEADER_LEN + body.len());
+    packet.push(WIRE_VERSION);
+    packet.push(MessageType::of(message) as u8);
+    packet.extend_from_slice(&body_len.to_be_bytes());
+    packet.extend_from_slice(&body);
+    Ok(packet)
+}
+
+/// Header-only Handshake at our version, sent in answer to a Handshake at another
+/// version so the caller can report the mismatch rather than time out
+pub fn version_notice() -> Vec<u8> {
+    vec![WIRE_VERSION, MessageType::Handshake as u8, 0, 0]
+}
+
+/// Validate the packet header and return its message type without decoding the body
+pub fn peek_message_type(packet: &[u8]) -> Result<MessageType, WireError> {
+    split_header(packet).map(|(message_type, _)| message_type)
+}
+
+/// Decode a packet produced by [`encode_message`]
+pub fn decode_message(packet: &[u8]) -> Result<SecureMessage, WireError> {
+    let (message_type, body) = split_header(packet)?;
+    let mut reader = Reader::new(body);
+
+    let message = match message_type {
+        MessageType::Handshake => SecureMessage::Handshake {
+            identity_public_key: reader.array::<PUBLIC_KEY_LEN>()?,
+            ephemeral_public_key: reader.array::<PUBLIC_KEY_LEN>()?,
+            signature: reader.array::<SIGNATURE_LEN>()?,
+            timestamp: reader.u64()?,
+            capabilities: CapabilityOffer::decode(&mut reader)?,
+        },
+        MessageType::HandshakeResponse => SecureMessage::HandshakeResponse {
+            identity_public_key: reader.array::<PUBLIC_KEY_LEN>()?,
+            ephemeral_public_key: reader.array::<PUBLIC_KEY_LEN>()?,
+            signature: reader.array::<SIGNATURE_LEN>()?,
+            timestamp: reader.u64()?,
+            capabilities: CapabilityOffer::decode(&mut reader)?,
+        },
+        MessageType::EncryptedAudio => {
+            let frame_number = reader.u64()?;
+            let timestamp = reader.u64()?;
+            let nonce = reader.array::<NONCE_LEN>()?;
+            let ciphertext = reader.rest().to_vec();
+            if ciphertext.len() < TAG_LEN {
+                return Err(WireError::InvalidBody("ciphertext shorter than authentication tag"));
+            }
+            SecureMessage::EncryptedAudio { frame_number, timestamp, nonce, ciphertext }
+        }
+        MessageType::EncryptedControl => {
+            let counter = reader.u64()?;
+            let nonce = reader.array::<NONCE_LEN>()?;
+            let ciphertext = reader.rest().to_vec();
+            if ciphertext.len() < TAG_LEN {
+                return Err(WireError::InvalidBody("ciphertext shorter than authentication tag"));
+            }
+            SecureMessage::EncryptedControl { counter, nonce, ciphertext }
+        }
+        MessageType::Disconnect => {
+            let reason_len = reader.u16()? as usize;
+            let reason = std::str::from_utf8(reader.take(reason_len)?)
+                .map_err(|_| WireError::InvalidBody("disconnect reason is not valid UTF-8"))?
+                .to_string();
+            SecureMessage::Disconnect { reason, signature: reader.array::<SIGNATURE_LEN>()? }
+        }
+        MessageType::Rekey => SecureMessage::Rekey {
+            generation: reader.u32()?,
+            ephemeral_public_key: reader.array::<PUBLIC_KEY_LEN>()?,
+            signature: reader.array::<SIGNATURE_LEN>()?,
+        },
+        MessageType::RekeyResponse => SecureMessage::RekeyResponse {
+            generation: reader.u32()?,
+            ephemeral_public_key: reader.array::<PUBLIC_KEY_LEN>()?,
+            signature: reader.array::<SIGNATURE_LEN>()?,
+        },
+    };
+
+    if reader.remaining() > 0 {
+        return Err(WireError::TrailingBytes(reader.remaining()));
+    }
+
+    Ok(message)
+}
+
+/// Check version and length prefix, returning the message type and body
+fn split_header(packet: &[u8]) -> Result<(MessageType, &[u8]), WireError> {
+    if packet.len() < HEADER_LEN {
+        return Err(WireError::Truncated { needed: HEADER_LEN, available: packet.len() });
+    }
+
+    if packet[0] != WIRE_VERSION {
+        return Err(WireError::UnsupportedVersion(packet[0]));
+    }
+
+    let message_type = MessageType::try_from(packet[1])?;
+    let declared = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+    let body = &packet[HEADER_LEN..];
+
+    if body.len() != declared {
+        return Err(WireError::LengthMismatch { declared, actual: body.len() });
+    }
+
+    Ok((message_type, body))
+}
+
+/// Bounds-checked cursor over a message body
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], WireError> {
+        if self.remaining() < len {
+            return Err(WireError::Truncated { needed: self.position + len, available: self.data.len() });
+        }
+        let slice = &self.data[self.position..self.position + len];
+        self.position += len;
+        Ok(slice)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N], WireError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, WireError> {
+        self.array::<1>().map(|[byte]| byte)
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, WireError> {
+        self.array::<2>().map(u16::from_be_bytes)
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, WireError> {
+        self.array::<4>().map(u32::from_be_bytes)
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, WireError> {
+        self.array::<8>().map(u64::from_be_bytes)
+    }
+
+    pub(crate) fn rest(&mut self) -> &'a [u8] {
+        let slice = &self.data[self.position..];
+        self.position = self.data.len();
+        slice
+    }
+}